@@ -0,0 +1,102 @@
+//! Alternates between `&mut self` and `&self` calls through every kind of thin trait object, with
+//! references returned by earlier calls still in use. Meant to be run under Miri, with both
+//! Stacked Borrows and Tree Borrows, to check the pointer juggling done by the thunks.
+use thin_trait_object::*;
+
+#[thin_trait_object(
+    thin_ref(CounterRef),
+    thin_mut(CounterMut),
+    inline_storage(InlineCounter),
+    embedded(EmbeddedCounter),
+    clone = true
+)]
+trait Counter {
+    fn get(&self) -> &u64;
+    fn bump(&mut self) -> &mut u64;
+    fn finish(self) -> u64
+    where
+        Self: Sized;
+}
+#[derive(Clone)]
+struct Small(u64);
+impl Counter for Small {
+    fn get(&self) -> &u64 {
+        &self.0
+    }
+    fn bump(&mut self) -> &mut u64 {
+        self.0 += 1;
+        &mut self.0
+    }
+    fn finish(self) -> u64 {
+        self.0
+    }
+}
+// Too large for the buffer of the inline storage thin trait object.
+#[derive(Clone)]
+struct Large([u64; 8]);
+impl Counter for Large {
+    fn get(&self) -> &u64 {
+        &self.0[0]
+    }
+    fn bump(&mut self) -> &mut u64 {
+        self.0[0] += 1;
+        &mut self.0[0]
+    }
+    fn finish(self) -> u64 {
+        self.0[0]
+    }
+}
+
+#[thin_trait_object(storage = "rc", thin_ref(SharedRef))]
+trait Shared {
+    fn get(&self) -> &u64;
+}
+impl Shared for u64 {
+    fn get(&self) -> &u64 {
+        self
+    }
+}
+
+fn main() {
+    let mut boxed = BoxedCounter::new(Small(0));
+    *boxed.bump() += 1;
+    let (first, second) = (boxed.as_thin_ref(), boxed.as_thin_ref());
+    assert_eq!((*first.get(), *second.get()), (2, 2));
+    assert_eq!(first.get(), boxed.get());
+    {
+        let mut borrowed = boxed.as_thin_mut();
+        *borrowed.reborrow().bump() += 1;
+        let value = borrowed.get();
+        assert_eq!(*value, 4);
+    }
+    let copy = boxed.clone();
+    *boxed.bump() += 1;
+    assert_eq!((*boxed.get(), *copy.get()), (6, 4));
+    assert_eq!(boxed.finish(), 6);
+
+    let (mut inline, mut spilled) = (
+        InlineCounter::new(Small(0)),
+        InlineCounter::new(Large([0; 8])),
+    );
+    assert!(inline.is_inline() && !spilled.is_inline());
+    for inline in &mut [&mut inline, &mut spilled] {
+        *inline.bump() += 1;
+        let value = inline.get();
+        assert_eq!(*value, 2);
+        assert_eq!(*inline.bump(), 3);
+    }
+
+    let mut embedded = EmbeddedCounter::new(Small(0));
+    *embedded.as_thin_mut().bump() += 1;
+    let (first, second) = (embedded.as_thin_ref(), embedded.as_thin_ref());
+    assert_eq!(first.get(), second.get());
+    embedded.get_mut().0 += 1;
+    assert_eq!(*embedded.as_thin_ref().get(), 3);
+
+    let shared = BoxedShared::new(1_u64);
+    let other = shared.clone();
+    let value = shared.get();
+    assert_eq!(value, other.as_thin_ref().get());
+    drop(other);
+    assert_eq!(*value, 1);
+}
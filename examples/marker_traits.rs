@@ -13,6 +13,8 @@ trait Foo: SafeMarker + UnsafeMarker {
 }
 
 trait SafeMarker {}
+/// # Safety
+/// Not actually unsafe to implement, only used to showcase `unsafe` marker traits.
 unsafe trait UnsafeMarker {}
 
 impl Foo for String {
@@ -0,0 +1,213 @@
+//! Generates the allocator-aware representation struct and the constructors which use it, enabled by the `allocator_api` option.
+//!
+//! The allocator-aware representation struct has the same layout as the regular one, with the allocator appended at the end. This means that all thunks which only access the vtable and the stored value can be shared between them, and only the ones which allocate or deallocate need to be replaced.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Abi;
+
+use crate::{
+    attr::StageStash,
+    extra_entries::ExtraEntry,
+    marker_traits::MarkerTrait,
+    repr::allow_internal_lints,
+};
+
+pub fn generate_allocator_support(
+    stash: &mut StageStash,
+    inline_vtable: bool,
+    drop_abi: Option<&Abi>,
+    has_static_bound: bool,
+    markers: &[MarkerTrait],
+) -> TokenStream {
+    let StageStash {
+        vtable_name,
+        repr_name,
+        trait_object_name,
+        extra_entries,
+        storage,
+        drop_name,
+        trait_generics,
+        ..
+    } = &*stash;
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let where_clause = trait_generics.where_clause();
+    let repr_in_name = format_ident!("{}In", repr_name);
+    let path_to_box = storage.path();
+    let (path_to_global, path_to_handle_alloc_error) = {
+        // Same crate as the one Box comes from.
+        let krate = &path_to_box.segments[0];
+        (
+            quote! { ::#krate::alloc::Global },
+            quote! { ::#krate::alloc::handle_alloc_error },
+        )
+    };
+
+    let (vtable_field_type, vtable_field_val) = if inline_vtable {
+        (
+            vtable_type.clone(),
+            quote! { Self::__THINTRAITOBJECTMACRO_VTABLE },
+        )
+    } else {
+        (
+            quote! { &'static #vtable_type },
+            quote! { &Self::__THINTRAITOBJECTMACRO_VTABLE },
+        )
+    };
+    let extra_bounds = stash.value_bounds();
+    // The allocator is stored inside the thin trait object, so it has to satisfy the same marker
+    // traits as the ones the trait object claims to implement, and be cloned along with the value.
+    let has_clone = extra_entries.contains(&ExtraEntry::Clone);
+    let marker_paths = markers
+        .iter()
+        .map(|marker| &marker.path)
+        .collect::<Vec<_>>();
+    let alloc_clone_bound = if has_clone {
+        quote! { + ::core::clone::Clone }
+    } else {
+        quote! {}
+    };
+    let (clone_initializer, clone_thunk) = if has_clone {
+        (
+            quote! { clone: Self::__thintraitobjectmacro_repr_clone, },
+            quote! {
+                unsafe #drop_abi fn __thintraitobjectmacro_repr_clone(
+                    __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                ) -> *mut ::core::ffi::c_void {
+                    let __thintraitobjectmacro_arg0 = &*(__thintraitobjectmacro_arg0 as *mut Self);
+                    let result = Self::__thintraitobjectmacro_repr_create(
+                        ::core::clone::Clone::clone(
+                            &__thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_value,
+                        ),
+                        ::core::clone::Clone::clone(
+                            &*__thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_alloc.as_ptr(),
+                        ),
+                    );
+                    match result {
+                        ::core::result::Result::Ok(ptr) => ptr as *mut _,
+                        ::core::result::Result::Err(_) => #path_to_handle_alloc_error(
+                            ::core::alloc::Layout::new::<Self>(),
+                        ),
+                    }
+                }
+            },
+        )
+    } else {
+        Default::default()
+    };
+
+    let (lifetime, creation_bound) = if has_static_bound {
+        (quote! {}, quote! { 'static })
+    } else {
+        (quote! { 'inner }, quote! { 'inner })
+    };
+    let params = trait_generics.params_with(lifetime.clone());
+    let args = trait_generics.args_with(lifetime);
+    let value_generic = trait_generics.unused_ident("T");
+    let alloc_generic = trait_generics.unused_ident("A");
+    let value_bound = quote! {
+        #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+    };
+    let repr_in_def_params = trait_generics.params_with_defaults(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0: #trait_path,
+        __ThinTraitObjectMacro_ReprGeneric1: ::core::alloc::Allocator
+    });
+    let repr_in_params = trait_generics.params_with(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0: #trait_path #(+ #extra_bounds)*,
+        __ThinTraitObjectMacro_ReprGeneric1: ::core::alloc::Allocator #alloc_clone_bound
+    });
+    let repr_in_args = trait_generics.args_with(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0, __ThinTraitObjectMacro_ReprGeneric1
+    });
+    let value_repr_in_args = trait_generics.args_with(quote! { #value_generic, #alloc_generic });
+    let base_repr_type = stash.repr_type(&quote! { __ThinTraitObjectMacro_ReprGeneric0 });
+    let alloc_bound = quote! {
+        ::core::alloc::Allocator #(+ #marker_paths)* #alloc_clone_bound + #creation_bound
+    };
+    let (phantom_field, phantom_initializer) = trait_generics.repr_phantom();
+    let allow_internal_lints = allow_internal_lints();
+
+    quote! {
+        #allow_internal_lints
+        #[repr(C)]
+        struct #repr_in_name #repr_in_def_params #where_clause {
+            __thintraitobjectmacro_repr_vtable: #vtable_field_type,
+            __thintraitobjectmacro_repr_value: __ThinTraitObjectMacro_ReprGeneric0,
+            // Only initialized after the allocation is made, since the allocator is needed to make
+            // it in the first place.
+            __thintraitobjectmacro_repr_alloc: ::core::mem::MaybeUninit<__ThinTraitObjectMacro_ReprGeneric1>,
+            #phantom_field
+        }
+        #allow_internal_lints
+        impl #repr_in_params #repr_in_name #repr_in_args #where_clause {
+            const __THINTRAITOBJECTMACRO_VTABLE: #vtable_type = #vtable_name {
+                #drop_name: Self::__thintraitobjectmacro_repr_drop,
+                #clone_initializer
+                ..<#base_repr_type>::__THINTRAITOBJECTMACRO_VTABLE
+            };
+
+            fn __thintraitobjectmacro_repr_create(
+                __thintraitobjectmacro_arg0: __ThinTraitObjectMacro_ReprGeneric0,
+                __thintraitobjectmacro_arg1: __ThinTraitObjectMacro_ReprGeneric1,
+            ) -> ::core::result::Result<*mut #vtable_type, ::core::alloc::AllocError> {
+                let (ptr, alloc) = #path_to_box::into_raw_with_allocator(
+                    #path_to_box::try_new_in(
+                        Self {
+                            __thintraitobjectmacro_repr_vtable: #vtable_field_val,
+                            __thintraitobjectmacro_repr_value: __thintraitobjectmacro_arg0,
+                            __thintraitobjectmacro_repr_alloc: ::core::mem::MaybeUninit::uninit(),
+                            #phantom_initializer
+                        },
+                        __thintraitobjectmacro_arg1,
+                    )?,
+                );
+                unsafe {
+                    (*ptr).__thintraitobjectmacro_repr_alloc = ::core::mem::MaybeUninit::new(alloc);
+                }
+                ::core::result::Result::Ok(ptr as *mut _)
+            }
+            // Moves the allocator out before handing it over to Box, which will drop it after
+            // deallocating.
+            unsafe #drop_abi fn __thintraitobjectmacro_repr_drop(
+                __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+            ) {
+                let ptr = __thintraitobjectmacro_arg0 as *mut Self;
+                let alloc = (*ptr).__thintraitobjectmacro_repr_alloc.as_ptr().read();
+                let _ = #path_to_box::from_raw_in(ptr, alloc);
+            }
+            #clone_thunk
+        }
+        impl #params #trait_object_name #args #where_clause {
+            /// Constructs a boxed thin trait object from a type implementing the trait, returning an error if the allocation fails.
+            #[inline]
+            pub fn try_new<#value_generic: #value_bound>(
+                val: #value_generic,
+            ) -> ::core::result::Result<Self, ::core::alloc::AllocError> {
+                Self::try_new_in(val, #path_to_global)
+            }
+            /// Constructs a boxed thin trait object from a type implementing the trait, using the given allocator.
+            #[inline]
+            pub fn new_in<#value_generic: #value_bound, #alloc_generic: #alloc_bound>(
+                val: #value_generic,
+                alloc: #alloc_generic,
+            ) -> Self {
+                match Self::try_new_in(val, alloc) {
+                    ::core::result::Result::Ok(this) => this,
+                    ::core::result::Result::Err(_) => #path_to_handle_alloc_error(
+                        ::core::alloc::Layout::new::<#repr_in_name #value_repr_in_args>(),
+                    ),
+                }
+            }
+            /// Constructs a boxed thin trait object from a type implementing the trait, using the given allocator and returning an error if the allocation fails.
+            #[inline]
+            pub fn try_new_in<#value_generic: #value_bound, #alloc_generic: #alloc_bound>(
+                val: #value_generic,
+                alloc: #alloc_generic,
+            ) -> ::core::result::Result<Self, ::core::alloc::AllocError> {
+                <#repr_in_name #value_repr_in_args>::__thintraitobjectmacro_repr_create(val, alloc)
+                    .map(|ptr| unsafe { Self::from_raw(ptr as *mut ()) })
+            }
+        }
+    }
+}
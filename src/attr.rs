@@ -1,46 +1,283 @@
 //! The main body of the attribute macro. Uses entirely `proc_macro2` stuff to make unit testing possible — compile error conversions and `proc_macro` conversions are delegated to the crate root wrapper.
 
-use super::{marker_traits::*, options::*, repr::*, trait_object::*, vtable::*};
-use proc_macro2::{Ident, Span, TokenStream};
-use quote::{format_ident, quote};
-use std::convert::TryFrom;
+use super::{
+    allocator::*,
+    embedded::*,
+    export_ctor::*,
+    extra_entries::*,
+    free_fn::*,
+    from_parts::*,
+    generics::*,
+    inline_storage::*,
+    marker_traits::*,
+    options::*,
+    repr::*,
+    static_storage::*,
+    storage::*,
+    thin_ref::*,
+    trait_object::*,
+    vtable::*,
+    vtable_static::*,
+};
+use proc_macro2::{Group, Ident, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use std::{convert::TryFrom, iter, mem, slice};
 use syn::{
     parse::Parser,
+    parse_quote,
     punctuated::Punctuated,
+    token::Unsafe,
     Abi,
     Attribute,
     ItemTrait,
+    LitBool,
+    LitInt,
+    LitStr,
     Path,
-    PathArguments,
-    PathSegment,
     TraitBound,
+    TraitItem,
+    Type,
+    TypeParamBound,
     Visibility,
+    WherePredicate,
 };
 
+/// Returns the trait definition to be emitted in place of the original one, which differs from it only in that the `#[inline]` and `#[cold]` attributes are removed from methods without a default implementation, since the compiler rejects them there but they're carried over to the methods of the thin trait objects.
+pub fn emitted_trait_definition(item: TokenStream) -> TokenStream {
+    let mut trait_def = match syn::parse2::<ItemTrait>(item.clone()) {
+        Ok(trait_def) => trait_def,
+        // The error is reported by `attribute_main`.
+        Err(..) => return item,
+    };
+    for trait_item in &mut trait_def.items {
+        if let TraitItem::Method(method) = trait_item {
+            // The options of the method are only meaningful to this macro.
+            method.attrs.retain(|attr| !attr.path.is_ident("thin"));
+            if method.default.is_none() {
+                method
+                    .attrs
+                    .retain(|attr| !attr.path.is_ident("inline") && !attr.path.is_ident("cold"));
+            }
+        }
+    }
+    trait_def.into_token_stream()
+}
+
+/// Accumulates the errors found while validating the trait, so that all of them can be reported together.
+#[derive(Default)]
+struct Diagnostics(Option<syn::Error>);
+impl Diagnostics {
+    /// Records the error if there is one, returning the value otherwise.
+    fn check<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                match &mut self.0 {
+                    Some(errors) => errors.combine(error),
+                    None => self.0 = Some(error),
+                }
+                None
+            }
+        }
+    }
+    fn finish(self) -> syn::Result<()> {
+        match self.0 {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+}
+
 pub fn attribute_main(attr: TokenStream, item: TokenStream) -> Result<TokenStream, syn::Error> {
     let options = Punctuated::parse_terminated.parse2(attr)?;
-    let config = Config::from(options);
-    let trait_def = syn::parse2::<ItemTrait>(item)?;
-    if !trait_def.generics.params.is_empty() {
-        return Err(syn::Error::new_spanned(
-            trait_def.generics.params,
-            "generic traits are not yet supported by #[thin_trait_object]",
-        ));
+    let mut config = Config::from(options);
+    let mut trait_def = syn::parse2::<ItemTrait>(item)?;
+    // Bounds on `Self` in the `where` clause are the same thing as supertraits.
+    if let Some(where_clause) = &mut trait_def.generics.where_clause {
+        for predicate in mem::take(&mut where_clause.predicates) {
+            match predicate {
+                WherePredicate::Type(predicate) if predicate.bounded_ty == parse_quote!(Self) => {
+                    for mut bound in predicate.bounds {
+                        if let (TypeParamBound::Trait(bound), Some(lifetimes)) =
+                            (&mut bound, &predicate.lifetimes)
+                        {
+                            bound.lifetimes.get_or_insert_with(|| lifetimes.clone());
+                        }
+                        trait_def.supertraits.push(bound);
+                    }
+                }
+                predicate => where_clause.predicates.push(predicate),
+            }
+        }
     }
+    // Every stored value fixes the associated types, so the generated items are generic over them
+    // instead.
+    let associated_types = trait_def
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            TraitItem::Type(associated_type) => Some(associated_type.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let trait_generics = TraitGenerics::new(trait_def.generics.clone(), associated_types)?;
     let vtable_name = config
         .vtable_name
+        .take()
         .unwrap_or_else(|| format_ident!("{}Vtable", &trait_def.ident));
     let trait_object_name = config
         .trait_object_name
+        .take()
         .unwrap_or_else(|| format_ident!("Boxed{}", &trait_def.ident));
-    let vtable_items = trait_def
+    let repr_name = config
+        .repr_name
+        .take()
+        .unwrap_or_else(|| repr_name_from_trait_name(trait_def.ident.clone()));
+    let blanket_impl = match &config.blanket_impl {
+        Some(blanket_impl) => generate_blanket_impl(&trait_def, &trait_generics, blanket_impl)?,
+        None => TokenStream::new(),
+    };
+    // Every item is validated before giving up, so that the problems with a large trait are
+    // reported all at once instead of one at a time. Each item contributes at most one error.
+    let mut diagnostics = Diagnostics::default();
+    let mut vtable_items = trait_def
         .items
         .into_iter()
-        .map(VtableItem::try_from)
-        .collect::<Result<Vec<_>, _>>()?;
-    let (markers, lifetime_bounds) = supertraits_to_markers_and_lifetimes(
+        .filter(|item| !matches!(item, TraitItem::Type(..)))
+        .filter_map(|mut item| {
+            trait_generics.replace_associated_types(&mut item);
+            diagnostics.check(VtableItem::try_from(item))
+        })
+        .flat_map(VtableItem::monomorphize)
+        .collect::<Vec<_>>();
+    // Supertraits from the standard library whose methods the macro knows about don't need to be
+    // listed, unless their other methods are to be flattened into the vtable as well.
+    let listed_supertraits = &config.supertraits;
+    let implied_supertraits = trait_def
+        .supertraits
+        .iter()
+        .filter_map(|bound| match bound {
+            TypeParamBound::Trait(bound) => SupertraitMethods::from_supertrait(bound),
+            TypeParamBound::Lifetime(..) => None,
+        })
+        .filter(|implied| {
+            !listed_supertraits
+                .iter()
+                .any(|supertrait| supertrait.path == implied.path)
+        })
+        .collect::<Vec<_>>();
+    // Supertrait methods come first, like in the vtables generated by the compiler.
+    let mut flattened_supertraits = Vec::<Path>::new();
+    let mut supertrait_items = Vec::<VtableItem>::new();
+    let supertraits = mem::take(&mut config.supertraits)
+        .into_iter()
+        .chain(implied_supertraits);
+    for SupertraitMethods { path, items, .. } in supertraits {
+        for item in items {
+            let item = match diagnostics.check(VtableItem::try_from(item)) {
+                Some(item) => item,
+                None => continue,
+            };
+            for mut item in item.monomorphize() {
+                // In a diamond, the same supertrait is reachable through several others and may
+                // thus be listed more than once, but its methods only need one vtable entry each.
+                let already_listed = supertrait_items.iter().any(|other| {
+                    other.name == item.name && other.supertrait.as_ref() == Some(&path)
+                });
+                if !already_listed {
+                    item.supertrait = Some(path.clone());
+                    supertrait_items.push(item);
+                }
+            }
+        }
+        if !flattened_supertraits.contains(&path) {
+            flattened_supertraits.push(path);
+        }
+    }
+    supertrait_items.append(&mut vtable_items);
+    vtable_items = supertrait_items;
+    for (index, item) in vtable_items.iter().enumerate() {
+        diagnostics.check(config.validate_item(item, &vtable_items[..index], &trait_generics));
+    }
+    diagnostics.finish()?;
+    // The vtable entries replace those with `'static`, see `TraitGenerics::lifetimes`.
+    let trait_lifetimes = trait_generics.lifetimes();
+    for item in &mut vtable_items {
+        item.trait_lifetimes = trait_lifetimes.clone();
+    }
+    if !config.dispatch_defaults {
+        // Everything is generated from the list of vtable items, so simply not having those there
+        // makes them use the default implementation everywhere.
+        vtable_items.retain(|item| !item.has_default);
+    }
+    // Same for the methods left out with `#[thin(skip)]`, which have default implementations.
+    vtable_items.retain(|item| !item.skip);
+    if let Some(error_code) = &config.error_code {
+        // Rust code can pass the `Result` through the vtable as it is.
+        let vtable_abi = config.abi.as_ref();
+        for item in &mut vtable_items {
+            let abi = item.abi.as_ref().or(vtable_abi);
+            if abi.map(|abi| abi_name(abi) != "Rust") == Some(true) {
+                item.error_code = Some(error_code.clone());
+            }
+        }
+    }
+    // The drop handler uses the same ABI as the rest of the vtable unless told otherwise.
+    let drop_abi = match &config.drop_abi {
+        Some(drop_abi) => Some(drop_abi.clone()),
+        None => config.abi.clone(),
+    };
+    let mut supertrait_entries = Vec::new();
+    for supertrait in &trait_def.supertraits {
+        if let TypeParamBound::Trait(bound) = supertrait {
+            if let Some(entry) = ExtraEntry::from_supertrait(bound) {
+                supertrait_entries.push(entry);
+            }
+        }
+    }
+    let mut extra_entries = supertrait_entries.clone();
+    if config.clone {
+        extra_entries.push(ExtraEntry::Clone);
+    }
+    if config.debug {
+        extra_entries.push(ExtraEntry::Debug);
+    }
+    if config.display {
+        extra_entries.push(ExtraEntry::Display);
+    }
+    if config.type_id {
+        extra_entries.push(ExtraEntry::TypeId);
+    }
+    if config.type_name {
+        extra_entries.push(ExtraEntry::TypeName);
+    }
+    if config.as_dyn {
+        extra_entries.push(ExtraEntry::AsDyn);
+    }
+    if config.box_dyn.is_some() {
+        extra_entries.push(ExtraEntry::IntoBoxDyn);
+    }
+    if config.eq.is_some() {
+        extra_entries.push(ExtraEntry::PartialEq);
+    }
+    if config.hash.is_some() {
+        extra_entries.push(ExtraEntry::Hash);
+    }
+    if config.ord.is_some() {
+        extra_entries.push(ExtraEntry::Ord);
+    }
+    if config.manual_drop.is_some() {
+        extra_entries.push(ExtraEntry::Dealloc);
+    }
+    // Shared storage can always be cloned by incrementing the reference count.
+    if config.storage.is_shared() {
+        extra_entries.push(ExtraEntry::Clone);
+    }
+    extra_entries.sort();
+    extra_entries.dedup();
+    let (mut markers, lifetime_bounds) = supertraits_to_markers_and_lifetimes(
         trait_def.supertraits,
-        config.marker_traits.map_or(
+        config.marker_traits.take().map_or(
             Box::new(default_marker_filter) as Box<dyn FnMut(_) -> _>,
             |markers| {
                 Box::new(move |bound: TraitBound| {
@@ -54,51 +291,341 @@ pub fn attribute_main(attr: TokenStream, item: TokenStream) -> Result<TokenStrea
             },
         ),
     );
+    // The `cmp` entry comes with an implementation of `Eq`, which the marker would duplicate.
+    if config.ord.is_some() {
+        markers.retain(|marker| !marker.is("Eq"));
+    }
     let has_static_bound = lifetime_bounds
         .iter()
         .any(|lifetime| lifetime.ident == "static");
+    config
+        .storage
+        .check_trait(&markers, trait_def.ident.span())?;
+    let drop_name = if vtable_items.iter().any(|item| item.name == "drop") {
+        let drop_name = format_ident!("__thintraitobjectmacro_drop");
+        // Renamed right away rather than together with everything else, since the C header refers
+        // to the field by name as well.
+        match &config.prefix {
+            Some(prefix) => prefixed_ident(&drop_name, prefix).unwrap(),
+            None => drop_name,
+        }
+    } else {
+        format_ident!("drop")
+    };
     let mut stash = StageStash {
         trait_name: trait_def.ident.clone(),
         vtable_name,
         trait_object_name,
-        repr_name: repr_name_from_trait_name(trait_def.ident.clone()),
+        repr_name,
         vtable_items,
+        vtable_abi: config.abi.clone(),
+        extra_entries,
+        supertrait_entries,
+        storage: config.storage,
+        allocator_api: config.allocator_api.is_some(),
+        static_storage: config.static_storage.is_some(),
+        inline_always: config.inline_always,
+        drop_name,
+        flattened_supertraits,
+        trait_generics,
+        trait_unsafety: trait_def.unsafety,
+        blanket_impl: config.blanket_impl.is_some(),
+        header: config.header.clone(),
+        repr_modifier: config.repr.as_ref().map(|(_, modifier)| modifier.clone()),
+    };
+    config.validate(&stash)?;
+    let inline_capacity = match &config.inline_capacity {
+        Some(inline_capacity) => inline_capacity.base10_parse::<usize>()?,
+        None => DEFAULT_INLINE_CAPACITY,
     };
     let trait_visibility = &trait_def.vis;
+    // The thin trait object structs are re-exported from the module under the visibilities they
+    // would have had without it, and need to be visible from the same places from inside of it.
+    let mut reexports = Vec::new();
+    let vtable_visibility = config
+        .vtable_visibility
+        .unwrap_or_else(|| trait_visibility.clone());
+    let mut trait_object_visibility = config
+        .trait_object_visibility
+        .unwrap_or_else(|| trait_visibility.clone());
+    let vtable_visibility = if config.module.is_some() {
+        reexports.push((
+            trait_object_visibility.clone(),
+            stash.trait_object_name.clone(),
+        ));
+        trait_object_visibility = nest_visibility(trait_object_visibility);
+        let additions = config
+            .thin_ref
+            .iter_mut()
+            .chain(config.thin_mut.iter_mut())
+            .chain(config.inline_storage.iter_mut())
+            .chain(config.static_storage.iter_mut())
+            .chain(config.embedded.iter_mut())
+            .chain(config.free_fn.iter_mut())
+            .chain(
+                config
+                    .vtable_statics
+                    .iter_mut()
+                    .map(|vtable_static| &mut vtable_static.additions),
+            );
+        for additions in additions {
+            reexports.push((additions.visibility.clone(), additions.name.clone()));
+            additions.visibility = nest_visibility(additions.visibility.clone());
+        }
+        for ctor in &config.export_ctors {
+            reexports.push((parse_quote!(pub), export_ctor_name(ctor)?));
+        }
+        nest_visibility(vtable_visibility)
+    } else {
+        vtable_visibility
+    };
     let vtable = generate_vtable(
         &mut stash,
-        config
-            .vtable_visibility
-            .unwrap_or_else(|| trait_visibility.clone()),
+        vtable_visibility,
         config.vtable_attributes,
-        config.drop_abi.as_ref(),
+        drop_abi.as_ref(),
         config.store_layout,
-    );
+        config.inline_vtable,
+        config.c_header.as_ref(),
+    )?;
     let repr = generate_repr(
         &mut stash,
         config.inline_vtable,
-        path_to_box(),
-        config.drop_abi.as_ref(),
+        drop_abi.as_ref(),
         config.store_layout,
+        config.shared_drop.is_some(),
     );
     let trait_object = generate_trait_object(
         &mut stash,
-        config
-            .trait_object_visibility
-            .unwrap_or_else(|| trait_visibility.clone()),
+        trait_object_visibility,
         config.inline_vtable,
+        config.store_layout,
         has_static_bound,
         &config.trait_object_attributes,
         &markers,
     )?;
+    let allocator_support = if config.allocator_api.is_some() {
+        generate_allocator_support(
+            &mut stash,
+            config.inline_vtable,
+            drop_abi.as_ref(),
+            has_static_bound,
+            &markers,
+        )
+    } else {
+        TokenStream::new()
+    };
+    let mut thin_refs = TokenStream::new();
+    for (additions, mutable) in config
+        .thin_ref
+        .iter()
+        .map(|additions| (additions, false))
+        .chain(config.thin_mut.iter().map(|additions| (additions, true)))
+    {
+        thin_refs.extend(generate_thin_ref(
+            &mut stash,
+            additions,
+            mutable,
+            config.inline_vtable,
+            has_static_bound,
+            &markers,
+        )?);
+    }
+    let inline_storage = match &config.inline_storage {
+        Some(additions) => generate_inline_storage(
+            &mut stash,
+            additions,
+            inline_capacity,
+            config.inline_vtable,
+            drop_abi.as_ref(),
+            has_static_bound,
+            &markers,
+        )?,
+        None => TokenStream::new(),
+    };
+    let static_storage = match &config.static_storage {
+        Some(additions) => generate_static_storage(
+            &mut stash,
+            additions,
+            config.inline_vtable,
+            drop_abi.as_ref(),
+            has_static_bound,
+        )?,
+        None => TokenStream::new(),
+    };
+    let embedded = match &config.embedded {
+        Some(additions) => generate_embedded(
+            &mut stash,
+            additions,
+            config.inline_vtable,
+            config.thin_ref.as_ref().map(|additions| &additions.name),
+            config.thin_mut.as_ref().map(|additions| &additions.name),
+        )?,
+        None => TokenStream::new(),
+    };
+    let free_fn = match &config.free_fn {
+        Some(additions) => generate_free_fn(
+            &mut stash,
+            additions,
+            config.inline_vtable,
+            drop_abi.as_ref(),
+        )?,
+        None => TokenStream::new(),
+    };
+    let export_ctors = config
+        .export_ctors
+        .iter()
+        .map(|ctor| generate_export_ctor(&mut stash, ctor, drop_abi.as_ref()))
+        .collect::<syn::Result<TokenStream>>()?;
+    let vtable_statics = if config.vtable_statics.is_empty() {
+        TokenStream::new()
+    } else {
+        generate_vtable_statics(
+            &stash,
+            &config.vtable_statics,
+            config.inline_vtable,
+            has_static_bound,
+        )?
+    };
+    let from_parts = if config.from_parts.is_some() {
+        generate_from_parts(&stash, &markers, has_static_bound)
+    } else {
+        TokenStream::new()
+    };
+    let default_impl = match &config.default {
+        Some(ty) => generate_default_impl(&stash, ty, has_static_bound),
+        None => TokenStream::new(),
+    };
     // We don't need to add the original input to the output here because the
     // public wrapper does that, see its definition for more on that.
     let output = quote! {
-        #vtable #repr #trait_object
+        #vtable #repr #trait_object #allocator_support #thin_refs #inline_storage #static_storage
+        #embedded #free_fn #export_ctors #vtable_statics #from_parts #default_impl #blanket_impl
+    };
+    let output = match &config.prefix {
+        Some(prefix) => apply_prefix(output, prefix),
+        None => output,
+    };
+    let output = match &config.module {
+        // Everything the generated items refer to by a relative path, like the trait itself and
+        // the types in the method signatures, is brought into scope with the glob import.
+        Some(module) => {
+            let reexports = reexports.iter().map(|(visibility, name)| {
+                quote! { #visibility use self::#module::#name; }
+            });
+            // Allowing `missing_docs` on the module would extend to the items inside of it.
+            let doc = LitStr::new(
+                &format!("The items generated for the `{}` trait.", trait_def.ident),
+                Span::call_site(),
+            );
+            quote! {
+                #[doc = #doc]
+                #trait_visibility mod #module {
+                    use super::*;
+                    #output
+                }
+                #(#reexports)*
+            }
+        }
+        None => output,
     };
     Ok(output)
 }
 
+/// The prefix which all hidden identifiers in the generated code start with, capitalized in the same way as the rest of the identifier.
+const DEFAULT_PREFIX: &str = "__thintraitobjectmacro";
+
+/// Replaces the default prefix of all hidden identifiers in the generated code with the one set by the `prefix` option.
+fn apply_prefix(tokens: TokenStream, prefix: &Ident) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) => {
+                let mut renamed =
+                    Group::new(group.delimiter(), apply_prefix(group.stream(), prefix));
+                renamed.set_span(group.span());
+                TokenTree::Group(renamed)
+            }
+            TokenTree::Ident(ident) => {
+                TokenTree::Ident(prefixed_ident(&ident, prefix).unwrap_or(ident))
+            }
+            token => token,
+        })
+        .collect()
+}
+
+/// Returns the hidden identifier with the default prefix replaced by the given one, which is lowercased or uppercased if the default prefix is, or `None` if the identifier doesn't start with the default prefix.
+fn prefixed_ident(ident: &Ident, prefix: &Ident) -> Option<Ident> {
+    let name = ident.to_string();
+    let default_prefix = name
+        .get(..DEFAULT_PREFIX.len())
+        .filter(|start| start.eq_ignore_ascii_case(DEFAULT_PREFIX))?;
+    let prefix = prefix.to_string();
+    let prefix = if default_prefix == DEFAULT_PREFIX {
+        prefix.to_ascii_lowercase()
+    } else if default_prefix == DEFAULT_PREFIX.to_ascii_uppercase() {
+        prefix.to_ascii_uppercase()
+    } else {
+        prefix
+    };
+    Some(Ident::new(
+        &format!("{}{}", prefix, &name[DEFAULT_PREFIX.len()..]),
+        ident.span(),
+    ))
+}
+
+/// Generates the blanket implementation of the trait for every type implementing all of its supertraits, enabled by the `blanket_impl` option.
+fn generate_blanket_impl(
+    trait_def: &ItemTrait,
+    trait_generics: &TraitGenerics,
+    blanket_impl: &LitBool,
+) -> syn::Result<TokenStream> {
+    // The implementation could only provide the items of the trait by using their defaults.
+    if let Some(item) = trait_def.items.first() {
+        return Err(syn::Error::new_spanned(
+            item,
+            "`blanket_impl = true` requires the trait to have no items of its own, since the blanket implementation cannot provide them; declare them in a supertrait instead",
+        ));
+    }
+    // Implementing an unsafe trait for every type would uphold its contract on their behalf.
+    if trait_def.unsafety.is_some() {
+        return Err(syn::Error::new_spanned(
+            blanket_impl,
+            "`blanket_impl = true` is not supported for unsafe traits",
+        ));
+    }
+    let trait_name = &trait_def.ident;
+    let supertraits = &trait_def.supertraits;
+    let value_generic = trait_generics.unused_ident("T");
+    let params = trait_generics.params_with(quote! { #value_generic: ?Sized + #supertraits });
+    let args = trait_generics.args();
+    let where_clause = trait_generics.where_clause();
+    Ok(quote! {
+        impl #params #trait_name #args for #value_generic #where_clause {}
+    })
+}
+
+/// Adjusts the visibility of an item which is moved into a child module by the `module` option for it to be visible from the same places as before.
+fn nest_visibility(visibility: Visibility) -> Visibility {
+    match visibility {
+        Visibility::Inherited => parse_quote!(pub(super)),
+        Visibility::Restricted(mut restricted) => {
+            let first = &mut restricted.path.segments[0].ident;
+            if first == "self" {
+                *first = Ident::new("super", first.span());
+            } else if first == "super" {
+                restricted.path.segments.insert(0, parse_quote!(super));
+            } else {
+                return Visibility::Restricted(restricted);
+            }
+            // Only `self`, `super` and `crate` can be used without `in`.
+            restricted.in_token.get_or_insert_with(Default::default);
+            Visibility::Restricted(restricted)
+        }
+        visibility => visibility,
+    }
+}
+
 struct Config {
     vtable_attributes: Vec<Attribute>,
     vtable_visibility: Option<Visibility>,
@@ -107,9 +634,57 @@ struct Config {
     trait_object_attributes: Vec<Attribute>,
     trait_object_visibility: Option<Visibility>,
     trait_object_name: Option<Ident>,
+    repr_name: Option<Ident>,
+    module: Option<Ident>,
+    prefix: Option<Ident>,
     drop_abi: Option<Abi>,
     marker_traits: Option<Vec<MarkerTrait>>,
     store_layout: bool,
+    header: Option<Type>,
+    abi: Option<Abi>,
+    error_code: Option<Path>,
+    thin_ref: Option<OutputAdditions>,
+    thin_mut: Option<OutputAdditions>,
+    clone: bool,
+    debug: bool,
+    display: bool,
+    type_id: bool,
+    type_name: bool,
+    as_dyn: bool,
+    /// Holds the literal for its span, `None` if disabled.
+    box_dyn: Option<LitBool>,
+    /// Holds the literal for its span, `None` if disabled.
+    from_parts: Option<LitBool>,
+    default: Option<Type>,
+    /// Holds the literal for its span, `None` if disabled.
+    eq: Option<LitBool>,
+    /// Holds the literal for its span, `None` if disabled.
+    hash: Option<LitBool>,
+    ord: Option<LitBool>,
+    /// Holds the literal for its span, `None` if disabled.
+    c_header: Option<LitBool>,
+    storage: Storage,
+    /// The modifier added to the `#[repr(C)]` of the representation struct, along with the string it was parsed from.
+    repr: Option<(LitStr, ReprModifier)>,
+    /// Holds the literal for its span, `None` if disabled.
+    allocator_api: Option<LitBool>,
+    dispatch_defaults: bool,
+    inline_storage: Option<OutputAdditions>,
+    inline_capacity: Option<LitInt>,
+    inline_always: bool,
+    /// Holds the literal for its span, `None` if disabled.
+    shared_drop: Option<LitBool>,
+    /// Holds the literal for its span, `None` if disabled.
+    manual_drop: Option<LitBool>,
+    free_fn: Option<OutputAdditions>,
+    export_ctors: Vec<ExportedCtor>,
+    vtable_statics: Vec<VtableStatic>,
+    static_storage: Option<OutputAdditions>,
+    embedded: Option<OutputAdditions>,
+    async_methods: bool,
+    supertraits: Vec<SupertraitMethods>,
+    /// Holds the literal for its span, `None` if disabled.
+    blanket_impl: Option<LitBool>,
 }
 impl From<AttrOptions> for Config {
     fn from(options: AttrOptions) -> Self {
@@ -124,11 +699,32 @@ impl From<AttrOptions> for Config {
                 AttrOption::InlineVtable { val, .. } => {
                     config.inline_vtable = val.value;
                 }
+                AttrOption::VtableMode { inline_vtable, .. } => {
+                    config.inline_vtable = inline_vtable;
+                }
                 AttrOption::TraitObject { additions, .. } => {
                     config.trait_object_attributes = additions.attributes;
                     config.trait_object_visibility = Some(additions.visibility);
                     config.trait_object_name = Some(additions.name);
                 }
+                AttrOption::Vis { visibility, .. } => {
+                    config.trait_object_visibility = Some(visibility);
+                }
+                AttrOption::Name { val, .. } => {
+                    config.trait_object_name = Some(val);
+                }
+                AttrOption::VtableName { val, .. } => {
+                    config.vtable_name = Some(val);
+                }
+                AttrOption::ReprName { val, .. } => {
+                    config.repr_name = Some(val);
+                }
+                AttrOption::Module { val, .. } => {
+                    config.module = Some(val);
+                }
+                AttrOption::Prefix { val, .. } => {
+                    config.prefix = Some(val);
+                }
                 AttrOption::DropAbi { abi, .. } => {
                     config.drop_abi = Some(Abi {
                         extern_token: Default::default(),
@@ -141,6 +737,114 @@ impl From<AttrOptions> for Config {
                 AttrOption::StoreLayout { val, .. } => {
                     config.store_layout = val.value;
                 }
+                AttrOption::Header { ty, .. } => {
+                    config.header = Some(ty);
+                }
+                AttrOption::Abi { abi, .. } => {
+                    config.abi = Some(Abi {
+                        extern_token: Default::default(),
+                        name: Some(abi),
+                    })
+                }
+                AttrOption::ErrorCode { path, .. } => {
+                    config.error_code = Some(path);
+                }
+                AttrOption::ThinRef { additions, .. } => {
+                    config.thin_ref = Some(additions);
+                }
+                AttrOption::ThinMut { additions, .. } => {
+                    config.thin_mut = Some(additions);
+                }
+                AttrOption::Clone { val, .. } => {
+                    config.clone = val.value;
+                }
+                AttrOption::Debug { val, .. } => {
+                    config.debug = val.value;
+                }
+                AttrOption::Display { val, .. } => {
+                    config.display = val.value;
+                }
+                AttrOption::TypeId { val, .. } => {
+                    config.type_id = val.value;
+                }
+                AttrOption::TypeName { val, .. } => {
+                    config.type_name = val.value;
+                }
+                AttrOption::AsDyn { val, .. } => {
+                    config.as_dyn = val.value;
+                }
+                AttrOption::BoxDyn { val, .. } => {
+                    config.box_dyn = Some(val).filter(|val| val.value);
+                }
+                AttrOption::FromParts { val, .. } => {
+                    config.from_parts = Some(val).filter(|val| val.value);
+                }
+                AttrOption::Default { ty, .. } => {
+                    config.default = Some(ty);
+                }
+                AttrOption::Eq { val, .. } => {
+                    config.eq = Some(val).filter(|val| val.value);
+                }
+                AttrOption::Hash { val, .. } => {
+                    config.hash = Some(val).filter(|val| val.value);
+                }
+                AttrOption::Ord { val, .. } => {
+                    config.ord = Some(val).filter(|val| val.value);
+                }
+                AttrOption::CHeader { val, .. } => {
+                    config.c_header = Some(val).filter(|val| val.value);
+                }
+                AttrOption::Storage { storage, .. } => {
+                    config.storage = storage;
+                }
+                AttrOption::Repr { val, modifier, .. } => {
+                    config.repr = Some((val, modifier));
+                }
+                AttrOption::AllocatorApi { val, .. } => {
+                    config.allocator_api = Some(val).filter(|val| val.value);
+                }
+                AttrOption::DispatchDefaults { val, .. } => {
+                    config.dispatch_defaults = val.value;
+                }
+                AttrOption::InlineStorage { additions, .. } => {
+                    config.inline_storage = Some(additions);
+                }
+                AttrOption::InlineCapacity { val, .. } => {
+                    config.inline_capacity = Some(val);
+                }
+                AttrOption::InlineAlways { val, .. } => {
+                    config.inline_always = val.value;
+                }
+                AttrOption::SharedDrop { val, .. } => {
+                    config.shared_drop = Some(val).filter(|val| val.value);
+                }
+                AttrOption::ManualDrop { val, .. } => {
+                    config.manual_drop = Some(val).filter(|val| val.value);
+                }
+                AttrOption::FreeFn { additions, .. } => {
+                    config.free_fn = Some(additions);
+                }
+                AttrOption::ExportCtor { ctor, .. } => {
+                    config.export_ctors.push(ctor);
+                }
+                AttrOption::VtableStatic { vtable_static, .. } => {
+                    config.vtable_statics.push(vtable_static);
+                }
+                AttrOption::StaticStorage { additions, .. } => {
+                    config.static_storage = Some(additions);
+                }
+                AttrOption::Embedded { additions, .. } => {
+                    config.embedded = Some(additions);
+                }
+                AttrOption::Async { val, .. } => {
+                    config.async_methods = val.value;
+                }
+                AttrOption::Supertrait { supertrait, .. } => {
+                    config.supertraits.push(supertrait);
+                }
+                AttrOption::BlanketImpl { val, .. } => {
+                    config.blanket_impl = Some(val).filter(|val| val.value);
+                }
             }
         }
         config
@@ -149,6 +853,7 @@ impl From<AttrOptions> for Config {
 // Not using the derive because at some point I'm gonna introduce a config entry which should have
 // a different default and then forget to refator into a manual implementation, which will take
 // some time to figure out when it becomes a bug.
+#[allow(clippy::derivable_impls)]
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -159,10 +864,632 @@ impl Default for Config {
             trait_object_attributes: Vec::new(),
             trait_object_visibility: None,
             trait_object_name: None,
+            repr_name: None,
+            module: None,
+            prefix: None,
             drop_abi: None,
             marker_traits: None,
             store_layout: false,
+            header: None,
+            abi: None,
+            error_code: None,
+            thin_ref: None,
+            thin_mut: None,
+            clone: false,
+            debug: false,
+            display: false,
+            type_id: false,
+            type_name: false,
+            as_dyn: false,
+            box_dyn: None,
+            from_parts: None,
+            default: None,
+            eq: None,
+            hash: None,
+            ord: None,
+            c_header: None,
+            storage: Storage::Box,
+            repr: None,
+            allocator_api: None,
+            dispatch_defaults: true,
+            inline_storage: None,
+            inline_capacity: None,
+            inline_always: false,
+            shared_drop: None,
+            manual_drop: None,
+            free_fn: None,
+            export_ctors: Vec::new(),
+            vtable_statics: Vec::new(),
+            static_storage: None,
+            embedded: None,
+            async_methods: false,
+            supertraits: Vec::new(),
+            blanket_impl: None,
+        }
+    }
+}
+
+impl Config {
+    /// Checks a vtable item against the options and the items before it, reporting the first problem found.
+    fn validate_item(
+        &self,
+        item: &VtableItem,
+        previous: &[VtableItem],
+        trait_generics: &TraitGenerics,
+    ) -> syn::Result<()> {
+        let prefix = self.prefix.as_ref();
+        let vtable_abi = self.abi.as_ref();
+        let packed = self
+            .repr
+            .as_ref()
+            .filter(|(_, modifier)| modifier.is_packed());
+        // All of the internal names start with this prefix, so a method using it could collide
+        // with a thunk, a field or a constant generated by the macro.
+        for name in iter::once(&item.name).chain(&item.rename) {
+            let lowercase = name.to_string().to_ascii_lowercase();
+            let reserved_prefix = iter::once(DEFAULT_PREFIX.to_string())
+                .chain(prefix.map(|prefix| prefix.to_string().to_ascii_lowercase()))
+                .find(|prefix| lowercase.starts_with(prefix));
+            if let Some(prefix) = reserved_prefix {
+                return Err(syn::Error::new_spanned(
+                name,
+                format!(
+                    "method names starting with `{}` are reserved for the items generated by #[thin_trait_object]",
+                    prefix,
+                ),
+            ));
+            }
+        }
+        if let Some(other) = previous.iter().find(|other| {
+            other.name == item.name && (other.constant.is_some() || item.constant.is_some())
+        }) {
+            let constant = other.constant.as_ref().or(item.constant.as_ref()).unwrap();
+            return Err(syn::Error::new_spanned(
+                &item.name,
+                format!(
+                "the getter for associated constant `{}` would have the same name as method `{}`",
+                constant, item.name,
+            ),
+            ));
+        }
+        if let Some(mono) = previous
+            .iter()
+            .find(|other| other.name == item.name)
+            .and_then(|other| other.mono.as_ref().or(item.mono.as_ref()))
+        {
+            return Err(syn::Error::new_spanned(
+                &item.name,
+                format!(
+                    "\
+the vtable entry `{}` for one of the types listed in `mono` on method `{}` has the same name as \
+another vtable entry; specify a different name for it with `as name`",
+                    item.name, mono.signature.ident,
+                ),
+            ));
+        }
+        if previous.iter().any(|other| other.name == item.name) {
+            return Err(syn::Error::new_spanned(
+                &item.name,
+                format!(
+                    "\
+method `{}` is declared by more than one of the trait and the supertraits flattened into the \
+vtable, which would need to have two vtable entries with the same name",
+                    item.name,
+                ),
+            ));
+        }
+        if let Some(rename) = &item.rename {
+            if RESERVED_METHOD_NAMES.iter().any(|name| rename == name) {
+                return Err(syn::Error::new_spanned(
+                rename,
+                format!(
+                    "method `{}` cannot be renamed to `{}`, which is taken by one of the methods generated on the thin trait object struct",
+                    item.name, rename,
+                ),
+            ));
+            }
+        }
+        if let Some(other) = previous.iter().find(|other| {
+            other.inherent_name() == item.inherent_name()
+                && (other.rename.is_some() || item.rename.is_some())
+        }) {
+            let span = item.rename.as_ref().or(other.rename.as_ref()).unwrap();
+            return Err(syn::Error::new_spanned(
+                span,
+                format!(
+                    "the inherent methods calling methods `{}` and `{}` would both be named `{}`",
+                    other.name,
+                    item.name,
+                    item.inherent_name(),
+                ),
+            ));
+        }
+        let predicates = item
+            .where_clause
+            .iter()
+            .flat_map(|where_clause| &where_clause.predicates);
+        for predicate in predicates {
+            if trait_generics.mentioned_by(predicate) {
+                let message = match &item.constant {
+                Some(constant) => format!(
+                    "the type of associated constant `{}` mentions the generic parameters of the trait, which is not supported",
+                    constant,
+                ),
+                None => format!(
+                    "\
+method `{}` has a `where` clause bounding the generic parameters of the trait, which the vtable \
+entry cannot be conditional on; add the bound to the trait instead",
+                    item.name,
+                ),
+            };
+                return Err(syn::Error::new_spanned(predicate, message));
+            }
+        }
+        if item.skip && !item.has_default {
+            return Err(syn::Error::new_spanned(
+            &item.name,
+            format!(
+                "\
+method `{}` is left out of the vtable with `#[thin(skip)]`, but has no default implementation for the \
+thin trait objects to use",
+                item.name,
+            ),
+        ));
+        }
+        if item.tag {
+            if let Some(other) = previous.iter().find(|other| other.tag) {
+                return Err(syn::Error::new_spanned(
+                &item.name,
+                format!(
+                    "methods `{}` and `{}` are both marked with `#[thin(tag)]`, but `downcast_ref_tagged` can only compare one tag",
+                    other.name, item.name,
+                ),
+            ));
+            }
+            if item.has_default && !self.dispatch_defaults {
+                return Err(syn::Error::new_spanned(
+                &item.name,
+                format!(
+                    "\
+method `{}` is marked with `#[thin(tag)]`, but its default implementation isn't dispatched through \
+the vtable with `dispatch_defaults = false`, so the thin trait objects wouldn't return the tag of the \
+stored value",
+                    item.name,
+                ),
+            ));
+            }
+        }
+        // Default implementations which aren't dispatched through the vtable are left alone.
+        if item.has_default && (!self.dispatch_defaults || item.skip) {
+            let reason = if item.skip {
+                "`#[thin(skip)]`"
+            } else {
+                "`dispatch_defaults = false`"
+            };
+            return match &item.rename {
+            Some(rename) => Err(syn::Error::new_spanned(
+                rename,
+                format!(
+                    "\
+method `{}` has a default implementation, which isn't dispatched through the vtable and thus has no \
+inherent method to rename with {}",
+                    item.name, reason,
+                ),
+            )),
+            None => Ok(()),
+        };
+        }
+        if let Some(abi) = vtable_abi {
+            check_abi_consistency(slice::from_ref(item), abi)?;
+        }
+        self.storage.check_item(item)?;
+        // The thunks call the methods on a copy of the value, which must not be borrowed beyond the
+        // call or pinned.
+        if let Some((repr, _)) = packed {
+            let conflict = if item.receiver_kind() == Some(ReceiverKind::Pinned) {
+                Some("takes `self: Pin<&mut Self>`")
+            } else if item.asyncness.is_some() {
+                Some("is async")
+            } else if item.receiver().is_some() && item.borrows_from_receiver() {
+                Some("returns a value borrowing from `self`")
+            } else {
+                None
+            };
+            if let Some(conflict) = conflict {
+                return Err(syn::Error::new_spanned(
+                    &item.name,
+                    format!(
+                        "\
+method `{}` {}, which is not supported with `repr = {}`, since the value can only be accessed by \
+copying it out of the representation struct",
+                        item.name,
+                        conflict,
+                        repr.to_token_stream(),
+                    ),
+                ));
+            }
+        }
+        item.check_ffi_safe_trait_objects(vtable_abi)?;
+        if let (false, Some(asyncness)) = (self.async_methods, item.asyncness) {
+            return Err(syn::Error::new(
+            asyncness.span,
+            "async methods require `async = true`, which boxes the future returned by every call",
+        ));
         }
+        Ok(())
+    }
+    /// Checks that the options can be combined with each other and with the items of the trait.
+    fn validate(&self, stash: &StageStash) -> syn::Result<()> {
+        let vtable_items = &stash.vtable_items;
+        let extra_entries = &stash.extra_entries;
+        let supertrait_entries = &stash.supertrait_entries;
+        let trait_generics = &stash.trait_generics;
+        let trait_lifetimes = trait_generics.lifetimes();
+        if let Some(error_code) = &self.error_code {
+            // The entries get the trait to convert the errors with if they use a foreign ABI.
+            if !vtable_items.iter().any(|item| item.error_code.is_some()) {
+                return Err(syn::Error::new_spanned(
+                    error_code,
+                    "`error_code` only applies to vtable entries with a foreign ABI, which none of the methods use; set one with the `abi` option",
+                ));
+            }
+        }
+        // Cloning the value would need somewhere to put the clone.
+        if self.storage == Storage::Buffer && extra_entries.contains(&ExtraEntry::Clone) {
+            return Err(syn::Error::new_spanned(
+                &stash.trait_name,
+                "`Clone` for the thin trait object is not supported with `storage = \"buffer\"`, since there is no buffer to put the clone into",
+            ));
+        }
+        // Comparing values of two different types would be undefined behavior, and the type ID is the
+        // only way to tell whether the types are the same.
+        if extra_entries.contains(&ExtraEntry::PartialEq)
+            && !extra_entries.contains(&ExtraEntry::TypeId)
+        {
+            let error = "`PartialEq` for the thin trait object requires `type_id = true`, which is used to check that both values are of the same type before comparing them";
+            return Err(match &self.eq {
+                Some(eq) => syn::Error::new_spanned(eq, error),
+                None => syn::Error::new_spanned(&stash.trait_name, error),
+            });
+        }
+        // Without `PartialEq`, nothing would guarantee that equal values have equal hashes.
+        if extra_entries.contains(&ExtraEntry::Hash)
+            && !extra_entries.contains(&ExtraEntry::PartialEq)
+        {
+            let error = "`Hash` for the thin trait object requires `eq = true`, since `Hash` is only meaningful together with `PartialEq` and `Eq`";
+            return Err(match &self.hash {
+                Some(hash) => syn::Error::new_spanned(hash, error),
+                None => syn::Error::new_spanned(&stash.trait_name, error),
+            });
+        }
+        // Values of different types are ordered by their type IDs, and those of the same type using
+        // the `cmp` entry, which has to agree with the `eq` one.
+        if let Some(ord) = &self.ord {
+            if !extra_entries.contains(&ExtraEntry::TypeId) {
+                return Err(syn::Error::new_spanned(
+                    ord,
+                    "`Ord` for the thin trait object requires `type_id = true`, which is used to order values of different types by their type IDs before comparing values of the same type",
+                ));
+            }
+            if !extra_entries.contains(&ExtraEntry::PartialEq) {
+                return Err(syn::Error::new_spanned(
+                    ord,
+                    "`Ord` for the thin trait object requires `eq = true`, since `Ord` has `PartialEq` and `Eq` as supertraits",
+                ));
+            }
+        }
+        // Traits with associated constants are not object-safe.
+        if let Some(constant) = vtable_items.iter().find_map(|item| item.constant.as_ref()) {
+            if extra_entries.contains(&ExtraEntry::AsDyn)
+                || extra_entries.contains(&ExtraEntry::IntoBoxDyn)
+            {
+                return Err(syn::Error::new_spanned(
+                    constant,
+                    "`as_dyn` and `box_dyn` are not supported for traits with associated constants, which cannot be made into `dyn` trait objects",
+                ));
+            }
+        }
+        // Neither are traits with generic methods.
+        if let Some(mono) = vtable_items.iter().find_map(|item| item.mono.as_ref()) {
+            if extra_entries.contains(&ExtraEntry::AsDyn)
+                || extra_entries.contains(&ExtraEntry::IntoBoxDyn)
+            {
+                return Err(syn::Error::new_spanned(
+                    &mono.signature.ident,
+                    "`as_dyn` and `box_dyn` are not supported for traits with generic methods, which cannot be made into `dyn` trait objects",
+                ));
+            }
+        }
+        // The entries for those return a `dyn` trait object, whose type can't be written without the
+        // lifetimes.
+        if let Some(lifetime) = trait_lifetimes.first() {
+            if extra_entries.contains(&ExtraEntry::AsDyn)
+                || extra_entries.contains(&ExtraEntry::IntoBoxDyn)
+            {
+                return Err(syn::Error::new_spanned(
+                    lifetime,
+                    "`as_dyn` and `box_dyn` are not supported for traits with lifetime parameters, which the vtable is not generic over",
+                ));
+            }
+        }
+        if let (true, Some(allocator_api)) = (self.storage != Storage::Box, &self.allocator_api) {
+            return Err(syn::Error::new_spanned(
+                allocator_api,
+                "custom allocators are only supported with `storage = \"box\"`",
+            ));
+        }
+        if self.allocator_api.is_some() {
+            // The thunks consuming the value don't know about the allocator which has to deallocate it.
+            let by_value = vtable_items
+                .iter()
+                .find(|item| item.receiver_kind().map(ReceiverKind::is_by_value) == Some(true));
+            if let Some(item) = by_value {
+                return Err(syn::Error::new_spanned(
+                    item.receiver(),
+                    "methods taking `self` by value are not supported with custom allocators",
+                ));
+            }
+        }
+        if let Some(box_dyn) = &self.box_dyn {
+            // The value is moved out of its allocation, which only a unique owner can do, and the
+            // allocation is freed with the global allocator.
+            let mut error = if self.storage != Storage::Box {
+                Some("`box_dyn = true` is only supported with `storage = \"box\"`".to_string())
+            } else if self.allocator_api.is_some() {
+                Some("`box_dyn = true` is not supported with custom allocators".to_string())
+            } else {
+                None
+            };
+            // `Box<dyn Trait>` has to be able to store any of the values, but can only pass through
+            // what the trait itself requires.
+            let unsupported_entry = extra_entries.iter().find(|entry| match entry {
+                ExtraEntry::Clone | ExtraEntry::PartialEq | ExtraEntry::Hash | ExtraEntry::Ord => {
+                    true
+                }
+                ExtraEntry::Debug | ExtraEntry::Display => !supertrait_entries.contains(entry),
+                _ => false,
+            });
+            if let (None, Some(entry)) = (&error, unsupported_entry) {
+                error = Some(format!(
+                    "`box_dyn = true` cannot be combined with the `{}` entry, since `Box<dyn {}>` cannot implement the trait it requires the stored value to implement",
+                    entry.field_name(),
+                    stash.trait_name,
+                ));
+            }
+            if let Some(error) = error {
+                return Err(syn::Error::new_spanned(box_dyn, error));
+            }
+            // Those can't be called on `dyn Trait` to forward them.
+            let by_value = vtable_items
+                .iter()
+                .find(|item| item.receiver_kind() == Some(ReceiverKind::Value));
+            if let Some(item) = by_value {
+                return Err(syn::Error::new_spanned(
+                    item.receiver(),
+                    "methods taking `self` by value are not supported with `box_dyn = true`, since they cannot be called on the `Box<dyn Trait>` stored by `from_box_dyn`",
+                ));
+            }
+            // `Box<dyn Trait>` is `Unpin`, so pinning it doesn't pin the trait object inside.
+            let pinned = vtable_items
+                .iter()
+                .find(|item| item.receiver_kind() == Some(ReceiverKind::Pinned));
+            if let Some(item) = pinned {
+                return Err(syn::Error::new_spanned(
+                    item.receiver(),
+                    "methods taking `self: Pin<&mut Self>` are not supported with `box_dyn = true`, since pinning the `Box<dyn Trait>` stored by `from_box_dyn` doesn't pin the value inside of it",
+                ));
+            }
+        }
+        if let Some(from_parts) = &self.from_parts {
+            // The parts are stored like a value of a concrete type, which has to implement the trait
+            // along with everything the vtable entries require of the stored value.
+            let storage = self.storage;
+            let unsupported_entry = extra_entries
+                .iter()
+                .find(|entry| **entry != ExtraEntry::TypeId && entry.bound(storage).is_some());
+            let error = if storage == Storage::Buffer {
+                Some("`from_parts = true` is not supported with `storage = \"buffer\"`".to_string())
+            } else if !trait_generics.is_empty() {
+                Some("`from_parts = true` is not supported for generic traits and traits with associated types".to_string())
+            } else if vtable_items.iter().any(|item| item.constant.is_some()) {
+                Some("`from_parts = true` is not supported for traits with associated constants, whose values cannot be provided at runtime".to_string())
+            } else if vtable_items.iter().any(|item| item.tag) {
+                Some("`from_parts = true` cannot be combined with `#[thin(tag)]`, since the parts are stored in place of a value of the type which the tag stands for".to_string())
+            } else {
+                unsupported_entry.map(|entry| {
+                    format!(
+                        "`from_parts = true` cannot be combined with the `{}` entry, since the parts cannot implement the trait it requires the stored value to implement",
+                        entry.field_name(),
+                    )
+                })
+            };
+            if let Some(error) = error {
+                return Err(syn::Error::new_spanned(from_parts, error));
+            }
+        }
+        if let Some((repr, modifier)) = self
+            .repr
+            .as_ref()
+            .filter(|(_, modifier)| modifier.is_packed())
+        {
+            // Those need references to the fields of the representation struct, which may be
+            // misaligned, or lay it out on their own.
+            if self.storage != Storage::Box {
+                return Err(syn::Error::new_spanned(
+                    repr,
+                    format!(
+                        "`repr = \"{}\"` is only supported with `storage = \"box\"`",
+                        modifier.to_token_stream(),
+                    ),
+                ));
+            }
+            let conflict = if self.inline_vtable {
+                Some("`vtable = \"inline\"`".to_string())
+            } else if self.header.is_some() {
+                Some("a header".to_string())
+            } else if self.shared_drop.is_some() {
+                Some("the shared drop thunk".to_string())
+            } else if self.allocator_api.is_some() {
+                Some("custom allocators".to_string())
+            } else if self.embedded.is_some() {
+                Some("embedded storage".to_string())
+            } else if self.from_parts.is_some() {
+                Some("`from_parts = true`".to_string())
+            } else if vtable_items.iter().any(|item| item.tag) {
+                Some("`#[thin(tag)]`".to_string())
+            } else {
+                extra_entries
+                    .iter()
+                    .find(|entry| {
+                        !matches!(
+                            entry,
+                            ExtraEntry::TypeName | ExtraEntry::IntoBoxDyn | ExtraEntry::Dealloc
+                        )
+                    })
+                    .map(|entry| format!("the `{}` entry", entry.field_name()))
+            };
+            if let Some(conflict) = conflict {
+                return Err(syn::Error::new_spanned(
+                    repr,
+                    format!(
+                        "`repr = \"{}\"` cannot be combined with {}, since the fields of the packed representation struct may be misaligned",
+                        modifier.to_token_stream(),
+                        conflict,
+                    ),
+                ));
+            }
+        }
+        if let (Storage::Buffer, Some(default)) = (self.storage, &self.default) {
+            return Err(syn::Error::new_spanned(
+                default,
+                "`default = ...` is not supported with `storage = \"buffer\"`, since there is no buffer to store the default value in",
+            ));
+        }
+        if let Some(prefix) = &self.prefix {
+            if prefix
+                .to_string()
+                .to_ascii_lowercase()
+                .starts_with(DEFAULT_PREFIX)
+            {
+                return Err(syn::Error::new_spanned(
+                    prefix,
+                    format!(
+                        "the prefix must not start with the default one, `{}`",
+                        DEFAULT_PREFIX
+                    ),
+                ));
+            }
+        }
+        if let (true, Some(thin_mut)) = (self.storage.is_shared(), &self.thin_mut) {
+            return Err(syn::Error::new_spanned(
+                &thin_mut.name,
+                "mutably borrowed thin trait objects are not supported with shared storage",
+            ));
+        }
+        if let (true, Some(inline_storage)) = (self.storage != Storage::Box, &self.inline_storage) {
+            return Err(syn::Error::new_spanned(
+                &inline_storage.name,
+                "inline storage is only supported with `storage = \"box\"`",
+            ));
+        }
+        if let Some(shared_drop) = &self.shared_drop {
+            let error = if self.storage != Storage::Box {
+                Some("the shared drop thunk is only supported with `storage = \"box\"`")
+            } else if !self.store_layout {
+                Some("the shared drop thunk reads the layout of the stored value from the vtable, which requires `store_layout = true`")
+            } else {
+                None
+            };
+            if let Some(error) = error {
+                return Err(syn::Error::new_spanned(shared_drop, error));
+            }
+        }
+        if let Some(manual_drop) = &self.manual_drop {
+            // All of those free the value on their own, through the drop thunk or otherwise.
+            let conflict = if self.storage.is_shared() {
+                Some("`storage = \"arc\"` or `storage = \"rc\"`")
+            } else if self.storage == Storage::Buffer {
+                Some("`storage = \"buffer\"`")
+            } else if self.allocator_api.is_some() {
+                Some("custom allocators")
+            } else if self.inline_storage.is_some() {
+                Some("inline storage")
+            } else if self.static_storage.is_some() {
+                Some("static storage")
+            } else if self.shared_drop.is_some() {
+                Some("the shared drop thunk")
+            } else {
+                None
+            };
+            if let Some(conflict) = conflict {
+                return Err(syn::Error::new_spanned(
+                    manual_drop,
+                    format!("`manual_drop = true` cannot be combined with {}", conflict),
+                ));
+            }
+        }
+        if let Some(header) = &self.header {
+            // Those construct the representation struct without being given a header for it.
+            let conflict = if self.allocator_api.is_some() {
+                Some("custom allocators")
+            } else if self.inline_storage.is_some() {
+                Some("inline storage")
+            } else if self.static_storage.is_some() {
+                Some("static storage")
+            } else if self.embedded.is_some() {
+                Some("embedded storage")
+            } else if self.box_dyn.is_some() {
+                Some("`box_dyn = true`")
+            } else if !self.export_ctors.is_empty() {
+                Some("exported constructors")
+            } else {
+                None
+            };
+            if let Some(conflict) = conflict {
+                return Err(syn::Error::new_spanned(
+                    header,
+                    format!("a header cannot be combined with {}", conflict),
+                ));
+            }
+            // The representation struct requires the header to be `Copy`, which would otherwise have
+            // to be repeated on everything using it.
+            if trait_generics.mentioned_by(&parse_quote!(#header: ::core::marker::Copy)) {
+                return Err(syn::Error::new_spanned(
+                    header,
+                    "the header type cannot mention the generic parameters of the trait",
+                ));
+            }
+            if let Some(item) = vtable_items.iter().find(|item| item.name == "header") {
+                return Err(syn::Error::new_spanned(
+                    &item.name,
+                    "`header` has the same name as the method reading the header of the thin trait object",
+                ));
+            }
+        }
+        if let (None, Some(inline_capacity)) = (&self.inline_storage, &self.inline_capacity) {
+            return Err(syn::Error::new_spanned(
+                inline_capacity,
+                "`inline_capacity` requires `inline_storage(...)` to be specified",
+            ));
+        }
+        // Unlike `drop`, the other generated vtable entries only exist if enabled, so a collision is
+        // better reported than worked around.
+        for item in vtable_items {
+            let collides = (self.store_layout && (item.name == "size" || item.name == "align"))
+                || extra_entries
+                    .iter()
+                    .any(|entry| item.name == entry.field_name());
+            if collides {
+                return Err(syn::Error::new_spanned(
+                    &item.name,
+                    format!(
+                        "method `{}` has the same name as a vtable entry generated because of the macro's options",
+                        item.name,
+                    ),
+                ));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -173,27 +1500,73 @@ pub struct StageStash {
     pub repr_name: Ident,
     pub trait_object_name: Ident,
     pub vtable_items: Vec<VtableItem>,
+    /// The ABI applied to vtable entries which don't specify one in the trait definition.
+    pub vtable_abi: Option<Abi>,
+    /// Additional vtable entries, in the order in which they appear in the vtable.
+    pub extra_entries: Vec<ExtraEntry>,
+    /// Additional vtable entries which are there because the trait requires them as supertraits.
+    pub supertrait_entries: Vec<ExtraEntry>,
+    /// The smart pointer type which owns the stored value.
+    pub storage: Storage,
+    /// Whether the thin trait object can also be allocated using custom allocators.
+    pub allocator_api: bool,
+    /// Whether the thin trait object can also point to a value in static storage, which is immutable.
+    pub static_storage: bool,
+    /// Whether the methods which call vtable entries are marked `#[inline(always)]` rather than `#[inline]`.
+    pub inline_always: bool,
+    /// The name of the vtable entry for the destructor, `drop` unless a trait method already has that name.
+    pub drop_name: Ident,
+    /// The supertraits whose methods were flattened into the vtable, which get implemented alongside the trait.
+    pub flattened_supertraits: Vec<Path>,
+    pub trait_generics: TraitGenerics,
+    /// Set if the trait is an `unsafe trait`, which makes its implementations `unsafe impl`s.
+    pub trait_unsafety: Option<Unsafe>,
+    /// Whether the trait is implemented by a blanket implementation instead of being implemented for the thin trait object structs.
+    pub blanket_impl: bool,
+    /// The type of the header stored between the vtable and the value, if any.
+    pub header: Option<Type>,
+    /// The modifier added to the `#[repr(C)]` of the representation struct, if any.
+    pub repr_modifier: Option<ReprModifier>,
 }
-
-fn path_to_box() -> Path {
-    let mut segments = Punctuated::new();
-    let mut push_segment = |name| {
-        segments.push(PathSegment {
-            ident: Ident::new(name, Span::call_site()),
-            arguments: PathArguments::None,
-        });
-    };
-
-    #[cfg(feature = "std")]
-    push_segment("std");
-    #[cfg(not(feature = "std"))]
-    push_segment("alloc");
-
-    push_segment("boxed");
-    push_segment("Box");
-
-    Path {
-        leading_colon: Some(Default::default()),
-        segments,
+impl StageStash {
+    /// The trait along with its generic arguments and associated types, for use in bounds.
+    pub fn trait_path(&self) -> TokenStream {
+        let trait_name = &self.trait_name;
+        let args = self.trait_generics.trait_args(true);
+        quote! { #trait_name #args }
+    }
+    /// The trait along with its generic arguments but without its associated types, for use in implementations and qualified paths.
+    pub fn trait_impl_path(&self) -> TokenStream {
+        let trait_name = &self.trait_name;
+        let args = self.trait_generics.trait_args(false);
+        quote! { #trait_name #args }
+    }
+    /// The representation struct for the given stored type along with the generic arguments of the trait, for use as a type.
+    pub fn repr_type(&self, value: &impl ToTokens) -> TokenStream {
+        let repr_name = &self.repr_name;
+        let args = self.trait_generics.args_with(value.to_token_stream());
+        quote! { #repr_name #args }
+    }
+    /// Returns whether the fields of the representation struct may be misaligned, in which case the vtable pointer is read with `read_unaligned` and values are only ever copied out of it.
+    pub fn is_packed(&self) -> bool {
+        matches!(self.repr_modifier, Some(ReprModifier::Packed(..)))
+    }
+    /// The bounds which stored values need to satisfy on top of implementing the trait: the traits required by the extra entries, and `Copy` if the representation struct is packed.
+    pub fn value_bounds(&self) -> Vec<TokenStream> {
+        let mut bounds = self
+            .extra_entries
+            .iter()
+            .filter_map(|entry| entry.bound(self.storage))
+            .collect::<Vec<_>>();
+        if self.is_packed() {
+            bounds.push(quote! { ::core::marker::Copy });
+        }
+        bounds
+    }
+    /// The vtable struct along with its generic arguments, for use as a type.
+    pub fn vtable_type(&self) -> TokenStream {
+        let vtable_name = &self.vtable_name;
+        let args = self.trait_generics.without_lifetimes().args();
+        quote! { #vtable_name #args }
     }
 }
@@ -0,0 +1,328 @@
+//! Generates the C header describing the vtable struct and the thin trait object it belongs to, enabled by the `c_header` option.
+//!
+//! The header is generated from the same list of fields as the vtable struct itself, so the order of the fields always matches. Instead of being written to a file during macro expansion, which would depend on the working directory of the compiler and wouldn't be tracked by incremental compilation, it is provided as an associated constant which a build step can write out.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    ext::IdentExt,
+    Abi,
+    GenericArgument,
+    LitStr,
+    Path,
+    PathArguments,
+    ReturnType,
+    Type,
+    TypeBareFn,
+    TypePath,
+};
+
+use crate::{
+    attr::StageStash,
+    extra_entries::ExtraEntry,
+    vtable::{abi_name, VtableField, VtableFieldKind, VtableFnArg},
+};
+
+pub fn generate_c_header(
+    stash: &StageStash,
+    fields: &[VtableField],
+    drop_abi: Option<&Abi>,
+    inline_vtable: bool,
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        trait_name,
+        vtable_name,
+        trait_object_name,
+        header,
+        ..
+    } = stash;
+    let mut declarations = Vec::with_capacity(fields.len());
+    for field in fields {
+        let name = field.name.unraw().to_string();
+        let declaration = match &field.kind {
+            VtableFieldKind::Layout => format!("size_t {};", name),
+            VtableFieldKind::Method(item) if !item.cfg_attributes.is_empty() => {
+                return Err(syn::Error::new_spanned(
+                    &item.cfg_attributes[0],
+                    "the C header cannot be generated for traits with methods which are only present under some `cfg`",
+                ));
+            }
+            VtableFieldKind::Method(item) => {
+                check_c_abi(item.abi.as_ref(), &item.name, || {
+                    format!("method `{}`", item.name.unraw())
+                })?;
+                let mut params = item
+                    .inputs
+                    .iter()
+                    .map(|input| match input {
+                        VtableFnArg::Normal(arg) => Ok(declare(&c_type(&arg.ty)?, "")),
+                        // Already replaced with raw pointers.
+                        _ => unreachable!(),
+                    })
+                    .collect::<syn::Result<Vec<_>>>()?;
+                if item.variadic.is_some() {
+                    params.push("...".to_string());
+                }
+                if params.is_empty() {
+                    params.push("void".to_string());
+                }
+                let output = match &item.output {
+                    ReturnType::Default => CType::named("void"),
+                    ReturnType::Type(_, ty) => c_type(ty)?,
+                };
+                let declarator = format!("(*{})({})", name, params.join(", "));
+                format!("{};", declare(&output, &declarator))
+            }
+            VtableFieldKind::Drop => {
+                check_c_abi(drop_abi, &field.name, || "the `drop` entry".to_string())?;
+                format!("void (*{})(void *);", name)
+            }
+            VtableFieldKind::Extra(entry) => match entry {
+                ExtraEntry::Clone
+                | ExtraEntry::PartialEq
+                | ExtraEntry::Ord
+                | ExtraEntry::Dealloc => {
+                    check_c_abi(drop_abi, &field.name, || format!("the `{}` entry", name))?;
+                    match entry {
+                        ExtraEntry::Clone => format!("void *(*{})(void *);", name),
+                        ExtraEntry::PartialEq => format!("bool (*{})(void *, void *);", name),
+                        // `Ordering` is -1, 0 or 1.
+                        ExtraEntry::Ord => format!("int8_t (*{})(void *, void *);", name),
+                        _ => format!("void (*{})(void *);", name),
+                    }
+                }
+                // Those take or return Rust types, so C code can only pass them around.
+                ExtraEntry::Debug | ExtraEntry::Display | ExtraEntry::Hash => {
+                    format!(
+                        "void (*{})(void); /* takes Rust types, not callable from C */",
+                        name
+                    )
+                }
+                ExtraEntry::TypeId
+                | ExtraEntry::TypeName
+                | ExtraEntry::AsDyn
+                | ExtraEntry::IntoBoxDyn => {
+                    format!(
+                        "void (*{})(void); /* uses the Rust ABI, not callable from C */",
+                        name
+                    )
+                }
+            },
+        };
+        declarations.push(declaration);
+    }
+
+    let vtable_field = if inline_vtable {
+        format!("{} vtable;", vtable_name)
+    } else {
+        format!("const {} *vtable;", vtable_name)
+    };
+    let header_field = match header {
+        Some(header) => format!("\n    {};", declare(&c_type(header)?, "header")),
+        None => String::new(),
+    };
+    let guard = format!("{}_H", vtable_name.to_string().to_uppercase());
+    let mut header = String::new();
+    header.push_str(&format!(
+        "/* Generated by #[thin_trait_object] for the `{}` trait, do not edit. */\n",
+        trait_name,
+    ));
+    header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+    header.push_str("#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n\n");
+    header.push_str(&format!("typedef struct {} {{\n", vtable_name));
+    for declaration in &declarations {
+        header.push_str(&format!("    {}\n", declaration));
+    }
+    header.push_str(&format!("}} {};\n\n", vtable_name));
+    header.push_str(&format!(
+        "/* The beginning of a thin trait object, followed by the stored value. A `{0} *` is what a `{0}` is in Rust. */\n",
+        trait_object_name,
+    ));
+    header.push_str(&format!(
+        "typedef struct {0} {{\n    {1}{2}\n}} {0};\n\n",
+        trait_object_name, vtable_field, header_field,
+    ));
+    header.push_str(&format!("#endif /* {} */\n", guard));
+
+    let header = LitStr::new(&header, Span::call_site());
+    Ok(quote! {
+        impl #vtable_name {
+            /// A C header declaring the vtable struct and the beginning of the thin trait object it belongs to, with the fields in the same order and with the same types as in Rust.
+            pub const C_HEADER: &'static str = #header;
+        }
+    })
+}
+
+/// Makes sure that a vtable entry which C code is supposed to call uses the C ABI, or the `"C-unwind"` one, which only differs in what happens when a panic reaches the entry.
+fn check_c_abi(
+    abi: Option<&Abi>,
+    span: &impl ToTokens,
+    description: impl FnOnce() -> String,
+) -> syn::Result<()> {
+    let found = abi.map_or_else(|| "Rust".to_string(), abi_name);
+    if found == "C" || found == "C-unwind" {
+        return Ok(());
+    }
+    Err(syn::Error::new_spanned(
+        span,
+        format!(
+            "\
+{} uses the \"{}\" ABI, but the C header requires all vtable entries which C code can call to use \
+the \"C\" or \"C-unwind\" ABI",
+            description(),
+            found,
+        ),
+    ))
+}
+
+/// A C type, split into what comes before and after the declarator when declaring something of that type, since the name of a function pointer goes in the middle of its type.
+struct CType {
+    prefix: String,
+    suffix: String,
+}
+impl CType {
+    fn named(name: &str) -> Self {
+        Self {
+            prefix: name.to_string(),
+            suffix: String::new(),
+        }
+    }
+    fn spaced_prefix(&self) -> String {
+        if self.prefix.ends_with('*') {
+            self.prefix.clone()
+        } else {
+            format!("{} ", self.prefix)
+        }
+    }
+}
+
+/// Converts a Rust type to the C type with the same layout, which is either a primitive type, a pointer, a function pointer or assumed to be a type with the same name declared in C.
+fn c_type(ty: &Type) -> syn::Result<CType> {
+    let name = match ty {
+        Type::Paren(ty) => return c_type(&ty.elem),
+        Type::Group(ty) => return c_type(&ty.elem),
+        Type::Tuple(tuple) if tuple.elems.is_empty() => "void".to_string(),
+        Type::Never(..) => "void".to_string(),
+        Type::Ptr(ptr) => return pointer_to(&ptr.elem, ptr.mutability.is_none()),
+        Type::Reference(reference) => {
+            return pointer_to(&reference.elem, reference.mutability.is_none())
+        }
+        Type::BareFn(function) => return function_pointer(function),
+        Type::Path(TypePath { qself: None, path }) if option_of_function(path).is_some() => {
+            return function_pointer(option_of_function(path).unwrap())
+        }
+        Type::Path(TypePath { qself: None, path }) => {
+            let last_segment = match path.segments.last() {
+                Some(segment) if segment.arguments.is_empty() => segment,
+                _ => return Err(unrepresentable(ty)),
+            };
+            let name = last_segment.ident.unraw().to_string();
+            let primitive = match name.as_str() {
+                "u8" => "uint8_t",
+                "u16" => "uint16_t",
+                "u32" => "uint32_t",
+                "u64" => "uint64_t",
+                "i8" => "int8_t",
+                "i16" => "int16_t",
+                "i32" => "int32_t",
+                "i64" => "int64_t",
+                "usize" => "size_t",
+                "isize" => "ptrdiff_t",
+                "f32" | "c_float" => "float",
+                "f64" | "c_double" => "double",
+                "bool" => "bool",
+                "char" => "uint32_t",
+                "c_void" => "void",
+                "c_char" => "char",
+                "c_schar" => "signed char",
+                "c_uchar" => "unsigned char",
+                "c_short" => "short",
+                "c_ushort" => "unsigned short",
+                "c_int" => "int",
+                "c_uint" => "unsigned int",
+                "c_long" => "long",
+                "c_ulong" => "unsigned long",
+                "c_longlong" => "long long",
+                "c_ulonglong" => "unsigned long long",
+                _ => return Ok(CType::named(&name)),
+            };
+            primitive.to_string()
+        }
+        _ => return Err(unrepresentable(ty)),
+    };
+    Ok(CType::named(&name))
+}
+/// Declares something of the given C type, with the declarator being the name and everything after it, or an empty one for a parameter without a name.
+fn declare(ty: &CType, declarator: &str) -> String {
+    format!("{}{}{}", ty.spaced_prefix(), declarator, ty.suffix)
+        .trim_end()
+        .to_string()
+}
+fn pointer_to(pointee: &Type, is_const: bool) -> syn::Result<CType> {
+    let CType { prefix, suffix } = c_type(pointee)?;
+    // The qualifier of a pointer to a pointer goes after the asterisk of the pointee.
+    let prefix = match (is_const, prefix.ends_with('*')) {
+        (true, true) => format!("{}const *", prefix),
+        (true, false) => format!("const {} *", prefix),
+        (false, true) => format!("{}*", prefix),
+        (false, false) => format!("{} *", prefix),
+    };
+    Ok(CType { prefix, suffix })
+}
+/// Function pointers are declared with the declarator between the return type and the parameters, which is where the one of a function pointer returned by another one goes as well.
+fn function_pointer(function: &TypeBareFn) -> syn::Result<CType> {
+    let found = function
+        .abi
+        .as_ref()
+        .map_or_else(|| "Rust".to_string(), abi_name);
+    if found != "C" && found != "C-unwind" {
+        return Err(syn::Error::new_spanned(
+            function,
+            format!(
+                "function pointers using the \"{}\" ABI have no equivalent in C, so they cannot be used in a trait with `c_header = true`",
+                found,
+            ),
+        ));
+    }
+    let mut params = function
+        .inputs
+        .iter()
+        .map(|input| Ok(declare(&c_type(&input.ty)?, "")))
+        .collect::<syn::Result<Vec<_>>>()?;
+    if function.variadic.is_some() {
+        params.push("...".to_string());
+    }
+    if params.is_empty() {
+        params.push("void".to_string());
+    }
+    let output = match &function.output {
+        ReturnType::Default => CType::named("void"),
+        ReturnType::Type(_, ty) => c_type(ty)?,
+    };
+    Ok(CType {
+        prefix: format!("{}(*", output.spaced_prefix()),
+        suffix: format!(")({}){}", params.join(", "), output.suffix),
+    })
+}
+/// Returns the function pointer type inside of an `Option`, which has the same layout as the function pointer with null standing for `None`.
+fn option_of_function(path: &Path) -> Option<&TypeBareFn> {
+    let last_segment = path.segments.last()?;
+    if last_segment.ident != "Option" {
+        return None;
+    }
+    match &last_segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match arguments.args.first()? {
+                GenericArgument::Type(Type::BareFn(function)) => Some(function),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+fn unrepresentable(ty: &Type) -> syn::Error {
+    syn::Error::new_spanned(
+        ty,
+        "this type has no equivalent in C, so it cannot be used in a trait with `c_header = true`",
+    )
+}
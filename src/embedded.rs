@@ -0,0 +1,124 @@
+//! Generates the embedded storage struct, enabled by the `embedded` option, which wraps a representation struct for a value of a known type so that it can be a field of other structures, as well as the accessors which hand out borrowed thin trait objects pointing to it.
+//!
+//! The representation struct inside uses the regular vtable, since only the boxed thin trait object ever calls the `drop` entry, and the embedded storage struct never creates one — the value is dropped along with the struct instead.
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{attr::StageStash, options::OutputAdditions, trait_object::check_attribute};
+
+pub fn generate_embedded(
+    stash: &mut StageStash,
+    additions: &OutputAdditions,
+    inline_vtable: bool,
+    thin_ref: Option<&Ident>,
+    thin_mut: Option<&Ident>,
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        repr_name,
+        storage,
+        trait_generics,
+        ..
+    } = &*stash;
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let value_generic = trait_generics.unused_ident("T");
+    let value_repr_type = stash.repr_type(&value_generic);
+    let where_clause = trait_generics.where_clause();
+    let OutputAdditions {
+        attributes,
+        visibility,
+        name,
+    } = additions;
+    attributes.iter().try_for_each(check_attribute)?;
+    // The entries of the vtable expect the representation struct to be inside of the allocation
+    // made by `Arc` or `Rc`, next to the reference counts.
+    if storage.is_shared() {
+        return Err(syn::Error::new_spanned(
+            name,
+            "embedded storage is only supported with `storage = \"box\"`",
+        ));
+    }
+
+    let vtable_field_val = if inline_vtable {
+        quote! { <#value_repr_type>::__THINTRAITOBJECTMACRO_VTABLE }
+    } else {
+        quote! { <#value_repr_type>::__thintraitobjectmacro_repr_vtable_ref() }
+    };
+    let vtable_getter_impl = if inline_vtable {
+        quote! { &self.0.__thintraitobjectmacro_repr_vtable }
+    } else {
+        quote! { self.0.__thintraitobjectmacro_repr_vtable }
+    };
+    let extra_bounds = stash.value_bounds();
+    let def_params = trait_generics.params_with_defaults(quote! { #value_generic: #trait_path });
+    let params = trait_generics.params_with(quote! {
+        #value_generic: #trait_path #(+ #extra_bounds)*
+    });
+    let args = trait_generics.args_with(value_generic.to_token_stream());
+    let (_, phantom_initializer) = trait_generics.repr_phantom();
+    let args_with_elided = trait_generics.args_with(quote! { '_ });
+    let thin_ref_getter = thin_ref.map(|thin_ref| {
+        quote! {
+            /// Borrows the stored value as a borrowed thin trait object, which points into the embedded storage struct.
+            #[inline]
+            pub fn as_thin_ref(&self) -> #thin_ref #args_with_elided {
+                unsafe { #thin_ref::from_raw(self.as_raw()) }
+            }
+        }
+    });
+    let thin_mut_getter = thin_mut.map(|thin_mut| {
+        quote! {
+            /// Mutably borrows the stored value as a mutably borrowed thin trait object, which points into the embedded storage struct.
+            #[inline]
+            pub fn as_thin_mut(&mut self) -> #thin_mut #args_with_elided {
+                unsafe { #thin_mut::from_raw(self as *mut Self as *mut ()) }
+            }
+        }
+    });
+
+    let result = quote! {
+        #(#attributes)*
+        #[repr(transparent)]
+        #visibility struct #name #def_params (#value_repr_type) #where_clause;
+        impl #params #name #args #where_clause {
+            /// Wraps a value together with its vtable. Usable in constant expressions.
+            #[inline]
+            pub const fn new(val: #value_generic) -> Self {
+                Self(#repr_name {
+                    __thintraitobjectmacro_repr_vtable: #vtable_field_val,
+                    __thintraitobjectmacro_repr_value: val,
+                    #phantom_initializer
+                })
+            }
+            /// Returns a reference to the stored value.
+            #[inline]
+            pub fn get(&self) -> &#value_generic {
+                &self.0.__thintraitobjectmacro_repr_value
+            }
+            /// Returns a mutable reference to the stored value.
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut #value_generic {
+                &mut self.0.__thintraitobjectmacro_repr_value
+            }
+            /// Unwraps the stored value.
+            #[inline]
+            pub fn into_inner(self) -> #value_generic {
+                self.0.__thintraitobjectmacro_repr_value
+            }
+            /// Returns a pointer to the trait object, which points to the embedded storage struct itself and is thus invalidated when it is moved. Only valid for calling `&self` methods through, like the pointer returned by the `as_raw` method of the borrowed thin trait object.
+            #[inline]
+            pub fn as_raw(&self) -> *mut () {
+                self as *const Self as *mut ()
+            }
+            /// Retrieves the vtable of the stored value.
+            #[inline]
+            pub fn vtable(&self) -> &#vtable_type {
+                #vtable_getter_impl
+            }
+            #thin_ref_getter
+            #thin_mut_getter
+        }
+    };
+    Ok(result)
+}
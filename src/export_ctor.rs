@@ -0,0 +1,94 @@
+//! Generates the exported constructors, enabled by the `export_ctor` option, which let foreign code create a boxed thin trait object storing a specific type by calling a function with a known symbol name.
+//!
+//! The value is passed by value through the ABI of the `drop` entry, so its type has to be FFI-safe, which the compiler is told to check by denying the `improper_ctypes_definitions` lint on the function.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Abi, GenericArgument, Ident, PathArguments, Type, TypePath};
+
+use crate::{attr::StageStash, options::ExportedCtor, storage::Storage, vtable::abi_name};
+
+pub fn generate_export_ctor(
+    stash: &mut StageStash,
+    ctor: &ExportedCtor,
+    drop_abi: Option<&Abi>,
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        trait_generics,
+        storage,
+        ..
+    } = &*stash;
+    let ExportedCtor { ty, symbol, .. } = ctor;
+    let name = export_ctor_name(ctor)?;
+    if *storage == Storage::Buffer {
+        return Err(syn::Error::new_spanned(
+            symbol,
+            "exported constructors are not supported with `storage = \"buffer\"`, since they allocate the value",
+        ));
+    }
+    // A function with a symbol name cannot be generic.
+    if !trait_generics.is_empty() {
+        return Err(syn::Error::new_spanned(
+            symbol,
+            "exported constructors are not supported for generic traits and traits with associated types, since they would have to be generic as well",
+        ));
+    }
+    let found = drop_abi.map_or_else(|| "Rust".to_string(), abi_name);
+    if found == "Rust" {
+        return Err(syn::Error::new_spanned(
+            symbol,
+            "exported constructors use the ABI of the `drop` entry, which has to be set to a foreign ABI with `drop_abi`",
+        ));
+    }
+    check_exported_type(ty)?;
+
+    let repr_type = stash.repr_type(ty);
+    let result = quote! {
+        #[no_mangle]
+        #[deny(improper_ctypes_definitions)]
+        pub unsafe #drop_abi fn #name(val: #ty) -> *mut ::core::ffi::c_void {
+            <#repr_type>::__thintraitobjectmacro_repr_create(val) as *mut ::core::ffi::c_void
+        }
+    };
+    Ok(result)
+}
+
+/// The name of the exported constructor, which is also its symbol name.
+pub fn export_ctor_name(ctor: &ExportedCtor) -> syn::Result<Ident> {
+    ctor.symbol.parse::<Ident>().map_err(|_| {
+        syn::Error::new_spanned(
+            &ctor.symbol,
+            "the symbol name of an exported constructor must be a valid identifier",
+        )
+    })
+}
+
+/// Makes sure that the type stored by an exported constructor is named by a plain path without lifetimes, since the thin trait object handed to foreign code isn't bound by any lifetime.
+fn check_exported_type(ty: &Type) -> syn::Result<()> {
+    let path = match ty {
+        Type::Path(TypePath { qself: None, path }) => path,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "the type stored by an exported constructor must be a named struct or enum with a C-compatible layout",
+            ))
+        }
+    };
+    let has_lifetime = path
+        .segments
+        .iter()
+        .any(|segment| match &segment.arguments {
+            PathArguments::AngleBracketed(arguments) => arguments
+                .args
+                .iter()
+                .any(|argument| matches!(argument, GenericArgument::Lifetime(..))),
+            _ => false,
+        });
+    if has_lifetime {
+        return Err(syn::Error::new_spanned(
+            ty,
+            "the type stored by an exported constructor cannot have lifetime arguments, since foreign code may keep the thin trait object around for as long as it wants",
+        ));
+    }
+    Ok(())
+}
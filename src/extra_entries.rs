@@ -0,0 +1,528 @@
+//! Optional vtable entries which don't correspond to any of the trait's methods and are used to implement standard library traits for the trait object struct.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Abi, TraitBound};
+
+use crate::{attr::StageStash, storage::Storage};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExtraEntry {
+    /// `Clone`, implemented by allocating a new trait object with a clone of the stored value.
+    Clone,
+    /// `Debug`, implemented by calling `Debug::fmt` on the stored value.
+    Debug,
+    /// `Display`, implemented by calling `Display::fmt` on the stored value.
+    Display,
+    /// The `TypeId` of the stored type, used for downcasting. Not a trait implementation, but rather a set of inherent methods.
+    TypeId,
+    /// The name of the stored type, for diagnostics. Also a set of inherent methods.
+    TypeName,
+    /// `PartialEq`, implemented by comparing the type IDs and then calling `PartialEq::eq` on the stored values if those match.
+    PartialEq,
+    /// `Hash`, implemented by calling `Hash::hash` on the stored value with the hasher behind a trait object.
+    Hash,
+    /// `PartialOrd` and `Ord`, implemented by comparing the type IDs and then calling `Ord::cmp` on the stored values if those match. Also implements `Eq`.
+    Ord,
+    /// A pointer to the stored value as a regular trait object. Not a trait implementation, but rather a set of inherent methods.
+    AsDyn,
+    /// Moves the stored value into a regular boxed trait object. The methods using it are generated along with the thin trait object struct, since they need to name its lifetime.
+    IntoBoxDyn,
+    /// Drops the stored value and frees the allocation, in place of the `drop` entry which does nothing with `manual_drop = true`. Also an inherent method.
+    Dealloc,
+}
+impl ExtraEntry {
+    /// Finds the extra entry which the supertrait requires, if there is one.
+    pub fn from_supertrait(bound: &TraitBound) -> Option<Self> {
+        let last_segment = bound.path.segments.last()?;
+        if !last_segment.arguments.is_empty() {
+            return None;
+        }
+        let entry = match last_segment.ident.to_string().as_str() {
+            "Clone" => Self::Clone,
+            "Debug" => Self::Debug,
+            "Display" => Self::Display,
+            // `Eq` itself is a marker trait, but it has `PartialEq` as a supertrait.
+            "PartialEq" | "Eq" => Self::PartialEq,
+            "Hash" => Self::Hash,
+            _ => return None,
+        };
+        Some(entry)
+    }
+    pub fn field_name(self) -> Ident {
+        let name = match self {
+            Self::Clone => "clone",
+            Self::Debug => "debug_fmt",
+            Self::Display => "display_fmt",
+            Self::TypeId => "type_id",
+            Self::TypeName => "type_name",
+            Self::PartialEq => "eq",
+            Self::Hash => "hash",
+            Self::Ord => "cmp",
+            Self::AsDyn => "as_dyn",
+            Self::IntoBoxDyn => "into_box_dyn",
+            Self::Dealloc => "dealloc",
+        };
+        Ident::new(name, Span::call_site())
+    }
+    /// Whether the trait implementation also makes sense for the borrowed thin trait object structs.
+    pub fn usable_through_ref(self) -> bool {
+        !matches!(
+            self,
+            Self::Clone | Self::TypeId | Self::IntoBoxDyn | Self::Dealloc
+        )
+    }
+    fn thunk_name(self) -> Ident {
+        format_ident!("__thintraitobjectmacro_repr_{}", self.field_name())
+    }
+    /// The type of the function pointer in the vtable.
+    pub fn field_type(self, abi: Option<&Abi>, trait_path: &TokenStream) -> TokenStream {
+        match self {
+            Self::Clone => quote! {
+                unsafe #abi fn(*mut ::core::ffi::c_void) -> *mut ::core::ffi::c_void
+            },
+            Self::Debug | Self::Display => quote! {
+                unsafe #abi fn(
+                    *mut ::core::ffi::c_void,
+                    &mut ::core::fmt::Formatter<'_>,
+                ) -> ::core::fmt::Result
+            },
+            // `TypeId` can't cross FFI boundaries in a meaningful way anyway, so the ABI is ignored.
+            Self::TypeId => quote! {
+                fn() -> ::core::any::TypeId
+            },
+            // Same story with string slices.
+            Self::TypeName => quote! {
+                fn() -> &'static str
+            },
+            Self::PartialEq => quote! {
+                unsafe #abi fn(*mut ::core::ffi::c_void, *mut ::core::ffi::c_void) -> bool
+            },
+            Self::Hash => quote! {
+                unsafe #abi fn(*mut ::core::ffi::c_void, &mut dyn ::core::hash::Hasher)
+            },
+            // `Ordering` is `#[repr(i8)]`, so this one is FFI-safe.
+            Self::Ord => quote! {
+                unsafe #abi fn(
+                    *mut ::core::ffi::c_void,
+                    *mut ::core::ffi::c_void,
+                ) -> ::core::cmp::Ordering
+            },
+            Self::Dealloc => quote! {
+                unsafe #abi fn(*mut ::core::ffi::c_void)
+            },
+            // Fat pointers aren't FFI-safe either. The vtable has no lifetime to put on the trait
+            // object, so the methods calling the entry shorten it back to the borrow.
+            Self::AsDyn | Self::IntoBoxDyn => quote! {
+                unsafe fn(*mut ::core::ffi::c_void) -> *mut (dyn #trait_path + 'static)
+            },
+        }
+    }
+    /// The bound which the stored type has to satisfy for the entry to be implementable, if any.
+    pub fn bound(self, storage: Storage) -> Option<TokenStream> {
+        let bound = match self {
+            Self::TypeName | Self::AsDyn | Self::IntoBoxDyn | Self::Dealloc => return None,
+            // Shared storage is cloned by reference counting instead.
+            Self::Clone if storage.is_shared() => return None,
+            Self::Clone => quote! { ::core::clone::Clone },
+            Self::Debug => quote! { ::core::fmt::Debug },
+            Self::Display => quote! { ::core::fmt::Display },
+            Self::TypeId => quote! { 'static },
+            Self::PartialEq => quote! { ::core::cmp::PartialEq },
+            Self::Hash => quote! { ::core::hash::Hash },
+            Self::Ord => quote! { ::core::cmp::Ord },
+        };
+        Some(bound)
+    }
+    /// The field initializer for the vtable constant inside the representation struct's `impl` block.
+    pub fn vtable_initializer(self) -> TokenStream {
+        let field_name = self.field_name();
+        // No thunk needed for those, the function itself has the right signature.
+        match self {
+            Self::TypeId => {
+                return quote! {
+                    #field_name: ::core::any::TypeId::of::<__ThinTraitObjectMacro_ReprGeneric0>,
+                };
+            }
+            Self::TypeName => {
+                return quote! {
+                    #field_name: ::core::any::type_name::<__ThinTraitObjectMacro_ReprGeneric0>,
+                };
+            }
+            // The regular drop thunk, which the `drop` entry doesn't point to.
+            Self::Dealloc => {
+                return quote! {
+                    #field_name: Self::__thintraitobjectmacro_repr_drop,
+                };
+            }
+            _ => {}
+        }
+        let thunk_name = self.thunk_name();
+        quote! { #field_name: Self::#thunk_name, }
+    }
+    /// The thunk inside the representation struct's `impl` block.
+    pub fn thunk(
+        self,
+        abi: Option<&Abi>,
+        storage: Storage,
+        trait_path: &TokenStream,
+        has_header: bool,
+    ) -> TokenStream {
+        let thunk_name = self.thunk_name();
+        // The header is `Copy`, so the clone gets the same one.
+        let header_arg = if has_header {
+            quote! { __thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_header, }
+        } else {
+            quote! {}
+        };
+        match self {
+            Self::Clone if storage.is_shared() => {
+                let path = storage.path();
+                quote! {
+                    unsafe #abi fn #thunk_name(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                    ) -> *mut ::core::ffi::c_void {
+                        #path::increment_strong_count(__thintraitobjectmacro_arg0 as *const Self);
+                        __thintraitobjectmacro_arg0
+                    }
+                }
+            }
+            Self::Clone => {
+                quote! {
+                    unsafe #abi fn #thunk_name(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                    ) -> *mut ::core::ffi::c_void {
+                        let __thintraitobjectmacro_arg0 = &*(__thintraitobjectmacro_arg0 as *mut Self);
+                        Self::__thintraitobjectmacro_repr_create(
+                            ::core::clone::Clone::clone(
+                                &__thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_value,
+                            ),
+                            #header_arg
+                        ) as *mut _
+                    }
+                }
+            }
+            Self::Debug | Self::Display => {
+                let fmt_trait = self.bound(storage);
+                quote! {
+                    unsafe #abi fn #thunk_name(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                        __thintraitobjectmacro_arg1: &mut ::core::fmt::Formatter<'_>,
+                    ) -> ::core::fmt::Result {
+                        let __thintraitobjectmacro_arg0 = &*(__thintraitobjectmacro_arg0 as *mut Self);
+                        #fmt_trait::fmt(
+                            &__thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_value,
+                            __thintraitobjectmacro_arg1,
+                        )
+                    }
+                }
+            }
+            // The caller has already checked that both values are of the stored type.
+            Self::PartialEq => {
+                quote! {
+                    unsafe #abi fn #thunk_name(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                        __thintraitobjectmacro_arg1: *mut ::core::ffi::c_void,
+                    ) -> bool {
+                        let __thintraitobjectmacro_arg0 = &*(__thintraitobjectmacro_arg0 as *mut Self);
+                        let __thintraitobjectmacro_arg1 = &*(__thintraitobjectmacro_arg1 as *mut Self);
+                        ::core::cmp::PartialEq::eq(
+                            &__thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_value,
+                            &__thintraitobjectmacro_arg1.__thintraitobjectmacro_repr_value,
+                        )
+                    }
+                }
+            }
+            // Same as with `eq`.
+            Self::Ord => {
+                quote! {
+                    unsafe #abi fn #thunk_name(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                        __thintraitobjectmacro_arg1: *mut ::core::ffi::c_void,
+                    ) -> ::core::cmp::Ordering {
+                        let __thintraitobjectmacro_arg0 = &*(__thintraitobjectmacro_arg0 as *mut Self);
+                        let __thintraitobjectmacro_arg1 = &*(__thintraitobjectmacro_arg1 as *mut Self);
+                        ::core::cmp::Ord::cmp(
+                            &__thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_value,
+                            &__thintraitobjectmacro_arg1.__thintraitobjectmacro_repr_value,
+                        )
+                    }
+                }
+            }
+            Self::Hash => {
+                quote! {
+                    unsafe #abi fn #thunk_name(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                        mut __thintraitobjectmacro_arg1: &mut dyn ::core::hash::Hasher,
+                    ) {
+                        let __thintraitobjectmacro_arg0 = &*(__thintraitobjectmacro_arg0 as *mut Self);
+                        ::core::hash::Hash::hash(
+                            &__thintraitobjectmacro_arg0.__thintraitobjectmacro_repr_value,
+                            &mut __thintraitobjectmacro_arg1,
+                        )
+                    }
+                }
+            }
+            // The value may be shared, so no reference to it is created here.
+            Self::AsDyn => quote! {
+                unsafe fn #thunk_name(
+                    __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                ) -> *mut (dyn #trait_path + 'static) {
+                    let __thintraitobjectmacro_arg0 = __thintraitobjectmacro_arg0 as *mut Self;
+                    let value: *mut (dyn #trait_path + '_) =
+                        ::core::ptr::addr_of_mut!((*__thintraitobjectmacro_arg0).__thintraitobjectmacro_repr_value);
+                    ::core::mem::transmute(value)
+                }
+            },
+            // Moves the value out of its allocation, which is freed, and into a new one with the
+            // regular trait object's layout.
+            Self::IntoBoxDyn => {
+                let path = storage.path();
+                quote! {
+                    unsafe fn #thunk_name(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                    ) -> *mut (dyn #trait_path + 'static) {
+                        let __thintraitobjectmacro_repr = Self::__thintraitobjectmacro_repr_take(__thintraitobjectmacro_arg0);
+                        let boxed: #path<dyn #trait_path + '_> =
+                            #path::new(__thintraitobjectmacro_repr.__thintraitobjectmacro_repr_value);
+                        ::core::mem::transmute(#path::into_raw(boxed))
+                    }
+                }
+            }
+            Self::TypeId | Self::TypeName | Self::Dealloc => quote! {},
+        }
+    }
+    /// The trait implementation for the owned or borrowed trait object struct, with `implementor` being its name along with its generic arguments and `unique` telling whether it has exclusive access to the stored value.
+    pub fn trait_object_impl(
+        self,
+        implementor: &TokenStream,
+        unique: bool,
+        stash: &StageStash,
+    ) -> TokenStream {
+        let StageStash {
+            storage,
+            allocator_api,
+            trait_generics,
+            ..
+        } = stash;
+        let trait_path = stash.trait_path();
+        let params = trait_generics.params();
+        let where_clause = trait_generics.where_clause();
+        let value_generic = trait_generics.unused_ident("T");
+        let value_repr_type = stash.repr_type(&value_generic);
+        let field_name = self.field_name();
+        match self {
+            Self::TypeName => quote! {
+                impl #params #implementor #where_clause {
+                    /// Returns the name of the type of the stored value, as returned by [`core::any::type_name`].
+                    ///
+                    /// [`core::any::type_name`]: https://doc.rust-lang.org/std/any/fn.type_name.html " "
+                    #[inline]
+                    pub fn value_type_name(&self) -> &'static str {
+                        (self.vtable().#field_name)()
+                    }
+                }
+            },
+            Self::Clone => quote! {
+                impl #params ::core::clone::Clone for #implementor #where_clause {
+                    #[inline]
+                    fn clone(&self) -> Self {
+                        unsafe {
+                            Self::from_raw(
+                                (self.vtable().#field_name)(self.as_raw() as *mut _) as *mut ()
+                            )
+                        }
+                    }
+                }
+            },
+            Self::Debug | Self::Display => {
+                let fmt_trait = self.bound(*storage);
+                quote! {
+                    impl #params #fmt_trait for #implementor #where_clause {
+                        #[inline]
+                        fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                            unsafe { (self.vtable().#field_name)(self.as_raw() as *mut _, f) }
+                        }
+                    }
+                }
+            }
+            Self::PartialEq => {
+                let type_id = Self::TypeId.field_name();
+                quote! {
+                    impl #params ::core::cmp::PartialEq for #implementor #where_clause {
+                        #[inline]
+                        fn eq(&self, other: &Self) -> bool {
+                            let vtable = self.vtable();
+                            (vtable.#type_id)() == (other.vtable().#type_id)()
+                                && unsafe {
+                                    (vtable.#field_name)(self.as_raw() as *mut _, other.as_raw() as *mut _)
+                                }
+                        }
+                    }
+                }
+            }
+            // Values of different types are never equal, so ordering those by their type IDs is
+            // consistent with `PartialEq`.
+            Self::Ord => {
+                let type_id = Self::TypeId.field_name();
+                quote! {
+                    impl #params ::core::cmp::Eq for #implementor #where_clause {}
+                    impl #params ::core::cmp::PartialOrd for #implementor #where_clause {
+                        #[inline]
+                        fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                            ::core::option::Option::Some(::core::cmp::Ord::cmp(self, other))
+                        }
+                    }
+                    impl #params ::core::cmp::Ord for #implementor #where_clause {
+                        #[inline]
+                        fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                            let vtable = self.vtable();
+                            ::core::cmp::Ord::cmp(&(vtable.#type_id)(), &(other.vtable().#type_id)())
+                                .then_with(|| unsafe {
+                                    (vtable.#field_name)(self.as_raw() as *mut _, other.as_raw() as *mut _)
+                                })
+                        }
+                    }
+                }
+            }
+            Self::Hash => {
+                let hasher_generic = trait_generics.unused_ident("H");
+                quote! {
+                    impl #params ::core::hash::Hash for #implementor #where_clause {
+                        #[inline]
+                        fn hash<#hasher_generic: ::core::hash::Hasher>(&self, state: &mut #hasher_generic) {
+                            unsafe { (self.vtable().#field_name)(self.as_raw() as *mut _, state) }
+                        }
+                    }
+                }
+            }
+            Self::AsDyn => {
+                let as_dyn_mut = if unique {
+                    quote! {
+                        /// Mutably borrows the stored value as a regular trait object, for use with code which expects a `&mut dyn` reference to the trait.
+                        #[inline]
+                        pub fn as_dyn_mut(&mut self) -> &mut dyn #trait_path {
+                            unsafe { &mut *(self.vtable().#field_name)(self.as_raw() as *mut _) }
+                        }
+                    }
+                } else {
+                    quote! {}
+                };
+                quote! {
+                    impl #params #implementor #where_clause {
+                        /// Borrows the stored value as a regular trait object, for use with code which expects a `&dyn` reference to the trait.
+                        #[inline]
+                        pub fn as_dyn(&self) -> &dyn #trait_path {
+                            unsafe { &*(self.vtable().#field_name)(self.as_raw() as *mut _) }
+                        }
+                        #as_dyn_mut
+                    }
+                }
+            }
+            Self::IntoBoxDyn => quote! {},
+            // Takes the function pointer out of the vtable before giving up ownership, like the
+            // methods taking `self` by value.
+            Self::Dealloc => quote! {
+                impl #params #implementor #where_clause {
+                    /// Drops the stored value and frees the allocation, which dropping the thin trait object doesn't do because of `manual_drop = true`.
+                    #[inline]
+                    pub fn dealloc(self) {
+                        let dealloc = self.vtable().#field_name;
+                        unsafe { dealloc(self.into_raw() as *mut ::core::ffi::c_void) }
+                    }
+                }
+            },
+            Self::TypeId => {
+                let result_generic = trait_generics.unused_ident("R");
+                // The value can't be mutated or moved out of if it might be shared, and can't be
+                // moved out of without knowing the allocator which needs to deallocate it.
+                let unique_downcasts = if storage.is_shared() {
+                    quote! {}
+                } else {
+                    let downcast = if *allocator_api {
+                        quote! {}
+                    } else {
+                        quote! {
+                            /// Moves the stored value out of the trait object if it is of type `T`, deallocating the trait object, or gives the trait object back if it isn't.
+                            #[inline]
+                            pub fn downcast<#value_generic: #trait_path + 'static>(self) -> ::core::result::Result<#value_generic, Self> {
+                                if self.is::<#value_generic>() {
+                                    let repr = unsafe {
+                                        <#value_repr_type>::__thintraitobjectmacro_repr_take(self.into_raw() as *mut _)
+                                    };
+                                    ::core::result::Result::Ok(repr.__thintraitobjectmacro_repr_value)
+                                } else {
+                                    ::core::result::Result::Err(self)
+                                }
+                            }
+                        }
+                    };
+                    quote! {
+                        /// Returns a mutable reference to the stored value if it is of type `T`, or `None` if it isn't.
+                        #[inline]
+                        pub fn downcast_mut<#value_generic: #trait_path + 'static>(&mut self) -> ::core::option::Option<&mut #value_generic> {
+                            if self.is::<#value_generic>() {
+                                unsafe {
+                                    ::core::option::Option::Some(
+                                        &mut (*(self.as_raw() as *mut #value_repr_type))
+                                            .__thintraitobjectmacro_repr_value,
+                                    )
+                                }
+                            } else {
+                                ::core::option::Option::None
+                            }
+                        }
+                        /// Calls the closure with a mutable reference to the stored value if it is of type `T`, returning what it returns, or returns `None` without calling it if it isn't.
+                        #[inline]
+                        pub fn with_mut<#value_generic: #trait_path + 'static, #result_generic>(
+                            &mut self,
+                            f: impl ::core::ops::FnOnce(&mut #value_generic) -> #result_generic,
+                        ) -> ::core::option::Option<#result_generic> {
+                            self.downcast_mut::<#value_generic>().map(f)
+                        }
+                        /// Replaces the stored value with the given one if it is of type `T`, dropping the old value in place without deallocating the trait object, or returns `None` and drops the given value if it isn't.
+                        #[inline]
+                        pub fn replace<#value_generic: #trait_path + 'static>(&mut self, val: #value_generic) -> ::core::option::Option<()> {
+                            let stored = self.downcast_mut::<#value_generic>()?;
+                            *stored = val;
+                            ::core::option::Option::Some(())
+                        }
+                        #downcast
+                    }
+                };
+                quote! {
+                    impl #params #implementor #where_clause {
+                        /// Returns `true` if the stored value is of type `T`.
+                        #[inline]
+                        pub fn is<#value_generic: #trait_path + 'static>(&self) -> bool {
+                            (self.vtable().#field_name)() == ::core::any::TypeId::of::<#value_generic>()
+                        }
+                        /// Returns a reference to the stored value if it is of type `T`, or `None` if it isn't.
+                        #[inline]
+                        pub fn downcast_ref<#value_generic: #trait_path + 'static>(&self) -> ::core::option::Option<&#value_generic> {
+                            if self.is::<#value_generic>() {
+                                unsafe {
+                                    ::core::option::Option::Some(
+                                        &(*(self.as_raw() as *const #value_repr_type))
+                                            .__thintraitobjectmacro_repr_value,
+                                    )
+                                }
+                            } else {
+                                ::core::option::Option::None
+                            }
+                        }
+                        /// Calls the closure with a reference to the stored value if it is of type `T`, returning what it returns, or returns `None` without calling it if it isn't. The reference cannot escape the closure.
+                        #[inline]
+                        pub fn with_ref<#value_generic: #trait_path + 'static, #result_generic>(
+                            &self,
+                            f: impl ::core::ops::FnOnce(&#value_generic) -> #result_generic,
+                        ) -> ::core::option::Option<#result_generic> {
+                            self.downcast_ref::<#value_generic>().map(f)
+                        }
+                        #unique_downcasts
+                    }
+                }
+            }
+        }
+    }
+}
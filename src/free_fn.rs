@@ -0,0 +1,65 @@
+//! Generates the standalone free function, enabled by the `free_fn` option, which takes a pointer to a thin trait object and calls the `drop` entry of its vtable, or the `dealloc` entry with `manual_drop = true`, for foreign code to be able to free thin trait objects by calling a function with a known symbol name rather than going through the vtable itself.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Abi;
+
+use crate::{
+    attr::StageStash,
+    extra_entries::ExtraEntry,
+    options::OutputAdditions,
+    trait_object::check_attribute,
+};
+
+pub fn generate_free_fn(
+    stash: &mut StageStash,
+    additions: &OutputAdditions,
+    inline_vtable: bool,
+    drop_abi: Option<&Abi>,
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        extra_entries,
+        drop_name,
+        trait_generics,
+        ..
+    } = &*stash;
+    let vtable_type = stash.vtable_type();
+    let OutputAdditions {
+        attributes,
+        visibility,
+        name,
+    } = additions;
+    attributes.iter().try_for_each(check_attribute)?;
+    // A function with a symbol name cannot be generic.
+    if !trait_generics.is_empty() {
+        return Err(syn::Error::new_spanned(
+            name,
+            "the free function is not supported for generic traits and traits with associated types, since it would have to be generic as well",
+        ));
+    }
+
+    let entry = if extra_entries.contains(&ExtraEntry::Dealloc) {
+        ExtraEntry::Dealloc.field_name()
+    } else {
+        drop_name.clone()
+    };
+    let vtable_ref = if inline_vtable {
+        quote! { &*(ptr as *const #vtable_type) }
+    } else if stash.is_packed() {
+        quote! { (ptr as *const &'static #vtable_type).read_unaligned() }
+    } else {
+        quote! { *(ptr as *const &'static #vtable_type) }
+    };
+    let result = quote! {
+        #(#attributes)*
+        #visibility unsafe #drop_abi fn #name(ptr: *mut ::core::ffi::c_void) {
+            // Like `free` in C, does nothing when given a null pointer.
+            if ptr.is_null() {
+                return;
+            }
+            let vtable: &#vtable_type = #vtable_ref;
+            (vtable.#entry)(ptr)
+        }
+    };
+    Ok(result)
+}
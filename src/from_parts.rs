@@ -0,0 +1,112 @@
+//! Generates the `from_parts` constructor of the boxed thin trait object, enabled by the `from_parts` option, which stores a vtable built at runtime together with an opaque data pointer instead of a value of a Rust type.
+//!
+//! The two are stored in a parts struct, which implements the trait by calling the entries of the given vtable with the data pointer the same way the thin trait object calls the entries of its own, and is then stored in the thin trait object like any other value. Calls thus go through both vtables, but the entries of the given one receive the data pointer rather than a pointer to the parts struct, so that code providing them at runtime never needs to know its layout.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{
+    attr::StageStash,
+    marker_traits::MarkerTrait,
+    repr::allow_internal_lints,
+    trait_object::{dispatch_inline_attribute, dispatch_method, optimization_hints, trait_impls},
+};
+
+pub fn generate_from_parts(
+    stash: &StageStash,
+    markers: &[MarkerTrait],
+    has_static_bound: bool,
+) -> TokenStream {
+    let StageStash {
+        trait_name,
+        trait_object_name,
+        inline_always,
+        drop_name,
+        trait_generics,
+        header,
+        ..
+    } = stash;
+    let vtable_type = stash.vtable_type();
+    let parts_name = format_ident!("__ThinTraitObjectMacro_PartsFor{}", trait_name);
+    let inline = dispatch_inline_attribute(*inline_always);
+    let trait_impls = trait_impls(stash, &quote! { #parts_name }, |item| {
+        let cfg_attributes = &item.cfg_attributes;
+        let hints = optimization_hints(item, &inline);
+        let method = dispatch_method(item, &item.name);
+        quote! {
+            #(#cfg_attributes)*
+            #hints
+            #method
+        }
+    });
+    // Whoever provides the parts vouches for those, as part of the safety contract of `from_parts`.
+    let marker_impls = markers
+        .iter()
+        .map(|marker| marker.as_impl_for(&parts_name, trait_generics));
+    let trait_object_type = if has_static_bound {
+        quote! { #trait_object_name }
+    } else {
+        quote! { #trait_object_name<'_> }
+    };
+    let (header_param, header_arg) = match header {
+        Some(header) => (quote! { header: #header, }, quote! { header, }),
+        None => (quote! {}, quote! {}),
+    };
+
+    let allow_internal_lints = allow_internal_lints();
+
+    quote! {
+        #allow_internal_lints
+        struct #parts_name {
+            __thintraitobjectmacro_vtable: &'static #vtable_type,
+            __thintraitobjectmacro_data: *mut ::core::ffi::c_void,
+        }
+        // The same methods as on the thin trait object, which the dispatching methods call.
+        #allow_internal_lints
+        impl #parts_name {
+            #[inline]
+            fn vtable(&self) -> &'static #vtable_type {
+                self.__thintraitobjectmacro_vtable
+            }
+            #[inline]
+            fn as_raw(&self) -> *mut () {
+                self.__thintraitobjectmacro_data as *mut ()
+            }
+            #[inline]
+            fn into_raw(self) -> *mut () {
+                let pointer = self.as_raw();
+                ::core::mem::forget(self);
+                pointer
+            }
+        }
+        #trait_impls
+        #(#marker_impls)*
+        impl ::core::ops::Drop for #parts_name {
+            fn drop(&mut self) {
+                unsafe { (self.vtable().#drop_name)(self.__thintraitobjectmacro_data) }
+            }
+        }
+        impl #trait_object_type {
+            /// Constructs a boxed thin trait object which dispatches through the given vtable, passing the given data pointer to its entries in place of a pointer to a stored value. The vtable and the data pointer are allocated together and stored like a value implementing the trait, so that calls go through two vtables.
+            ///
+            /// # Safety
+            /// The thin trait object has to behave as if it stored a value of a type implementing the trait, which is up to the vtable and the data pointer:
+            /// - The entries for the trait methods must be valid to call with the data pointer as their first argument, with the signatures and ABIs of their function pointer types, for as long as the thin trait object exists, and must do what an implementation of the trait would do, including upholding its contract if it is an unsafe trait;
+            /// - The `drop` entry must be valid to call with the data pointer exactly once, which happens when the thin trait object is dropped, and the entries of methods taking `self` by value must take over the ownership of whatever the data pointer refers to, since `drop` is not called after such a method;
+            /// - If the trait has marker traits like `Send` or `Sync` as supertraits, the data pointer and the entries must make it sound for the thin trait object to implement them.
+            ///
+            /// Only the entries for the trait methods and `drop` are ever called. The other entries which the thin trait object uses, like `type_id` or `type_name`, are the ones of the type which holds the vtable and the data pointer, and the ones in the given vtable are left alone.
+            #[inline]
+            pub unsafe fn from_parts(
+                #header_param
+                vtable: &'static #vtable_type,
+                data: *mut ::core::ffi::c_void,
+            ) -> Self {
+                Self::new(#header_arg #parts_name {
+                    __thintraitobjectmacro_vtable: vtable,
+                    __thintraitobjectmacro_data: data,
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! Handles the generic parameters of the trait, which all generated items which refer to the trait or the vtable are generic over as well.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse_quote,
+    punctuated::Punctuated,
+    visit_mut::{self, VisitMut},
+    Block,
+    GenericParam,
+    Generics,
+    Lifetime,
+    Token,
+    TraitItem,
+    TraitItemType,
+    Type,
+    TypeParam,
+    TypePath,
+    WherePredicate,
+};
+
+use crate::vtable::{mentions_ident, mentions_lifetime};
+
+pub struct TraitGenerics {
+    params: Punctuated<GenericParam, Token![,]>,
+    predicates: Vec<WherePredicate>,
+    /// The names of the associated types of the trait, which are type parameters of the generated items with the same names, since the stored value fixes them.
+    associated_types: Vec<Ident>,
+}
+impl TraitGenerics {
+    pub fn new(generics: Generics, associated_types: Vec<TraitItemType>) -> syn::Result<Self> {
+        let mut params = generics.params;
+        // Type parameters with defaults have to come last.
+        let position = params
+            .iter()
+            .position(|param| match param {
+                GenericParam::Type(param) => param.default.is_some(),
+                GenericParam::Const(param) => param.default.is_some(),
+                GenericParam::Lifetime(..) => false,
+            })
+            .unwrap_or_else(|| params.len());
+        let associated_type_names = associated_types
+            .iter()
+            .map(|associated_type| associated_type.ident.clone())
+            .collect::<Vec<_>>();
+        for (index, associated_type) in associated_types.into_iter().enumerate() {
+            let TraitItemType {
+                ident,
+                generics,
+                mut bounds,
+                ..
+            } = associated_type;
+            if !generics.params.is_empty() || generics.where_clause.is_some() {
+                return Err(syn::Error::new_spanned(
+                    generics,
+                    "generic associated types are not supported, since the thin trait object structs would need to be generic over a type constructor",
+                ));
+            }
+            let taken = params.iter().any(|param| match param {
+                GenericParam::Type(param) => param.ident == ident,
+                GenericParam::Const(param) => param.ident == ident,
+                GenericParam::Lifetime(..) => false,
+            });
+            if taken {
+                return Err(syn::Error::new_spanned(
+                    &ident,
+                    format!(
+                        "associated type `{}` has the same name as a generic parameter of the trait, which the type parameter of the thin trait object structs standing in for it would clash with",
+                        ident,
+                    ),
+                ));
+            }
+            let mut replace = ReplaceAssociatedTypes(&associated_type_names);
+            for bound in &mut bounds {
+                replace.visit_type_param_bound_mut(bound);
+            }
+            if let Some(bound) = bounds
+                .iter()
+                .find(|bound| mentions_ident(&parse_quote!(#ident: #bound), "Self"))
+            {
+                return Err(syn::Error::new_spanned(
+                    bound,
+                    format!(
+                        "the bounds of associated type `{}` mention `Self`, which differs between the stored types",
+                        ident,
+                    ),
+                ));
+            }
+            let param = TypeParam {
+                attrs: Vec::new(),
+                ident,
+                colon_token: None,
+                bounds,
+                eq_token: None,
+                default: None,
+            };
+            params.insert(position + index, GenericParam::Type(param));
+        }
+        for param in &mut params {
+            match param {
+                // Clashes with the lifetime of the stored value on the thin trait object structs.
+                GenericParam::Lifetime(param) if param.lifetime.ident == "inner" => {
+                    return Err(syn::Error::new_spanned(
+                        param,
+                        "the lifetime name `'inner` is reserved for the lifetime of the value stored by the thin trait object",
+                    ));
+                }
+                // The vtable isn't generic over those, see `lifetimes`.
+                GenericParam::Lifetime(..) => {}
+                // The vtable is referenced as `&'static`, which would outlive the type parameters
+                // if they weren't required to be `'static`.
+                GenericParam::Type(param) => {
+                    param.colon_token.get_or_insert_with(Default::default);
+                    param.bounds.insert(0, parse_quote!('static));
+                }
+                GenericParam::Const(..) => {}
+            }
+        }
+        // Bounds on `Self` were already turned into supertraits.
+        let predicates = generics
+            .where_clause
+            .map(|where_clause| where_clause.predicates.into_iter().collect())
+            .unwrap_or_default();
+        Ok(Self {
+            params,
+            predicates,
+            associated_types: associated_type_names,
+        })
+    }
+    /// Replaces the associated types of the trait in the declaration of a trait item, as in `Self::Output` or `<Self as Trait>::Output`, with the type parameters standing in for them. The body of a default implementation is left alone.
+    pub fn replace_associated_types(&self, item: &mut TraitItem) {
+        ReplaceAssociatedTypes(&self.associated_types).visit_trait_item_mut(item);
+    }
+    /// Returns `true` if the trait has no generic parameters and no associated types, which means that the generated items aren't generic either.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+    /// The lifetime parameters of the trait.
+    ///
+    /// The vtable is referenced as `&'static`, which rules out it being generic over them, so they are replaced with `'static` in the vtable entries and only carried by the other generated items.
+    pub fn lifetimes(&self) -> Vec<Lifetime> {
+        self.params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Lifetime(param) => Some(param.lifetime.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+    /// The generic parameters of the trait without the lifetime parameters, which the vtable struct is generic over. The bounds mentioning those lifetimes are left out as well.
+    pub fn without_lifetimes(&self) -> Self {
+        let lifetimes = self.lifetimes();
+        let mentions_lifetime = |predicate: &WherePredicate| {
+            lifetimes
+                .iter()
+                .any(|lifetime| mentions_lifetime(predicate, lifetime))
+        };
+        let params = self
+            .params
+            .iter()
+            .filter(|param| !matches!(param, GenericParam::Lifetime(..)))
+            .cloned()
+            .map(|mut param| {
+                if let GenericParam::Type(param) = &mut param {
+                    param.bounds = param
+                        .bounds
+                        .clone()
+                        .into_iter()
+                        .filter(|bound| !mentions_lifetime(&parse_quote!(Self: #bound)))
+                        .collect();
+                }
+                param
+            })
+            .collect();
+        let predicates = self
+            .predicates
+            .iter()
+            .filter(|predicate| !mentions_lifetime(predicate))
+            .cloned()
+            .collect();
+        Self {
+            params,
+            predicates,
+            associated_types: self.associated_types.clone(),
+        }
+    }
+    /// A type which is invariant over the lifetime parameters of the trait, like `dyn Trait<'a>` is, for marking the generated structs as using them. `None` if the trait has no lifetime parameters.
+    fn lifetime_marker(&self) -> Option<TokenStream> {
+        let lifetimes = self.lifetimes();
+        if lifetimes.is_empty() {
+            return None;
+        }
+        Some(quote! { fn(#(&#lifetimes (),)*) -> (#(&#lifetimes (),)*) })
+    }
+    /// The type of the `PhantomData` field of a thin trait object struct, which marks it as using the given type as well as the lifetime parameters of the trait.
+    pub fn phantom_with(&self, own: TokenStream) -> TokenStream {
+        match self.lifetime_marker() {
+            Some(marker) => quote! { ::core::marker::PhantomData<(#own, #marker)> },
+            None => quote! { ::core::marker::PhantomData<#own> },
+        }
+    }
+    /// The field of a representation struct which marks it as using the lifetime parameters of the trait, since the stored value only mentions them in its bound, along with its initializer. Both empty if the trait has no lifetime parameters.
+    pub fn repr_phantom(&self) -> (TokenStream, TokenStream) {
+        match self.lifetime_marker() {
+            Some(marker) => (
+                quote! { __thintraitobjectmacro_repr_phantom: ::core::marker::PhantomData<#marker>, },
+                quote! { __thintraitobjectmacro_repr_phantom: ::core::marker::PhantomData, },
+            ),
+            None => Default::default(),
+        }
+    }
+    /// Returns the name for a lifetime parameter of a generated item, which is the given one unless the trait has a lifetime parameter with that name.
+    pub fn unused_lifetime(&self, name: &str) -> Lifetime {
+        let taken = self
+            .lifetimes()
+            .iter()
+            .any(|lifetime| lifetime.ident == name);
+        if taken {
+            Lifetime::new(
+                &format!("'__thintraitobjectmacro_{}", name),
+                Span::call_site(),
+            )
+        } else {
+            Lifetime::new(&format!("'{}", name), Span::call_site())
+        }
+    }
+    /// Returns `true` if the bound mentions any of the type or constant parameters of the trait.
+    pub fn mentioned_by(&self, predicate: &WherePredicate) -> bool {
+        self.params.iter().any(|param| match param {
+            GenericParam::Type(param) => mentions_ident(predicate, &param.ident.to_string()),
+            GenericParam::Const(param) => mentions_ident(predicate, &param.ident.to_string()),
+            GenericParam::Lifetime(..) => false,
+        })
+    }
+    /// Returns the name for a generic parameter of a generated item, which is the given one unless the trait has a generic parameter with that name.
+    pub fn unused_ident(&self, name: &str) -> Ident {
+        let taken = self.params.iter().any(|param| match param {
+            GenericParam::Type(param) => param.ident == name,
+            GenericParam::Const(param) => param.ident == name,
+            GenericParam::Lifetime(..) => false,
+        });
+        if taken {
+            format_ident!("__ThinTraitObjectMacro_{}", name)
+        } else {
+            format_ident!("{}", name)
+        }
+    }
+    /// The generic parameter list for an item which has the given parameters of its own in addition to the ones of the trait, with the bounds but without the defaults. Empty if there are no parameters at all.
+    pub fn params_with(&self, own: TokenStream) -> TokenStream {
+        let params = self.params.iter().map(|param| {
+            let mut param = param.clone();
+            match &mut param {
+                GenericParam::Type(param) => {
+                    param.eq_token = None;
+                    param.default = None;
+                }
+                GenericParam::Const(param) => {
+                    param.eq_token = None;
+                    param.default = None;
+                }
+                GenericParam::Lifetime(..) => {}
+            }
+            param.into_token_stream()
+        });
+        self.list(own, params)
+    }
+    /// Same as [`params_with`], but keeps the defaults, for use in the definitions of the generated structs.
+    ///
+    /// [`params_with`]: #method.params_with " "
+    pub fn params_with_defaults(&self, own: TokenStream) -> TokenStream {
+        self.list(own, self.params.iter().map(ToTokens::to_token_stream))
+    }
+    /// The generic argument list for an item which has the given arguments of its own in addition to the ones of the trait. Empty if there are no arguments at all.
+    pub fn args_with(&self, own: TokenStream) -> TokenStream {
+        let args = self.params.iter().map(|param| match param {
+            GenericParam::Type(param) => param.ident.to_token_stream(),
+            GenericParam::Const(param) => param.ident.to_token_stream(),
+            GenericParam::Lifetime(param) => param.lifetime.to_token_stream(),
+        });
+        self.list(own, args)
+    }
+    /// The generic parameter list of the trait, for items which have none of their own.
+    #[inline]
+    pub fn params(&self) -> TokenStream {
+        self.params_with(TokenStream::new())
+    }
+    /// The generic argument list of the trait, for items which have none of their own.
+    #[inline]
+    pub fn args(&self) -> TokenStream {
+        self.args_with(TokenStream::new())
+    }
+    /// The generic argument list of the trait itself, which leaves out the type parameters standing in for the associated types. Those are specified as the associated types if `bindings` is `true`, as in `Trait<Output = Output>`, which may only be done in bounds and trait object types, not in implementations and qualified paths.
+    pub fn trait_args(&self, bindings: bool) -> TokenStream {
+        let (associated_types, params) =
+            self.params
+                .iter()
+                .partition::<Vec<_>, _>(|param| match param {
+                    GenericParam::Type(param) => self.associated_types.contains(&param.ident),
+                    _ => false,
+                });
+        let args = params.into_iter().map(|param| match param {
+            GenericParam::Type(param) => param.ident.to_token_stream(),
+            GenericParam::Const(param) => param.ident.to_token_stream(),
+            GenericParam::Lifetime(param) => param.lifetime.to_token_stream(),
+        });
+        let bindings = associated_types
+            .into_iter()
+            .filter(|_| bindings)
+            .filter_map(|param| match param {
+                GenericParam::Type(param) => {
+                    let ident = &param.ident;
+                    Some(quote! { #ident = #ident })
+                }
+                _ => None,
+            });
+        let items = args.chain(bindings).collect::<Vec<_>>();
+        if items.is_empty() {
+            TokenStream::new()
+        } else {
+            quote! { <#(#items),*> }
+        }
+    }
+    /// The associated types of an implementation of the trait for a generated item, each of which is the type parameter standing in for it.
+    pub fn associated_type_items(&self) -> TokenStream {
+        let associated_types = &self.associated_types;
+        quote! { #(type #associated_types = #associated_types;)* }
+    }
+    /// The type of a zero-sized field marking the vtable struct as using the type parameters of the trait, which the vtable entries might not mention. `None` if the trait has no type parameters.
+    pub fn phantom_type(&self) -> Option<TokenStream> {
+        let type_params = self
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Type(param) => Some(&param.ident),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if type_params.is_empty() {
+            return None;
+        }
+        Some(quote! { ::core::marker::PhantomData<fn() -> (#(#type_params,)*)> })
+    }
+    /// The `where` clause of the trait, with the bounds on `Self` left out. Empty if there is none.
+    pub fn where_clause(&self) -> TokenStream {
+        if self.predicates.is_empty() {
+            return TokenStream::new();
+        }
+        let predicates = &self.predicates;
+        quote! { where #(#predicates),* }
+    }
+    /// Same as [`where_clause`], but with an additional bound of the item's own, if any.
+    ///
+    /// [`where_clause`]: #method.where_clause " "
+    pub fn where_clause_with(&self, own: Option<TokenStream>) -> TokenStream {
+        let predicates = self.predicates.iter().map(ToTokens::to_token_stream);
+        let predicates = own.into_iter().chain(predicates).collect::<Vec<_>>();
+        if predicates.is_empty() {
+            return TokenStream::new();
+        }
+        quote! { where #(#predicates),* }
+    }
+    fn list(
+        &self,
+        own: TokenStream,
+        trait_params: impl Iterator<Item = TokenStream>,
+    ) -> TokenStream {
+        // Lifetime parameters have to come first, and the ones of the items are never types.
+        let (lifetimes, others): (Vec<_>, Vec<_>) = self
+            .params
+            .iter()
+            .zip(trait_params)
+            .partition(|(param, _)| matches!(param, GenericParam::Lifetime(..)));
+        let own_lifetime = own.to_string().starts_with('\'');
+        let (before, after) = if own_lifetime {
+            (Some(own), None)
+        } else {
+            (None, Some(own))
+        };
+        let items = before
+            .into_iter()
+            .chain(lifetimes.into_iter().map(|(_, param)| param))
+            .chain(after)
+            .filter(|item| !item.is_empty())
+            .chain(others.into_iter().map(|(_, param)| param))
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            TokenStream::new()
+        } else {
+            quote! { <#(#items),*> }
+        }
+    }
+}
+
+/// Replaces the paths to the associated types of the trait through `Self` with the type parameters of the same names.
+struct ReplaceAssociatedTypes<'a>(&'a [Ident]);
+impl VisitMut for ReplaceAssociatedTypes<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(TypePath { qself, path }) = ty {
+            let through_self = match qself {
+                None => {
+                    path.segments.len() == 2
+                        && path.segments[0].ident == "Self"
+                        && path.segments[0].arguments.is_empty()
+                }
+                Some(qself) => {
+                    matches!(&*qself.ty, Type::Path(TypePath { qself: None, path }) if path.is_ident("Self"))
+                        && path.segments.len() == qself.position + 1
+                }
+            };
+            let last_segment = path.segments.last().filter(|_| through_self);
+            if let Some(ident) = last_segment
+                .filter(|segment| segment.arguments.is_empty())
+                .and_then(|segment| self.0.iter().find(|ident| segment.ident == **ident))
+            {
+                *ty = parse_quote!(#ident);
+                return;
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+    fn visit_block_mut(&mut self, _: &mut Block) {}
+}
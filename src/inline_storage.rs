@@ -0,0 +1,254 @@
+//! Generates the inline storage thin trait object struct, enabled by the `inline_storage` option, which stores values small enough to fit into its buffer directly instead of allocating them.
+//!
+//! The buffer holds either a representation struct, which is then not behind a pointer, or a pointer to a boxed one. The inline representation struct uses a separate vtable which only differs from the regular one in that its drop thunk doesn't deallocate, which means that all other thunks can be shared and that the pointer passed to them doesn't need to care where the representation struct lives.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Abi;
+
+use crate::{
+    attr::StageStash,
+    extra_entries::ExtraEntry,
+    marker_traits::MarkerTrait,
+    options::OutputAdditions,
+    repr::allow_internal_lints,
+    trait_object::{
+        check_attribute,
+        dispatch_inline_attribute,
+        dispatch_method,
+        forwarded_attributes,
+        is_inline_vtable_method,
+        optimization_hints,
+        trait_impls,
+        vtable_getter_impl,
+    },
+    vtable::{ReceiverKind, VtableItem},
+};
+
+pub const DEFAULT_INLINE_CAPACITY: usize = 3;
+
+pub fn generate_inline_storage(
+    stash: &mut StageStash,
+    additions: &OutputAdditions,
+    capacity: usize,
+    inline_vtable: bool,
+    drop_abi: Option<&Abi>,
+    has_static_bound: bool,
+    markers: &[MarkerTrait],
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        vtable_name,
+        repr_name,
+        trait_object_name,
+        vtable_items,
+        extra_entries,
+        supertrait_entries,
+        inline_always,
+        drop_name,
+        trait_generics,
+        ..
+    } = &*stash;
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let value_generic = trait_generics.unused_ident("T");
+    let value_repr_type = stash.repr_type(&value_generic);
+    let trait_params = trait_generics.params();
+    let where_clause = trait_generics.where_clause();
+    let OutputAdditions {
+        attributes,
+        visibility,
+        name,
+    } = additions;
+    attributes.iter().try_for_each(check_attribute)?;
+
+    let (vtable_field_type, vtable_field_val) = if inline_vtable {
+        (
+            vtable_type.clone(),
+            quote! { <#value_repr_type>::__THINTRAITOBJECTMACRO_INLINE_STORAGE_VTABLE },
+        )
+    } else {
+        (
+            quote! { &'static #vtable_type },
+            quote! { &<#value_repr_type>::__THINTRAITOBJECTMACRO_INLINE_STORAGE_VTABLE },
+        )
+    };
+    let buffer_type = quote! {
+        ::core::cell::UnsafeCell<::core::mem::MaybeUninit<(#vtable_field_type, [usize; #capacity])>>
+    };
+    let (lifetime, creation_bound, elided_lifetime, phantomdata) = if has_static_bound {
+        (
+            quote! {},
+            quote! { 'static },
+            quote! {},
+            trait_generics
+                .phantom_with(quote! { (::core::ptr::NonNull<#vtable_type>, &'static ()) }),
+        )
+    } else {
+        (
+            quote! { 'inner },
+            quote! { 'inner },
+            quote! { '_ },
+            trait_generics
+                .phantom_with(quote! { (::core::ptr::NonNull<#vtable_type>, &'inner ()) }),
+        )
+    };
+    let def_params = trait_generics.params_with_defaults(lifetime.clone());
+    let params = trait_generics.params_with(lifetime.clone());
+    let args = trait_generics.args_with(lifetime.clone());
+    let impl_elided_lifetime = trait_generics.args_with(elided_lifetime);
+    let trait_object_args = trait_generics.args_with(lifetime);
+    let extra_bounds = stash.value_bounds();
+
+    // The value might not be behind a pointer, so it can't be moved out of without knowing its
+    // type, and the clone thunk would allocate instead of filling the buffer.
+    let can_call = |item: &&VtableItem| {
+        matches!(
+            item.receiver_kind(),
+            Some(ReceiverKind::Shared) | Some(ReceiverKind::Mutable)
+        )
+    };
+    let implements_trait = vtable_items.iter().all(|item| can_call(&item))
+        && vtable_items.iter().all(|item| item.constant.is_none())
+        && !supertrait_entries.contains(&ExtraEntry::Clone);
+    let inline = dispatch_inline_attribute(*inline_always);
+    let method_visibility = if implements_trait {
+        quote! {}
+    } else {
+        quote! { pub }
+    };
+    let method = |item: &VtableItem| {
+        let cfg_attributes = &item.cfg_attributes;
+        // Only inherent methods get the documentation of the trait method, as trait
+        // implementations are documented by the trait.
+        let attributes = forwarded_attributes(item).filter(|_| !implements_trait);
+        let hints = optimization_hints(item, &inline);
+        let method = if implements_trait {
+            dispatch_method(item, &item.name)
+        } else {
+            dispatch_method(item, item.inherent_name())
+        };
+        quote! {
+            #(#cfg_attributes)*
+            #(#attributes)*
+            #hints
+            #method_visibility #method
+        }
+    };
+    let methods = if implements_trait {
+        trait_impls(stash, &quote! { #name #impl_elided_lifetime }, method)
+    } else {
+        let methods = vtable_items.iter().filter(can_call).map(method);
+        quote! {
+            #[allow(clippy::ref_in_deref)] // see https://github.com/rust-lang/rust-clippy/issues/6658
+            impl #params #name #args #where_clause {
+                #(#methods)*
+            }
+        }
+    };
+    // The value is owned the same way as with the boxed thin trait object, so the same marker
+    // traits apply.
+    let marker_impls = markers
+        .iter()
+        .map(|marker| marker.as_impl_for(&quote! { #name #impl_elided_lifetime }, trait_generics));
+    let extra_impls = extra_entries
+        .iter()
+        .filter(|entry| entry.usable_through_ref())
+        .map(|entry| entry.trait_object_impl(&quote! { #name #impl_elided_lifetime }, true, stash));
+    let vtable_getter_impl = vtable_getter_impl(inline_vtable, stash.is_packed(), &vtable_type);
+    let is_inline_vtable_method = is_inline_vtable_method(inline_vtable);
+    let repr_params = trait_generics.params_with(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0: #trait_path #(+ #extra_bounds)*
+    });
+    let repr_type = stash.repr_type(&quote! { __ThinTraitObjectMacro_ReprGeneric0 });
+    let (_, repr_phantom_initializer) = trait_generics.repr_phantom();
+
+    let allow_internal_lints = allow_internal_lints();
+
+    let result = quote! {
+        #allow_internal_lints
+        impl #repr_params #repr_type #where_clause {
+            const __THINTRAITOBJECTMACRO_INLINE_STORAGE_VTABLE: #vtable_type = #vtable_name {
+                #drop_name: Self::__thintraitobjectmacro_repr_drop_in_place,
+                ..Self::__THINTRAITOBJECTMACRO_VTABLE
+            };
+            // Drops the value without deallocating, since the representation struct is stored in
+            // the buffer of the thin trait object.
+            unsafe #drop_abi fn __thintraitobjectmacro_repr_drop_in_place(
+                __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+            ) {
+                ::core::ptr::drop_in_place(__thintraitobjectmacro_arg0 as *mut Self);
+            }
+        }
+
+        #(#attributes)*
+        #visibility struct #name #def_params #where_clause {
+            // Interior mutability is needed because `&mut self` methods on the trait object struct
+            // are dispatched through `as_raw`, which only takes `&self`.
+            __thintraitobjectmacro_buffer: #buffer_type,
+            __thintraitobjectmacro_inline: bool,
+            // Makes the struct have the same auto trait implementations as the boxed one.
+            __thintraitobjectmacro_phantom: #phantomdata,
+        }
+        impl #params #name #args #where_clause {
+            /// Constructs an inline storage thin trait object from a type implementing the trait, storing the value inside the thin trait object if it fits and allocating it like the boxed thin trait object otherwise.
+            #[inline]
+            pub fn new<
+                #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+                >(val: #value_generic) -> Self {
+                let buffer = <#buffer_type>::new(::core::mem::MaybeUninit::uninit());
+                let fits = ::core::mem::size_of::<#value_repr_type>() <= ::core::mem::size_of::<#buffer_type>()
+                    && ::core::mem::align_of::<#value_repr_type>() <= ::core::mem::align_of::<#buffer_type>();
+                if fits {
+                    unsafe {
+                        (buffer.get() as *mut #value_repr_type).write(#repr_name {
+                            __thintraitobjectmacro_repr_vtable: #vtable_field_val,
+                            __thintraitobjectmacro_repr_value: val,
+                            #repr_phantom_initializer
+                        });
+                    }
+                } else {
+                    // The buffer is at least as big and as aligned as its first field, which is the
+                    // vtable or a reference to it.
+                    unsafe {
+                        (buffer.get() as *mut *mut ()).write(<#trait_object_name #trait_object_args>::new(val).into_raw());
+                    }
+                }
+                Self {
+                    __thintraitobjectmacro_buffer: buffer,
+                    __thintraitobjectmacro_inline: fits,
+                    __thintraitobjectmacro_phantom: ::core::marker::PhantomData,
+                }
+            }
+            /// Returns `true` if the value is stored inside the thin trait object rather than in a separate allocation.
+            #[inline]
+            pub fn is_inline(&self) -> bool {
+                self.__thintraitobjectmacro_inline
+            }
+            /// Returns a pointer to the trait object, which may point inside of the inline storage thin trait object itself and is thus invalidated when it is moved.
+            #[inline]
+            pub fn as_raw(&self) -> *mut () {
+                let buffer = self.__thintraitobjectmacro_buffer.get();
+                if self.__thintraitobjectmacro_inline {
+                    buffer as *mut ()
+                } else {
+                    unsafe { *(buffer as *mut *mut ()) }
+                }
+            }
+            /// Retrieves the raw vtable of the contained trait object.
+            #[inline]
+            pub fn vtable(&self) -> &#vtable_type {
+                #vtable_getter_impl
+            }
+            #is_inline_vtable_method
+        }
+        #methods
+        impl #trait_params ::core::ops::Drop for #name #impl_elided_lifetime #where_clause {
+            fn drop(&mut self) {
+                unsafe { (self.vtable().#drop_name)(self.as_raw() as *mut ::core::ffi::c_void) }
+            }
+        }
+        #(#extra_impls)*
+        #(#marker_impls)*
+    };
+    Ok(result)
+}
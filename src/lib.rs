@@ -15,6 +15,8 @@
 //!
 //! The macro was **heavily** inspired by the design and implementation of an FFI-safe trait object described in the [*FFI-Safe Polymorphism: Thin Trait Objects*] article by Michael-F-Bryan. The article is a walkthrough for writing such a trait object manually, and this crate serves as the macro to perform the same task in an automated fashion.
 //!
+//! The minimum supported Rust version is 1.81.
+//!
 //! # Usage
 //! The most basic use case:
 //! ```rust
@@ -37,6 +39,8 @@
 //!
 //! Both of those will have the same visibility modifier as the trait on which the `#[thin_trait_object]` attribute is placed, unless you override it — the section up ahead is there to explain how.
 //!
+//! Methods which cannot be dispatched through the vtable, like generic ones or ones returning `Self`, are rejected with an error pointing to the offending part of the method. The whole trait is checked before giving up, so all such methods are reported together, each of them once, rather than one per compilation.
+//!
 //! ## Configuring the macro
 //! The basic invocation form, `#[thin_trait_object]`, will use the reasonable defaults for all possible configuration values. To override those configuration parameters, the following syntax is used:
 //! ```rust
@@ -81,8 +85,247 @@
 //!   
 //!   By default, `#[repr(transparent)]` is attached (cannot be overriden), the visibility is taken from the trait definition, and the name is of form `Boxed<trait_name>`, as in `BoxedMyTrait`.
 //!   
-//! - `inline_vtable = <true/false>` — specifies whether the vtable should be stored directly in the trait object (`true`) or be stored as a `&'static` reference to the vtable. Set to `false` by default, and **overriding this is not recommended** unless the trait has very few (one or two) methods, or it is absolutely necessary to override this in order to be compatible with certain third-party code.
-//!   
+//! - `vis = "<visibility>"` — overrides only the visibility of the generated boxed trait object structure, keeping the default name. The vtable structure keeps the visibility of the trait unless overriden with `vtable(...)`, and the representation struct which the values are stored in is never visible outside of the module, regardless of these options.
+//!
+//!   Example:
+//!   ```rust
+//!   mod private {
+//!       # use thin_trait_object::*;
+//!       #[thin_trait_object(
+//!           vis = "pub(crate)"
+//!       )]
+//!       pub trait MyTrait {}
+//!       impl MyTrait for u32 {}
+//!   }
+//!   let _ = private::BoxedMyTrait::new(1_u32);
+//!   ```
+//!
+//! - `name = "<name>"`, `vtable_name = "<name>"`, `repr_name = "<name>"` — override only the names of the generated boxed trait object structure, the vtable structure and the hidden representation struct respectively, keeping the default visibility and attributes. The names which are not overriden are still derived from the name of the trait, so two traits specifying only `name` in the same module do not collide.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       name = "ThinFoo",
+//!       vtable_name = "FooFunctionTable",
+//!   )]
+//!   trait Foo {
+//!       fn foo(&self) -> u32;
+//!   }
+//!   #[thin_trait_object(
+//!       name = "ThinBar",
+//!   )]
+//!   trait Bar {
+//!       fn bar(&self) -> u32;
+//!   }
+//!   impl Foo for u32 {
+//!       fn foo(&self) -> u32 { *self }
+//!   }
+//!   impl Bar for u32 {
+//!       fn bar(&self) -> u32 { *self + 1 }
+//!   }
+//!   let foo = ThinFoo::new(1_u32);
+//!   let _: &FooFunctionTable = foo.vtable();
+//!   assert_eq!(foo.foo(), 1);
+//!   let bar = ThinBar::new(1_u32);
+//!   let _: &BarVtable = bar.vtable();
+//!   assert_eq!(bar.bar(), 2);
+//!   ```
+//!
+//! - `module = "<name>"` — places all generated items into a child module with the given name instead of the module containing the trait, which keeps the vtable structure and the hidden representation struct out of the latter. The module has the visibility of the trait, and the thin trait object structures — the boxed one and those enabled by `thin_ref(...)`, `thin_mut(...)`, `inline_storage(...)`, `static_storage(...)` and `embedded(...)` — and the functions enabled by `free_fn(...)` and `export_ctor(...)` are re-exported from it with their usual visibilities, while the vtable structure has to be named through the module. The generated items are made visible from the same places as they would be without the module, and everything from the containing module is brought into scope inside of it with `use super::*;`, so paths in the trait definition keep working, except for ones starting with `self::` or `super::`. That glob import cannot see items declared directly inside of a function body, so a trait declared there cannot use this option. Not set by default.
+//!
+//!   Example:
+//!   ```rust
+//!   mod shapes {
+//!       use thin_trait_object::*;
+//!       pub struct Point(pub f64, pub f64);
+//!       #[thin_trait_object(module = "shape_thin")]
+//!       pub trait Shape {
+//!           fn center(&self) -> Point;
+//!       }
+//!       impl Shape for Point {
+//!           fn center(&self) -> Point { Point(self.0, self.1) }
+//!       }
+//!   }
+//!   use shapes::{shape_thin::ShapeVtable, BoxedShape, Point};
+//!   let shape = BoxedShape::new(Point(1.0, 2.0));
+//!   let _: &ShapeVtable = shape.vtable();
+//!   assert_eq!(shape.center().1, 2.0);
+//!   ```
+//!
+//! - `prefix = "<prefix>"` — replaces the `__thintraitobjectmacro` prefix of all hidden names in the generated code, such as the ones of the representation struct, its fields, the thunks and the generic parameters, with the given one, which is lowercased in names which are entirely lowercase and uppercased in names of constants. Useful when the macro is invoked by another code generator which may itself produce items with the default names, and thus has to make sure the hidden names of different invocations never collide. Trait methods starting with the given prefix are rejected just like those starting with the default one. Not set by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[allow(non_camel_case_types)]
+//!   struct __ThinTraitObjectMacro_ReprForTask;
+//!   #[thin_trait_object(prefix = "__TaskMacro")]
+//!   trait Task {
+//!       fn drop(&mut self);
+//!   }
+//!   impl Task for () {
+//!       fn drop(&mut self) {}
+//!   }
+//!   let task = BoxedTask::new(());
+//!   let _: unsafe fn(*mut std::ffi::c_void) = task.vtable().__taskmacro_drop;
+//!   ```
+//!
+//! - `thin_ref(<attributes> <visibility> <name>)` — enables generation of a borrowed thin trait object structure, which is to the boxed one what `&dyn Trait` is to `Box<dyn Trait>`, and specifies its name, visibility and attributes in the same way as `trait_object(...)`. Not generated by default.
+//!
+//!   The borrowed version can be obtained from the boxed one using the `as_thin_ref` method, is `Copy` and never drops the trait object. If none of the trait methods take `&mut self`, the trait is implemented for the borrowed version as well; otherwise, the methods which take `&self` are available on it as inherent methods.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       thin_ref(pub FooRef)
+//!   )]
+//!   trait Foo {
+//!       fn fooify(&self) -> String;
+//!   }
+//!   impl Foo for String {
+//!       fn fooify(&self) -> String {
+//!           format!("Fooified a string: {}", self)
+//!       }
+//!   }
+//!   fn call_fooify(foo: FooRef<'_>) -> String {
+//!       foo.fooify()
+//!   }
+//!   let boxed = BoxedFoo::new("Hello World!".to_string());
+//!   assert_eq!(call_fooify(boxed.as_thin_ref()), "Fooified a string: Hello World!");
+//!   ```
+//!
+//! - `thin_mut(<attributes> <visibility> <name>)` — same as `thin_ref(...)`, but for a mutably borrowed thin trait object, which is analogous to `&mut dyn Trait`, can be obtained using the `as_thin_mut` method and can call methods which take `&mut self`. While it exists, the boxed thin trait object it was obtained from is considered mutably borrowed. Since it cannot be copied, it has a `reborrow` method, which borrows it again for a shorter lifetime without giving it up, like `&mut *r` does for an `r: &mut dyn Trait`. Also adds a `leak` method to the boxed thin trait object, which consumes it without ever dropping or deallocating the value and returns a mutably borrowed thin trait object with the `'static` lifetime, like [`Box::leak`] does — only available if the boxed thin trait object itself is `'static`, i.e. doesn't borrow anything.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       thin_mut(pub CounterMut)
+//!   )]
+//!   trait Counter {
+//!       fn get(&self) -> u32;
+//!       fn increment(&mut self);
+//!   }
+//!   impl Counter for u32 {
+//!       fn get(&self) -> u32 { *self }
+//!       fn increment(&mut self) { *self += 1; }
+//!   }
+//!   fn increment_twice(mut counter: CounterMut<'_>) {
+//!       counter.increment();
+//!       counter.increment();
+//!   }
+//!   let mut boxed = BoxedCounter::new(0_u32);
+//!   increment_twice(boxed.as_thin_mut());
+//!   assert_eq!(boxed.get(), 2);
+//!
+//!   let mut counter = boxed.as_thin_mut();
+//!   increment_twice(counter.reborrow());
+//!   increment_twice(counter.reborrow());
+//!   assert_eq!(counter.get(), 6);
+//!
+//!   let leaked: CounterMut<'static> = boxed.leak();
+//!   increment_twice(leaked);
+//!   ```
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   # #[thin_trait_object(
+//!   #     thin_mut(pub CounterMut)
+//!   # )]
+//!   # trait Counter {
+//!   #     fn increment(&mut self);
+//!   # }
+//!   impl Counter for &mut u32 {
+//!       fn increment(&mut self) { **self += 1; }
+//!   }
+//!   let mut count = 0_u32;
+//!   // Would outlive `count`.
+//!   let leaked: CounterMut<'static> = BoxedCounter::new(&mut count).leak();
+//!   ```
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   # #[thin_trait_object(
+//!   #     thin_mut(pub CounterMut)
+//!   # )]
+//!   # trait Counter {
+//!   #     fn increment(&mut self);
+//!   # }
+//!   # impl Counter for u32 {
+//!   #     fn increment(&mut self) { *self += 1; }
+//!   # }
+//!   let mut boxed = BoxedCounter::new(0_u32);
+//!   let mut counter = boxed.as_thin_mut();
+//!   let reborrowed = counter.reborrow();
+//!   // The original is still mutably borrowed by `reborrowed`.
+//!   counter.increment();
+//!   drop(reborrowed);
+//!   ```
+//!   Like the other methods generated on the boxed thin trait object, `leak` takes precedence over a trait method of the same name, which then doesn't get an inherent method, and renaming the inherent method of a trait method to `leak` is rejected:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       thin_mut(pub PipeMut)
+//!   )]
+//!   trait Pipe {
+//!       fn leak(&mut self) -> u32;
+//!   }
+//!   impl Pipe for u32 {
+//!       fn leak(&mut self) -> u32 { std::mem::take(self) }
+//!   }
+//!   let mut boxed = BoxedPipe::new(5_u32);
+//!   assert_eq!(Pipe::leak(&mut boxed), 5);
+//!   let mut leaked: PipeMut<'static> = boxed.leak();
+//!   assert_eq!(leaked.leak(), 0);
+//!   ```
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       thin_mut(pub PipeMut)
+//!   )]
+//!   trait Pipe {
+//!       #[thin(rename = "leak")] // Error: `leak` is taken by one of the generated methods
+//!       fn drain(&mut self) -> u32;
+//!   }
+//!   ```
+//!
+//! - `vtable = "static"` or `vtable = "inline"` — specifies whether the allocation starts with a `&'static` reference to a vtable shared by all thin trait objects storing the same type (`"static"`) or with a copy of the vtable itself (`"inline"`). The former keeps the allocation small, only one pointer larger than the value, while the latter makes it larger by one pointer per vtable entry, but saves one indirection on every call. Set to `"static"` by default, and **overriding this is not recommended** unless the trait has very few (one or two) methods, or it is absolutely necessary to override this in order to be compatible with certain third-party code. For non-generic traits without a custom `#[repr]` on the vtable or methods under `#[cfg(...)]`, the generated code checks at compile time that the vtable or the reference to it takes up exactly as many words as described.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   # use std::mem::size_of;
+//!   #[thin_trait_object(
+//!       vtable = "static",
+//!       repr_name = "StaticRepr"
+//!   )]
+//!   trait Small {
+//!       fn get(&self) -> usize;
+//!       fn set(&mut self, val: usize);
+//!   }
+//!   #[thin_trait_object(
+//!       vtable = "inline",
+//!       repr_name = "InlineRepr"
+//!   )]
+//!   trait Fast {
+//!       fn get(&self) -> usize;
+//!       fn set(&mut self, val: usize);
+//!   }
+//!   # impl Small for usize {
+//!   #     fn get(&self) -> usize { *self }
+//!   #     fn set(&mut self, val: usize) { *self = val; }
+//!   # }
+//!   # impl Fast for usize {
+//!   #     fn get(&self) -> usize { *self }
+//!   #     fn set(&mut self, val: usize) { *self = val; }
+//!   # }
+//!   // A reference to the vtable, followed by the value.
+//!   assert_eq!(size_of::<StaticRepr<usize>>(), 2 * size_of::<usize>());
+//!   // `get`, `set` and `drop`, followed by the value.
+//!   assert_eq!(size_of::<InlineRepr<usize>>(), 4 * size_of::<usize>());
+//!   ```
+//! - `inline_vtable = <true/false>` — the same as `vtable = "inline"` (`true`) or `vtable = "static"` (`false`).
+//!
 //!   Example:
 //!   ```rust
 //!   # use thin_trait_object::*;
@@ -92,7 +335,28 @@
 //!   # trait MyTrait {}
 //!   ```
 //!   
-//! - `drop_abi = "..."` — specifies the ABI (the `"C"` in `extern "C"`) for the `drop` function pointer in the vtable. The ABI for all other methods in the vtable can be specified in the trait definition directly.
+//! - `drop_abi = "..."` — specifies the ABI (the `"C"` in `extern "C"`) for the `drop` function pointer in the vtable. The ABI for all other methods in the vtable can be specified in the trait definition directly or for all of them at once with the `abi` option. If the `abi` option is set, `drop_abi` defaults to the same value.
+//!
+//!   A panic which reaches the end of a function with an ABI other than `"Rust"` or one of the `"...-unwind"` ones aborts the process instead of unwinding out of it, which the compiler ensures on its own, so the stored value panicking while being dropped, cloned, formatted or having one of its methods called through such a vtable entry never lets the panic cross the FFI boundary. With `"C-unwind"`, which is the C ABI as far as foreign code is concerned, panics are let through instead and unwind into the caller of the vtable entry, which is useful when the code on both sides of the boundary is Rust:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::panic::{self, AssertUnwindSafe};
+//!   #[thin_trait_object(abi = "C-unwind")]
+//!   trait Divider {
+//!       fn divide(&self, divisor: u32) -> u32;
+//!   }
+//!   struct Dividend(u32);
+//!   impl Divider for Dividend {
+//!       fn divide(&self, divisor: u32) -> u32 {
+//!           self.0.checked_div(divisor).expect("division by zero")
+//!       }
+//!   }
+//!   let divider = BoxedDivider::new(Dividend(42));
+//!   let _: unsafe extern "C-unwind" fn(*mut std::ffi::c_void, u32) -> u32 = divider.vtable().divide;
+//!   assert_eq!(divider.divide(2), 21);
+//!   let result = panic::catch_unwind(AssertUnwindSafe(|| divider.divide(0)));
+//!   assert!(result.is_err());
+//!   ```
 //!   
 //!   Example:
 //!   ```rust
@@ -102,9 +366,134 @@
 //!   )]
 //!   # trait MyTrait {}
 //!   ```
+//! - `abi = "..."` — specifies the ABI for all function pointers in the vtable, including `drop` unless `drop_abi` is also specified. The trait methods themselves keep the ABI they were declared with — only the vtable entries and the generated glue code are affected, allowing implementations of the trait to be written in plain Rust while the vtable is usable from C.
+//!
+//!   Methods which explicitly specify an ABI in the trait definition must specify the same one as this option, otherwise a compile error is emitted: a vtable with entries of mixed ABIs is almost certainly a mistake when the vtable is meant to be used from foreign code. Methods which take or return regular trait objects are rejected with any ABI other than the Rust one, see the *Trait object arguments* section.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       abi = "C" // All vtable entries will be extern "C"
+//!   )]
+//!   trait MyTrait {
+//!       fn no_abi_specified(&self) -> u32;
+//!       extern "C" fn same_abi(&self) -> u32; // Cannot be extern "Rust"
+//!   }
+//!   # impl MyTrait for u32 {
+//!   #     fn no_abi_specified(&self) -> u32 { *self }
+//!   #     extern "C" fn same_abi(&self) -> u32 { *self }
+//!   # }
+//!   let vtable = *BoxedMyTrait::new(1_u32).vtable();
+//!   let _: unsafe extern "C" fn(*mut std::ffi::c_void) -> u32 = vtable.no_abi_specified;
+//!   ```
+//!
+//!   Only the ABI of the vtable entries themselves is affected — function pointer types among the parameters and return types of the methods keep the ABI they are written with, so a method taking an `extern "C"` callback takes one regardless of the ABI of its vtable entry:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::cell::Cell;
+//!   #[thin_trait_object]
+//!   trait Button {
+//!       fn set_callback(&self, cb: extern "C" fn(i32) -> i32);
+//!   }
+//!   #[thin_trait_object(abi = "C")]
+//!   trait ForeignButton {
+//!       fn set_callback(&self, cb: extern "C" fn(i32) -> i32);
+//!   }
+//!   struct Clicks(Cell<i32>);
+//!   impl Button for Clicks {
+//!       fn set_callback(&self, cb: extern "C" fn(i32) -> i32) {
+//!           self.0.set(cb(self.0.get()));
+//!       }
+//!   }
+//!   impl ForeignButton for Clicks {
+//!       fn set_callback(&self, cb: extern "C" fn(i32) -> i32) {
+//!           self.0.set(cb(self.0.get()));
+//!       }
+//!   }
+//!   extern "C" fn increment(clicks: i32) -> i32 {
+//!       clicks + 1
+//!   }
+//!   let button = BoxedButton::new(Clicks(Cell::new(0)));
+//!   button.set_callback(increment);
+//!   let _: unsafe fn(*mut std::ffi::c_void, extern "C" fn(i32) -> i32) = button.vtable().set_callback;
+//!   let foreign = BoxedForeignButton::new(Clicks(Cell::new(1)));
+//!   foreign.set_callback(increment);
+//!   let _: unsafe extern "C" fn(*mut std::ffi::c_void, extern "C" fn(i32) -> i32) =
+//!       foreign.vtable().set_callback;
+//!   ```
+//! - `error_code = <path>` — specifies a trait which converts errors to and from `i32` error codes, making the vtable entries of methods which return `Result<T, E>` and use a foreign ABI return an error code instead, which is `0` on success, and write the success value through an additional `*mut T` parameter at the end, which C code can call without knowing the layout of the `Result`. The methods of the thin trait objects still return the `Result`, recovering the error from the code. The trait is declared by the crate using the macro, since the macro cannot provide one, and has to have the following two methods, with `into_error_code` never returning `0`, which is checked by the generated glue code:
+//!   ```rust
+//!   # /*
+//!   fn into_error_code(self) -> i32;
+//!   fn from_error_code(code: i32) -> Self;
+//!   # */
+//!   ```
+//!   Only return types spelled out as `Result` with both the success and the error type are recognized — aliases like `io::Result<T>` are passed through unchanged. If none of the vtable entries use a foreign ABI, which the `abi` option sets for all of them, a compile error is emitted.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   pub trait IntoErrorCode: Sized {
+//!       fn into_error_code(self) -> i32;
+//!       fn from_error_code(code: i32) -> Self;
+//!   }
+//!   #[derive(Debug, PartialEq)]
+//!   pub struct Invalid;
+//!   impl IntoErrorCode for Invalid {
+//!       fn into_error_code(self) -> i32 { 1 }
+//!       fn from_error_code(_: i32) -> Self { Invalid }
+//!   }
+//!
+//!   #[thin_trait_object(
+//!       abi = "C",
+//!       error_code = IntoErrorCode
+//!   )]
+//!   trait Digits {
+//!       fn digit(&self, byte: u8) -> Result<u32, Invalid>;
+//!   }
+//!   impl Digits for () {
+//!       fn digit(&self, byte: u8) -> Result<u32, Invalid> {
+//!           (byte as char).to_digit(10).ok_or(Invalid)
+//!       }
+//!   }
+//!   let digits = BoxedDigits::new(());
+//!   assert_eq!(digits.digit(b'7'), Ok(7));
+//!   assert_eq!(digits.digit(b'x'), Err(Invalid));
+//!   // What C code calls instead
+//!   let mut out = 0;
+//!   let code = unsafe { (digits.vtable().digit)(digits.as_raw() as *mut _, b'3', &mut out) };
+//!   assert_eq!((code, out), (0, 3));
+//!   ```
+//! - `c_header = <true/false>` — specifies whether a C header declaring the vtable structure and the beginning of the thin trait object, which is the vtable or the pointer to it, is generated as the `C_HEADER` associated constant of the vtable structure. The fields are declared in the same order and with the same types as in Rust, with primitive, pointer and function pointer types converted to their C equivalents — the latter only for the `"C"` and `"C-unwind"` ABIs, and also when wrapped in an `Option`, which is null for `None` — and all other types assumed to be declared in C under the same name. The header is not written to a file during compilation, since that wouldn't be tracked by the compiler — a build step or a test can write the constant out instead. Requires all vtable entries which C code can call to use the `"C"` or `"C-unwind"` ABI and the vtable to keep the default `#[repr(C)]`, and is not supported for generic traits or traits with methods which are only present under some `cfg`; the `debug_fmt`, `display_fmt`, `hash`, `type_id`, `type_name`, `as_dyn` and `into_box_dyn` entries are declared as function pointers without parameters, since they take or return Rust types. Set to `false` by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       abi = "C",
+//!       c_header = true
+//!   )]
+//!   trait MyTrait {
+//!       fn add(&self, val: u32) -> u32;
+//!       fn set_callback(&self, cb: Option<extern "C" fn(u32) -> bool>);
+//!   }
+//!   assert!(MyTraitVtable::C_HEADER.contains("
+//!   typedef struct MyTraitVtable {
+//!       uint32_t (*add)(void *, uint32_t);
+//!       void (*set_callback)(void *, bool (*)(uint32_t));
+//!       void (*drop)(void *);
+//!   } MyTraitVtable;
+//!   "));
+//!   assert!(MyTraitVtable::C_HEADER.contains("
+//!   typedef struct BoxedMyTrait {
+//!       const MyTraitVtable *vtable;
+//!   } BoxedMyTrait;
+//!   "));
+//!   ```
 //! - `marker_traits(...)` — specifies a comma-separated list of traits which are to be considered marker traits, i.e. be implemented via an empty `impl` block on the generated thin trait object structure if the trait definition lists them as supertraits. Unsafe traits in the list need to be prefixed with the `unsafe` keyword.
 //!   
-//!   By default, the list is `marker_traits(unsafe Send, unsafe Sync, UnwindSafe, RefUnwindSafe)`.
+//!   By default, the list is `marker_traits(unsafe Send, unsafe Sync, UnwindSafe, RefUnwindSafe, Eq)`.
 //!   
 //!   See the [Supertraits](#supertraits) section for more on how the macro interacts with supertraits.
 //!   
@@ -124,7 +513,7 @@
 //!   )]
 //!   trait MyTrait: SafeTrait + UnsafeTrait {}
 //!   ```
-//! - `store_layout = <true/false>` — specifies whether the generated vtable should also contain the `size` and `align` fields, storing the size of the stored type and its preferred alignment respectively. Set to `false` by default for compatibility.
+//! - `store_layout = <true/false>` — specifies whether the generated vtable should also contain the `size` and `align` fields, storing the size of the stored type and its preferred alignment respectively, and adds the `value_size` and `value_align` methods to the boxed thin trait object, which read those. Set to `false` by default for compatibility.
 //!
 //!   Example:
 //!   ```rust
@@ -132,89 +521,1226 @@
 //!   #[thin_trait_object(
 //!       store_layout = true
 //!   )]
-//!   # trait MyTrait {}
+//!   trait MyTrait {}
+//!   impl MyTrait for [u16; 3] {}
+//!   let boxed = BoxedMyTrait::new([0_u16; 3]);
+//!   assert_eq!(boxed.value_size(), 6);
+//!   assert_eq!(boxed.value_align(), 2);
 //!   ```
+//! - `header = <type>` — specifies the type of a header which is stored in the allocation right after the vtable or the pointer to it and before the value, and can thus be read without knowing the type of the latter, by Rust code through the `header` method added to the boxed and borrowed thin trait objects, and by foreign code at a fixed offset, as the C header generated with `c_header = true` declares it as the second field of the thin trait object. The `new` constructor of the boxed thin trait object takes the header as its first argument, and cloning with the `clone` entry copies it. The header type has to implement `Copy`, since the value is moved out of the allocation without it by consuming methods, and cannot mention the generic parameters of the trait. Cannot be combined with custom allocators, inline or static storage, `box_dyn = true` and `export_ctor(...)`, all of which construct the value without a header. Not set by default.
 //!
-//! ## Use with FFI
-//! One of the main focuses of the macro is FFI, which is why usage of the macro with FFI is simple and natural:
-//! ```no_run
-//! use thin_trait_object::*;
-//! use std::ffi::c_void;
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[derive(Clone, Copy, Debug, PartialEq)]
+//!   #[repr(C)]
+//!   pub struct Tag {
+//!       kind: u32,
+//!   }
+//!   #[thin_trait_object(
+//!       header = Tag,
+//!       thin_ref(pub MessageRef)
+//!   )]
+//!   trait Message {
+//!       fn text(&self) -> String;
+//!   }
+//!   impl Message for String {
+//!       fn text(&self) -> String { self.clone() }
+//!   }
+//!   impl Message for [u8; 64] {
+//!       fn text(&self) -> String { String::from_utf8_lossy(self).into_owned() }
+//!   }
+//!   let small = BoxedMessage::new(Tag { kind: 1 }, "Hello".to_string());
+//!   let big = BoxedMessage::new(Tag { kind: 2 }, [b'a'; 64]);
+//!   assert_eq!(small.header(), &Tag { kind: 1 });
+//!   assert_eq!(big.as_thin_ref().header().kind, 2);
+//!   assert_eq!(small.text(), "Hello");
+//!   ```
+//! - `clone = <true/false>` — specifies whether the generated vtable should contain a `clone` entry, which allocates a new thin trait object with a clone of the stored value, and implements `Clone` for the boxed thin trait object using it. Only types which implement `Clone` can then be stored in the thin trait object. Set to `false` by default, but is implied if the trait has `Clone` as a supertrait.
 //!
-//! #[thin_trait_object(drop_abi = "C")]
-//! trait Foo {
-//!     extern "C" fn say_hello(&self);
-//! }
+//!   Like other entries which don't correspond to any trait method, `clone` uses the ABI specified by `drop_abi`.
 //!
-//! impl Foo for String {
-//!     extern "C" fn say_hello(&self) {
-//!          println!("Hello from \"{}\"", self);
-//!     }
-//! }
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       clone = true
+//!   )]
+//!   trait MyTrait {
+//!       fn get(&self) -> String;
+//!   }
+//!   impl MyTrait for String {
+//!       fn get(&self) -> String { self.clone() }
+//!   }
+//!   let boxed = BoxedMyTrait::new("Hello World!".to_string());
+//!   let cloned = boxed.clone();
+//!   drop(boxed);
+//!   assert_eq!(cloned.get(), "Hello World!");
+//!   ```
+//! - `debug = <true/false>` — specifies whether the generated vtable should contain a `debug_fmt` entry, which formats the stored value using its `Debug` implementation, and implements `Debug` for the boxed thin trait object (as well as the borrowed ones, if enabled) using it. Only types which implement `Debug` can then be stored in the thin trait object. Set to `false` by default, but is implied if the trait has `Debug` as a supertrait. Like `clone`, uses the ABI specified by `drop_abi`.
 //!
-//! # /*
-//! extern "C" {
-//!     fn eater_of_foo(foo: *mut c_void);
-//!     fn creator_of_foo() -> *mut c_void;
-//! }
-//! # */
-//! # unsafe extern "C" fn eater_of_foo(_foo: *mut c_void) {}
-//! # unsafe extern "C" fn creator_of_foo() -> *mut c_void {
-//! #     BoxedFoo::new("Rust pretending to be C".to_string()).into_raw() as *mut _
-//! # }
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       debug = true
+//!   )]
+//!   trait MyTrait {}
+//!   impl MyTrait for Vec<u8> {}
+//!   let boxed = BoxedMyTrait::new(vec![1_u8, 2, 3]);
+//!   assert_eq!(format!("{:?}", boxed), "[1, 2, 3]");
+//!   ```
+//! - `display = <true/false>` — same as `debug`, but for `Display`: specifies whether the generated vtable should contain a `display_fmt` entry and implements `Display` for the boxed thin trait object (as well as the borrowed ones, if enabled) using it. Independent of `debug`, so either, both or neither can be enabled. Set to `false` by default, but is implied if the trait has `Display` as a supertrait.
 //!
-//! let foo = BoxedFoo::new("Hello World!".to_string());
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::fmt::Display;
+//!   #[thin_trait_object]
+//!   trait Message: Display {
+//!       fn urgent(&self) -> bool;
+//!   }
+//!   impl Message for String {
+//!       fn urgent(&self) -> bool { self.ends_with('!') }
+//!   }
+//!   let boxed = BoxedMessage::new("Hello World!".to_string());
+//!   assert_eq!(boxed.to_string(), "Hello World!");
+//!   ```
+//! - `type_id = <true/false>` — specifies whether the generated vtable should contain a `type_id` entry, which returns the [`TypeId`] of the stored type, and adds `is`, `downcast_ref`, `downcast_mut` and `downcast` methods to the boxed thin trait object, which recover the stored value if its type is known, in the same fashion as `dyn Any`, `with_ref` and `with_mut` methods, which call a closure with a borrow of the stored value if its type is known, as well as a `replace` method, which overwrites the stored value with another one of the same type without reallocating. Only `'static` types can then be stored in the thin trait object, which [tagged downcasting](#tagged-downcasting) doesn't require. Set to `false` by default.
 //!
-//! unsafe {
-//!     // Will transfer ownership to the C side.
-//!     eater_of_foo(foo.into_raw() as *mut c_void);
-//! }
-//! // Acquire ownership of a different implementation from the C side.
-//! let foo = unsafe { BoxedFoo::from_raw(creator_of_foo() as *mut ()) };
-//! foo.say_hello();
-//! ```
-//! The C side would do:
-//! ```c
-//! #include <stdio.h>
+//!   Since `TypeId` is not FFI-safe, the `type_id` entry always uses the Rust ABI regardless of `drop_abi`.
 //!
-//! typedef void (*vtable_say_hello)(void*);
-//! typedef void (*vtable_drop)(void*);
-//! typedef struct foo_vtable {
-//!    vtable_say_hello say_hello;
-//!    vtable_drop drop;
-//! } foo_vtable;
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       type_id = true
+//!   )]
+//!   trait MyTrait {}
+//!   impl MyTrait for String {}
+//!   impl MyTrait for u32 {}
+//!   let mut boxed = BoxedMyTrait::new(1234_u32);
+//!   assert!(boxed.downcast_ref::<String>().is_none());
+//!   *boxed.downcast_mut::<u32>().unwrap() += 1;
+//!   // A failed downcast gives the trait object back
+//!   let boxed = boxed.downcast::<String>().unwrap_err();
+//!   assert_eq!(boxed.downcast::<u32>().ok(), Some(1235));
+//!   ```
+//! - `type_name = <true/false>` — specifies whether the generated vtable should contain a `type_name` entry, which returns the name of the stored type as given by [`core::any::type_name`], and adds a `value_type_name` method returning it to the boxed thin trait object (as well as the borrowed ones, if enabled). Meant for diagnostics only. Set to `false` by default, since the names of all stored types then end up in the binary. Like `type_id`, always uses the Rust ABI.
 //!
-//! void eater_of_foo(void* foo) {
-//!     // The first field is a pointer to the vtable, so we have to first
-//!     // extract that pointer and then dereference the function pointers.
-//!     foo_vtable* vtable = *((foo_vtable**)foo);
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       type_name = true
+//!   )]
+//!   trait MyTrait {}
+//!   impl MyTrait for u32 {}
+//!   let boxed = BoxedMyTrait::new(1234_u32);
+//!   assert_eq!(boxed.value_type_name(), "u32");
+//!   ```
+//! - `as_dyn = <true/false>` — specifies whether the generated vtable should contain an `as_dyn` entry, which returns a pointer to the stored value as a regular trait object, and adds `as_dyn` and `as_dyn_mut` methods borrowing it as `&dyn Trait` and `&mut dyn Trait` to the boxed thin trait object (as well as the borrowed ones, if enabled, with only `as_dyn` on the shared one). Useful for passing the stored value to code which expects regular trait objects. Requires the trait to be object-safe as far as the compiler is concerned, which traits with `async fn` methods aren't, and cannot be combined with `static_storage(...)`. With `storage = "arc"` or `storage = "rc"`, only `as_dyn` is available, since the value may be shared. Set to `false` by default. Like `type_id`, always uses the Rust ABI.
 //!
-//!     // Have to provide the pointer twice, firstly for
-//!     // lookup and then to provide the &self reference.
-//!     vtable.say_hello(foo);
-//!     // Don't forget about manual memory management — the C side owns the trait object now.
-//!     vtable.drop(foo);
-//! }
-//! void* creator_of_foo(void) {
-//!     // Allocate space for one pointer, the pointer to the vtable.
-//!     void* allocation = malloc(sizeof(foo_vtable*));
-//!     void* vtable_pointer = &custom_vtable;
-//!     // Put the pointer into the allocation.
-//!     memcpy(allocation, &vtable_pointer, sizeof(foo_vtable*));
-//!     return allocation;
-//! }
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       as_dyn = true
+//!   )]
+//!   trait Shape {
+//!       fn area(&self) -> f64;
+//!       fn scale(&mut self, factor: f64);
+//!   }
+//!   struct Square(f64);
+//!   impl Shape for Square {
+//!       fn area(&self) -> f64 { self.0 * self.0 }
+//!       fn scale(&mut self, factor: f64) { self.0 *= factor; }
+//!   }
+//!   fn total_area(shapes: &[&dyn Shape]) -> f64 {
+//!       shapes.iter().map(|shape| shape.area()).sum()
+//!   }
+//!   let mut small = BoxedShape::new(Square(1.0));
+//!   let big = BoxedShape::new(Square(3.0));
+//!   small.as_dyn_mut().scale(2.0);
+//!   assert_eq!(total_area(&[small.as_dyn(), big.as_dyn()]), 13.0);
+//!   ```
+//! - `box_dyn = <true/false>` — specifies whether the generated vtable should contain an `into_box_dyn` entry, which adds an `into_box_dyn` method converting the boxed thin trait object into a regular `Box<dyn Trait>`, and a `from_box_dyn` constructor doing the opposite. Both conversions allocate: `into_box_dyn` moves the stored value into a new allocation and frees the one of the thin trait object, while `from_box_dyn` stores the `Box<dyn Trait>` itself, which stays where it is, inside a new thin trait object allocation, so that calls go through both vtables. For the latter, the trait is also implemented for `Box<dyn Trait>` by calling the corresponding methods of the trait object, which means that the trait cannot already have an implementation for it, has to be object-safe as far as the compiler is concerned and cannot have methods taking `self` by value, since those cannot be called on `dyn Trait` — `self: Box<Self>` is fine. Only supported with `storage = "box"` and without custom allocators or static storage, and cannot be combined with the `clone`, `eq`, `hash` and `cmp` entries, as well as the `debug_fmt` and `display_fmt` ones unless the trait has `Debug` or `Display` respectively as a supertrait, since `Box<dyn Trait>` cannot implement the traits those require. Set to `false` by default. Like `type_id`, always uses the Rust ABI.
 //!
-//! static foo_vtable custom_vtable {
-//!     // Using C11 designated initializers, consult your local C expert for
-//!     // ways to do this on an old compiler.
-//!     .say_hello = &impl_say_hello,
-//!     .drop = &impl_drop
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       box_dyn = true
+//!   )]
+//!   trait Greeter {
+//!       fn greet(&self) -> String;
+//!   }
+//!   struct English;
+//!   impl Greeter for English {
+//!       fn greet(&self) -> String { "Hello".to_string() }
+//!   }
+//!   fn greet_twice(greeter: Box<dyn Greeter>) -> String {
+//!       format!("{0}! {0}!", greeter.greet())
+//!   }
+//!   let thin = BoxedGreeter::new(English);
+//!   let fat: Box<dyn Greeter> = thin.into_box_dyn();
+//!   assert_eq!(fat.greet(), "Hello");
+//!   let thin = BoxedGreeter::from_box_dyn(fat);
+//!   assert_eq!(greet_twice(thin.into_box_dyn()), "Hello! Hello!");
+//!   ```
+//! - `from_parts = <true/false>` — specifies whether the boxed thin trait object gets an `unsafe` `from_parts` constructor, which takes a `&'static` reference to a vtable and an opaque `*mut c_void` data pointer and makes a thin trait object dispatching through that vtable, for thin trait objects backed by function pointers obtained at runtime, such as from a dynamically loaded library or a scripting language, rather than by a Rust type implementing the trait. The entries of the given vtable are called with the data pointer in place of the pointer to the stored value, so they never see anything laid out by the macro, and the `drop` entry is called with it once the thin trait object is dropped. The vtable structure and its fields can be made public with `vtable(...)` for the vtable to be filled in outside of the module. The two pointers are allocated together and stored in the thin trait object as if they were a value implementing the trait, so every call goes through both vtables, and the other entries, like `type_id`, describe that internal type. Not supported for generic traits, traits with associated types or constants and with `storage = "buffer"`, and cannot be combined with the entries which require the stored value to implement a trait, like `clone` (apart from the one of `storage = "arc"` and `"rc"`), `debug_fmt` or `eq`. Set to `false` by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::ffi::c_void;
+//!   #[thin_trait_object(
+//!       from_parts = true
+//!   )]
+//!   trait Script {
+//!       fn run(&mut self, input: i64) -> i64;
+//!   }
+//!   // Stand-ins for functions exported by a script engine, which keeps its state behind the
+//!   // data pointer.
+//!   unsafe fn run_accumulator(state: *mut c_void, input: i64) -> i64 {
+//!       let total = &mut *(state as *mut i64);
+//!       *total += input;
+//!       *total
+//!   }
+//!   unsafe fn free_accumulator(state: *mut c_void) {
+//!       drop(Box::from_raw(state as *mut i64));
+//!   }
+//!   let vtable: &'static ScriptVtable = Box::leak(Box::new(ScriptVtable {
+//!       run: run_accumulator,
+//!       drop: free_accumulator,
+//!   }));
+//!   let state = Box::into_raw(Box::new(0_i64)) as *mut c_void;
+//!   // The entries expect the state to be a boxed `i64`, which it is.
+//!   let mut script = unsafe { BoxedScript::from_parts(vtable, state) };
+//!   script.run(40);
+//!   assert_eq!(script.run(2), 42);
+//!   ```
+//! - `default = <type>` — specifies a type which implements both the trait and `Default`, making the boxed thin trait object implement `Default` by storing the default value of that type, so that code constructing one in tests or examples doesn't have to name the type everywhere. With `header = ...`, the header is the default value of its type as well, which then has to implement `Default` too. If the type doesn't implement the trait or `Default`, the compile error points at the type in the attribute. Not supported with `storage = "buffer"`. Not set by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       default = Silence
+//!   )]
+//!   trait Sound {
+//!       fn volume(&self) -> u8;
+//!   }
+//!   #[derive(Default)]
+//!   struct Silence;
+//!   impl Sound for Silence {
+//!       fn volume(&self) -> u8 { 0 }
+//!   }
+//!   let sound = BoxedSound::default();
+//!   assert_eq!(sound.volume(), 0);
+//!   let sounds: Vec<BoxedSound> = (0..3).map(|_| Default::default()).collect();
+//!   assert_eq!(sounds.len(), 3);
+//!   ```
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       default = Silence // Doesn't implement `Default`
+//!   )]
+//!   trait Sound {
+//!       fn volume(&self) -> u8;
+//!   }
+//!   struct Silence;
+//!   impl Sound for Silence {
+//!       fn volume(&self) -> u8 { 0 }
+//!   }
+//!   ```
+//! - `eq = <true/false>` — specifies whether the generated vtable should contain an `eq` entry, which compares two stored values of the same type using their `PartialEq` implementation, and implements `PartialEq` for the boxed thin trait object (as well as the borrowed ones, if enabled) using it. Thin trait objects storing values of different types compare as not equal without calling the `eq` entry, which is why this requires `type_id = true` — the `eq` entry alone would have no way of knowing whether the other value is of the same type. Only types which implement `PartialEq` can then be stored in the thin trait object. Set to `false` by default, but is implied if the trait has `PartialEq` or `Eq` as a supertrait, and in the latter case, `Eq` is also implemented, like the other marker traits. Like `clone`, uses the ABI specified by `drop_abi`.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       type_id = true
+//!   )]
+//!   trait MyTrait: Eq {}
+//!   impl MyTrait for u32 {}
+//!   impl MyTrait for String {}
+//!   let boxed = BoxedMyTrait::new(1234_u32);
+//!   assert!(boxed == BoxedMyTrait::new(1234_u32));
+//!   assert!(boxed != BoxedMyTrait::new(4321_u32));
+//!   assert!(BoxedMyTrait::new("1234".to_string()) != boxed);
+//!   ```
+//! - `hash = <true/false>` — specifies whether the generated vtable should contain a `hash` entry, which feeds the stored value to a type-erased [`Hasher`] using its `Hash` implementation, and implements `Hash` for the boxed thin trait object (as well as the borrowed ones, if enabled) using it, allowing thin trait objects to be used as keys in hash maps. Since `Hash` only makes sense if equal values hash the same, this requires the `eq` entry as well. Only types which implement `Hash` can then be stored in the thin trait object. Set to `false` by default, but is implied if the trait has `Hash` as a supertrait. Like `clone`, uses the ABI specified by `drop_abi`.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::collections::HashSet;
+//!   #[thin_trait_object(
+//!       type_id = true,
+//!       hash = true
+//!   )]
+//!   trait MyTrait: Eq {}
+//!   impl MyTrait for u32 {}
+//!   impl MyTrait for String {}
+//!   let mut set = HashSet::new();
+//!   set.insert(BoxedMyTrait::new(1234_u32));
+//!   set.insert(BoxedMyTrait::new("1234".to_string()));
+//!   assert!(!set.insert(BoxedMyTrait::new(1234_u32)));
+//!   assert_eq!(set.len(), 2);
+//!   ```
+//! - `ord = <true/false>` — specifies whether the generated vtable should contain a `cmp` entry, which compares two stored values of the same type using their `Ord` implementation, and implements `PartialOrd`, `Ord` and `Eq` for the boxed thin trait object (as well as the borrowed ones, if enabled) using it, allowing thin trait objects to be stored in sorted collections. Thin trait objects storing values of different types are ordered by the [`TypeId`]s of those types without calling the `cmp` entry, so a collection mixing types orders its elements by type first and by value second, which is why this requires `type_id = true`. Since `Ord` has `PartialEq` and `Eq` as supertraits, the `eq` entry is required as well. Only types which implement `Ord` can then be stored in the thin trait object. Set to `false` by default. Like `clone`, uses the ABI specified by `drop_abi`.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::collections::BTreeSet;
+//!   #[thin_trait_object(
+//!       type_id = true,
+//!       eq = true,
+//!       ord = true
+//!   )]
+//!   trait Label {
+//!       fn text(&self) -> String;
+//!   }
+//!   impl Label for u32 {
+//!       fn text(&self) -> String { self.to_string() }
+//!   }
+//!   impl Label for &'static str {
+//!       fn text(&self) -> String { self.to_string() }
+//!   }
+//!   let mut set = BTreeSet::new();
+//!   set.insert(BoxedLabel::new(20_u32));
+//!   set.insert(BoxedLabel::new("b"));
+//!   set.insert(BoxedLabel::new(3_u32));
+//!   set.insert(BoxedLabel::new("a"));
+//!   assert!(!set.insert(BoxedLabel::new(3_u32)));
+//!   // The values of each type are sorted, in whichever order the types themselves are.
+//!   let texts = set.iter().map(|label| label.text()).collect::<Vec<_>>();
+//!   assert!(texts == ["3", "20", "a", "b"] || texts == ["a", "b", "3", "20"]);
+//!   ```
+//!
+//!   The ordering between types depends on the type IDs, which are not stable between compilations, so it should not be relied on beyond being consistent within a single run of the program.
+//! - `storage = "<box/arc/rc/buffer>"` — specifies the smart pointer type which owns the stored value. With the default, `"box"`, the thin trait object owns the value exclusively, the same way `Box<dyn Trait>` does. With `"arc"` or `"rc"`, the thin trait object is instead analogous to `Arc<dyn Trait>` or `Rc<dyn Trait>` respectively: it implements `Clone` by incrementing the reference count (which means that the `clone` vtable entry is always present and doesn't require the stored type to implement `Clone`), and the value is dropped when the last clone is. With `"buffer"`, the thin trait object owns the value exclusively but stores it in a buffer borrowed from the caller, as described below.
+//!
+//!   Since the value may be shared, the following restrictions apply with `"arc"` and `"rc"`:
+//!   - trait methods cannot take `&mut self`, and `thin_mut(...)` cannot be used;
+//!   - with `type_id = true`, only `is`, `downcast_ref` and `with_ref` are generated;
+//!   - with `"arc"`, `Send` and `Sync` can only be supertraits together, and with `"rc"`, neither of them can be.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       storage = "arc"
+//!   )]
+//!   trait MyTrait: Send + Sync {
+//!       fn get(&self) -> String;
+//!   }
+//!   impl MyTrait for String {
+//!       fn get(&self) -> String { self.clone() }
+//!   }
+//!   let shared = BoxedMyTrait::new("Hello World!".to_string());
+//!   let clone = shared.clone();
+//!   std::thread::spawn(move || assert_eq!(clone.get(), "Hello World!")).join().unwrap();
+//!   assert_eq!(shared.get(), "Hello World!");
+//!   ```
+//!
+//!   Buffer storage never allocates, and the generated code doesn't refer to `alloc` or `std` at all, which makes the thin trait objects usable in `#![no_std]` crates without a global allocator. Instead of `new` and `from_box`, the thin trait object has a `new_in_buffer` constructor, which writes the value into a `&'inner mut [MaybeUninit<u8>]` buffer (`&'static mut` if `'static` is a supertrait) at the first address aligned for it and gives the value back if it doesn't fit, and a `buffer_size_for::<T>()` constant function returning a size which always fits a value of type `T`, whatever the alignment of the buffer. The thin trait object borrows the buffer for as long as it exists, and dropping it drops the value exactly once, without freeing anything, while methods taking `self` by value move the value out of the buffer instead. Everything which would allocate is unavailable: the `clone` entry, `box_dyn = true`, `allocator_api = true`, inline storage, the shared drop thunk, `manual_drop = true`, exported constructors, `async fn` methods and methods taking `self: Box<Self>`. Methods taking `self: Pin<&mut Self>` are not supported either, since forgetting the thin trait object would end the borrow of the buffer without dropping the pinned value.
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   # use std::sync::atomic::{AtomicUsize, Ordering};
+//!   use core::mem::MaybeUninit;
+//!   #[thin_trait_object(storage = "buffer")]
+//!   trait Sensor {
+//!       fn read(&mut self) -> u16;
+//!   }
+//!   static DROPPED: AtomicUsize = AtomicUsize::new(0);
+//!   struct Counter(u16);
+//!   impl Sensor for Counter {
+//!       fn read(&mut self) -> u16 {
+//!           self.0 += 1;
+//!           self.0
+//!       }
+//!   }
+//!   impl Drop for Counter {
+//!       fn drop(&mut self) {
+//!           DROPPED.fetch_add(1, Ordering::Relaxed);
+//!       }
+//!   }
+//!   const SIZE: usize = BoxedSensor::buffer_size_for::<Counter>();
+//!   let mut buffer = [MaybeUninit::uninit(); SIZE + 8];
+//!   // Wherever it starts, a buffer of that size fits the value after aligning it.
+//!   let align = core::mem::align_of::<usize>();
+//!   for start in 0..align {
+//!       let mut sensor = BoxedSensor::new_in_buffer(&mut buffer[start..start + SIZE], Counter(0))
+//!           .unwrap_or_else(|_| panic!("does not fit"));
+//!       assert_eq!(sensor.read(), 1);
+//!       assert_eq!(sensor.as_raw() as usize % align, 0);
+//!   }
+//!   assert_eq!(DROPPED.load(Ordering::Relaxed), align);
+//!   // The buffer is too short for a value after a reference to the vtable.
+//!   let mut short = [MaybeUninit::uninit(); 2];
+//!   assert!(BoxedSensor::new_in_buffer(&mut short, Counter(0)).is_err());
+//!   ```
+//!   The borrow of the buffer is what keeps the value alive, so the buffer cannot be touched while the thin trait object exists:
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   # use core::mem::MaybeUninit;
+//!   #[thin_trait_object(storage = "buffer")]
+//!   trait Sensor {
+//!       fn read(&mut self) -> u16;
+//!   }
+//!   impl Sensor for u16 {
+//!       fn read(&mut self) -> u16 { *self }
+//!   }
+//!   let mut buffer = [MaybeUninit::uninit(); 32];
+//!   let mut sensor = BoxedSensor::new_in_buffer(&mut buffer, 1_u16).ok().unwrap();
+//!   buffer = [MaybeUninit::uninit(); 32];
+//!   assert_eq!(sensor.read(), 1);
+//!   ```
+//! - `repr = "<modifier>"` — adds `packed`, `packed(<n>)` or `align(<n>)` to the `#[repr(C)]` attribute of the representation struct, which is what the allocation of the boxed thin trait object holds, for mapping it onto a fixed layout like a wire format; `repr = "C, packed"` is accepted as well. `align(<n>)` only raises the alignment of the allocation. With `packed`, the value directly follows the pointer to the vtable without any padding, and either of them can be misaligned, so the thin trait object reads the vtable pointer with `read_unaligned` and the thunks never borrow the value in place: they call the methods on a copy of it read with `read_unaligned`, and write the copy back with `write_unaligned` after methods taking `&mut self`. This requires stored values to implement `Copy`, which also means that they have nothing to drop, and rules out methods with a pinned `self`, async methods and methods returning something borrowed from `self`, as well as everything which needs references to the fields of the representation struct or lays it out on its own: storage other than `"box"`, `vtable = "inline"`, headers, the shared drop thunk, custom allocators, embedded storage, `from_parts = true` and the extra entries other than `type_name`, `into_box_dyn` and `dealloc`. Not set by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[derive(Clone, Copy)]
+//!   #[repr(C, packed)]
+//!   struct Frame {
+//!       kind: u8,
+//!       length: u32,
+//!   }
+//!   #[thin_trait_object(repr = "packed")]
+//!   trait Message {
+//!       fn length(&self) -> u32;
+//!       fn grow(&mut self, by: u32);
+//!   }
+//!   impl Message for Frame {
+//!       fn length(&self) -> u32 { self.length }
+//!       fn grow(&mut self, by: u32) { self.length += by; }
+//!   }
+//!   // The wire layout of the allocation, as foreign code would declare it.
+//!   #[repr(C, packed)]
+//!   struct Wire {
+//!       vtable: *const MessageVtable,
+//!       kind: u8,
+//!       length: u32,
+//!   }
+//!   let mut message = BoxedMessage::new(Frame { kind: 1, length: 2 });
+//!   message.grow(3);
+//!   assert_eq!(message.length(), 5);
+//!   let wire = unsafe { (message.as_raw() as *const Wire).read_unaligned() };
+//!   assert_eq!((wire.kind, wire.length), (1, 5));
+//!   assert_eq!(std::mem::size_of::<Wire>(), std::mem::size_of::<usize>() + 5);
+//!   ```
+//!   Storing a value which isn't `Copy` fails to compile:
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(repr = "packed")]
+//!   trait Message {
+//!       fn length(&self) -> usize;
+//!   }
+//!   impl Message for String {
+//!       fn length(&self) -> usize { self.len() }
+//!   }
+//!   let message = BoxedMessage::new(String::from("hello")); // Error: `String` is not `Copy`
+//!   ```
+//! - `allocator_api = <true/false>` — enables generation of the `try_new`, `new_in` and `try_new_in` constructors on the boxed thin trait object, which report allocation failure instead of aborting and/or allocate using a custom [`Allocator`]. The allocator is stored inside the thin trait object after the value and is used to deallocate it, which takes no space if the allocator is zero-sized. Requires the unstable `allocator_api` feature to be enabled in the crate using the macro, and is only supported with `storage = "box"`. Set to `false` by default.
+//!
+//!   The allocator is picked at the call site, separately for every thin trait object, rather than when the macro is expanded. Its type is erased along with the one of the value, since the drop thunk which passes the allocator to [`Box::from_raw_in`] is generic over both, so the boxed thin trait object type does not have an allocator type parameter: thin trait objects using different allocators have the same type, and stay one pointer wide regardless of the size of the allocator, which is only paid for in the allocation.
+//!
+//!   Since the allocator is stored inside the thin trait object, it has to implement all marker traits that the trait has as supertraits, as well as `Clone` if the `clone` vtable entry is present. With `type_id = true`, the `downcast` method, which moves the value out, is not generated, since the allocator which needs to deallocate the object is unknown.
+//!
+//!   Example:
+//!   ```rust
+//!   # /*
+//!   #![feature(allocator_api)]
+//!   use std::alloc::Global;
+//!
+//!   #[thin_trait_object(
+//!       allocator_api = true
+//!   )]
+//!   trait MyTrait {}
+//!   impl MyTrait for String {}
+//!   let boxed = BoxedMyTrait::try_new("Hello World!".to_string())?;
+//!   let boxed_in = BoxedMyTrait::new_in("Hello World!".to_string(), Global);
+//!   # */
+//!   ```
+//! - `dispatch_defaults = <true/false>` — specifies whether methods which have a default implementation in the trait definition get vtable entries like all other methods (`true`) or are left out of the vtable, in which case the thin trait object uses the default implementation, which is then free to call the other methods through the vtable. Set to `true` by default, which is necessary for the supertrait pattern described below. Single methods can be left out with a `#[thin(skip)]` attribute instead, see [Skipping methods](#skipping-methods).
+//!
+//!   Note that with `false`, **an implementation which overrides a default method is not dynamically dispatched to** — calling the method on the thin trait object will always run the default implementation.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       dispatch_defaults = false
+//!   )]
+//!   trait MyTrait {
+//!       fn get(&self) -> u32;
+//!       fn get_twice(&self) -> u32 {
+//!           self.get() * 2
+//!       }
+//!   }
+//!   impl MyTrait for u32 {
+//!       fn get(&self) -> u32 { *self }
+//!       // Not used by the thin trait object
+//!       fn get_twice(&self) -> u32 { 0 }
+//!   }
+//!   // Only `get` and `drop` are in the vtable
+//!   assert_eq!(std::mem::size_of::<MyTraitVtable>(), 2 * std::mem::size_of::<usize>());
+//!   assert_eq!(BoxedMyTrait::new(21_u32).get_twice(), 42);
+//!   ```
+//!
+//! - `inline_storage(<attributes> <visibility> <name>)` — enables generation of an inline storage thin trait object structure, which stores the value directly inside of itself instead of allocating it if the value is small enough, and specifies its name, visibility and attributes in the same way as `trait_object(...)`. Values which don't fit are allocated the same way the boxed thin trait object does it. Not generated by default, and only supported with `storage = "box"`.
+//!
+//!   Since it contains the buffer for the value, the inline storage version is not one pointer wide and cannot be passed over FFI, but the pointer returned by its `as_raw` method can be used in the same way as the one returned by the boxed thin trait object's `as_raw`, for as long as the inline storage version is not moved. Methods which take `self` or `self: Box<Self>` can't be called on it, and neither can `Clone` be implemented for it; if the trait has such methods, the other ones are available on it as inherent methods, otherwise, the trait is implemented for it.
+//!
+//! - `inline_capacity = <number>` — specifies the size of the buffer of the inline storage thin trait object structure in multiples of the size of `usize`, not counting the vtable or the pointer to it, which is also stored in the buffer. Set to `3` by default, which is enough for a `String` or a `Vec`.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       inline_storage(pub InlineCounter),
+//!       inline_capacity = 1
+//!   )]
+//!   trait Counter {
+//!       fn get(&self) -> u64;
+//!       fn increment(&mut self);
+//!   }
+//!   impl Counter for u64 {
+//!       fn get(&self) -> u64 { *self }
+//!       fn increment(&mut self) { *self += 1; }
+//!   }
+//!   impl Counter for (u64, u64) {
+//!       fn get(&self) -> u64 { self.0 + self.1 }
+//!       fn increment(&mut self) { self.0 += 1; }
+//!   }
+//!   let mut small = InlineCounter::new(0_u64);
+//!   small.increment();
+//!   assert!(small.is_inline());
+//!   assert_eq!(small.get(), 1);
+//!   let big = InlineCounter::new((1_u64, 2_u64));
+//!   assert!(!big.is_inline());
+//!   assert_eq!(big.get(), 3);
+//!   ```
+//!
+//! - `inline_always = <true/false>` — specifies whether the methods which call into the vtable are marked `#[inline(always)]` instead of `#[inline]`. Those only load a function pointer from the vtable and call it, so inlining them removes the only overhead besides the indirect call itself. Set to `false` by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       inline_always = true
+//!   )]
+//!   # trait MyTrait {}
+//!   ```
+//!
+//! - `shared_drop = <true/false>` — specifies whether all stored types which don't need to be dropped, i.e. for which [`mem::needs_drop`] returns `false`, share a single `drop` function instead of each having their own, reducing the amount of generated code when there are many such types. Since the shared `drop` function needs to know the layout of the allocation, this requires `store_layout = true`, and is only supported with `storage = "box"`. Set to `false` by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       store_layout = true,
+//!       shared_drop = true
+//!   )]
+//!   trait MyTrait {}
+//!   impl MyTrait for u32 {}
+//!   impl MyTrait for [u8; 3] {}
+//!   let (a, b) = (BoxedMyTrait::new(1_u32), BoxedMyTrait::new([1_u8, 2, 3]));
+//!   assert_eq!(a.vtable().drop as usize, b.vtable().drop as usize);
+//!   ```
+//!
+//! - `manual_drop = <true/false>` — specifies whether the thin trait object leaves the value alone when dropped, for when foreign code owns the allocation and decides when it is freed. The `drop` entry of the vtable then points to a function which does nothing, the boxed thin trait object doesn't implement `Drop`, and a `dealloc` entry is added to the vtable instead, which drops the value and frees the allocation like `drop` would otherwise, and is called by the `dealloc` method of the boxed thin trait object. Constructing the thin trait object still allocates as usual, and methods taking `self` by value still free the allocation when they are done with the value. Cannot be combined with `storage = "arc"`, `storage = "rc"` or `storage = "buffer"`, custom allocators, inline or static storage and `shared_drop = true`. Set to `false` by default. Uses the same ABI as the `drop` entry.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   # use std::ffi::c_void;
+//!   #[thin_trait_object(manual_drop = true)]
+//!   trait Buffer {
+//!       fn len(&self) -> usize;
+//!   }
+//!   impl Buffer for Vec<u8> {
+//!       fn len(&self) -> usize { self.len() }
+//!   }
+//!   // Foreign code would hold on to the pointer and call `drop` whenever it likes.
+//!   let raw = BoxedBuffer::new(vec![1, 2, 3]).into_raw();
+//!   let buffer = unsafe { BoxedBuffer::from_raw(raw) };
+//!   unsafe { (buffer.vtable().drop)(raw as *mut c_void) };
+//!   assert_eq!(buffer.len(), 3);
+//!   drop(buffer);
+//!   // Frees the allocation, which nothing else did.
+//!   unsafe { BoxedBuffer::from_raw(raw) }.dealloc();
+//!   ```
+//!
+//! - `free_fn(<attributes> <visibility> <name>)` — enables generation of a standalone function with the given name, visibility and attributes, which takes a pointer to a thin trait object, as returned by `into_raw`, and frees it by calling the `drop` entry of its vtable, or the `dealloc` entry with `manual_drop = true`. This gives foreign code a function to call by its symbol name, like a C library's `..._free` function, rather than having to go through the vtable. The function uses the ABI of the `drop` entry, which means that `drop_abi = "C"` or `abi = "C"` is needed for it to be callable from C, and does nothing when given a null pointer, like `free`. It is `unsafe`, since the pointer has to point to a valid thin trait object which nothing else uses afterwards — after the call, the pointer is dangling, and passing it to the function again is a double free. Not supported for generic traits. Not generated by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       drop_abi = "C",
+//!       free_fn(#[no_mangle] pub task_free)
+//!   )]
+//!   trait Task {
+//!       fn run(&self);
+//!   }
+//!   impl Task for String {
+//!       fn run(&self) { println!("{}", self); }
+//!   }
+//!   let _: unsafe extern "C" fn(*mut std::ffi::c_void) = task_free;
+//!   let task = BoxedTask::new("Hello".to_string());
+//!   unsafe { task_free(task.into_raw() as *mut _) };
+//!   ```
+//!
+//! - `export_ctor(<type>, "<symbol>")` — enables generation of a `#[no_mangle]` function with the given symbol name, which takes a value of the given type and returns a pointer to a boxed thin trait object storing it, like `into_raw` does. This lets foreign code create thin trait objects storing a specific implementation without any Rust code having to do it on its behalf. The value is passed by value, so its type has to be FFI-safe, like a `#[repr(C)]` struct built by foreign code from its fields, which the compiler checks by denying the `improper_ctypes_definitions` lint on the function, and it cannot have lifetime arguments. The function uses the ABI of the `drop` entry, which has to be set with `drop_abi` or `abi`, and is `unsafe` to call from Rust code. Can be specified more than once, to export constructors for several types. Not supported for generic traits and with `storage = "buffer"`. Not generated by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       drop_abi = "C",
+//!       export_ctor(Countdown, "countdown_new"),
+//!       free_fn(#[no_mangle] pub counter_free)
+//!   )]
+//!   trait Counter {
+//!       fn next(&mut self) -> u32;
+//!   }
+//!   #[repr(C)]
+//!   pub struct Countdown {
+//!       left: u32,
+//!   }
+//!   impl Counter for Countdown {
+//!       fn next(&mut self) -> u32 {
+//!           self.left = self.left.saturating_sub(1);
+//!           self.left
+//!       }
+//!   }
+//!   let _: unsafe extern "C" fn(Countdown) -> *mut std::ffi::c_void = countdown_new;
+//!   unsafe {
+//!       let raw = countdown_new(Countdown { left: 3 });
+//!       let mut counter = BoxedCounter::from_raw(raw as *mut ());
+//!       assert_eq!(counter.next(), 2);
+//!       counter_free(counter.into_raw() as *mut _);
+//!   }
+//!   ```
+//!   Types which are not FFI-safe are rejected:
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       drop_abi = "C",
+//!       export_ctor(String, "greeter_new")
+//!   )]
+//!   trait Greeter {
+//!       fn greet(&self) -> String;
+//!   }
+//!   impl Greeter for String {
+//!       fn greet(&self) -> String { self.clone() }
+//!   }
+//!   ```
+//!
+//! - `vtable_static(<attributes> <visibility> <name> = <type>)` — enables generation of a `static` with the given name, visibility and attributes, which holds the vtable for values of the given type. The vtable which `new` points to is a constant borrowed by the generated code, which the compiler may place anywhere and even duplicate, while a static has a single address and can be placed in a specific linker section with `#[link_section = "..."]`, as firmware keeping its vtables in flash memory needs to. Also adds an unsafe `new_with_vtable` constructor to the boxed thin trait object, which takes the vtable to point to in addition to the value and has to be given the vtable for the type of the value. Like `new`, it doesn't allocate zero-sized values, which then point to the vtable only if it is one of the statics, and to the one `new` uses otherwise. Statics cannot be generic, so the stored type has to be concrete, and the option can be specified more than once, to generate statics for several types. Not supported for generic traits, with `vtable = "inline"`, which copies the vtable into the allocation instead, and with `storage = "buffer"`. Not generated by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       vtable_static(
+//!           /// The vtable for `Blink`, in the section of the flash memory which holds the vtables.
+//!           #[cfg_attr(target_os = "none", link_section = ".rodata.vtables")]
+//!           pub BLINK_VTABLE = Blink
+//!       )
+//!   )]
+//!   trait Task {
+//!       fn poll(&mut self) -> bool;
+//!   }
+//!   pub struct Blink {
+//!       on: bool,
+//!   }
+//!   impl Task for Blink {
+//!       fn poll(&mut self) -> bool {
+//!           self.on = !self.on;
+//!           self.on
+//!       }
+//!   }
+//!   let mut task = unsafe { BoxedTask::new_with_vtable(&BLINK_VTABLE, Blink { on: false }) };
+//!   assert!(std::ptr::eq(task.vtable(), &BLINK_VTABLE));
+//!   assert!(task.poll());
+//!   assert!(!task.poll());
+//!   ```
+//!
+//!   Zero-sized values point to the static without being allocated:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::alloc::{GlobalAlloc, Layout, System};
+//!   use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//!   struct CountingAllocator;
+//!   static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+//!   unsafe impl GlobalAlloc for CountingAllocator {
+//!       unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+//!           ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+//!           System.alloc(layout)
+//!       }
+//!       unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+//!           System.dealloc(ptr, layout)
+//!       }
+//!   }
+//!   #[global_allocator]
+//!   static ALLOCATOR: CountingAllocator = CountingAllocator;
+//!
+//!   #[thin_trait_object(
+//!       vtable_static(pub IDLE_VTABLE = Idle)
+//!   )]
+//!   trait Task {
+//!       fn poll(&mut self) -> bool;
+//!   }
+//!   pub struct Idle;
+//!   impl Task for Idle {
+//!       fn poll(&mut self) -> bool {
+//!           false
+//!       }
+//!   }
+//!
+//!   let before = ALLOCATIONS.load(Ordering::SeqCst);
+//!   let mut task = unsafe { BoxedTask::new_with_vtable(&IDLE_VTABLE, Idle) };
+//!   assert!(std::ptr::eq(task.vtable(), &IDLE_VTABLE));
+//!   assert!(!task.poll());
+//!   drop(task);
+//!   assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before);
+//!   ```
+//!
+//! - `static_storage(<attributes> <visibility> <name>)` — enables generation of a static storage structure, which wraps a value in the same way the allocation made by the boxed thin trait object does, but can be created in constant expressions and placed in a `static`, and specifies its name, visibility and attributes in the same way as `trait_object(...)`. Also adds a `const fn from_static` constructor to the boxed thin trait object, which points to a static storage structure in a static instead of allocating. Dropping such a thin trait object does nothing. Not generated by default.
+//!
+//!   Since statics can't be mutated or moved out of, all trait methods must take `&self`, and this option cannot be combined with `type_id = true`, `storage = "arc"` or `storage = "rc"`. Cloning such a thin trait object with the `clone` vtable entry allocates a boxed one as usual.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object(
+//!       static_storage(pub StaticGreeter)
+//!   )]
+//!   trait Greeter: Send + Sync {
+//!       fn greet(&self) -> String;
+//!   }
+//!   struct Hello;
+//!   impl Greeter for Hello {
+//!       fn greet(&self) -> String { "Hello".to_string() }
+//!   }
+//!   struct Named(&'static str);
+//!   impl Greeter for Named {
+//!       fn greet(&self) -> String { format!("Hello, {}", self.0) }
+//!   }
+//!   static HELLO: StaticGreeter<Hello> = StaticGreeter::new(Hello);
+//!   static NAMED: StaticGreeter<Named> = StaticGreeter::new(Named("World"));
+//!   static TABLE: [BoxedGreeter<'static>; 2] = [
+//!       BoxedGreeter::from_static(&HELLO),
+//!       BoxedGreeter::from_static(&NAMED),
+//!   ];
+//!   assert_eq!(TABLE[1].greet(), "Hello, World");
+//!   ```
+//!
+//! - `embedded(<attributes> <visibility> <name>)` — enables generation of an embedded storage structure, which wraps a value in the same way the allocation made by the boxed thin trait object does, so that it can be a field of another structure, and specifies its name, visibility and attributes in the same way as `trait_object(...)`. Borrowed thin trait objects pointing into it are handed out by its `as_thin_ref` and `as_thin_mut` methods, if `thin_ref(...)` and `thin_mut(...)` are enabled respectively, which lets code which only knows the layout of the trait object, such as foreign code given the result of `as_raw`, call the methods of the value without it being allocated separately. The value is dropped together with the structure. Not generated by default.
+//!
+//!   Cannot be combined with `storage = "arc"`, `storage = "rc"` and `header = ...`.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   # use std::{cell::Cell, rc::Rc};
+//!   #[thin_trait_object(
+//!       embedded(pub EmbeddedCounter),
+//!       thin_ref(CounterRef),
+//!       thin_mut(CounterMut)
+//!   )]
+//!   trait Counter {
+//!       fn get(&self) -> u32;
+//!       fn bump(&mut self);
+//!   }
+//!   struct Clicks(u32, Rc<Cell<bool>>);
+//!   impl Counter for Clicks {
+//!       fn get(&self) -> u32 { self.0 }
+//!       fn bump(&mut self) { self.0 += 1; }
+//!   }
+//!   impl Drop for Clicks {
+//!       fn drop(&mut self) { self.1.set(true); }
+//!   }
+//!   #[repr(C)]
+//!   struct Widget {
+//!       counter: EmbeddedCounter<Clicks>,
+//!       id: u32,
+//!   }
+//!   fn bump_twice(mut counter: CounterMut<'_>) {
+//!       counter.bump();
+//!       counter.bump();
+//!   }
+//!   let dropped = Rc::new(Cell::new(false));
+//!   let mut widget = Widget {
+//!       counter: EmbeddedCounter::new(Clicks(0, dropped.clone())),
+//!       id: 7,
+//!   };
+//!   bump_twice(widget.counter.as_thin_mut());
+//!   assert_eq!(widget.counter.as_thin_ref().get(), 2);
+//!   widget.counter.get_mut().0 += 1;
+//!   assert_eq!(widget.counter.get().0, 3);
+//!   assert_eq!(widget.id, 7);
+//!   drop(widget);
+//!   assert!(dropped.get());
+//!   ```
+//!
+//! - `async = <true/false>` — specifies whether `async fn` methods are allowed in the trait. Function pointers can't be `async`, so the vtable entry for such a method returns the future boxed and type-erased, as `Pin<Box<dyn Future<Output = ...>>>`, which costs an allocation on every call — hence this being opt-in. The thin trait object structures implement the method as an `async fn` which awaits the boxed future, so the borrow of `self` and of the other arguments lasts until the future completes, like with the method of the trait itself. Uses `alloc` instead of `std` if the `std` feature of this crate is disabled. The boxed futures are not `Send`, since the macro has no way of requiring the futures of the implementations to be. Set to `false` by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   # use std::{future::Future, ptr, task::{Context, Poll, RawWaker, RawWakerVTable, Waker}};
+//!   #[thin_trait_object(
+//!       async = true
+//!   )]
+//!   trait Handler {
+//!       async fn handle(&self, request: &str) -> String;
+//!   }
+//!   struct Greeter(String);
+//!   impl Handler for Greeter {
+//!       async fn handle(&self, request: &str) -> String {
+//!           format!("{}, {}", self.0, request)
+//!       }
+//!   }
+//!   # fn block_on<F: Future>(future: F) -> F::Output {
+//!   #     fn raw_waker() -> RawWaker {
+//!   #         fn clone(_: *const ()) -> RawWaker { raw_waker() }
+//!   #         fn noop(_: *const ()) {}
+//!   #         static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+//!   #         RawWaker::new(ptr::null(), &VTABLE)
+//!   #     }
+//!   #     let waker = unsafe { Waker::from_raw(raw_waker()) };
+//!   #     let mut future = Box::pin(future);
+//!   #     loop {
+//!   #         if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+//!   #             return output;
+//!   #         }
+//!   #     }
+//!   # }
+//!   let boxed = BoxedHandler::new(Greeter("Hello".to_string()));
+//!   assert_eq!(block_on(boxed.handle("World")), "Hello, World");
+//!   ```
+//!
+//! - `supertrait(<path> { <methods> })` — flattens the methods of a supertrait into the vtable, placing them before the methods of the trait itself, and implements the supertrait for the thin trait object structures in the same way as the trait. Since the macro cannot see the definition of the supertrait, its methods have to be declared again inside the braces, with the same signatures. Can be specified multiple times, once for every supertrait, including the indirect ones. A supertrait which is listed more than once, as can happen with diamond-shaped supertrait hierarchies, only gets one vtable entry per method, but two different traits declaring methods with the same name are rejected, since their vtable entries would have the same name. Associated types specified in the path, as in `Iterator<Item = u8>`, become the associated types of the implementations. See the *Supertraits* section for an example.
+//!
+//! - `blanket_impl = <true/false>` — specifies whether the trait is implemented for every type which implements all of its supertraits, which turns a trait with no items of its own into an alias grouping several traits, stored behind a single thin pointer with a single vtable containing the methods of all supertraits listed with `supertrait(...)`. The macro then doesn't implement the trait for the thin trait object structures itself, since that would conflict with the blanket implementation, which applies to them because they implement the supertraits. The trait cannot have any items and cannot be `unsafe`. Set to `false` by default.
+//!
+//!   Example:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   trait Draw {
+//!       fn draw(&self) -> String;
+//!   }
+//!   trait Serialize {
+//!       fn serialize(&self) -> Vec<u8>;
+//!   }
+//!   #[thin_trait_object(
+//!       supertrait(Draw {
+//!           fn draw(&self) -> String;
+//!       }),
+//!       supertrait(Serialize {
+//!           fn serialize(&self) -> Vec<u8>;
+//!       }),
+//!       blanket_impl = true
+//!   )]
+//!   trait Widget: Draw + Serialize {}
+//!   struct Label(&'static str);
+//!   impl Draw for Label {
+//!       fn draw(&self) -> String { format!("[{}]", self.0) }
+//!   }
+//!   impl Serialize for Label {
+//!       fn serialize(&self) -> Vec<u8> { self.0.as_bytes().to_vec() }
+//!   }
+//!   fn render(widget: &impl Widget) -> (String, usize) {
+//!       (widget.draw(), widget.serialize().len())
+//!   }
+//!   // No `impl Widget for Label` is needed.
+//!   let widget = BoxedWidget::new(Label("OK"));
+//!   assert_eq!(render(&widget), ("[OK]".to_string(), 2));
+//!   assert_eq!(std::mem::size_of_val(&widget), std::mem::size_of::<usize>());
+//!   ```
+//!
+//! ## Use with FFI
+//! One of the main focuses of the macro is FFI, which is why usage of the macro with FFI is simple and natural:
+//! ```no_run
+//! use thin_trait_object::*;
+//! use std::ffi::c_void;
+//!
+//! #[thin_trait_object(drop_abi = "C")]
+//! trait Foo {
+//!     extern "C" fn say_hello(&self);
+//! }
+//!
+//! impl Foo for String {
+//!     extern "C" fn say_hello(&self) {
+//!          println!("Hello from \"{}\"", self);
+//!     }
+//! }
+//!
+//! # /*
+//! extern "C" {
+//!     fn eater_of_foo(foo: *mut c_void);
+//!     fn creator_of_foo() -> *mut c_void;
+//! }
+//! # */
+//! # unsafe extern "C" fn eater_of_foo(_foo: *mut c_void) {}
+//! # unsafe extern "C" fn creator_of_foo() -> *mut c_void {
+//! #     BoxedFoo::new("Rust pretending to be C".to_string()).into_raw() as *mut _
+//! # }
+//!
+//! let foo = BoxedFoo::new("Hello World!".to_string());
+//!
+//! unsafe {
+//!     // Will transfer ownership to the C side.
+//!     eater_of_foo(foo.into_raw() as *mut c_void);
+//! }
+//! // Acquire ownership of a different implementation from the C side.
+//! let foo = unsafe { BoxedFoo::from_raw(creator_of_foo() as *mut ()) };
+//! foo.say_hello();
+//! ```
+//! The C side would do:
+//! ```c
+//! #include <stdio.h>
+//!
+//! typedef void (*vtable_say_hello)(void*);
+//! typedef void (*vtable_drop)(void*);
+//! typedef struct foo_vtable {
+//!    vtable_say_hello say_hello;
+//!    vtable_drop drop;
+//! } foo_vtable;
+//!
+//! void eater_of_foo(void* foo) {
+//!     // The first field is a pointer to the vtable, so we have to first
+//!     // extract that pointer and then dereference the function pointers.
+//!     foo_vtable* vtable = *((foo_vtable**)foo);
+//!
+//!     // Have to provide the pointer twice, firstly for
+//!     // lookup and then to provide the &self reference.
+//!     vtable.say_hello(foo);
+//!     // Don't forget about manual memory management — the C side owns the trait object now.
+//!     vtable.drop(foo);
+//! }
+//! void* creator_of_foo(void) {
+//!     // Allocate space for one pointer, the pointer to the vtable.
+//!     void* allocation = malloc(sizeof(foo_vtable*));
+//!     void* vtable_pointer = &custom_vtable;
+//!     // Put the pointer into the allocation.
+//!     memcpy(allocation, &vtable_pointer, sizeof(foo_vtable*));
+//!     return allocation;
+//! }
+//!
+//! static foo_vtable custom_vtable {
+//!     // Using C11 designated initializers, consult your local C expert for
+//!     // ways to do this on an old compiler.
+//!     .say_hello = &impl_say_hello,
+//!     .drop = &impl_drop
+//! };
+//! void impl_say_hello(void* self) {
+//!     puts("Hello from C!");
+//! }
+//! void impl_drop(void* self) {
+//!     free(self);
+//! }
+//! ```
+//!
+//! ## Consuming methods
+//! Methods which take `self` or `self: Box<Self>` are supported as well. Calling them on the boxed thin trait object gives up ownership of it: the stored value is moved out of its allocation, which is freed, and then passed to the method. For `self: Box<Self>`, the value is then put into a new `Box`, since the original allocation also contains the vtable.
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Builder {
+//!     fn push(&mut self, c: char);
+//!     fn finish(self) -> String;
+//! }
+//! impl Builder for String {
+//!     fn push(&mut self, c: char) { String::push(self, c) }
+//!     fn finish(self) -> String { self }
+//! }
+//! let mut builder = BoxedBuilder::new(String::new());
+//! builder.push('a');
+//! builder.push('b');
+//! assert_eq!(builder.finish(), "ab");
+//! ```
+//! Those methods cannot be called on borrowed thin trait objects, and are not supported with `storage = "arc"`, `storage = "rc"`, `storage = "buffer"` or `allocator_api = true`. Other kinds of `self` types, like `self: Rc<Self>`, are not supported, apart from `self: Pin<&mut Self>`, which is described below. Either way, the macro reports an error pointing to the receiver:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object(storage = "arc")]
+//! trait Builder {
+//!     fn finish(self) -> String;
+//! }
+//! ```
+//!
+//! ## Pinned methods
+//! Methods which take `self: Pin<&mut Self>` can be implemented by self-referential types, which must not be moved once pinned. The value is stored in an allocation which it never leaves until it is dropped, so the thunks pass it to the method pinned in place. To uphold that, the boxed thin trait object is `!Unpin` if the trait has such methods, and the methods can only be called on it through `Pin<&mut BoxedFoo>`, obtained with [`pin!`] or [`Box::pin`], which can be reborrowed for further calls using [`Pin::as_mut`]:
+//! ```rust
+//! # use thin_trait_object::*;
+//! use std::{marker::PhantomPinned, pin::{pin, Pin}, ptr};
+//!
+//! #[thin_trait_object]
+//! trait Step {
+//!     fn step(self: Pin<&mut Self>) -> u32;
+//! }
+//! struct SelfRef {
+//!     count: u32,
+//!     // Points to `count` after the first step.
+//!     count_ptr: *const u32,
+//!     _pinned: PhantomPinned,
+//! }
+//! impl Step for SelfRef {
+//!     fn step(self: Pin<&mut Self>) -> u32 {
+//!         let this = unsafe { self.get_unchecked_mut() };
+//!         if this.count_ptr.is_null() {
+//!             this.count_ptr = &this.count;
+//!         }
+//!         assert!(ptr::eq(this.count_ptr, &this.count));
+//!         this.count += 1;
+//!         this.count
+//!     }
+//! }
+//! let mut stepper = pin!(BoxedStep::new(SelfRef {
+//!     count: 0,
+//!     count_ptr: ptr::null(),
+//!     _pinned: PhantomPinned,
+//! }));
+//! stepper.as_mut().step();
+//! assert_eq!(stepper.as_mut().step(), 2);
+//! ```
+//! Just like methods taking `&mut self`, those cannot be called through shared references, and thus are not supported with `storage = "arc"`, `storage = "rc"` or static storage. Neither are they with `storage = "buffer"`, since forgetting the thin trait object would let the buffer be reused without dropping the value. The inline storage thin trait object, which is moved together with the value it stores inside of itself, and the borrowed ones do not provide them either. `box_dyn = true` is not supported, since `Box<dyn Trait>` is `Unpin` regardless of the value inside.
+//!
+//! ## Compound return types
+//! Return types are copied into the function pointer types of the vtable entries and into the thunks as they are written, so tuples, arrays and types with nested generic arguments are returned by value like from any other function:
+//! ```rust
+//! # use thin_trait_object::*;
+//! use std::collections::HashMap;
+//! #[thin_trait_object]
+//! trait Region {
+//!     fn bounds(&self) -> (f32, f32, f32, f32);
+//!     fn corners(&self) -> [(i32, i32); 4];
+//!     fn label(&self) -> Option<(u8, String)>;
+//!     fn layers(&self) -> Vec<HashMap<String, Vec<u8>>>;
+//! }
+//! struct Square;
+//! impl Region for Square {
+//!     fn bounds(&self) -> (f32, f32, f32, f32) { (0.0, 0.0, 1.0, 1.0) }
+//!     fn corners(&self) -> [(i32, i32); 4] { [(0, 0), (1, 0), (1, 1), (0, 1)] }
+//!     fn label(&self) -> Option<(u8, String)> { Some((4, "square".to_string())) }
+//!     fn layers(&self) -> Vec<HashMap<String, Vec<u8>>> {
+//!         vec![HashMap::from([("edges".to_string(), vec![1, 2, 3, 4])])]
+//!     }
+//! }
+//! let region = BoxedRegion::new(Square);
+//! let _: unsafe fn(*mut std::ffi::c_void) -> (f32, f32, f32, f32) = region.vtable().bounds;
+//! assert_eq!(region.bounds(), (0.0, 0.0, 1.0, 1.0));
+//! assert_eq!(region.corners()[2], (1, 1));
+//! assert_eq!(region.label(), Some((4, "square".to_string())));
+//! assert_eq!(region.layers()[0]["edges"], [1, 2, 3, 4]);
+//! ```
+//! With a foreign ABI, types without a defined layout, such as tuples, cannot be understood by foreign code, which the compiler points out with an `improper_ctypes_definitions` warning at the type in the trait definition:
+//! ```compile_fail
+//! #![deny(improper_ctypes_definitions)]
+//! # use thin_trait_object::*;
+//! #[thin_trait_object(abi = "C")]
+//! trait Region {
+//!     fn bounds(&self) -> (f32, f32, f32, f32); // Not FFI-safe
+//! }
+//! ```
+//!
+//! ## Borrowing return types
+//! References returned by methods are usually borrowed from `self`, including those nested inside other types, like `Option<&T>` or `Result<&T, E>`. The vtable entry only receives a raw pointer, which carries no lifetime, so it returns them with the `'static` lifetime instead, while the methods of the thin trait object structures keep the signature of the trait method and thus tie them back to the borrow of the thin trait object:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[derive(Debug)]
+//! pub struct Entry(pub u32);
+//! #[thin_trait_object(thin_mut(pub MapMut))]
+//! trait Map {
+//!     fn find(&self, key: u32) -> Option<&Entry>;
+//!     fn find_mut(&mut self, key: u32) -> Option<&mut Entry>;
+//!     fn get(&self, key: u32) -> Result<&Entry, String>;
+//! }
+//! impl Map for Vec<Entry> {
+//!     fn find(&self, key: u32) -> Option<&Entry> {
+//!         self.iter().find(|entry| entry.0 == key)
+//!     }
+//!     fn find_mut(&mut self, key: u32) -> Option<&mut Entry> {
+//!         self.iter_mut().find(|entry| entry.0 == key)
+//!     }
+//!     fn get(&self, key: u32) -> Result<&Entry, String> {
+//!         self.find(key).ok_or_else(|| format!("no entry for {}", key))
+//!     }
+//! }
+//! let mut map = BoxedMap::new(vec![Entry(1), Entry(2)]);
+//! let _: unsafe fn(*mut std::ffi::c_void, u32) -> Option<&'static Entry> = map.vtable().find;
+//! map.find_mut(2).unwrap().0 = 5;
+//! map.as_thin_mut().find_mut(1).unwrap().0 = 4;
+//! assert_eq!(map.find(5).map(|entry| entry.0), Some(5));
+//! assert_eq!(map.get(4).unwrap().0, 4);
+//! assert_eq!(map.get(2).unwrap_err(), "no entry for 2");
+//! ```
+//! The `'static` lifetime never leaves the vtable, so the returned reference cannot outlive the thin trait object it was borrowed from:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! # pub struct Entry(pub u32);
+//! # #[thin_trait_object]
+//! # trait Map {
+//! #     fn find(&self, key: u32) -> Option<&Entry>;
+//! # }
+//! # impl Map for Vec<Entry> {
+//! #     fn find(&self, key: u32) -> Option<&Entry> {
+//! #         self.iter().find(|entry| entry.0 == key)
+//! #     }
+//! # }
+//! let entry: Option<&'static Entry> = BoxedMap::new(vec![Entry(1)]).find(1);
+//! ```
+//! Neither can it be held onto after the thin trait object is dropped:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! # pub struct Entry(pub u32);
+//! # #[thin_trait_object]
+//! # trait Map {
+//! #     fn find(&self, key: u32) -> Option<&Entry>;
+//! # }
+//! # impl Map for Vec<Entry> {
+//! #     fn find(&self, key: u32) -> Option<&Entry> {
+//! #         self.iter().find(|entry| entry.0 == key)
+//! #     }
+//! # }
+//! let map = BoxedMap::new(vec![Entry(1)]);
+//! let entry = map.find(1);
+//! drop(map);
+//! assert!(entry.is_some());
+//! ```
+//!
+//! Only the return type is tied to the borrow of `self`: elided lifetimes in the other arguments stay independent of it, both in the methods of the thin trait object structures and in the function pointer types of the vtable entries, where they become lifetime parameters of their own. Arguments can thus borrow values which are dropped before a reference returned from `self`, including through a pinned `self`, and ones like `&mut &[u8]`, whose inner lifetime is invariant, can be passed along with a borrow of `self` of any length:
+//! ```rust
+//! # use thin_trait_object::*;
+//! use std::{ffi::c_void, pin::Pin};
+//! #[thin_trait_object(thin_ref(pub DecoderRef))]
+//! trait Decoder {
+//!     fn name(&self, input: &[u8]) -> &str;
+//!     fn next(&self, input: &mut &[u8]) -> Option<u8>;
+//!     fn last_name(self: Pin<&mut Self>, input: &[u8]) -> &str;
+//! }
+//! struct Bytes(String);
+//! impl Decoder for Bytes {
+//!     fn name(&self, _input: &[u8]) -> &str { &self.0 }
+//!     fn next(&self, input: &mut &[u8]) -> Option<u8> {
+//!         let (&first, rest) = input.split_first()?;
+//!         *input = rest;
+//!         Some(first)
+//!     }
+//!     fn last_name(self: Pin<&mut Self>, _input: &[u8]) -> &str { &self.get_mut().0 }
+//! }
+//! let mut decoder = Box::pin(BoxedDecoder::new(Bytes("bytes".to_string())));
+//! let _: for<'a> unsafe fn(*mut c_void, &'a [u8]) -> &'static str = decoder.vtable().name;
+//! let _: for<'a, 'b> unsafe fn(*mut c_void, &'a mut &'b [u8]) -> Option<u8> = decoder.vtable().next;
+//! let name = {
+//!     let input = vec![1, 2];
+//!     decoder.name(&input)
 //! };
-//! void impl_say_hello(void* self) {
-//!     puts("Hello from C!");
+//! assert_eq!(name, "bytes");
+//! {
+//!     let input = vec![1, 2];
+//!     let mut rest = &input[..];
+//!     assert_eq!(decoder.next(&mut rest), Some(1));
+//!     assert_eq!(decoder.as_thin_ref().next(&mut rest), Some(2));
 //! }
-//! void impl_drop(void* self) {
-//!     free(self);
+//! let name = {
+//!     let input = vec![3];
+//!     decoder.as_mut().last_name(&input)
+//! };
+//! assert_eq!(name, "bytes");
+//! ```
+//!
+//! ## Returning thin trait objects
+//! Methods returning `Self` are not object-safe, since the type of the stored value is erased. Factory-style methods can return the generated thin trait object type by its name instead, which the thunks pass through like any other value, without dropping it:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Spawner {
+//!     fn spawn(&self) -> BoxedSpawner<'static>;
+//!     fn generation(&self) -> u32;
+//! }
+//! struct Cell(u32);
+//! impl Spawner for Cell {
+//!     fn spawn(&self) -> BoxedSpawner<'static> {
+//!         BoxedSpawner::new(Cell(self.0 + 1))
+//!     }
+//!     fn generation(&self) -> u32 { self.0 }
+//! }
+//! let first = BoxedSpawner::new(Cell(0));
+//! let second = first.spawn();
+//! assert_eq!(second.spawn().generation(), 2);
+//! ```
+//! Returning `Self` by value is rejected with an error pointing to the generated type:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Spawner {
+//!     fn spawn(&self) -> Self;
+//! }
+//! ```
+//!
+//! ## Trait object arguments
+//! Arguments which are regular trait objects, like `&dyn Trait` or `Box<dyn Trait>`, as well as ones which are thin trait objects, are passed through vtable entries with the Rust ABI as they are:
+//! ```rust
+//! # use thin_trait_object::*;
+//! trait Canvas {
+//!     fn draw(&mut self, shape: &str);
+//! }
+//! #[thin_trait_object]
+//! trait Shape {
+//!     fn render(&self, canvas: &mut dyn Canvas);
+//! }
+//! struct Square;
+//! impl Shape for Square {
+//!     fn render(&self, canvas: &mut dyn Canvas) { canvas.draw("square"); }
+//! }
+//! impl Canvas for Vec<String> {
+//!     fn draw(&mut self, shape: &str) { self.push(shape.to_string()); }
+//! }
+//! let mut canvas = Vec::new();
+//! BoxedShape::new(Square).render(&mut canvas);
+//! assert_eq!(canvas, ["square"]);
+//! ```
+//! The same goes for return values, which the thunk moves out of the call to the method of the stored value and the thin trait object moves out of the call to the vtable entry, so owning ones like `Box<dyn Trait>` or a boxed thin trait object end up with the caller, which drops them exactly once:
+//! ```rust
+//! # use thin_trait_object::*;
+//! use std::{cell::Cell, fmt::Display, rc::Rc};
+//! #[thin_trait_object]
+//! trait Plugin {
+//!     fn spawn_child(&self) -> BoxedPlugin<'static>;
+//!     fn label(&self) -> Box<dyn Display>;
+//! }
+//! struct Counted(Rc<Cell<u32>>);
+//! impl Drop for Counted {
+//!     fn drop(&mut self) { self.0.set(self.0.get() + 1); }
+//! }
+//! impl Plugin for Counted {
+//!     fn spawn_child(&self) -> BoxedPlugin<'static> { BoxedPlugin::new(Counted(self.0.clone())) }
+//!     fn label(&self) -> Box<dyn Display> { Box::new(self.0.get()) }
+//! }
+//! let drops = Rc::new(Cell::new(0));
+//! let plugin = BoxedPlugin::new(Counted(drops.clone()));
+//! let grandchild = plugin.spawn_child().spawn_child();
+//! assert_eq!(drops.get(), 1);
+//! assert_eq!(grandchild.label().to_string(), "1");
+//! drop((plugin, grandchild));
+//! assert_eq!(drops.get(), 3);
+//! ```
+//! Pointers to regular trait objects are two pointers wide and have no layout which foreign code could rely on, so arguments and return types containing trait objects are rejected for vtable entries with a foreign ABI, in which borrowed thin trait objects can be passed instead:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! # trait Canvas {}
+//! #[thin_trait_object(abi = "C")]
+//! trait Shape {
+//!     fn render(&self, canvas: &mut dyn Canvas);
 //! }
 //! ```
 //!
@@ -253,7 +1779,532 @@
 //! }
 //! ```
 //! This is necessary because the macro has no access to `A` and thus doesn't know that it needs to add its methods to the vtable.
-//! A little hacky, but there is no cleaner way of doing this using only procedural macros. If you have any suggestions for improving this pattern, raise an issue explaining your proposed solution or create a PR.
+//! A little hacky, but there is no cleaner way of doing this using only procedural macros without repeating the supertrait. If you don't mind repeating the declarations of its methods, the `supertrait(...)` option can be used instead, which adds them to the vtable and implements the supertrait on the thin trait object directly:
+//! ```rust
+//! # use thin_trait_object::*;
+//! trait Shape {
+//!     fn area(&self) -> f64;
+//! }
+//! #[thin_trait_object(
+//!     supertrait(Shape {
+//!         fn area(&self) -> f64;
+//!     })
+//! )]
+//! trait Drawable: Shape {
+//!     fn draw(&self) -> String;
+//! }
+//! struct Square(f64);
+//! impl Shape for Square {
+//!     fn area(&self) -> f64 { self.0 * self.0 }
+//! }
+//! impl Drawable for Square {
+//!     fn draw(&self) -> String { format!("a square of area {}", self.area()) }
+//! }
+//! fn total_area(shapes: &[BoxedDrawable]) -> f64 {
+//!     shapes.iter().map(Shape::area).sum()
+//! }
+//! let shapes = [BoxedDrawable::new(Square(1.0)), BoxedDrawable::new(Square(2.0))];
+//! assert_eq!(total_area(&shapes), 5.0);
+//! assert_eq!(shapes[1].draw(), "a square of area 4");
+//! ```
+//! `Iterator` doesn't need to be listed when its `Item` type is specified in the supertrait bound: its `next` method is added to the vtable automatically, so the thin trait object is an iterator itself and can be used directly in a `for` loop. The other methods of `Iterator` are left to their default implementations, which only call `next`, unless `Iterator` is listed in the `supertrait(...)` option along with the ones to flatten into the vtable, such as `size_hint`:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Stream: Iterator<Item = u8> {
+//!     fn name(&self) -> &str;
+//! }
+//! struct Countdown(u8);
+//! impl Iterator for Countdown {
+//!     type Item = u8;
+//!     fn next(&mut self) -> Option<u8> {
+//!         self.0 = self.0.checked_sub(1)?;
+//!         Some(self.0)
+//!     }
+//! }
+//! impl Stream for Countdown {
+//!     fn name(&self) -> &str { "countdown" }
+//! }
+//! let stream = BoxedStream::new(Countdown(3));
+//! assert_eq!(stream.name(), "countdown");
+//! let mut values = Vec::new();
+//! for value in stream {
+//!     values.push(value);
+//! }
+//! assert_eq!(values, [2, 1, 0]);
+//! ```
+//!
+//! Marker traits, on the other hand, are handled by the macro: if the trait has `Send`, `Sync` or any other trait listed in the `marker_traits(...)` option as a supertrait, the thin trait object implements it, which is sound because every stored value has to implement it as well. This works the same way regardless of whether the trait also has a `'static` bound:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Job: Send + Sync + 'static {
+//!     fn run(&self) -> u32;
+//! }
+//! impl Job for u32 {
+//!     fn run(&self) -> u32 { *self }
+//! }
+//! let job = BoxedJob::new(42_u32);
+//! assert_eq!(std::thread::spawn(move || job.run()).join().unwrap(), 42);
+//! ```
+//! Without those supertraits, the thin trait object implements neither `Send` nor `Sync`, since the stored value might not be safe to send or share between threads:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Job {
+//!     fn run(&self) -> u32;
+//! }
+//! impl Job for u32 {
+//!     fn run(&self) -> u32 { *self }
+//! }
+//! let job = BoxedJob::new(42_u32);
+//! std::thread::spawn(move || job.run());
+//! ```
+//!
+//! ## Generic traits
+//! Traits with type and const generic parameters are supported, in which case the vtable, the thin trait object and all other generated structures are generic over the same parameters, with the same defaults, and the `where` clause of the trait is repeated on all of them:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object(clone = true)]
+//! trait Container<T, U = T>
+//! where
+//!     T: Clone,
+//! {
+//!     fn get(&self) -> &T;
+//!     fn convert(&self) -> U;
+//! }
+//! #[derive(Clone)]
+//! struct Number(u32);
+//! impl Container<u32, String> for Number {
+//!     fn get(&self) -> &u32 { &self.0 }
+//!     fn convert(&self) -> String { self.0.to_string() }
+//! }
+//! let boxed: BoxedContainer<'_, u32, String> = BoxedContainer::new(Number(5));
+//! let cloned = boxed.clone();
+//! assert_eq!(*cloned.get(), 5);
+//! assert_eq!(cloned.convert(), "5");
+//! ```
+//! Const generic parameters work the same way, so thin trait objects with different values of the parameter are different types with separate vtables:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Buffer<const N: usize> {
+//!     fn len(&self) -> usize;
+//! }
+//! impl<const N: usize> Buffer<N> for [u8; N] {
+//!     fn len(&self) -> usize { N }
+//! }
+//! let small: BoxedBuffer<'_, 4> = BoxedBuffer::new([0_u8; 4]);
+//! let large: BoxedBuffer<'_, 64> = BoxedBuffer::new([0_u8; 64]);
+//! assert_eq!(small.len() + large.len(), 68);
+//! ```
+//! Const generic parameters on methods, on the other hand, are rejected along with type parameters, since a single function pointer in the vtable cannot cover all of their values. That includes ones with defaults, which only apply when the arguments are inferred, and the error points at the generic parameters of the method, suggesting to move them to the trait like above:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Buffer {
+//!     fn chunk<const N: usize>(&self) -> [u8; N];
+//! }
+//! ```
+//! Since the vtable is stored in a `static`, the type parameters are required to be `'static`. The `c_header` option is not supported either, since C has no equivalent of generics.
+//!
+//! Lifetime parameters are supported as well, and come right after the `'inner` lifetime on the thin trait object and the other generated structures, which are invariant over them like `dyn Trait<'a>` is. The vtable can't be generic over them for the same reason as with the type parameters, so its entries take and return the types mentioning them with `'static` in their place, and the thunks and the methods of the thin trait object transmute those back and forth. `as_dyn = true` and `box_dyn = true` are not supported for such traits, since the vtable entries for those would have to name the lifetimes in the `dyn` trait object type, and `'inner` is reserved as a lifetime name. Everything else goes through the thin trait object with the lifetimes intact:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Reader<'a> {
+//!     fn peek(&self) -> &'a [u8];
+//!     fn advance(&mut self, by: usize);
+//!     fn replace(&mut self, data: &'a [u8]) -> &'a [u8];
+//! }
+//! struct Slice<'a>(&'a [u8]);
+//! impl<'a> Reader<'a> for Slice<'a> {
+//!     fn peek(&self) -> &'a [u8] { self.0 }
+//!     fn advance(&mut self, by: usize) { self.0 = &self.0[by..]; }
+//!     fn replace(&mut self, data: &'a [u8]) -> &'a [u8] { std::mem::replace(&mut self.0, data) }
+//! }
+//! let data = [1, 2, 3];
+//! let rest = {
+//!     let mut reader: BoxedReader<'_, '_> = BoxedReader::new(Slice(&data));
+//!     reader.advance(1);
+//!     // The slice borrows from `data` rather than from the thin trait object.
+//!     reader.peek()
+//! };
+//! assert_eq!(rest, [2, 3]);
+//! ```
+//! Which means that a thin trait object holding a reference into borrowed data cannot outlive it:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Reader<'a> {
+//!     fn peek(&self) -> &'a [u8];
+//! }
+//! struct Slice<'a>(&'a [u8]);
+//! impl<'a> Reader<'a> for Slice<'a> {
+//!     fn peek(&self) -> &'a [u8] { self.0 }
+//! }
+//! let reader = {
+//!     let data = vec![1, 2, 3];
+//!     BoxedReader::new(Slice(&data))
+//! };
+//! assert_eq!(reader.peek(), [1, 2, 3]);
+//! ```
+//!
+//! ## Associated types
+//! Every stored value fixes the associated types of the trait, so the vtable, the thin trait object and all other generated structures get a type parameter with the same name and bounds for each one, which the associated type is set to in the implementations of the trait. `Self::Output` and `<Self as Trait>::Output` in the declarations of the trait items refer to the type parameter in everything the macro generates, and only values whose associated types match the type parameters can be stored:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Parser {
+//!     type Output: std::fmt::Debug;
+//!     fn parse(&self, input: &str) -> Option<Self::Output>;
+//! }
+//! struct Decimal;
+//! impl Parser for Decimal {
+//!     type Output = u32;
+//!     fn parse(&self, input: &str) -> Option<u32> { input.parse().ok() }
+//! }
+//! fn parse_twice<P: Parser<Output = u32>>(parser: &P, input: &str) -> Option<u32> {
+//!     Some(parser.parse(input)? * 2)
+//! }
+//! let parser: BoxedParser<'_, u32> = BoxedParser::new(Decimal);
+//! assert_eq!(parser.parse("42"), Some(42));
+//! assert_eq!(parse_twice(&parser, "21"), Some(42));
+//! ```
+//! The type parameters come after the generic parameters of the trait which don't have defaults, in the order the associated types are declared in, and are required to be `'static` like those. Their names may not clash with the ones of the generic parameters, and they cannot be referred to by their bare names in the trait, since those would name something else outside of the generated items. Generic associated types are not supported, and neither are bounds on associated types which mention `Self`, since that differs between the stored values. Like generic traits, traits with associated types cannot use the `c_header`, `free_fn(...)` and `export_ctor(...)` options:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Lender {
+//!     type Loan<'a>;
+//!     fn lend(&self) -> Self::Loan<'_>;
+//! }
+//! ```
+//!
+//! ## `where` clauses
+//! Bounds on `Self` in the `where` clause of the trait are treated the same way as supertraits, which they are equivalent to, and all other bounds are repeated on the generated structures as described above:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Task
+//! where
+//!     Self: Send + 'static,
+//! {
+//!     fn run(&self) -> u32;
+//! }
+//! impl Task for u32 {
+//!     fn run(&self) -> u32 { *self }
+//! }
+//! let task = BoxedTask::new(7_u32);
+//! assert_eq!(std::thread::spawn(move || task.run()).join().unwrap(), 7);
+//! ```
+//! Methods can have `where` clauses as well, which are repeated on the methods of the thin trait object, as long as the vtable entry doesn't need to depend on them: the bounds cannot involve the lifetime parameters of the method or the generic parameters of the trait, and `Self` can only be required to be `Sized`, which every stored value is. Generic methods are rejected regardless of their `where` clauses, unless their type arguments are listed up front, see [Monomorphized generic methods](#monomorphized-generic-methods).
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Counter {
+//!     fn count(&self) -> usize;
+//!     fn into_count(self) -> usize
+//!     where
+//!         Self: Sized,
+//!     {
+//!         self.count()
+//!     }
+//! }
+//! impl Counter for Vec<u8> {
+//!     fn count(&self) -> usize { self.len() }
+//! }
+//! assert_eq!(BoxedCounter::new(vec![1, 2, 3]).into_count(), 3);
+//! ```
+//! The same goes for methods taking `impl Trait` arguments, including ones nested in other types like `&impl Trait`, since those are generic methods in disguise. Taking `&dyn Trait` or `Box<dyn Trait>` works instead:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Logger {
+//!     fn log(&self, message: &impl std::fmt::Display); // Error: the method is generic
+//! }
+//! ```
+//!
+//! ## Unsafe traits and methods
+//! `unsafe fn` methods stay `unsafe` on the thin trait object structures, so the caller still has to uphold their contract, and the trait is implemented for those with an `unsafe impl` if it's an `unsafe trait` — the implementations only forward to the one of the stored value, which upholds the contract of the trait already:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! unsafe trait Memory {
+//!     unsafe fn peek(&self, addr: usize) -> u8;
+//! }
+//! unsafe impl Memory for Vec<u8> {
+//!     unsafe fn peek(&self, addr: usize) -> u8 { *self.get_unchecked(addr) }
+//! }
+//! fn peek_first(memory: &impl Memory) -> u8 {
+//!     unsafe { memory.peek(0) }
+//! }
+//! let memory = BoxedMemory::new(vec![4, 2]);
+//! assert_eq!(unsafe { memory.peek(1) }, 2);
+//! assert_eq!(peek_first(&memory), 4);
+//! ```
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! # #[thin_trait_object]
+//! # unsafe trait Memory {
+//! #     unsafe fn peek(&self, addr: usize) -> u8;
+//! # }
+//! # unsafe impl Memory for Vec<u8> {
+//! #     unsafe fn peek(&self, addr: usize) -> u8 { *self.get_unchecked(addr) }
+//! # }
+//! let memory = BoxedMemory::new(vec![4, 2]);
+//! memory.peek(1); // Error: call to unsafe function requires an unsafe block
+//! ```
+//!
+//! ## Associated constants
+//! Associated constants are read through the vtable too, with a getter thunk returning the value of the constant for the stored type. The thin trait object structures get a getter method for every constant, named like the constant but in lowercase, and don't implement the trait itself, since the value of a constant would have to be the same for all of the stored types:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Polygon {
+//!     const CORNERS: u8;
+//!     const NAME: &str = "polygon";
+//!     fn area(&self) -> f32;
+//! }
+//! struct Square(f32);
+//! impl Polygon for Square {
+//!     const CORNERS: u8 = 4;
+//!     const NAME: &'static str = "square";
+//!     fn area(&self) -> f32 { self.0 * self.0 }
+//! }
+//! let polygon = BoxedPolygon::new(Square(2.0));
+//! assert_eq!((polygon.corners(), polygon.name(), polygon.area()), (4, "square", 4.0));
+//! assert_eq!(PolygonVtable::OFFSET_AREA, 2 * std::mem::size_of::<usize>());
+//! ```
+//! The type of the constant has to be `Copy` and cannot mention `Self` or the generic parameters of the trait. Such traits cannot be made into `dyn` trait objects at all, which is why the `as_dyn` and `box_dyn` options are not supported for them.
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Named {
+//!     const NAME: String; // Error: the trait bound `String: Copy` is not satisfied
+//! }
+//! ```
+//!
+//! ## Conditional compilation
+//! Methods with `#[cfg(...)]` attributes are left out of everything the macro generates for them — the vtable field, the `OFFSET_` constant, the thunk and the methods of the thin trait object structures — whenever the trait method itself is left out, so the offsets of the fields after them stay correct either way:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Sensor {
+//!     fn read(&self) -> f32;
+//!     #[cfg(feature = "calibration")]
+//!     fn calibrate(&mut self, offset: f32);
+//!     fn name(&self) -> &str;
+//! }
+//! struct Thermometer;
+//! impl Sensor for Thermometer {
+//!     fn read(&self) -> f32 { 21.5 }
+//!     #[cfg(feature = "calibration")]
+//!     fn calibrate(&mut self, _offset: f32) {}
+//!     fn name(&self) -> &str { "thermometer" }
+//! }
+//! let sensor = BoxedSensor::new(Thermometer);
+//! assert_eq!(sensor.name(), "thermometer");
+//! assert_eq!(SensorVtable::OFFSET_NAME, std::mem::size_of::<usize>());
+//! ```
+//! The `c_header` option cannot be used for such traits, since the header is a single string which has to be the same regardless of the configuration.
+//!
+//! ## Renaming inherent methods
+//! The inherent methods which the thin trait object structures get for the trait methods shadow the methods of other traits with the same name, which gets in the way when the thin trait object implements one of those as well. A `#[thin(rename = "<name>")]` attribute on a trait method gives its inherent methods a different name, while the vtable entry, the thunk and the implementations of the trait keep the original one:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Widget {
+//!     #[thin(rename = "draw_widget")]
+//!     fn draw(&self) -> String;
+//!     fn width(&self) -> u32;
+//! }
+//! trait Shape {
+//!     fn draw(&self) -> String;
+//! }
+//! impl Shape for BoxedWidget<'_> {
+//!     fn draw(&self) -> String {
+//!         format!("a rectangle {} wide", self.width())
+//!     }
+//! }
+//! struct Button;
+//! impl Widget for Button {
+//!     fn draw(&self) -> String { "[ OK ]".to_string() }
+//!     fn width(&self) -> u32 { 6 }
+//! }
+//! let widget = BoxedWidget::new(Button);
+//! assert_eq!(widget.draw_widget(), "[ OK ]");
+//! assert_eq!(Widget::draw(&widget), "[ OK ]");
+//! assert_eq!(Shape::draw(&widget), "a rectangle 6 wide");
+//! ```
+//! The attribute is removed from the trait definition which the macro emits. The new name has to be a valid method name which isn't used by another inherent method of the thin trait object structures, and methods with default implementations can only be renamed if they are dispatched through the vtable:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Widget {
+//!     #[thin(rename = "width")]
+//!     fn draw(&self) -> String;
+//!     fn width(&self) -> u32;
+//! }
+//! ```
+//!
+//! ## Skipping methods
+//! A `#[thin(skip)]` attribute on a trait method with a default implementation leaves it out of the vtable, like `dispatch_defaults = false` does for all of them, which keeps the vtable small for traits with many convenience methods built on top of a few others. The method gets no vtable entry, thunk or inherent method, and the thin trait objects use the default implementation, even if the stored type overrides it:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Reader {
+//!     fn read_byte(&mut self) -> Option<u8>;
+//!     #[thin(skip)]
+//!     fn read_pair(&mut self) -> Option<(u8, u8)> {
+//!         Some((self.read_byte()?, self.read_byte()?))
+//!     }
+//!     fn remaining(&self) -> usize {
+//!         0
+//!     }
+//! }
+//! impl Reader for Vec<u8> {
+//!     fn read_byte(&mut self) -> Option<u8> { self.pop() }
+//!     fn remaining(&self) -> usize { self.len() }
+//! }
+//! // `read_byte`, `remaining` and `drop` are in the vtable
+//! assert_eq!(std::mem::size_of::<ReaderVtable>(), 3 * std::mem::size_of::<usize>());
+//! let mut reader = BoxedReader::new(vec![1, 2, 3]);
+//! assert_eq!(reader.read_pair(), Some((3, 2)));
+//! assert_eq!(reader.remaining(), 1);
+//! ```
+//! Methods without a default implementation cannot be skipped, since the thin trait objects would have nothing to implement them with, and neither can skipped methods be renamed:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Reader {
+//!     #[thin(skip)]
+//!     fn read_byte(&mut self) -> Option<u8>;
+//! }
+//! ```
+//!
+//! ## Monomorphized generic methods
+//! A generic method cannot have a single vtable entry, but a `#[thin(mono(<types>))]` attribute on a method with one type parameter gives it one entry for each of the listed type arguments, with the thunk calling the method with that type. The entries are named after the method and the last segment of the path to the type, in snake case, such as `read_u8` or `read_big_endian` for `BigEndian`, and become inherent methods of the thin trait object structures under those names. Types which the name cannot be derived from, like `Vec<u8>` or `[u8; 4]`, need one given with `<type> as <name>`. The implementations of the trait have the generic method itself, which compares the `TypeId` of its type argument with the ones of the listed types and calls the matching entry, and panics if there is none. That requires the type parameter to have a `'static` bound of its own, in its declaration or in the `where` clause of the method, even if one of its other bounds implies it, and the parameter can only appear as a type on its own in the arguments and the return type, not in a path like `T::Output`:
+//! ```rust
+//! # use thin_trait_object::*;
+//! pub trait Pod: Copy + Default + 'static {
+//!     fn from_bytes(bytes: &[u8]) -> Self;
+//! }
+//! impl Pod for u8 {
+//!     fn from_bytes(bytes: &[u8]) -> Self { bytes[0] }
+//! }
+//! impl Pod for u16 {
+//!     fn from_bytes(bytes: &[u8]) -> Self { u16::from_le_bytes([bytes[0], bytes[1]]) }
+//! }
+//! impl Pod for [u8; 2] {
+//!     fn from_bytes(bytes: &[u8]) -> Self { [bytes[0], bytes[1]] }
+//! }
+//!
+//! #[thin_trait_object]
+//! trait Reader {
+//!     #[thin(mono(u8, u16, [u8; 2] as read_pair))]
+//!     fn read<T: Pod + 'static>(&self) -> T;
+//! }
+//! impl Reader for Vec<u8> {
+//!     fn read<T: Pod + 'static>(&self) -> T { T::from_bytes(self) }
+//! }
+//! // `read_u8`, `read_u16`, `read_pair` and `drop` are in the vtable
+//! assert_eq!(std::mem::size_of::<ReaderVtable>(), 4 * std::mem::size_of::<usize>());
+//! let reader = BoxedReader::new(vec![1, 2]);
+//! assert_eq!(reader.read_u8(), 1);
+//! assert_eq!(reader.read_pair(), [1, 2]);
+//! // Calls the `read_u16` entry
+//! assert_eq!(Reader::read::<u16>(&reader), 0x0201);
+//! ```
+//! The listed types have to satisfy the bounds of the type parameter, and the error points at the one which doesn't:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Reader {
+//!     #[thin(mono(u8, String))] // Error: `String` is not `Copy`
+//!     fn read<T: Copy + 'static>(&self) -> T;
+//! }
+//! ```
+//! A type parameter without a `'static` bound is rejected with an error naming the method:
+//! ```compile_fail
+//! # use thin_trait_object::*;
+//! #[thin_trait_object]
+//! trait Reader {
+//!     #[thin(mono(u8, u16))] // Error: `T` is not bounded by `'static`
+//!     fn read<T: Copy>(&self) -> T;
+//! }
+//! ```
+//! The attribute cannot be combined with `rename` or `skip`, nor used on `async` methods, and traits with such methods are not object-safe, so they cannot be used with `as_dyn = true` or `box_dyn = true`.
+//!
+//! ## Tagged downcasting
+//! Downcasting with `type_id = true` relies on [`TypeId`], which only exists for `'static` types. Stored types which borrow something can be downcast by a tag of their own instead: a `#[thin(tag)]` attribute on a method taking only `&self` and returning a type which implements `PartialEq` adds an unsafe `downcast_ref_tagged::<T>(tag)` method to the boxed thin trait object, which calls the vtable entry of that method and borrows the stored value as a `T` if the result is equal to the given tag. The macro cannot check whether a tag belongs to the type it is used with, so the caller has to make sure that the implementations of the trait return tags identifying a single type each:
+//! ```rust
+//! # use thin_trait_object::*;
+//! #[derive(PartialEq)]
+//! pub enum Kind {
+//!     Word,
+//!     Number,
+//! }
+//! #[thin_trait_object]
+//! trait Token {
+//!     #[thin(tag)]
+//!     fn kind(&self) -> Kind;
+//! }
+//! struct Word<'a>(&'a str);
+//! impl Token for Word<'_> {
+//!     fn kind(&self) -> Kind { Kind::Word }
+//! }
+//! struct Number(u64);
+//! impl Token for Number {
+//!     fn kind(&self) -> Kind { Kind::Number }
+//! }
+//! let source = "let answer = 42".to_string();
+//! let tokens = [BoxedToken::new(Word(&source[4..10])), BoxedToken::new(Number(42))];
+//! // Only `Word` values return `Kind::Word`
+//! let word = unsafe { tokens[0].downcast_ref_tagged::<Word<'_>>(Kind::Word) };
+//! assert_eq!(word.unwrap().0, "answer");
+//! assert!(unsafe { tokens[1].downcast_ref_tagged::<Word<'_>>(Kind::Word) }.is_none());
+//! ```
+//! Only one method can be marked as the tag, it cannot be combined with `skip` or `mono`, nor with `from_parts = true` or a packed representation struct, and its default implementation, if it has one, has to be dispatched through the vtable.
+//!
+//! ## Lints
+//! The internals of the generated code — the representation structs and the thunks inside of them, as well as the fields of the vtable — allow the lints which they could otherwise trigger in crates with strict lint settings, since there is no way to fix those from the outside. The lints still apply to the thin trait object structures and the other items whose names and attributes are specified through the configuration options, so `missing_docs` requires documenting them with the attributes those options take, while the inherent methods dispatching to the vtable get the documentation of the trait methods:
+//! ```rust
+//! #![deny(warnings, missing_docs, unsafe_op_in_unsafe_fn, clippy::all, clippy::pedantic)]
+//! # //! Counting things.
+//! # use thin_trait_object::thin_trait_object;
+//! #[thin_trait_object(
+//!     vtable(
+//!         /// The vtable of [`Counter`].
+//!         pub CounterVtable
+//!     ),
+//!     trait_object(
+//!         /// A boxed [`Counter`].
+//!         pub BoxedCounter
+//!     ),
+//!     thin_ref(
+//!         /// A borrowed [`Counter`].
+//!         pub CounterRef
+//!     ),
+//!     clone = true,
+//! )]
+//! /// Counts things.
+//! pub trait Counter: Clone {
+//!     /// Returns the current count.
+//!     fn count(&self) -> u32;
+//!     /// Takes the counter and returns the final count.
+//!     fn finish(self) -> u32;
+//! }
+//! #[derive(Clone)]
+//! struct Clicks(u32);
+//! impl Counter for Clicks {
+//!     fn count(&self) -> u32 { self.0 }
+//!     fn finish(self) -> u32 { self.0 }
+//! }
+//! let counter = BoxedCounter::new(Clicks(3));
+//! assert_eq!(counter.as_thin_ref().count(), 3);
+//! assert_eq!(counter.finish(), 3);
+//! ```
 //!
 //! ## Output reference
 //! The following is a comprehensive list of everything the macro emits:
@@ -268,14 +2319,151 @@
 //!   #[repr(C)] // Can be customized via configuration options
 //!   #[derive(Copy, Clone, Debug, Hash)]
 //!   struct FooVtable {
-//!       // One field for every method in the trait
-//!       drop: unsafe fn(::core::ffi::c_void), // ABI can be customized via configuration options
+//!       // Only present with `store_layout = true`
+//!       size: usize,
+//!       align: usize,
+//!       // One field for every method in the trait, in declaration order, except for ones with
+//!       // default implementations if `dispatch_defaults = false`
+//!       drop: unsafe fn(*mut ::core::ffi::c_void), // ABI can be customized via configuration options
+//!       // Only present with `clone = true`, a `Clone` supertrait or `storage = "arc"`/`"rc"`
+//!       clone: unsafe fn(*mut ::core::ffi::c_void) -> *mut ::core::ffi::c_void,
+//!       // Only present with `debug = true` or a `Debug` supertrait
+//!       debug_fmt: unsafe fn(*mut ::core::ffi::c_void, &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result,
+//!       // Only present with `display = true` or a `Display` supertrait
+//!       display_fmt: unsafe fn(*mut ::core::ffi::c_void, &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result,
+//!       // Only present with `type_id = true`
+//!       type_id: fn() -> ::core::any::TypeId,
+//!       // Only present with `type_name = true`
+//!       type_name: fn() -> &'static str,
+//!       // Only present with `eq = true` or a `PartialEq`/`Eq` supertrait
+//!       eq: unsafe fn(*mut ::core::ffi::c_void, *mut ::core::ffi::c_void) -> bool,
+//!       // Only present with `hash = true` or a `Hash` supertrait
+//!       hash: unsafe fn(*mut ::core::ffi::c_void, &mut dyn ::core::hash::Hasher),
+//!       // Only present with `ord = true`
+//!       cmp: unsafe fn(*mut ::core::ffi::c_void, *mut ::core::ffi::c_void) -> ::core::cmp::Ordering,
+//!       // Only present with `as_dyn = true`
+//!       as_dyn: unsafe fn(*mut ::core::ffi::c_void) -> *mut (dyn Foo + 'static),
+//!       // Only present with `box_dyn = true`
+//!       into_box_dyn: unsafe fn(*mut ::core::ffi::c_void) -> *mut (dyn Foo + 'static),
+//!       // Only present with `manual_drop = true`, with the same ABI as `drop`
+//!       dealloc: unsafe fn(*mut ::core::ffi::c_void),
+//!   }
+//!   # */
+//!   ```
+//!   The other fields, ones besides `drop`, each have the same name as their corresponding trait method. The signatures are nearly identical, with three differences, not counting `async fn` methods, which return `Pin<Box<dyn Future<Output = ...> + 'static>>` as described for the `async` option, and methods returning a `Result`, which return an error code instead with `error_code = ...`:
+//!   - `&self`, `&mut self`, `self: Pin<&mut Self>`, `self` or `self: Box<Self>`, if present, are replaced with [`*mut ::core::ffi::c_void`][`core::ffi::c_void`] — for the latter two, the function takes ownership of the thin trait object and is responsible for deallocating it, and `drop` is not called afterwards;
+//!   - If there was no `unsafe` on the trait method, it is added automatically, since the pointer passed as the first argument is never validated;
+//!   - Lifetimes in the return type which refer to the borrow of `self` — elided ones, `'_` and the lifetime of the `&self` or `&mut self` receiver, if named — are replaced with `'static`, since the raw pointer carries no lifetime. The methods of the thin trait objects tie the returned value back to the borrow of the thin trait object, so it cannot outlive it:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Named {
+//!       fn name(&self) -> &str;
+//!       fn buffer(&mut self) -> &mut [u8];
+//!   }
+//!   impl Named for Vec<u8> {
+//!       fn name(&self) -> &str { "vec" }
+//!       fn buffer(&mut self) -> &mut [u8] { self }
+//!   }
+//!   let mut boxed = BoxedNamed::new(vec![1_u8, 2]);
+//!   boxed.buffer()[0] = 3;
+//!   assert_eq!(boxed.name(), "vec");
+//!   assert_eq!(boxed.buffer(), [3, 2]);
+//!   ```
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   # #[thin_trait_object]
+//!   # trait Named {
+//!   #     fn name(&self) -> &str;
+//!   # }
+//!   # impl Named for String {
+//!   #     fn name(&self) -> &str { self }
+//!   # }
+//!   let name = {
+//!       let boxed = BoxedNamed::new(String::from("dangling"));
+//!       boxed.name()
+//!   };
+//!   # let _ = name;
+//!   ```
+//!
+//!   Methods named with raw identifiers, such as `r#type`, get fields named with the same raw identifiers. The `Debug` implementation of the vtable and the `OFFSET_` constants described below use the name without the `r#` prefix.
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Keywords {
+//!       fn r#type(&self) -> u32;
+//!       fn r#async(&self) -> u32;
+//!       fn r#move(&self, r#in: u32) -> u32;
+//!   }
+//!   impl Keywords for u32 {
+//!       fn r#type(&self) -> u32 { *self }
+//!       fn r#async(&self) -> u32 { *self * 2 }
+//!       fn r#move(&self, r#in: u32) -> u32 { *self + r#in }
+//!   }
+//!   let boxed = BoxedKeywords::new(1_u32);
+//!   assert_eq!((boxed.r#type(), boxed.r#async(), boxed.r#move(2)), (1, 2, 3));
+//!   assert_eq!(KeywordsVtable::OFFSET_ASYNC, std::mem::size_of::<usize>());
+//!   assert!(format!("{:?}", boxed.vtable()).starts_with("KeywordsVtable { type: "));
+//!   ```
+//!
+//!   If one of the trait methods is itself named `drop`, the field for the destructor is named `__thintraitobjectmacro_drop` instead, so that both can coexist. Trait methods with the same name as any of the other optional fields, which only exist if enabled, cause a compile error.
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Resource {
+//!       fn drop(&mut self);
 //!   }
-//!   # */
+//!   impl Resource for bool {
+//!       fn drop(&mut self) { *self = true; }
+//!   }
+//!   let mut boxed = BoxedResource::new(false);
+//!   boxed.drop();
+//!   let _: unsafe fn(*mut std::ffi::c_void) = boxed.vtable().__thintraitobjectmacro_drop;
+//!   ```
+//!
+//!   Names starting with `__thintraitobjectmacro`, in any capitalization, are reserved for the items generated by the macro, and trait methods using them are rejected, as are ones starting with the prefix set by the `prefix` option:
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Sneaky {
+//!       fn __thintraitobjectmacro_thunk_foo(&self);
+//!   }
+//!   ```
+//!
+//!   The order of the fields is a stable guarantee: the layout fields come first if enabled, followed by the methods and the getters of associated constants in the order in which they are declared in the trait, followed by `drop`, followed by the optional entries which don't correspond to trait methods in the order listed above. Together with `#[repr(C)]`, this means that a matching C structure can be written by hand by simply listing the fields in the same order. Note that this means that **adding, removing or reordering methods in the trait is an ABI break**.
+//!
+//!   To make such breaks detectable, the vtable also has an `OFFSET_{FIELD NAME}` associated constant for every field, as in `FooVtable::OFFSET_DROP`, containing the offset of the field from the beginning of the vtable in bytes, as returned by `core::mem::offset_of!`. Those are not generated if the `#[repr(C)]` attribute was overriden.
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Foo {
+//!       fn first(&self);
+//!       fn second(&self);
+//!   }
+//!   // Fails to compile if a method is ever inserted before `second`.
+//!   const _: [(); 1] = [(); (FooVtable::OFFSET_SECOND == FooVtable::OFFSET_FIRST + std::mem::size_of::<usize>()) as usize];
+//!   assert_eq!(FooVtable::OFFSET_DROP, 2 * std::mem::size_of::<usize>());
+//!   ```
+//!   The constants are generated from the same list of fields as the vtable struct itself and have the same values as [`mem::offset_of!`] would give, which also makes them usable for loading a function pointer from the vtable without naming its field, for example in dispatch code which is generated at runtime. The function pointer found at the offset has the type of the field, as described above:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::ffi::c_void;
+//!   #[thin_trait_object]
+//!   trait Foo {
+//!       fn first(&self) -> u32;
+//!       fn second(&self) -> u32;
+//!   }
+//!   impl Foo for u32 {
+//!       fn first(&self) -> u32 { *self }
+//!       fn second(&self) -> u32 { *self * 2 }
+//!   }
+//!   let boxed = BoxedFoo::new(21_u32);
+//!   let second = unsafe {
+//!       let vtable = boxed.vtable() as *const FooVtable as *const u8;
+//!       *(vtable.add(FooVtable::OFFSET_SECOND) as *const unsafe fn(*mut c_void) -> u32)
+//!   };
+//!   assert_eq!(unsafe { second(boxed.as_raw() as *mut c_void) }, 42);
 //!   ```
-//!   The other fields, ones besides `drop`, each have the same name as their corresponding trait method. The signatures are nearly identical, with two differences:
-//!   - `&self` or `&mut self`, if present, are replaced with [`*mut ::core::ffi::c_void`][`core::ffi::c_void`];
-//!   - If there was no `unsafe` on the trait method, it is added automatically, since the pointer passed as the first argument is never validated.
 //! - **A thin trait object struct definition.**
 //!   
 //!   The name can be customized via the `trait_object(...)` configuration option (see the *Configuring the macro* section); the default name is `Boxed{trait name}`, as in, `BoxedFoo` for a trait named `Foo`.
@@ -291,6 +2479,39 @@
 //!   # */
 //!   ```
 //!   If the trait has a `'static` lifetime bound, the `'inner` lifetime parameter is not emitted, since all possible contained implementations are restricted to be `'static`.
+//!
+//!   If the trait has methods taking `self: Pin<&mut Self>`, the `PhantomData` also contains a `PhantomPinned`, which makes the struct `!Unpin`.
+//!
+//!   Since the pointer is a `NonNull`, the null pointer is available for the compiler to use as the niche for `None`, which means that `Option<BoxedFoo>` is guaranteed to be exactly one pointer wide and can be passed over FFI as a nullable pointer to a thin trait object:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   # use std::mem::size_of;
+//!   #[thin_trait_object]
+//!   trait Foo {}
+//!   assert_eq!(size_of::<Option<BoxedFoo<'_>>>(), size_of::<*mut ()>());
+//!   ```
+//!   
+//!   The boxed thin trait object itself is always exactly one pointer wide as well, which the generated code checks at compile time, so it can be used directly as a parameter or return type of `extern "C"` functions, where C code sees it as an opaque pointer:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Foo {
+//!       fn get(&self) -> u32;
+//!   }
+//!   impl Foo for u32 {
+//!       fn get(&self) -> u32 { *self }
+//!   }
+//!   #[no_mangle]
+//!   pub extern "C" fn make_foo() -> BoxedFoo<'static> {
+//!       BoxedFoo::new(1234_u32)
+//!   }
+//!   #[no_mangle]
+//!   pub extern "C" fn get_foo(foo: Option<&BoxedFoo<'static>>) -> u32 {
+//!       foo.map_or(0, |foo| foo.get())
+//!   }
+//!   assert_eq!(get_foo(Some(&make_foo())), 1234);
+//!   ```
+//!   This does not apply to the inline storage structure generated by `inline_storage(...)`, which is larger than a pointer because it stores small values inside of itself, and thus should not be passed over FFI by value.
 //!   
 //!   The following methods and associated functions are present on the boxed thin trait object structure:
 //!   - ```no_run
@@ -298,7 +2519,111 @@
 //!     fn new<T: {trait name} + Sized + 'inner>(val: T) -> Self
 //!     # */
 //!     ```
-//!     Constructs a boxed thin trait object from a type implementing the trait. The `'inner` bound is replaced with `'static` if the `'static` lifetime is one of the supertraits on the base trait.
+//!     Constructs a boxed thin trait object from a type implementing the trait. The `'inner` bound is replaced with `'static` if the `'static` lifetime is one of the supertraits on the base trait. With `header = ...`, takes the header as an additional first argument.
+//!
+//!     Values which take up no space, such as unit structs implementing the trait for stateless handlers, are not allocated: if the representation struct is no bigger and no more aligned than the vtable (or the reference to it) on its own, the pointer points to a constant holding just that, and dropping the thin trait object only runs the destructor of the value. Dispatch still goes through the vtable like for any other value. This only applies to `storage = "box"` without a header, since the reference count of `storage = "arc"` and `"rc"` and the header always need to be stored somewhere; over-aligned zero-sized types are allocated as usual.
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     # use std::sync::atomic::{AtomicUsize, Ordering};
+//!     #[thin_trait_object]
+//!     trait Handler {
+//!         fn handle(&self, input: u32) -> u32;
+//!     }
+//!     static DROPPED: AtomicUsize = AtomicUsize::new(0);
+//!     struct Double;
+//!     impl Handler for Double {
+//!         fn handle(&self, input: u32) -> u32 { input * 2 }
+//!     }
+//!     impl Drop for Double {
+//!         fn drop(&mut self) {
+//!             DROPPED.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//!     let first = BoxedHandler::new(Double);
+//!     let second = BoxedHandler::new(Double);
+//!     // Both point to the same constant rather than to separate allocations.
+//!     assert_eq!(first.as_raw(), second.as_raw());
+//!     assert_eq!(first.handle(21), 42);
+//!     drop((first, second));
+//!     assert_eq!(DROPPED.load(Ordering::Relaxed), 2);
+//!     ```
+//!   - ```no_run
+//!     # /*
+//!     fn from_box<T: {trait name} + Sized + 'inner>(val: Box<T>) -> Self
+//!     # */
+//!     ```
+//!     Constructs a boxed thin trait object from a value which is already boxed, with the same bounds and the same header argument as `new`. The allocation of the `Box` cannot be reused, since the one of the thin trait object also holds the vtable, so a new one is made, the value is copied into it directly from the old one, and the old one is freed without dropping the value. Unlike `new(*val)`, this never copies the value onto the stack, which matters for large values that are boxed in the first place to keep them off of it:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object]
+//!     trait Table {
+//!         fn lookup(&self, index: usize) -> u64;
+//!     }
+//!     struct Squares([u64; 4096]);
+//!     impl Table for Squares {
+//!         fn lookup(&self, index: usize) -> u64 { self.0[index] }
+//!     }
+//!     let mut squares = Box::new(Squares([0; 4096]));
+//!     for (i, square) in squares.0.iter_mut().enumerate() {
+//!         *square = (i * i) as u64;
+//!     }
+//!     let table = BoxedTable::from_box(squares);
+//!     assert_eq!(table.lookup(12), 144);
+//!     ```
+//!     Only available with `storage = "box"`, since `Arc` and `Rc` cannot allocate their contents uninitialized on stable Rust. Values which take up no space are handled like with `new`.
+//!   - ```no_run
+//!     # /*
+//!     fn new_in_buffer<T: {trait name} + Sized + 'inner>(buffer: &'inner mut [MaybeUninit<u8>], val: T) -> Result<Self, T>
+//!     const fn buffer_size_for<T: {trait name} + Sized + 'inner>() -> usize
+//!     # */
+//!     ```
+//!     Generated instead of `new` and `from_box` with `storage = "buffer"`. `new_in_buffer` constructs a thin trait object which stores the value in the given buffer, at the first address in it which is aligned for the representation struct, or gives the value back if it doesn't fit there; `buffer_size_for` returns the size of the representation struct plus its alignment minus one, which is enough for any buffer. The `'inner` lifetimes are replaced with `'static` under the same conditions as for `new`, and with `header = ...`, `new_in_buffer` takes the header as an additional first argument.
+//!   - ```no_run
+//!     # /*
+//!     unsafe fn from_parts(vtable: &'static {vtable name}, data: *mut c_void) -> Self
+//!     # */
+//!     ```
+//!     Only generated with `from_parts = true`. Constructs a boxed thin trait object which calls the entries of the given vtable with the given data pointer, and calls its `drop` entry with it when dropped, unless a method taking `self` by value takes ownership of the data first. With `header = ...`, takes the header as an additional first argument. The caller has to make sure that the entries are valid to call with the data pointer and behave like an implementation of the trait, including upholding the contract of the trait if it is unsafe and making it sound for the thin trait object to implement the marker traits which are its supertraits, such as `Send` and `Sync`.
+//!
+//!     No `impl<T: {trait name}> From<T>` is generated: since the boxed thin trait object implements the trait itself, the impl would overlap with the reflexive `impl<T> From<T> for T` of the standard library, and excluding the boxed thin trait object from it is not expressible without negative trait bounds. The constructor can be passed to `map` and similar functions as a substitute, or an `impl From<{type}>` can be written by hand for each concrete implementor which is to be convertible:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object]
+//!     trait Foo {
+//!         fn get(&self) -> u32;
+//!     }
+//!     impl Foo for u32 {
+//!         fn get(&self) -> u32 { *self }
+//!     }
+//!     impl From<u32> for BoxedFoo<'_> {
+//!         fn from(val: u32) -> Self {
+//!             Self::new(val)
+//!         }
+//!     }
+//!     let boxed: BoxedFoo = 1_u32.into();
+//!     assert_eq!(boxed.get(), 1);
+//!     let all = vec![2_u32, 3].into_iter().map(BoxedFoo::new).collect::<Vec<_>>();
+//!     assert_eq!(all[1].get(), 3);
+//!     ```
+//!     ```compile_fail
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object]
+//!     trait Foo {
+//!         fn get(&self) -> u32;
+//!     }
+//!     // Conflicts with `impl<T> From<T> for T`, with `T` being `BoxedFoo`.
+//!     impl<'inner, T: Foo + 'inner> From<T> for BoxedFoo<'inner> {
+//!         fn from(val: T) -> Self {
+//!             Self::new(val)
+//!         }
+//!     }
+//!     ```
+//!   - ```no_run
+//!     # /*
+//!     impl Default for {trait object name}<'_>
+//!     # */
+//!     ```
+//!     Only generated with `default = ...`. Constructs a boxed thin trait object with `new`, passing it the default value of the given type, and the default value of the header type as the header with `header = ...`.
 //!   - ```no_run
 //!     # /*
 //!     const unsafe fn from_raw(ptr: *mut ()) -> Self
@@ -323,11 +2648,60 @@
 //!     Unlike `into_raw`, ownership of the pointer is not released, and as such will be dropped normally. Unless the original copy is removed via [`mem::forget`] or [`ManuallyDrop`], calling `from_raw` and then dropping will cause undefined behavior.
 //!   - ```no_run
 //!     # /*
+//!     const fn as_ptr(&self) -> *const c_void
+//!     fn as_mut_ptr(&mut self) -> *mut c_void
+//!     # */
+//!     ```
+//!     Return the same pointer as `as_raw`, typed as an opaque pointer for passing the thin trait object to C functions which borrow it, with `as_mut_ptr` for ones which mutate the value through it. Ownership is not released, so the pointer is only valid for as long as the thin trait object is alive, and must not be freed by the callee, since the thin trait object still drops the value when it is dropped itself:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     use std::ffi::c_void;
+//!     #[thin_trait_object(abi = "C")]
+//!     trait Counter {
+//!         fn bump(&mut self) -> u32;
+//!     }
+//!     impl Counter for u32 {
+//!         fn bump(&mut self) -> u32 {
+//!             *self += 1;
+//!             *self
+//!         }
+//!     }
+//!     // Stands in for a C function which borrows the thin trait object.
+//!     unsafe extern "C" fn bump_twice(counter: *mut c_void) -> u32 {
+//!         let vtable = *(counter as *const &CounterVtable);
+//!         (vtable.bump)(counter);
+//!         (vtable.bump)(counter)
+//!     }
+//!     let mut counter = BoxedCounter::new(0);
+//!     assert_eq!(counter.as_ptr(), counter.as_raw() as *const c_void);
+//!     assert_eq!(unsafe { bump_twice(counter.as_mut_ptr()) }, 2);
+//!     // Still owned by `counter`, which drops it
+//!     assert_eq!(counter.bump(), 3);
+//!     ```
+//!   - ```no_run
+//!     # /*
 //!     fn into_raw(self) -> *mut ()
 //!     # */
 //!     ```
 //!     Releases ownership of the trait object, returning the contained pointer. It is the caller's responsibility to drop the trait object at a later time using `from_raw`.
 //!
+//!     The stored value is not dropped and stays in its allocation, and since the vtable pointer is stored there too, the pointer can be passed through FFI as an opaque `*mut c_void` and turned back into a thin trait object of the same type with `from_raw`, which will then deallocate it using the `drop` vtable entry:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     use std::ffi::c_void;
+//!     #[thin_trait_object]
+//!     trait Foo {
+//!         fn get(&self) -> u32;
+//!     }
+//!     impl Foo for u32 {
+//!         fn get(&self) -> u32 { *self }
+//!     }
+//!     let opaque = BoxedFoo::new(1234).into_raw() as *mut c_void;
+//!     // ...pass `opaque` to C code and get it back...
+//!     let boxed = unsafe { BoxedFoo::from_raw(opaque as *mut ()) };
+//!     assert_eq!(boxed.get(), 1234);
+//!     ```
+//!
 //!     For a version which does not release ownership, see `as_raw`.
 //!   - ```no_run
 //!     # /*
@@ -335,6 +2709,435 @@
 //!     # */
 //!     ```
 //!     Retrieves the raw vtable of the contained trait object.
+//!   - ```no_run
+//!     # /*
+//!     const fn is_inline_vtable() -> bool
+//!     # */
+//!     ```
+//!     Returns `true` if the type was generated with `vtable = "inline"` and `false` with `vtable = "static"`. Since it is a constant, code which is generic over several thin trait object types, such as debugging tools or layout assertions in tests, can branch on it without any cost at runtime:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object(vtable = "inline")]
+//!     trait Fast {
+//!         fn get(&self) -> u32;
+//!     }
+//!     #[thin_trait_object]
+//!     trait Small {
+//!         fn get(&self) -> u32;
+//!     }
+//!     const _: () = assert!(BoxedFast::is_inline_vtable());
+//!     assert!(!BoxedSmall::is_inline_vtable());
+//!     ```
+//!   - ```no_run
+//!     # /*
+//!     fn same_vtable(&self, other: &Self) -> bool
+//!     # */
+//!     ```
+//!     Returns `true` if both thin trait objects use the same vtable, which is a cheap way of checking whether they store values of the same type. Compares the addresses of the vtables, or all of their entries with `inline_vtable = true`:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object]
+//!     trait Foo {
+//!         fn get(&self) -> u32;
+//!     }
+//!     impl Foo for u32 {
+//!         fn get(&self) -> u32 { *self }
+//!     }
+//!     impl Foo for String {
+//!         fn get(&self) -> u32 { self.len() as u32 }
+//!     }
+//!     let (a, b) = (BoxedFoo::new(1_u32), BoxedFoo::new(2_u32));
+//!     assert!(a.same_vtable(&b));
+//!     assert!(!a.same_vtable(&BoxedFoo::new(String::new())));
+//!     ```
+//!     The result is only a hint: the compiler may merge identical functions or even vtables of distinct types, making values of those types compare equal, and may also duplicate the vtable of a single type across codegen units, making values of the same type compare unequal. Use `type_id = true` if a reliable check is needed.
+//!   - ```no_run
+//!     # /*
+//!     const fn vtable_for<T: {trait name} + Sized + 'inner>() -> &'static {vtable name}
+//!     # */
+//!     ```
+//!     Returns the vtable which thin trait objects storing a value of type `T` constructed with `new` use, for embedding it in other structures or for constructing thin trait objects manually. When called at runtime, the returned reference is the same one those thin trait objects store, with the same caveat about copies in different codegen units as with `same_vtable`, but evaluating it in a constant expression, such as the initializer of a `static`, makes a separate copy of the vtable:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object]
+//!     trait Foo {
+//!         fn get(&self) -> u32;
+//!     }
+//!     impl Foo for u32 {
+//!         fn get(&self) -> u32 { *self }
+//!     }
+//!     #[repr(C)]
+//!     struct Handler {
+//!         id: u32,
+//!         vtable: &'static FooVtable,
+//!     }
+//!     static HANDLER: Handler = Handler { id: 1, vtable: BoxedFoo::vtable_for::<u32>() };
+//!     assert_eq!(HANDLER.vtable.drop as usize, BoxedFoo::new(1_u32).vtable().drop as usize);
+//!     let boxed = BoxedFoo::new(2_u32);
+//!     assert!(std::ptr::eq(boxed.vtable(), BoxedFoo::vtable_for::<u32>()));
+//!     ```
+//!     Rust doesn't guarantee that the vtable has a single address even at runtime, so where pointer identity matters, the statics generated with `vtable_static(...)` should be used instead.
+//!
+//!   Besides implementing the trait, the boxed thin trait object also has an inherent method for every trait method which is in the vtable, carrying over the `#[doc]`, `#[must_use]`, `#[deprecated]`, `#[inline]`, `#[cold]` and `#[track_caller]` attributes of the trait method, since the compiler ignores or rejects most of those on methods in trait implementations. Since inherent methods take precedence, those are the ones which get called when the trait is in scope as well. Both the inherent methods and the ones in the trait implementation are marked `#[inline]`, or `#[inline(always)]` with `inline_always = true`, unless the trait method has an `#[inline]` attribute of its own. That one, along with `#[cold]`, is carried over to the trait implementations of all thin trait objects as well, which makes it possible to keep rarely used methods out of line:
+//!   ```rust
+//!   # #![deny(warnings)]
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Connection {
+//!       fn send(&mut self, byte: u8);
+//!       #[cold]
+//!       #[inline(never)]
+//!       fn reconnect(&mut self);
+//!   }
+//!   # impl Connection for Vec<u8> {
+//!   #     fn send(&mut self, byte: u8) { self.push(byte) }
+//!   #     fn reconnect(&mut self) { self.clear() }
+//!   # }
+//!   # let mut connection = BoxedConnection::new(Vec::new());
+//!   # connection.send(1);
+//!   # connection.reconnect();
+//!   ```
+//!   The compiler doesn't accept those two attributes on trait methods without a default implementation, so the macro removes them from the trait definition it emits. Trait methods which have the same name as one of the methods listed in this section are not made into inherent methods. The inherent methods of trait methods with a `#[thin(rename = "...")]` attribute get the name from that attribute instead, on all of the thin trait object structures.
+//!
+//!   The receivers of the methods are the same as in the trait, and the thunks behind `&self` methods only ever create a shared reference to the stored value, so those can be called through a shared reference to the thin trait object, while `&mut self` methods require a mutable one:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   #[thin_trait_object]
+//!   trait Tally {
+//!       fn count(&self) -> u32;
+//!       fn add(&mut self);
+//!   }
+//!   impl Tally for u32 {
+//!       fn count(&self) -> u32 { *self }
+//!       fn add(&mut self) { *self += 1 }
+//!   }
+//!   fn count_twice(tally: &BoxedTally) -> u32 {
+//!       tally.count() + tally.count()
+//!   }
+//!   let mut tally = BoxedTally::new(0_u32);
+//!   tally.add();
+//!   assert_eq!(count_twice(&tally), 2);
+//!   ```
+//!   ```compile_fail
+//!   # use thin_trait_object::*;
+//!   # #[thin_trait_object]
+//!   # trait Tally {
+//!   #     fn add(&mut self);
+//!   # }
+//!   fn add_through_shared(tally: &BoxedTally) {
+//!       tally.add();
+//!   }
+//!   ```
+//!
+//!   The location of the caller cannot be passed through the function pointer in the vtable, so a `#[track_caller]` implementation called through a thin trait object sees the declaration of the method in the trait as its caller, rather than the code which called the method of the thin trait object, or the code generated by the macro:
+//!   ```rust
+//!   # use thin_trait_object::*;
+//!   use std::panic::Location;
+//!   const DECLARATION_LINE: u32 = line!() + 4;
+//!   #[thin_trait_object]
+//!   trait Checked {
+//!       #[track_caller]
+//!       fn caller(&self) -> &'static Location<'static>;
+//!   }
+//!   impl Checked for () {
+//!       #[track_caller]
+//!       fn caller(&self) -> &'static Location<'static> { Location::caller() }
+//!   }
+//!   assert_eq!(BoxedChecked::new(()).caller().line(), DECLARATION_LINE);
+//!   ```
+//!
+//!   With `store_layout = true`, the following methods are also present:
+//!   - ```no_run
+//!     # /*
+//!     fn value_size(&self) -> usize
+//!     fn value_align(&self) -> usize
+//!     # */
+//!     ```
+//!     Return the size and alignment of the stored value, as stored in the vtable.
+//!
+//!   With `store_layout = true`, unless the storage is `"arc"` or `"rc"` or `static_storage(...)` is enabled, the following method is also present:
+//!   - ```no_run
+//!     # /*
+//!     fn swap(&mut self, other: &mut Self)
+//!     # */
+//!     ```
+//!     Swaps the values stored in the two thin trait objects, along with their headers, without reallocating. If both store values of the same type, which is checked with the `type_id` entry if there is one and by comparing the vtables the same way `same_vtable` does otherwise, the values are swapped inside of the allocations using the size and alignment from the vtable, so each allocation stays with its thin trait object. Values of different types are swapped by exchanging the pointers to the allocations, like [`mem::swap`] does:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object(store_layout = true)]
+//!     trait Foo {
+//!         fn get(&self) -> u32;
+//!     }
+//!     impl Foo for u32 {
+//!         fn get(&self) -> u32 { *self }
+//!     }
+//!     impl Foo for String {
+//!         fn get(&self) -> u32 { self.len() as u32 }
+//!     }
+//!     let (mut a, mut b) = (BoxedFoo::new(String::from("one")), BoxedFoo::new(String::from("four")));
+//!     let (a_ptr, b_ptr) = (a.as_raw(), b.as_raw());
+//!     a.swap(&mut b);
+//!     assert_eq!((a.get(), b.get()), (4, 3));
+//!     assert_eq!((a.as_raw(), b.as_raw()), (a_ptr, b_ptr));
+//!
+//!     let mut c = BoxedFoo::new(1_u32);
+//!     let c_ptr = c.as_raw();
+//!     a.swap(&mut c);
+//!     assert_eq!((a.get(), c.get()), (1, 4));
+//!     assert_eq!((a.as_raw(), c.as_raw()), (c_ptr, a_ptr));
+//!     ```
+//!     Since the vtables of distinct types may be merged by the compiler, without `type_id = true` values of distinct types which have identical vtables, including their size, alignment and destructor, may be swapped in place as well, which is then indistinguishable from swapping them as values of the same type. With `type_id = true`, `replace` writes a value of a known type into the existing allocation instead.
+//!
+//!   With `header = ...`, the following method is also present, on the borrowed thin trait objects as well:
+//!   - ```no_run
+//!     # /*
+//!     fn header(&self) -> &{header type}
+//!     # */
+//!     ```
+//!     Returns the header stored in front of the value.
+//!
+//!   With `type_id = true`, the following methods are also present:
+//!   - ```no_run
+//!     # /*
+//!     fn is<T: {trait name} + 'static>(&self) -> bool
+//!     # */
+//!     ```
+//!     Returns `true` if the stored value is of type `T`.
+//!   - ```no_run
+//!     # /*
+//!     fn downcast_ref<T: {trait name} + 'static>(&self) -> Option<&T>
+//!     fn downcast_mut<T: {trait name} + 'static>(&mut self) -> Option<&mut T>
+//!     # */
+//!     ```
+//!     Borrow the stored value if it is of type `T`.
+//!   - ```no_run
+//!     # /*
+//!     fn with_ref<T: {trait name} + 'static, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R>
+//!     fn with_mut<T: {trait name} + 'static, R>(&mut self, f: impl FnOnce(&mut T) -> R) -> Option<R>
+//!     # */
+//!     ```
+//!     Call `f` with a borrow of the stored value and return its result if the value is of type `T`, otherwise return `None` without calling it. Unlike with `downcast_ref` and `downcast_mut`, the borrow cannot outlive the call:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object(type_id = true)]
+//!     trait Shape {}
+//!     struct Circle {
+//!         radius: f64,
+//!     }
+//!     impl Shape for Circle {}
+//!     struct Square;
+//!     impl Shape for Square {}
+//!     let mut shape = BoxedShape::new(Circle { radius: 1.0 });
+//!     assert_eq!(shape.with_ref(|circle: &Circle| circle.radius), Some(1.0));
+//!     assert_eq!(shape.with_ref(|_: &Square| ()), None);
+//!     shape.with_mut(|circle: &mut Circle| circle.radius *= 2.0);
+//!     assert_eq!(shape.with_ref(|circle: &Circle| circle.radius), Some(2.0));
+//!     ```
+//!     ```compile_fail
+//!     # use thin_trait_object::*;
+//!     # #[thin_trait_object(type_id = true)]
+//!     # trait Shape {}
+//!     # struct Circle {
+//!     #     radius: f64,
+//!     # }
+//!     # impl Shape for Circle {}
+//!     let shape = BoxedShape::new(Circle { radius: 1.0 });
+//!     let circle = shape.with_ref(|circle: &Circle| circle); // Error: the borrow escapes the closure
+//!     ```
+//!   - ```no_run
+//!     # /*
+//!     fn downcast<T: {trait name} + 'static>(self) -> Result<T, Self>
+//!     # */
+//!     ```
+//!     Moves the stored value out of the trait object and deallocates it if the value is of type `T`, otherwise returns the trait object unchanged. The value is returned by itself rather than in a `Box`, since the allocation also contains the vtable, and is not dropped by the `drop` vtable entry, which is not called in that case:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     use std::cell::Cell;
+//!
+//!     #[thin_trait_object(type_id = true)]
+//!     trait Token {}
+//!     thread_local! {
+//!         static DROPS: Cell<u32> = Cell::new(0);
+//!     }
+//!     struct Guard;
+//!     impl Token for Guard {}
+//!     impl Drop for Guard {
+//!         fn drop(&mut self) { DROPS.with(|drops| drops.set(drops.get() + 1)) }
+//!     }
+//!     impl Token for u32 {}
+//!     let boxed = BoxedToken::new(Guard);
+//!     let boxed = boxed.downcast::<u32>().unwrap_err();
+//!     assert_eq!(DROPS.with(Cell::get), 0);
+//!     let guard = Box::new(boxed.downcast::<Guard>().ok().unwrap());
+//!     assert_eq!(DROPS.with(Cell::get), 0);
+//!     drop(guard);
+//!     assert_eq!(DROPS.with(Cell::get), 1);
+//!     ```
+//!   - ```no_run
+//!     # /*
+//!     fn replace<T: {trait name} + 'static>(&mut self, val: T) -> Option<()>
+//!     # */
+//!     ```
+//!     Replaces the stored value with `val` if the stored value is of type `T`, dropping the old one in place, otherwise returns `None`, dropping `val` instead of storing it. The new value is written into the same allocation, which makes this cheaper than constructing a new thin trait object, e.g. when reusing thin trait objects from a pool:
+//!     ```rust
+//!     # use thin_trait_object::*;
+//!     #[thin_trait_object(type_id = true)]
+//!     trait Job {
+//!         fn run(&mut self) -> u32;
+//!     }
+//!     impl Job for u32 {
+//!         fn run(&mut self) -> u32 { *self * 2 }
+//!     }
+//!     let mut job = BoxedJob::new(1_u32);
+//!     let allocation = job.as_raw();
+//!     assert_eq!(job.replace(21_u32), Some(()));
+//!     assert_eq!(job.run(), 42);
+//!     assert_eq!(job.as_raw(), allocation);
+//!     assert_eq!(job.replace(0_u8), None);
+//!     assert_eq!(job.run(), 42);
+//!     # impl Job for u8 {
+//!     #     fn run(&mut self) -> u32 { 0 }
+//!     # }
+//!     ```
+//!
+//!   If one of the methods is marked with `#[thin(tag)]`, as described in [Tagged downcasting](#tagged-downcasting), the following method is also present:
+//!   - ```no_run
+//!     # /*
+//!     unsafe fn downcast_ref_tagged<T: {trait name}>(&self, tag: {tag type}) -> Option<&T>
+//!     # */
+//!     ```
+//!     Returns a reference to the stored value if the tag method returns a tag equal to `tag`, trusting the caller that only values of type `T` do.
+//!
+//!   With `type_name = true`, the following method is also present, on the borrowed thin trait objects as well:
+//!   - ```no_run
+//!     # /*
+//!     fn value_type_name(&self) -> &'static str
+//!     # */
+//!     ```
+//!     Returns the name of the type of the stored value.
+//!
+//!   With `as_dyn = true`, the following methods are also present, on the borrowed thin trait objects as well, except for `as_dyn_mut` on the shared one and with shared storage:
+//!   - ```no_run
+//!     # /*
+//!     fn as_dyn(&self) -> &dyn {trait name}
+//!     fn as_dyn_mut(&mut self) -> &mut dyn {trait name}
+//!     # */
+//!     ```
+//!     Borrow the stored value as a regular trait object.
+//!
+//!   With `box_dyn = true`, the following methods are also present, with the boxed trait object taken by `from_box_dyn` having to be `'static` instead with `type_id = true`:
+//!   - ```no_run
+//!     # /*
+//!     fn into_box_dyn(self) -> Box<(dyn {trait name} + 'inner)>
+//!     fn from_box_dyn(boxed: Box<(dyn {trait name} + 'inner)>) -> Self
+//!     # */
+//!     ```
+//!     Convert the thin trait object into a regular boxed trait object and back, allocating each time.
+//!
+//!   With `manual_drop = true`, the following method is also present, and the `Drop` implementation is not:
+//!   - ```no_run
+//!     # /*
+//!     fn dealloc(self)
+//!     # */
+//!     ```
+//!     Drops the stored value and frees the allocation through the `dealloc` vtable entry.
+//!
+//!   With `allocator_api = true`, the following constructors are also present, with `A` also being bound by all marker supertraits and `Clone` if the `clone` vtable entry is present:
+//!   - ```no_run
+//!     # /*
+//!     fn try_new<T: {trait name} + Sized + 'inner>(val: T) -> Result<Self, AllocError>
+//!     # */
+//!     ```
+//!     Same as `new`, but returns an error if the allocation fails.
+//!   - ```no_run
+//!     # /*
+//!     fn new_in<T: {trait name} + Sized + 'inner, A: Allocator + 'inner>(val: T, alloc: A) -> Self
+//!     fn try_new_in<T: {trait name} + Sized + 'inner, A: Allocator + 'inner>(val: T, alloc: A) -> Result<Self, AllocError>
+//!     # */
+//!     ```
+//!     Same as `new` and `try_new`, but use the given allocator instead of the global one.
+//!
+//!   With `static_storage(...)`, the following constructor is also present:
+//!   - ```no_run
+//!     # /*
+//!     const fn from_static<T: {trait name}>(val: &'static {static storage name}<T>) -> Self
+//!     # */
+//!     ```
+//!     Constructs a boxed thin trait object which points to the value in the static storage structure instead of owning it.
+//!
+//!   With `vtable_static(...)`, the following constructor is also present:
+//!   - ```no_run
+//!     # /*
+//!     unsafe fn new_with_vtable<T: {trait name} + 'inner>(vtable: &'static {vtable name}, val: T) -> Self
+//!     # */
+//!     ```
+//!     Constructs a boxed thin trait object which points to the given vtable, which has to be the one for values of type `T`, like the static generated for `T`, instead of the one `new` uses. Zero-sized values aren't allocated, as with `new`. With `header = ...`, takes the header as an additional first argument.
+//! - **Borrowed thin trait object struct definitions**, if enabled with the `thin_ref(...)` and `thin_mut(...)` configuration options.
+//!
+//!   Defined as follows, with `&'a mut ()` instead of `&'a ()` for the mutable one:
+//!   ```rust
+//!   # /*
+//!   #[repr(transparent)]
+//!   struct FooRef<'a>(
+//!       ::core::ptr::NonNull<{vtable name}>,
+//!       ::core::marker::PhantomData<&'a ()>,
+//!   );
+//!   # */
+//!   ```
+//!   They have `from_raw`, `as_raw`, `vtable`, `is_inline_vtable` and `same_vtable` methods with the same meaning as the ones on the boxed thin trait object, except for `from_raw` not taking ownership and only being `const` on the shared one. The mutable one also has a `fn reborrow(&mut self) -> {mutably borrowed thin trait object name}<'_>` method, which reborrows it for a shorter lifetime. The boxed thin trait object gets additional methods:
+//!   - ```no_run
+//!     # /*
+//!     fn as_thin_ref(&self) -> {borrowed thin trait object name}<'_>
+//!     # */
+//!     ```
+//!     Borrows the trait object as a borrowed thin trait object.
+//!   - ```no_run
+//!     # /*
+//!     fn as_thin_mut(&mut self) -> {mutably borrowed thin trait object name}<'_>
+//!     # */
+//!     ```
+//!     Mutably borrows the trait object as a mutably borrowed thin trait object.
+//! - **A static storage struct definition**, if enabled with the `static_storage(...)` configuration option.
+//!
+//!   Generic over the stored type `T` and has the same layout as the allocation made by the boxed thin trait object, except for the `drop` entry of its vtable doing nothing. Has a `const fn new(val: T) -> Self` constructor.
+//! - **An embedded storage struct definition**, if enabled with the `embedded(...)` configuration option.
+//!
+//!   Generic over the stored type `T` and has the same layout as the allocation made by the boxed thin trait object. Has a `const fn new(val: T) -> Self` constructor, `get`, `get_mut` and `into_inner` methods for accessing the value, `as_raw` and `vtable` methods with the same meaning as the ones on the borrowed thin trait object, and the following methods if the borrowed thin trait objects are enabled:
+//!   - ```no_run
+//!     # /*
+//!     fn as_thin_ref(&self) -> {borrowed thin trait object name}<'_>
+//!     # */
+//!     ```
+//!     Borrows the stored value as a borrowed thin trait object.
+//!   - ```no_run
+//!     # /*
+//!     fn as_thin_mut(&mut self) -> {mutably borrowed thin trait object name}<'_>
+//!     # */
+//!     ```
+//!     Mutably borrows the stored value as a mutably borrowed thin trait object.
+//! - **An inline storage thin trait object struct definition**, if enabled with the `inline_storage(...)` configuration option.
+//!
+//!   Has a buffer of `inline_capacity` words for the value to be stored in, along with the vtable or a pointer to it, and a flag indicating whether the buffer holds the value itself or a pointer to a boxed one. If it does the former, the vtable has the same entries as the regular one except for `drop`, which drops the value without deallocating it. Has the following methods:
+//!   - ```no_run
+//!     # /*
+//!     fn new<T: {trait name} + Sized + 'inner>(val: T) -> Self
+//!     # */
+//!     ```
+//!     Constructs an inline storage thin trait object, storing the value inside of it if it fits and allocating otherwise.
+//!   - ```no_run
+//!     # /*
+//!     fn is_inline(&self) -> bool
+//!     # */
+//!     ```
+//!     Returns `true` if the value is stored inside the thin trait object rather than in a separate allocation.
+//!   - ```no_run
+//!     # /*
+//!     fn as_raw(&self) -> *mut ()
+//!     fn vtable(&self) -> &{vtable name}
+//!     const fn is_inline_vtable() -> bool
+//!     # */
+//!     ```
+//!     Same as the ones on the boxed thin trait object, except for the pointer returned by `as_raw` becoming invalid when the inline storage thin trait object is moved.
 //!
 //! [*FFI-Safe Polymorphism: Thin Trait Objects*]: https://adventures.michaelfbryan.com/posts/ffi-safe-polymorphism-in-rust/ " "
 //! [virtual dispatch table]: https://en.wikipedia.org/wiki/Virtual_method_table " "
@@ -342,6 +3145,18 @@
 //! [`core::ffi::c_void`]: https://doc.rust-lang.org/std/ffi/enum.c_void.html " "
 //! [`ManuallyDrop`]: https://doc.rust-lang.org/std/mem/struct.ManuallyDrop.html " "
 //! [`mem::forget`]: https://doc.rust-lang.org/std/mem/fn.forget.html " "
+//! [`mem::swap`]: https://doc.rust-lang.org/std/mem/fn.swap.html " "
+//! [`mem::offset_of!`]: https://doc.rust-lang.org/std/mem/macro.offset_of.html " "
+//! [`Box::leak`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.leak " "
+//! [`mem::needs_drop`]: https://doc.rust-lang.org/std/mem/fn.needs_drop.html " "
+//! [`pin!`]: https://doc.rust-lang.org/std/pin/macro.pin.html " "
+//! [`Box::pin`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.pin " "
+//! [`Pin::as_mut`]: https://doc.rust-lang.org/std/pin/struct.Pin.html#method.as_mut " "
+//! [`Box::from_raw_in`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw_in " "
+//! [`TypeId`]: https://doc.rust-lang.org/std/any/struct.TypeId.html " "
+//! [`Hasher`]: https://doc.rust-lang.org/std/hash/trait.Hasher.html " "
+//! [`core::any::type_name`]: https://doc.rust-lang.org/std/any/fn.type_name.html " "
+//! [`Allocator`]: https://doc.rust-lang.org/std/alloc/trait.Allocator.html " "
 
 #![deny(rust_2018_idioms)]
 #![warn(missing_docs, clippy::cargo)]
@@ -350,7 +3165,7 @@ use proc_macro::TokenStream;
 
 /// Creates a thin trait object interface for a trait.
 #[proc_macro_attribute]
-pub fn thin_trait_object(attr: TokenStream, mut item: TokenStream) -> TokenStream {
+pub fn thin_trait_object(attr: TokenStream, item: TokenStream) -> TokenStream {
     let output: TokenStream = attribute_main(attr.into(), item.clone().into())
         .unwrap_or_else(|error| error.to_compile_error())
         .into();
@@ -366,6 +3181,7 @@ pub fn thin_trait_object(attr: TokenStream, mut item: TokenStream) -> TokenStrea
     // • We can write concise unit tests which check the output of
     //   attribute_main (since only proc_macro2 is available in tests) and
     //   don't have to skip over the trait definition.
+    let mut item: TokenStream = attr::emitted_trait_definition(item.into()).into();
     item.extend(Some(output));
     item
 }
@@ -424,11 +3240,24 @@ pub(crate) mod util {
 
 mod attr;
 use attr::*;
+pub(crate) mod allocator;
+pub(crate) mod c_header;
+pub(crate) mod embedded;
+pub(crate) mod export_ctor;
+pub(crate) mod extra_entries;
+pub(crate) mod free_fn;
+pub(crate) mod from_parts;
+pub(crate) mod generics;
+pub(crate) mod inline_storage;
 pub(crate) mod marker_traits;
 pub(crate) mod options;
 pub(crate) mod repr;
+pub(crate) mod static_storage;
+pub(crate) mod storage;
+pub(crate) mod thin_ref;
 pub(crate) mod trait_object;
 pub(crate) mod vtable;
+pub(crate) mod vtable_static;
 
 /// Convinces [`cargo geiger`] that the crate has unsafe code.
 ///
@@ -15,17 +15,33 @@ use syn::{
     TypeParamBound,
 };
 
+use crate::generics::TraitGenerics;
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct MarkerTrait {
     pub unsafety: Option<token::Unsafe>,
     pub path: Path,
 }
 impl MarkerTrait {
-    pub fn as_impl_for(&self, implementor: &impl ToTokens) -> TokenStream {
+    /// Checks whether the path to the marker trait ends with the given name, as in `Send` or `core::marker::Send`.
+    pub fn is(&self, name: &str) -> bool {
+        self.path
+            .segments
+            .last()
+            .map(|segment| segment.ident == name)
+            == Some(true)
+    }
+    pub fn as_impl_for(
+        &self,
+        implementor: &impl ToTokens,
+        generics: &TraitGenerics,
+    ) -> TokenStream {
         let marker_unsafety = self.unsafety.as_ref();
         let marker_path = &self.path;
+        let params = generics.params();
+        let where_clause = generics.where_clause();
         quote! {
-            #marker_unsafety impl #marker_path for #implementor {}
+            #marker_unsafety impl #params #marker_path for #implementor #where_clause {}
         }
     }
 }
@@ -106,7 +122,7 @@ pub fn default_marker_filter(bound: TraitBound) -> Option<(TraitBound, bool)> {
 }
 
 thread_local! {
-    static LOOKUP_TABLE: Lazy<[(&'static str, Path, bool); 4]> = Lazy::new(|| {
+    static LOOKUP_TABLE: Lazy<[(&'static str, Path, bool); 5]> = Lazy::new(|| {
         [
             (
                 "Send",
@@ -128,6 +144,11 @@ thread_local! {
                 make_path![mkseg("std"), mkseg("panic"), mkseg("RefUnwindSafe")],
                 false,
             ),
+            (
+                "Eq",
+                make_path![mkseg("core"), mkseg("cmp"), mkseg("Eq")],
+                false,
+            ),
         ]
     });
 }
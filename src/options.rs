@@ -3,21 +3,33 @@
 use proc_macro2::Ident;
 use std::borrow::Borrow;
 use syn::{
+    braced,
+    ext::IdentExt,
     parenthesized,
     parse::{Parse, ParseStream},
+    parse_quote,
     punctuated::Punctuated,
     token,
     Attribute,
+    GenericArgument,
     LitBool,
+    LitInt,
     LitStr,
+    Path,
+    PathArguments,
     Token,
+    TraitBound,
+    TraitItem,
+    Type,
     Visibility,
 };
 
-use crate::marker_traits::MarkerTrait;
+use crate::{marker_traits::MarkerTrait, repr::ReprModifier, storage::Storage};
 
 pub type AttrOptions = Punctuated<AttrOption, Token![,]>;
 
+// The name and punctuation tokens are kept around for their spans.
+#[allow(dead_code)]
 pub enum AttrOption {
     /// Overrides the visibility modifier, name and optionally adds attributes to the generated vtable struct.
     ///
@@ -38,98 +50,777 @@ pub enum AttrOption {
         paren: token::Paren,
         additions: OutputAdditions,
     },
+    /// Sets whether the vtable will be stored inline within the thin trait object (`"inline"`) or as a pointer to a static (`"static"`), same as `inline_vtable`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     vtable = "inline",
+    /// )]
+    /// # */
+    /// ```
+    VtableMode {
+        name: custom_token::Vtable,
+        eq: Token![=],
+        val: LitStr,
+        inline_vtable: bool,
+    },
     /// Sets whether the vtable will be stored inline within the thin trait object or as a pointer.
     ///
     /// # Example
     /// ```rust
     /// # /*
     /// #[thin_trait_object(
-    ///     inline_vtable = false,
+    ///     inline_vtable = false,
+    /// )]
+    /// # */
+    /// ```
+    InlineVtable {
+        name: custom_token::InlineVtable,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Overrides the visibility modifier, name and optionally adds attributes to the generated thin trait object struct.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     trait_object(
+    ///         /// Documentation for my thin trait object type!
+    ///         #[fancy_attribute]
+    ///         pub MyTraitObjectName
+    ///     )
+    /// )]
+    /// # */
+    /// ```
+    TraitObject {
+        name: custom_token::TraitObject,
+        paren: token::Paren,
+        additions: OutputAdditions,
+    },
+    /// Overrides only the visibility modifier of the generated thin trait object struct.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     vis = "pub(crate)",
+    /// )]
+    /// # */
+    /// ```
+    Vis {
+        name: custom_token::Vis,
+        eq: Token![=],
+        visibility: Visibility,
+    },
+    /// Overrides only the name of the generated thin trait object struct.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     name = "MyThinTrait",
+    /// )]
+    /// # */
+    /// ```
+    Name {
+        name: custom_token::Name,
+        eq: Token![=],
+        val: Ident,
+    },
+    /// Overrides only the name of the generated vtable struct.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     vtable_name = "MyTraitFunctionTable",
+    /// )]
+    /// # */
+    /// ```
+    VtableName {
+        name: custom_token::VtableName,
+        eq: Token![=],
+        val: Ident,
+    },
+    /// Overrides the name of the private representation struct.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     repr_name = "MyTraitRepr",
+    /// )]
+    /// # */
+    /// ```
+    ReprName {
+        name: custom_token::ReprName,
+        eq: Token![=],
+        val: Ident,
+    },
+    /// Places all generated items into a child module with the given name, re-exporting the thin trait object structs.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     module = "my_trait_thin",
+    /// )]
+    /// # */
+    /// ```
+    Module {
+        name: custom_token::Module,
+        eq: Token![=],
+        val: Ident,
+    },
+    /// Replaces the `__thintraitobjectmacro` prefix of the hidden names used by the generated code with the given one.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     prefix = "__my_macro",
+    /// )]
+    /// # */
+    /// ```
+    Prefix {
+        name: custom_token::Prefix,
+        eq: Token![=],
+        val: Ident,
+    },
+    /// Specifies the ABI of the drop handler in the vtable, overriding the one set with `abi = "..."` for the drop entry. (ABI for all other methods can be specified directly in the trait definition or for all of them at once with `abi = "..."`.)
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     drop_abi = "C",
+    /// )]
+    /// # */
+    /// ```
+    DropAbi {
+        name: custom_token::DropAbi,
+        eq: Token![=],
+        abi: LitStr,
+    },
+    /// Specifies the supertraits which are to be considered marker traits and be automatically implemented on the trait object struct, as well as the safety/unsafety for every single one of them.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     marker_traits(
+    ///         MySafeTrait,
+    ///         unsafe MyUnsafeTrait,
+    ///     ),
+    /// )]
+    /// trait SomeTrait: MySafeTrait + MyUnsafeTrait {
+    ///     ...
+    /// }
+    /// # */
+    /// ```
+    MarkerTraits {
+        name: custom_token::MarkerTraits,
+        paren: token::Paren,
+        marker_traits: Punctuated<MarkerTrait, Token![,]>,
+    },
+    /// Sets whether the vtable will contain the size and alignment of the implementation it corresponds to.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     store_layout = true,
+    /// )]
+    /// # */
+    /// ```
+    StoreLayout {
+        name: custom_token::StoreLayout,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Specifies the type of a header which is stored in the allocation between the vtable and the value, and can be read without knowing the type of the value.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     header = MyHeader,
+    /// )]
+    /// # */
+    /// ```
+    Header {
+        name: custom_token::Header,
+        eq: Token![=],
+        ty: Type,
+    },
+    /// Specifies the ABI of all vtable entries. Methods which specify an ABI directly in the trait definition must use the same one.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     abi = "C",
+    /// )]
+    /// # */
+    /// ```
+    Abi {
+        name: custom_token::Abi,
+        eq: Token![=],
+        abi: LitStr,
+    },
+    /// Specifies the trait which converts the error types of methods returning a `Result` to and from the error code returned by their vtable entries, if those use a foreign ABI.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     abi = "C",
+    ///     error_code = IntoErrorCode,
+    /// )]
+    /// # */
+    /// ```
+    ErrorCode {
+        name: custom_token::ErrorCode,
+        eq: Token![=],
+        path: Path,
+    },
+    /// Enables generation of the borrowed thin trait object struct and specifies its visibility modifier, name and optionally attributes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     thin_ref(
+    ///         /// Documentation for my borrowed thin trait object type!
+    ///         pub MyTraitRef
+    ///     )
+    /// )]
+    /// # */
+    /// ```
+    ThinRef {
+        name: custom_token::ThinRef,
+        paren: token::Paren,
+        additions: OutputAdditions,
+    },
+    /// Enables generation of the mutably borrowed thin trait object struct and specifies its visibility modifier, name and optionally attributes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     thin_mut(
+    ///         /// Documentation for my mutably borrowed thin trait object type!
+    ///         pub MyTraitMut
+    ///     )
+    /// )]
+    /// # */
+    /// ```
+    ThinMut {
+        name: custom_token::ThinMut,
+        paren: token::Paren,
+        additions: OutputAdditions,
+    },
+    /// Sets whether the vtable will contain an entry for cloning the stored value, used to implement `Clone` on the thin trait object struct. Implied by a `Clone` supertrait.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     clone = true,
+    /// )]
+    /// # */
+    /// ```
+    Clone {
+        name: custom_token::Clone,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain an entry for formatting the stored value with `Debug`, used to implement `Debug` on the thin trait object struct. Implied by a `Debug` supertrait.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     debug = true,
+    /// )]
+    /// # */
+    /// ```
+    Debug {
+        name: custom_token::Debug,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain an entry for formatting the stored value with `Display`, used to implement `Display` on the thin trait object struct. Implied by a `Display` supertrait.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     display = true,
+    /// )]
+    /// # */
+    /// ```
+    Display {
+        name: custom_token::Display,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain the `TypeId` of the stored type, used to implement downcasting on the thin trait object struct.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     type_id = true,
+    /// )]
+    /// # */
+    /// ```
+    TypeId {
+        name: custom_token::TypeId,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain the name of the stored type, used for diagnostics.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     type_name = true,
+    /// )]
+    /// # */
+    /// ```
+    TypeName {
+        name: custom_token::TypeName,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain an `as_dyn` entry, used to borrow the stored value as a regular trait object.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     as_dyn = true,
+    /// )]
+    /// # */
+    /// ```
+    AsDyn {
+        name: custom_token::AsDyn,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain an `into_box_dyn` entry, used to convert the thin trait object into a regular boxed trait object, and whether the conversion in the other direction is generated as well.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     box_dyn = true,
+    /// )]
+    /// # */
+    /// ```
+    BoxDyn {
+        name: custom_token::BoxDyn,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the `from_parts` constructor is generated, which makes a boxed thin trait object dispatching through a vtable built at runtime.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     from_parts = true,
+    /// )]
+    /// # */
+    /// ```
+    FromParts {
+        name: custom_token::FromParts,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Specifies a type implementing the trait and `Default`, whose default value is stored by the boxed thin trait object created by its `Default` implementation.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     default = MyStruct,
+    /// )]
+    /// # */
+    /// ```
+    Default {
+        name: custom_token::Default,
+        eq: Token![=],
+        ty: Type,
+    },
+    /// Sets whether the vtable will contain an `eq` entry, used to implement `PartialEq` on the thin trait object struct. Requires the `type_id` entry.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     eq = true,
+    /// )]
+    /// # */
+    /// ```
+    Eq {
+        name: custom_token::Eq,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain a `hash` entry, used to implement `Hash` on the thin trait object struct. Requires the `eq` entry.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     hash = true,
+    /// )]
+    /// # */
+    /// ```
+    Hash {
+        name: custom_token::Hash,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether the vtable will contain a `cmp` entry, used to implement `PartialOrd` and `Ord` on the thin trait object struct. Requires the `type_id` and `eq` entries.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     ord = true,
+    /// )]
+    /// # */
+    /// ```
+    Ord {
+        name: custom_token::Ord,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether a C header describing the vtable struct and the thin trait object is generated as an associated constant of the vtable struct. Requires all vtable entries which C code can call to use the C or C-unwind ABI.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     c_header = true,
+    /// )]
+    /// # */
+    /// ```
+    CHeader {
+        name: custom_token::CHeader,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Specifies the smart pointer type which owns the stored value: `"box"`, `"arc"` or `"rc"`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     storage = "arc",
+    /// )]
+    /// # */
+    /// ```
+    Storage {
+        name: custom_token::Storage,
+        eq: Token![=],
+        storage: Storage,
+    },
+    /// Adds `packed`, `packed(N)` or `align(N)` to the `#[repr(C)]` attribute of the representation struct, for mapping the allocation onto a fixed layout. Values stored with `packed` have to implement `Copy`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     repr = "packed",
+    /// )]
+    /// # */
+    /// ```
+    Repr {
+        name: custom_token::Repr,
+        eq: Token![=],
+        val: LitStr,
+        modifier: ReprModifier,
+    },
+    /// Enables generation of constructors which use custom allocators or report allocation failure. Requires the unstable `allocator_api` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     allocator_api = true,
+    /// )]
+    /// # */
+    /// ```
+    AllocatorApi {
+        name: custom_token::AllocatorApi,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether methods with default implementations get vtable entries and are dynamically dispatched.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     dispatch_defaults = false,
+    /// )]
+    /// # */
+    /// ```
+    DispatchDefaults {
+        name: custom_token::DispatchDefaults,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Enables generation of the inline storage thin trait object struct, which stores small values without allocating, and specifies its visibility modifier, name and optionally attributes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     inline_storage(
+    ///         /// Documentation for my inline storage thin trait object type!
+    ///         pub InlineMyTrait
+    ///     )
+    /// )]
+    /// # */
+    /// ```
+    InlineStorage {
+        name: custom_token::InlineStorage,
+        paren: token::Paren,
+        additions: OutputAdditions,
+    },
+    /// Specifies the size of the buffer of the inline storage thin trait object struct, in multiples of the size of `usize`, not counting the vtable.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     inline_capacity = 4,
+    /// )]
+    /// # */
+    /// ```
+    InlineCapacity {
+        name: custom_token::InlineCapacity,
+        eq: Token![=],
+        val: LitInt,
+    },
+    /// Sets whether the methods which call vtable entries will be marked `#[inline(always)]` instead of `#[inline]`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     inline_always = true,
+    /// )]
+    /// # */
+    /// ```
+    InlineAlways {
+        name: custom_token::InlineAlways,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether all stored types without drop glue will share a single drop thunk, which reads the layout from the vtable. Requires `store_layout = true`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     store_layout = true,
+    ///     shared_drop = true,
+    /// )]
+    /// # */
+    /// ```
+    SharedDrop {
+        name: custom_token::SharedDrop,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Sets whether dropping the thin trait object does nothing, with the `drop` vtable entry doing nothing as well, leaving the value to be deallocated explicitly through the `dealloc` entry.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     manual_drop = true,
+    /// )]
+    /// # */
+    /// ```
+    ManualDrop {
+        name: custom_token::ManualDrop,
+        eq: Token![=],
+        val: LitBool,
+    },
+    /// Enables generation of a standalone function freeing a thin trait object given a pointer to it, and specifies its visibility modifier, name and optionally attributes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     free_fn(
+    ///         #[no_mangle]
+    ///         pub my_trait_free
+    ///     )
+    /// )]
+    /// # */
+    /// ```
+    FreeFn {
+        name: custom_token::FreeFn,
+        paren: token::Paren,
+        additions: OutputAdditions,
+    },
+    /// Enables generation of a `#[no_mangle]` function, with the ABI of the `drop` entry, which foreign code can call to create a boxed thin trait object storing the given FFI-safe type, passed by value, and specifies that type and the symbol name of the function. Can be specified more than once.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     drop_abi = "C",
+    ///     export_ctor(MyStruct, "my_trait_new_my_struct"),
+    /// )]
+    /// # */
+    /// ```
+    ExportCtor {
+        name: custom_token::ExportCtor,
+        paren: token::Paren,
+        ctor: ExportedCtor,
+    },
+    /// Enables generation of a `static` holding the vtable for the given stored type, which `new_with_vtable` can point boxed thin trait objects to, and specifies its visibility modifier, name and optionally attributes, like `#[link_section = "..."]`. Can be specified more than once.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     vtable_static(
+    ///         #[link_section = ".rodata.vtables"]
+    ///         pub MY_STRUCT_VTABLE = MyStruct
+    ///     ),
+    /// )]
+    /// # */
+    /// ```
+    VtableStatic {
+        name: custom_token::VtableStatic,
+        paren: token::Paren,
+        vtable_static: VtableStatic,
+    },
+    /// Enables generation of the static storage struct, which can be placed in a `static` and pointed to by thin trait objects without allocating, and specifies its visibility modifier, name and optionally attributes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin_trait_object(
+    ///     static_storage(
+    ///         /// Documentation for my static storage type!
+    ///         pub StaticMyTrait
+    ///     )
     /// )]
     /// # */
     /// ```
-    InlineVtable {
-        name: custom_token::InlineVtable,
-        eq: Token![=],
-        val: LitBool,
+    StaticStorage {
+        name: custom_token::StaticStorage,
+        paren: token::Paren,
+        additions: OutputAdditions,
     },
-    /// Overrides the visibility modifier, name and optionally adds attributes to the generated thin trait object struct.
+    /// Enables generation of the embedded storage struct, which stores a value of a known type along with its vtable and can be a field of other structures, and specifies its visibility modifier, name and optionally attributes.
     ///
     /// # Example
     /// ```rust
     /// # /*
     /// #[thin_trait_object(
-    ///     trait_object(
-    ///         /// Documentation for my thin trait object type!
-    ///         #[fancy_attribute]
-    ///         pub MyTraitObjectName
+    ///     embedded(
+    ///         /// Documentation for my embedded storage type!
+    ///         pub EmbeddedMyTrait
     ///     )
     /// )]
     /// # */
     /// ```
-    TraitObject {
-        name: custom_token::TraitObject,
+    Embedded {
+        name: custom_token::Embedded,
         paren: token::Paren,
         additions: OutputAdditions,
     },
-    /// Specifies the ABI of the drop handler in the vtable. (ABI for all other methods can be specified directly in the trait definition.)
+    /// Sets whether `async fn` methods are supported, by boxing the futures they return on every call.
     ///
     /// # Example
     /// ```rust
     /// # /*
     /// #[thin_trait_object(
-    ///     drop_abi = "C",
+    ///     async = true,
     /// )]
     /// # */
     /// ```
-    DropAbi {
-        name: custom_token::DropAbi,
+    Async {
+        name: custom_token::Async,
         eq: Token![=],
-        abi: LitStr,
+        val: LitBool,
     },
-    /// Specifies the supertraits which are to be considered marker traits and be automatically implemented on the trait object struct, as well as the safety/unsafety for every single one of them.
+    /// Flattens the methods of a supertrait into the vtable and implements the supertrait for the thin trait object structs. The methods have to be declared again, since the macro cannot see the definition of the supertrait. Can be specified multiple times.
     ///
     /// # Example
     /// ```rust
     /// # /*
     /// #[thin_trait_object(
-    ///     marker_traits(
-    ///         MySafeTrait,
-    ///         unsafe MyUnsafeTrait,
-    ///     ),
+    ///     supertrait(Shape {
+    ///         fn area(&self) -> f64;
+    ///     }),
     /// )]
-    /// trait SomeTrait: MySafeTrait + MyUnsafeTrait {
-    ///     ...
-    /// }
     /// # */
     /// ```
-    MarkerTraits {
-        name: custom_token::MarkerTraits,
+    Supertrait {
+        name: custom_token::Supertrait,
         paren: token::Paren,
-        marker_traits: Punctuated<MarkerTrait, Token![,]>,
+        supertrait: SupertraitMethods,
     },
-    /// Sets whether the vtable will contain the size and alignment of the implementation it corresponds to.
+    /// Sets whether the trait is implemented for every type implementing all of its supertraits, making it an alias grouping them, instead of being implemented for the thin trait object structs by the macro.
     ///
     /// # Example
     /// ```rust
     /// # /*
     /// #[thin_trait_object(
-    ///     store_layout = true,
+    ///     blanket_impl = true,
     /// )]
     /// # */
     /// ```
-    StoreLayout {
-        name: custom_token::StoreLayout,
+    BlanketImpl {
+        name: custom_token::BlanketImpl,
         eq: Token![=],
         val: LitBool,
     },
 }
 impl Parse for AttrOption {
     fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
-        let ident = input.parse::<Ident>()?;
+        // `async` is a keyword.
+        let ident = input.call(Ident::parse_any)?;
         // see https://github.com/rust-lang/rust-clippy/issues/4637
-        #[allow(clippy::eval_order_dependence)]
+        #[allow(clippy::mixed_read_write_in_expression)]
         let option = match ident.to_string().borrow() {
+            "vtable" if input.peek(Token![=]) => {
+                let eq = input.parse()?;
+                let val = input.parse::<LitStr>()?;
+                let inline_vtable = match val.value().as_str() {
+                    "inline" => true,
+                    "static" => false,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            val,
+                            "expected `\"inline\"` or `\"static\"`",
+                        ))
+                    }
+                };
+                Self::VtableMode {
+                    name: custom_token::Vtable(ident.span()),
+                    eq,
+                    val,
+                    inline_vtable,
+                }
+            }
             "vtable" => {
                 let inside_parens;
                 Self::Vtable {
@@ -151,6 +842,36 @@ impl Parse for AttrOption {
                     additions: inside_parens.parse()?,
                 }
             }
+            "vis" => Self::Vis {
+                name: custom_token::Vis(ident.span()),
+                eq: input.parse()?,
+                visibility: input.parse::<LitStr>()?.parse()?,
+            },
+            "name" => Self::Name {
+                name: custom_token::Name(ident.span()),
+                eq: input.parse()?,
+                val: input.parse::<LitStr>()?.parse()?,
+            },
+            "vtable_name" => Self::VtableName {
+                name: custom_token::VtableName(ident.span()),
+                eq: input.parse()?,
+                val: input.parse::<LitStr>()?.parse()?,
+            },
+            "repr_name" => Self::ReprName {
+                name: custom_token::ReprName(ident.span()),
+                eq: input.parse()?,
+                val: input.parse::<LitStr>()?.parse()?,
+            },
+            "module" => Self::Module {
+                name: custom_token::Module(ident.span()),
+                eq: input.parse()?,
+                val: input.parse::<LitStr>()?.parse()?,
+            },
+            "prefix" => Self::Prefix {
+                name: custom_token::Prefix(ident.span()),
+                eq: input.parse()?,
+                val: input.parse::<LitStr>()?.parse()?,
+            },
             "drop_abi" => Self::DropAbi {
                 name: custom_token::DropAbi(ident.span()),
                 eq: input.parse()?,
@@ -169,11 +890,222 @@ impl Parse for AttrOption {
                 eq: input.parse()?,
                 val: input.parse()?,
             },
+            "header" => Self::Header {
+                name: custom_token::Header(ident.span()),
+                eq: input.parse()?,
+                ty: input.parse()?,
+            },
+            "abi" => Self::Abi {
+                name: custom_token::Abi(ident.span()),
+                eq: input.parse()?,
+                abi: input.parse()?,
+            },
+            "error_code" => Self::ErrorCode {
+                name: custom_token::ErrorCode(ident.span()),
+                eq: input.parse()?,
+                path: input.parse()?,
+            },
+            "thin_ref" => {
+                let inside_parens;
+                Self::ThinRef {
+                    name: custom_token::ThinRef(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    additions: inside_parens.parse()?,
+                }
+            }
+            "thin_mut" => {
+                let inside_parens;
+                Self::ThinMut {
+                    name: custom_token::ThinMut(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    additions: inside_parens.parse()?,
+                }
+            }
+            "clone" => Self::Clone {
+                name: custom_token::Clone(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "debug" => Self::Debug {
+                name: custom_token::Debug(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "display" => Self::Display {
+                name: custom_token::Display(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "type_id" => Self::TypeId {
+                name: custom_token::TypeId(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "type_name" => Self::TypeName {
+                name: custom_token::TypeName(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "as_dyn" => Self::AsDyn {
+                name: custom_token::AsDyn(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "box_dyn" => Self::BoxDyn {
+                name: custom_token::BoxDyn(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "from_parts" => Self::FromParts {
+                name: custom_token::FromParts(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "default" => Self::Default {
+                name: custom_token::Default(ident.span()),
+                eq: input.parse()?,
+                ty: input.parse()?,
+            },
+            "eq" => Self::Eq {
+                name: custom_token::Eq(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "hash" => Self::Hash {
+                name: custom_token::Hash(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "ord" => Self::Ord {
+                name: custom_token::Ord(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "c_header" => Self::CHeader {
+                name: custom_token::CHeader(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "storage" => Self::Storage {
+                name: custom_token::Storage(ident.span()),
+                eq: input.parse()?,
+                storage: Storage::from_lit(&input.parse()?)?,
+            },
+            "repr" => {
+                let (eq, val) = (input.parse()?, input.parse()?);
+                Self::Repr {
+                    name: custom_token::Repr(ident.span()),
+                    eq,
+                    modifier: ReprModifier::from_lit(&val)?,
+                    val,
+                }
+            }
+            "allocator_api" => Self::AllocatorApi {
+                name: custom_token::AllocatorApi(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "dispatch_defaults" => Self::DispatchDefaults {
+                name: custom_token::DispatchDefaults(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "inline_storage" => {
+                let inside_parens;
+                Self::InlineStorage {
+                    name: custom_token::InlineStorage(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    additions: inside_parens.parse()?,
+                }
+            }
+            "inline_capacity" => Self::InlineCapacity {
+                name: custom_token::InlineCapacity(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "inline_always" => Self::InlineAlways {
+                name: custom_token::InlineAlways(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "shared_drop" => Self::SharedDrop {
+                name: custom_token::SharedDrop(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "manual_drop" => Self::ManualDrop {
+                name: custom_token::ManualDrop(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "free_fn" => {
+                let inside_parens;
+                Self::FreeFn {
+                    name: custom_token::FreeFn(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    additions: inside_parens.parse()?,
+                }
+            }
+            "export_ctor" => {
+                let inside_parens;
+                Self::ExportCtor {
+                    name: custom_token::ExportCtor(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    ctor: inside_parens.parse()?,
+                }
+            }
+            "vtable_static" => {
+                let inside_parens;
+                Self::VtableStatic {
+                    name: custom_token::VtableStatic(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    vtable_static: inside_parens.parse()?,
+                }
+            }
+            "static_storage" => {
+                let inside_parens;
+                Self::StaticStorage {
+                    name: custom_token::StaticStorage(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    additions: inside_parens.parse()?,
+                }
+            }
+            "embedded" => {
+                let inside_parens;
+                Self::Embedded {
+                    name: custom_token::Embedded(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    additions: inside_parens.parse()?,
+                }
+            }
+            "async" => Self::Async {
+                name: custom_token::Async(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "supertrait" => {
+                let inside_parens;
+                Self::Supertrait {
+                    name: custom_token::Supertrait(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    supertrait: inside_parens.parse()?,
+                }
+            }
+            "blanket_impl" => Self::BlanketImpl {
+                name: custom_token::BlanketImpl(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
             _ => {
                 return Err(syn::Error::new_spanned(
                     ident,
                     "\
-expected `vtable`, `inline_vtable`, `trait_object`, `drop_abi` or `marker_traits`",
+expected `vtable`, `inline_vtable`, `trait_object`, `vis`, `name`, `vtable_name`, `repr_name`, \
+`module`, `prefix`, `drop_abi`, `marker_traits`, `store_layout`, `header`, `abi`, `error_code`, \
+`thin_ref`, `thin_mut`, `clone`, `debug`, `display`, `type_id`, `type_name`, `as_dyn`, `box_dyn`, \
+`from_parts`, `default`, `eq`, `hash`, `ord`, `c_header`, `storage`, `repr`, `allocator_api`, \
+`dispatch_defaults`, `inline_storage`, `inline_capacity`, `inline_always`, `shared_drop`, `manual_drop`, \
+`free_fn`, `export_ctor`, `vtable_static`, `static_storage`, `embedded`, `async`, `supertrait` or `blanket_impl`",
                 ));
             }
         };
@@ -196,6 +1128,204 @@ impl Parse for OutputAdditions {
     }
 }
 
+/// The type stored by an exported constructor and the symbol name of the constructor.
+// The comma token is kept around for its span.
+#[allow(dead_code)]
+pub struct ExportedCtor {
+    pub ty: Type,
+    pub comma: Token![,],
+    pub symbol: LitStr,
+}
+impl Parse for ExportedCtor {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        Ok(Self {
+            ty: input.parse()?,
+            comma: input.parse()?,
+            symbol: input.parse()?,
+        })
+    }
+}
+
+/// The name, visibility and attributes of a static holding the vtable for a stored type, and that type.
+// The equals sign token is kept around for its span.
+#[allow(dead_code)]
+pub struct VtableStatic {
+    pub additions: OutputAdditions,
+    pub eq: Token![=],
+    pub ty: Type,
+}
+impl Parse for VtableStatic {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        Ok(Self {
+            additions: input.parse()?,
+            eq: input.parse()?,
+            ty: input.parse()?,
+        })
+    }
+}
+
+/// A supertrait whose methods are flattened into the vtable, along with the declarations of those methods.
+// The brace token is kept around for its span.
+#[allow(dead_code)]
+pub struct SupertraitMethods {
+    pub path: Path,
+    pub brace: token::Brace,
+    pub items: Vec<TraitItem>,
+}
+impl Parse for SupertraitMethods {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let inside_braces;
+        Ok(Self {
+            path: input.parse()?,
+            brace: braced!(inside_braces in input),
+            items: {
+                let mut items = Vec::new();
+                while !inside_braces.is_empty() {
+                    items.push(inside_braces.parse()?);
+                }
+                items
+            },
+        })
+    }
+}
+
+impl SupertraitMethods {
+    /// Declares the methods of a supertrait from the standard library which are flattened into the vtable without being listed in the `supertrait(...)` option, if the bound is one. Only `Iterator` with the `Item` type specified is recognized, for which the `next` method is declared.
+    pub fn from_supertrait(bound: &TraitBound) -> Option<Self> {
+        let last_segment = bound.path.segments.last()?;
+        if last_segment.ident != "Iterator" {
+            return None;
+        }
+        let arguments = match &last_segment.arguments {
+            PathArguments::AngleBracketed(arguments) => &arguments.args,
+            _ => return None,
+        };
+        let item = match arguments.iter().collect::<Vec<_>>()[..] {
+            [GenericArgument::Binding(binding)] if binding.ident == "Item" => &binding.ty,
+            _ => return None,
+        };
+        Some(Self {
+            path: bound.path.clone(),
+            brace: Default::default(),
+            items: vec![parse_quote! {
+                /// Advances the iterator and returns the next value.
+                fn next(&mut self) -> ::core::option::Option<#item>;
+            }],
+        })
+    }
+}
+
+pub type MethodOptions = Punctuated<MethodOption, Token![,]>;
+
+/// An option of a single trait method, specified in a `#[thin(...)]` attribute on it, which is removed from the emitted trait definition.
+// The name and punctuation tokens are kept around for their spans.
+#[allow(dead_code)]
+pub enum MethodOption {
+    /// Renames the inherent methods calling the vtable entry of the method, leaving the vtable entry and the implementations of the trait alone.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin(rename = "draw_widget")]
+    /// fn draw(&self);
+    /// # */
+    /// ```
+    Rename {
+        name: custom_token::Rename,
+        eq: Token![=],
+        val: LitStr,
+    },
+    /// Leaves the method out of the vtable, making all thin trait objects use its default implementation, which it is required to have.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin(skip)]
+    /// fn draw_twice(&self) {
+    ///     self.draw();
+    ///     self.draw();
+    /// }
+    /// # */
+    /// ```
+    Skip { name: custom_token::Skip },
+    /// Gives a generic method with a single type parameter a vtable entry for each of the listed type arguments, named after the method and the type, like `read_u8`, or given after `as`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin(mono(u8, u16, [u8; 4] as read_bytes))]
+    /// fn read<T: Pod>(&self) -> T;
+    /// # */
+    /// ```
+    Mono {
+        name: custom_token::Mono,
+        paren: token::Paren,
+        types: Punctuated<MonoType, Token![,]>,
+    },
+    /// Marks a method taking only `&self` as returning a tag which identifies the type of the stored value, adding an unsafe `downcast_ref_tagged` method to the boxed thin trait object which compares it to the one it is given instead of comparing `TypeId`s.
+    ///
+    /// # Example
+    /// ```rust
+    /// # /*
+    /// #[thin(tag)]
+    /// fn kind(&self) -> Kind;
+    /// # */
+    /// ```
+    Tag { name: custom_token::Tag },
+}
+impl Parse for MethodOption {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let ident = input.parse::<Ident>()?;
+        let option = match ident.to_string().borrow() {
+            "rename" => Self::Rename {
+                name: custom_token::Rename(ident.span()),
+                eq: input.parse()?,
+                val: input.parse()?,
+            },
+            "skip" => Self::Skip {
+                name: custom_token::Skip(ident.span()),
+            },
+            "mono" => {
+                let inside_parens;
+                Self::Mono {
+                    name: custom_token::Mono(ident.span()),
+                    paren: parenthesized!(inside_parens in input),
+                    types: inside_parens.call(Punctuated::parse_terminated)?,
+                }
+            }
+            "tag" => Self::Tag {
+                name: custom_token::Tag(ident.span()),
+            },
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected `rename`, `skip`, `mono` or `tag`",
+                ))
+            }
+        };
+        Ok(option)
+    }
+}
+
+/// A type argument listed in `#[thin(mono(...))]`, optionally followed by the name which the vtable entry for it uses in place of one derived from the type.
+// The `as` token is kept around for its span.
+#[allow(dead_code)]
+pub struct MonoType {
+    pub ty: Type,
+    pub name: Option<(Token![as], Ident)>,
+}
+impl Parse for MonoType {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let ty = input.parse()?;
+        let name = if input.peek(Token![as]) {
+            Some((input.parse()?, input.parse()?))
+        } else {
+            None
+        };
+        Ok(Self { ty, name })
+    }
+}
+
 pub mod custom_token {
     use proc_macro2::Span;
     use syn::{
@@ -205,6 +1335,7 @@ pub mod custom_token {
 
     macro_rules! custom_tokens {
         ($name:ident, $string:literal) => (
+            #[allow(dead_code)]
             pub struct $name (pub Span);
             impl Parse for $name {
                 #[inline]
@@ -231,8 +1362,53 @@ pub mod custom_token {
         (Vtable, "vtable"),
         (InlineVtable, "inline_vtable"),
         (TraitObject, "trait_object"),
+        (Vis, "vis"),
+        (Name, "name"),
+        (VtableName, "vtable_name"),
+        (ReprName, "repr_name"),
+        (Module, "module"),
+        (Prefix, "prefix"),
         (DropAbi, "drop_abi"),
         (MarkerTraits, "marker_traits"),
         (StoreLayout, "store_layout"),
+        (Header, "header"),
+        (Abi, "abi"),
+        (ErrorCode, "error_code"),
+        (ThinRef, "thin_ref"),
+        (ThinMut, "thin_mut"),
+        (Clone, "clone"),
+        (Debug, "debug"),
+        (Display, "display"),
+        (TypeId, "type_id"),
+        (TypeName, "type_name"),
+        (AsDyn, "as_dyn"),
+        (BoxDyn, "box_dyn"),
+        (FromParts, "from_parts"),
+        (Default, "default"),
+        (Eq, "eq"),
+        (Hash, "hash"),
+        (Ord, "ord"),
+        (CHeader, "c_header"),
+        (Storage, "storage"),
+        (Repr, "repr"),
+        (AllocatorApi, "allocator_api"),
+        (DispatchDefaults, "dispatch_defaults"),
+        (InlineStorage, "inline_storage"),
+        (InlineCapacity, "inline_capacity"),
+        (InlineAlways, "inline_always"),
+        (SharedDrop, "shared_drop"),
+        (ManualDrop, "manual_drop"),
+        (FreeFn, "free_fn"),
+        (ExportCtor, "export_ctor"),
+        (VtableStatic, "vtable_static"),
+        (StaticStorage, "static_storage"),
+        (Embedded, "embedded"),
+        (Async, "async"),
+        (Supertrait, "supertrait"),
+        (BlanketImpl, "blanket_impl"),
+        (Rename, "rename"),
+        (Skip, "skip"),
+        (Mono, "mono"),
+        (Tag, "tag"),
     }
 }
@@ -2,56 +2,168 @@
 
 use crate::{
     attr::StageStash,
-    vtable::{VtableFnArg, VtableItem},
+    extra_entries::ExtraEntry,
+    storage::Storage,
+    trait_object::split_associated_types,
+    vtable::{error_code_out_arg, ReceiverKind, VtableFnArg, VtableItem},
 };
 use proc_macro2::{Ident, TokenStream};
-use quote::{format_ident, quote, ToTokens};
-use syn::{token::Colon, Abi, BareFnArg, Path, Signature};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::{
+    parenthesized,
+    parse::ParseStream,
+    spanned::Spanned,
+    token::{Colon, Paren},
+    Abi,
+    BareFnArg,
+    LitInt,
+    LitStr,
+    Path,
+    Signature,
+    Token,
+    Type,
+};
+
+/// A modifier which the `repr` option adds to the `#[repr(C)]` attribute of the representation struct.
+#[derive(Clone)]
+pub enum ReprModifier {
+    /// `packed` or `packed(N)`, which can leave both the vtable pointer and the value misaligned. The thunks then call the methods on an aligned copy of the value, which is why it has to be `Copy`.
+    Packed(Option<LitInt>),
+    /// `align(N)`, which only raises the alignment of the whole representation struct.
+    Align(LitInt),
+}
+impl ReprModifier {
+    pub fn from_lit(lit: &LitStr) -> syn::Result<Self> {
+        let error = || {
+            syn::Error::new_spanned(
+                lit,
+                "expected `\"packed\"`, `\"packed(<n>)\"` or `\"align(<n>)\"`, optionally preceded by `C, `",
+            )
+        };
+        let modifier = lit.parse_with(|input: ParseStream<'_>| {
+            let mut ident = input.parse::<Ident>()?;
+            // The `C` is always there, but can be spelled out as well.
+            if ident == "C" && input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+                ident = input.parse()?;
+            }
+            let argument = if input.peek(Paren) {
+                let inside_parens;
+                parenthesized!(inside_parens in input);
+                Some(inside_parens.parse::<LitInt>()?)
+            } else {
+                None
+            };
+            match (ident.to_string().as_str(), argument) {
+                ("packed", argument) => Ok(Self::Packed(argument)),
+                ("align", Some(argument)) => Ok(Self::Align(argument)),
+                _ => Err(error()),
+            }
+        });
+        let modifier = modifier.map_err(|_| error())?;
+        if let Self::Packed(Some(argument)) | Self::Align(argument) = &modifier {
+            if !argument.base10_parse::<u32>()?.is_power_of_two() {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    "the alignment must be a power of two",
+                ));
+            }
+        }
+        Ok(modifier)
+    }
+    /// Returns whether the fields of the representation struct may be misaligned.
+    #[inline]
+    pub fn is_packed(&self) -> bool {
+        matches!(self, Self::Packed(..))
+    }
+}
+impl ToTokens for ReprModifier {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Self::Packed(None) => quote! { packed },
+            Self::Packed(Some(argument)) => quote! { packed(#argument) },
+            Self::Align(argument) => quote! { align(#argument) },
+        }
+        .to_tokens(tokens)
+    }
+}
 
 pub fn generate_repr(
     stash: &mut StageStash,
     inline_vtable: bool,
-    path_to_box: Path,
     drop_abi: Option<&Abi>,
     store_layout: bool,
+    shared_drop: bool,
 ) -> TokenStream {
     let StageStash {
         repr_name,
         vtable_name,
-        trait_name,
         vtable_items,
+        vtable_abi,
+        extra_entries,
+        storage,
+        drop_name,
+        trait_generics,
+        header,
+        repr_modifier,
         ..
-    } = stash;
+    } = &*stash;
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let repr_type = stash.repr_type(&quote! { __ThinTraitObjectMacro_ReprGeneric0 });
+    let params = trait_generics.params();
+    let where_clause = trait_generics.where_clause();
+    let path_to_box = storage.path();
     let (vtable_contents, thunk_methods) = generate_vtable_and_thunks(
-        &trait_name,
-        &repr_name,
-        vtable_items.iter().cloned(),
+        &stash.trait_impl_path(),
+        &repr_type,
+        &path_to_box,
+        vtable_items.iter().cloned().map(|mut item| {
+            item.apply_default_abi(vtable_abi.as_ref());
+            item
+        }),
         |_| true, // TODO
+        stash.is_packed(),
     );
 
+    // The header sits between the vtable and the value, and is passed to the constructor after the
+    // value.
+    let (header_field, header_initializer, header_param) = match header {
+        Some(header) => (
+            quote! { __thintraitobjectmacro_repr_header: #header, },
+            quote! { __thintraitobjectmacro_repr_header: __thintraitobjectmacro_arg1, },
+            quote! { __thintraitobjectmacro_arg1: #header, },
+        ),
+        None => (quote! {}, quote! {}, quote! {}),
+    };
+    let (repr_phantom_field, repr_phantom_initializer) = trait_generics.repr_phantom();
     // Perform necessary branching depending on vtable style in advance.
     let (vtable_field_type, ctor_val) = if inline_vtable {
         // The type of the vtable field is the vtable type's name itself,
         // so just get a token stream of it.
-        let vtable_field_type = vtable_name.to_token_stream();
+        let vtable_field_type = vtable_type.clone();
         // The constructor will memcpy the vtable into the repr struct.
         let ctor_val = quote! {
             Self {
                 __thintraitobjectmacro_repr_vtable: Self::__THINTRAITOBJECTMACRO_VTABLE,
+                #header_initializer
                 __thintraitobjectmacro_repr_value: __thintraitobjectmacro_arg0,
+                #repr_phantom_initializer
             }
         };
         (vtable_field_type, ctor_val)
     } else {
         // Here, we need to construct a reference-to-static type with the vtable typename.
         let vtable_field_type = quote! {
-            &'static #vtable_name
+            &'static #vtable_type
         };
         // The constructor will borrow the static vtable.
         let ctor_val = quote! {
             Self {
-                __thintraitobjectmacro_repr_vtable: &Self::__THINTRAITOBJECTMACRO_VTABLE,
+                __thintraitobjectmacro_repr_vtable: Self::__thintraitobjectmacro_repr_vtable_ref(),
+                #header_initializer
                 __thintraitobjectmacro_repr_value: __thintraitobjectmacro_arg0,
+                #repr_phantom_initializer
             }
         };
         (vtable_field_type, ctor_val)
@@ -64,39 +176,363 @@ pub fn generate_repr(
     } else {
         quote! {}
     };
+    // The header, if any, is laid out right after the vtable, the same way `#[repr(C)]` does it.
+    let header_layout = header.as_ref().map(|header| {
+        quote! {
+            let prefix = match prefix.extend(::core::alloc::Layout::new::<#header>()) {
+                ::core::result::Result::Ok((prefix, _)) => prefix,
+                ::core::result::Result::Err(_) => ::core::hint::unreachable_unchecked(),
+            };
+        }
+    });
+    // Values which take up no space, and thus can be anywhere, don't need to be allocated: the
+    // thin trait object can point to a reference to the vtable or a copy of it in a constant, which
+    // is where a value with no alignment requirements beyond the ones of the vtable field would be
+    // stored right behind. Only done for boxes, since `Arc` and `Rc` store the reference counts in
+    // the allocation, and without a header, which takes up space in it.
+    let stateless = *storage == Storage::Box && header.is_none();
+    let (stateless_items, stateless_create, stateless_check) = if stateless {
+        let slot_val = if inline_vtable {
+            quote! { &Self::__THINTRAITOBJECTMACRO_VTABLE }
+        } else {
+            quote! { &Self::__thintraitobjectmacro_repr_vtable_ref() }
+        };
+        (
+            quote! {
+                const __THINTRAITOBJECTMACRO_STATELESS_SLOT: &'static #vtable_field_type = #slot_val;
+            },
+            quote! {
+                if Self::__THINTRAITOBJECTMACRO_STATELESS {
+                    ::core::mem::forget(__thintraitobjectmacro_arg0);
+                    return Self::__THINTRAITOBJECTMACRO_STATELESS_SLOT as *const #vtable_field_type
+                        as *mut _;
+                }
+            },
+            quote! { || Self::__THINTRAITOBJECTMACRO_STATELESS },
+        )
+    } else {
+        Default::default()
+    };
+    let allow_internal_lints = allow_internal_lints();
+    let (drop_initializer, shared_drop_thunk) = if shared_drop {
+        // `align(N)` raises the alignment of the whole struct, after its fields are laid out.
+        let raise_alignment = match repr_modifier {
+            Some(ReprModifier::Align(align)) => quote! {
+                let layout = match layout.align_to(#align) {
+                    ::core::result::Result::Ok(layout) => layout,
+                    ::core::result::Result::Err(_) => ::core::hint::unreachable_unchecked(),
+                };
+            },
+            _ => quote! {},
+        };
+        let krate = &path_to_box.segments[0];
+        let vtable_ref = if inline_vtable {
+            quote! { &*(__thintraitobjectmacro_arg0 as *const #vtable_type) }
+        } else {
+            quote! { *(__thintraitobjectmacro_arg0 as *const &'static #vtable_type) }
+        };
+        (
+            // Types without drop glue only need to be deallocated, which can be done by a single
+            // function for all of them using the layout stored in the vtable.
+            quote! {
+                #drop_name: if ::core::mem::needs_drop::<__ThinTraitObjectMacro_ReprGeneric0>() #stateless_check {
+                    Self::__thintraitobjectmacro_repr_drop as unsafe #drop_abi fn(*mut ::core::ffi::c_void)
+                } else {
+                    <#vtable_type>::__thintraitobjectmacro_shared_drop
+                        as unsafe #drop_abi fn(*mut ::core::ffi::c_void)
+                },
+            },
+            quote! {
+                #allow_internal_lints
+                impl #params #vtable_type #where_clause {
+                    // Not generic, unlike all other thunks. Mirrors the `#[repr(C)]` layout of the
+                    // representation struct, which is never zero-sized since it contains the
+                    // vtable or a reference to it.
+                    unsafe #drop_abi fn __thintraitobjectmacro_shared_drop(
+                        __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                    ) {
+                        let vtable: &#vtable_type = #vtable_ref;
+                        let prefix = ::core::alloc::Layout::new::<#vtable_field_type>();
+                        #header_layout
+                        let layout = prefix.extend(::core::alloc::Layout::from_size_align_unchecked(
+                            vtable.size,
+                            vtable.align,
+                        ));
+                        let layout = match layout {
+                            ::core::result::Result::Ok((layout, _)) => layout,
+                            ::core::result::Result::Err(_) => ::core::hint::unreachable_unchecked(),
+                        };
+                        #raise_alignment
+                        let layout = layout.pad_to_align();
+                        ::#krate::alloc::dealloc(__thintraitobjectmacro_arg0 as *mut u8, layout);
+                    }
+                }
+            },
+        )
+    } else if extra_entries.contains(&ExtraEntry::Dealloc) {
+        // The regular drop thunk is in the `dealloc` entry instead.
+        (
+            quote! { #drop_name: Self::__thintraitobjectmacro_repr_drop_nothing, },
+            quote! {},
+        )
+    } else {
+        (
+            quote! { #drop_name: Self :: __thintraitobjectmacro_repr_drop, },
+            quote! {},
+        )
+    };
+    let drop_nothing_thunk = if extra_entries.contains(&ExtraEntry::Dealloc) {
+        quote! {
+            unsafe #drop_abi fn __thintraitobjectmacro_repr_drop_nothing(
+                _: *mut ::core::ffi::c_void,
+            ) {}
+        }
+    } else {
+        quote! {}
+    };
+    let extra_bounds = stash.value_bounds();
+    let extra_initializers = extra_entries.iter().map(|entry| entry.vtable_initializer());
+    let extra_thunks = extra_entries
+        .iter()
+        .map(|entry| entry.thunk(drop_abi, *storage, &trait_path, header.is_some()));
+    let drop_body = if *storage == Storage::Buffer {
+        // The buffer is borrowed from the caller, who frees it.
+        quote! {
+            ::core::ptr::drop_in_place(__thintraitobjectmacro_arg0 as *mut #repr_type);
+        }
+    } else if stateless {
+        quote! {
+            if Self::__THINTRAITOBJECTMACRO_STATELESS {
+                ::core::ptr::drop_in_place(::core::ptr::addr_of_mut!(
+                    (*(__thintraitobjectmacro_arg0 as *mut #repr_type)).__thintraitobjectmacro_repr_value
+                ));
+            } else {
+                let _ = #path_to_box::from_raw(__thintraitobjectmacro_arg0 as *mut #repr_type);
+            }
+        }
+    } else {
+        quote! {
+            let _ = #path_to_box::from_raw(__thintraitobjectmacro_arg0 as *mut #repr_type);
+        }
+    };
+    let repr_params = trait_generics.params_with(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0: #trait_path
+    });
+    let repr_impl_params = trait_generics.params_with(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0: #trait_path #(+ #extra_bounds)*
+    });
+    // Moving the value out of the representation struct in the thunks of consuming methods and
+    // `downcast` leaves the header behind without dropping it.
+    let repr_where_clause = trait_generics.where_clause_with(
+        header
+            .as_ref()
+            .map(|header| quote_spanned! {header.span()=> #header: ::core::marker::Copy }),
+    );
+    let phantom_initializer = trait_generics.phantom_type().map(|_| {
+        quote! { __thintraitobjectmacro_phantom: ::core::marker::PhantomData, }
+    });
+    let stateless_val = if stateless {
+        quote! {
+            ::core::mem::size_of::<Self>() == ::core::mem::size_of::<#vtable_field_type>()
+                && ::core::mem::align_of::<Self>() == ::core::mem::align_of::<#vtable_field_type>()
+        }
+    } else {
+        quote! { false }
+    };
+    // Not bound by the extra entries, since `downcast` only knows that the type implements the
+    // trait.
+    let (stateless_const, take_body) = if *storage == Storage::Buffer {
+        (
+            quote! {},
+            quote! { ::core::ptr::read(__thintraitobjectmacro_arg0 as *const Self) },
+        )
+    } else {
+        let take_body = quote! {
+            if Self::__THINTRAITOBJECTMACRO_STATELESS {
+                ::core::ptr::read(__thintraitobjectmacro_arg0 as *const Self)
+            } else {
+                *#path_to_box::from_raw(__thintraitobjectmacro_arg0 as *mut Self)
+            }
+        };
+        (
+            quote! { const __THINTRAITOBJECTMACRO_STATELESS: bool = #stateless_val; },
+            take_body,
+        )
+    };
+    let repr_base_impl = if !storage.is_shared() {
+        quote! {
+            #allow_internal_lints
+            impl #repr_params #repr_type #where_clause {
+                #stateless_const
+
+                // Moves the representation struct out of the pointer, freeing the allocation if
+                // there is one, for the thunks which consume the value.
+                unsafe fn __thintraitobjectmacro_repr_take(
+                    __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
+                ) -> Self {
+                    #take_body
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    // The value is copied from its box straight into the new allocation, rather than being moved
+    // out of it first, which would copy it onto the stack. `Arc` and `Rc` have no stable way of
+    // allocating without initializing, so only boxes get this.
+    let create_from_box = if stash.is_packed() {
+        // Writing the fields in place would need aligned pointers to them, and the value is `Copy`
+        // and thus cheap to move through the stack.
+        quote! {
+            fn __thintraitobjectmacro_repr_create_from_box(
+                __thintraitobjectmacro_arg0: #path_to_box<__ThinTraitObjectMacro_ReprGeneric0>,
+            ) -> *mut #vtable_type {
+                Self::__thintraitobjectmacro_repr_create(*__thintraitobjectmacro_arg0)
+            }
+        }
+    } else if *storage == Storage::Box {
+        let krate = &path_to_box.segments[0];
+        let vtable_val = if inline_vtable {
+            quote! { Self::__THINTRAITOBJECTMACRO_VTABLE }
+        } else {
+            quote! { Self::__thintraitobjectmacro_repr_vtable_ref() }
+        };
+        let header_write = header.as_ref().map(|_| {
+            quote! {
+                ::core::ptr::addr_of_mut!((*repr).__thintraitobjectmacro_repr_header)
+                    .write(__thintraitobjectmacro_arg1);
+            }
+        });
+        let stateless_create = if stateless {
+            quote! {
+                if Self::__THINTRAITOBJECTMACRO_STATELESS {
+                    return Self::__thintraitobjectmacro_repr_create(*__thintraitobjectmacro_arg0);
+                }
+            }
+        } else {
+            quote! {}
+        };
+        quote! {
+            fn __thintraitobjectmacro_repr_create_from_box(
+                __thintraitobjectmacro_arg0: #path_to_box<__ThinTraitObjectMacro_ReprGeneric0>,
+                #header_param
+            ) -> *mut #vtable_type {
+                #stateless_create
+                // Never zero-sized, since it contains the vtable or a reference to it.
+                let layout = ::core::alloc::Layout::new::<Self>();
+                unsafe {
+                    let repr = ::#krate::alloc::alloc(layout) as *mut Self;
+                    if repr.is_null() {
+                        ::#krate::alloc::handle_alloc_error(layout);
+                    }
+                    // The phantom field, if any, is zero-sized and needs no initialization.
+                    ::core::ptr::addr_of_mut!((*repr).__thintraitobjectmacro_repr_vtable)
+                        .write(#vtable_val);
+                    #header_write
+                    let val = #path_to_box::into_raw(__thintraitobjectmacro_arg0);
+                    ::core::ptr::copy_nonoverlapping(
+                        val as *const __ThinTraitObjectMacro_ReprGeneric0,
+                        ::core::ptr::addr_of_mut!((*repr).__thintraitobjectmacro_repr_value),
+                        1,
+                    );
+                    // Frees the old allocation without dropping the value, which was moved out.
+                    let _ = #path_to_box::from_raw(
+                        val as *mut ::core::mem::ManuallyDrop<__ThinTraitObjectMacro_ReprGeneric0>,
+                    );
+                    repr as *mut _
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let create = if *storage == Storage::Buffer {
+        quote! {
+            // Writes the representation struct into the buffer at the first suitably aligned
+            // address, giving the value back if it doesn't fit after that.
+            fn __thintraitobjectmacro_repr_create_in_buffer(
+                __thintraitobjectmacro_buffer: &mut [::core::mem::MaybeUninit<u8>],
+                __thintraitobjectmacro_arg0: __ThinTraitObjectMacro_ReprGeneric0,
+                #header_param
+            ) -> ::core::result::Result<*mut #vtable_type, __ThinTraitObjectMacro_ReprGeneric0> {
+                // Can be `usize::MAX` if the buffer can't be aligned at all.
+                let offset = __thintraitobjectmacro_buffer
+                    .as_ptr()
+                    .align_offset(::core::mem::align_of::<Self>());
+                let fits = offset <= __thintraitobjectmacro_buffer.len()
+                    && __thintraitobjectmacro_buffer.len() - offset >= ::core::mem::size_of::<Self>();
+                if !fits {
+                    return ::core::result::Result::Err(__thintraitobjectmacro_arg0);
+                }
+                unsafe {
+                    let repr = __thintraitobjectmacro_buffer.as_mut_ptr().add(offset) as *mut Self;
+                    repr.write(#ctor_val);
+                    ::core::result::Result::Ok(repr as *mut _)
+                }
+            }
+        }
+    } else {
+        quote! {
+            // The trait object struct wraps the result in a NonNull without checking it, so this must
+            // never return a null pointer, which Box guarantees even for zero-sized types.
+            fn __thintraitobjectmacro_repr_create(
+                __thintraitobjectmacro_arg0: __ThinTraitObjectMacro_ReprGeneric0,
+                #header_param
+            ) -> *mut #vtable_type {
+                #stateless_create
+                #path_to_box::into_raw(#path_to_box::new(#ctor_val)) as *mut _
+            }
+        }
+    };
+    let repr_modifier = repr_modifier.iter();
     // Here comes the cluttered part: heavily prefixed names.
     let repr = quote! {
-        #[repr(C)]
-        struct #repr_name <__ThinTraitObjectMacro_ReprGeneric0: #trait_name> {
+        #allow_internal_lints
+        #[repr(C #(, #repr_modifier)*)]
+        struct #repr_name #repr_params #repr_where_clause {
             __thintraitobjectmacro_repr_vtable: #vtable_field_type,
+            #header_field
             __thintraitobjectmacro_repr_value: __ThinTraitObjectMacro_ReprGeneric0,
+            #repr_phantom_field
         }
-        impl<
-            __ThinTraitObjectMacro_ReprGeneric0: #trait_name
-        > #repr_name<__ThinTraitObjectMacro_ReprGeneric0> {
-            const __THINTRAITOBJECTMACRO_VTABLE: #vtable_name = #vtable_name {
+        // The thunks call the methods of the trait, which may be deprecated.
+        #[allow(deprecated)]
+        #allow_internal_lints
+        impl #repr_impl_params #repr_type #where_clause {
+            const __THINTRAITOBJECTMACRO_VTABLE: #vtable_type = #vtable_name {
                 #size_and_align
                 #vtable_contents
-                drop: Self :: __thintraitobjectmacro_repr_drop,
+                #drop_initializer
+                #(#extra_initializers)*
+                #phantom_initializer
             };
 
-            fn __thintraitobjectmacro_repr_create(
-                __thintraitobjectmacro_arg0: __ThinTraitObjectMacro_ReprGeneric0,
-            ) -> *mut #vtable_name {
-                #path_to_box::into_raw(#path_to_box::new(#ctor_val)) as *mut _
+            // Borrowing the constant in a single place lets all boxed thin trait objects storing the
+            // same type and `vtable_for` share one copy of the vtable, but Rust doesn't guarantee that
+            // promoted constants have a single address, so the compiler may still duplicate it, for
+            // example across codegen units. `vtable_static(...)` gives the vtable a single address.
+            const fn __thintraitobjectmacro_repr_vtable_ref() -> &'static #vtable_type {
+                &Self::__THINTRAITOBJECTMACRO_VTABLE
             }
+
+            #stateless_items
+
+            #create
+            #create_from_box
             // Simple destructor which uses Box's internals to deallocate and
-            // drop the value as necessary.
+            // drop the value as necessary. With Arc or Rc, this only drops
+            // one reference and leaves the rest to the reference count.
             unsafe #drop_abi fn __thintraitobjectmacro_repr_drop(
                 __thintraitobjectmacro_arg0: *mut ::core::ffi::c_void,
             ) {
-                let _ = #path_to_box::from_raw(
-                    __thintraitobjectmacro_arg0
-                        as *mut #repr_name<__ThinTraitObjectMacro_ReprGeneric0>
-                );
+                #drop_body
             }
+            #drop_nothing_thunk
             #thunk_methods
+            #(#extra_thunks)*
         }
+        #repr_base_impl
+        #shared_drop_thunk
     };
     repr
 }
@@ -107,16 +543,55 @@ pub fn repr_name_from_trait_name(trait_name: Ident) -> Ident {
 }
 
 fn generate_vtable_and_thunks(
-    trait_name: &Ident,
-    repr_name: &Ident,
+    trait_path: &TokenStream,
+    repr_type: &TokenStream,
+    path_to_box: &Path,
     vtable_entries: impl IntoIterator<Item = VtableItem>,
     mut double_hop_predicate: impl FnMut(&VtableItem) -> bool,
+    packed: bool,
 ) -> (TokenStream, TokenStream) {
     let mut vtable_contents = TokenStream::new();
     let mut thunk_methods = TokenStream::new();
     for mut entry in vtable_entries {
         let double_hop = double_hop_predicate(&entry);
+        // The initializer and the thunk are emitted as single items, which the attributes are
+        // prepended to.
+        let cfg_attributes = entry.cfg_attributes.clone();
+        let cfg_attributes = quote! { #(#cfg_attributes)* };
 
+        let receiver_kind = entry.receiver_kind();
+        let constant = entry.constant.as_ref().map(|constant| {
+            let trait_path = match &entry.supertrait {
+                Some(path) => split_associated_types(path).0.to_token_stream(),
+                None => trait_path.clone(),
+            };
+            quote! { <__ThinTraitObjectMacro_ReprGeneric0 as #trait_path>::#constant }
+        });
+        // Instantiations of a generic method listed with `mono` call it with their type argument,
+        // and point at it if it doesn't satisfy the bounds of the method.
+        let method = match &entry.mono {
+            Some(mono) => {
+                let (ty, _) = &mono.instances[mono.index];
+                let name = Ident::new(&mono.signature.ident.to_string(), ty.span());
+                quote! { #name::<#ty> }
+            }
+            None => entry.name.to_token_stream(),
+        };
+        let is_async = entry.asyncness.is_some();
+        let unbound_output = entry.borrows_from_receiver();
+        let error_code = entry
+            .error_code_result()
+            .map(|(_, err)| (err, entry.error_code.clone().unwrap()));
+        // Arguments mentioning the lifetime parameters of the trait are passed with those replaced
+        // by `'static`.
+        let transmuted_args = entry
+            .inputs
+            .iter()
+            .map(|input| match input {
+                VtableFnArg::Normal(arg) => entry.mentions_trait_lifetime(&arg.ty),
+                _ => false,
+            })
+            .collect::<Vec<_>>();
         let has_receiver = entry.make_raw();
         if has_receiver {
             entry.make_unsafe();
@@ -124,11 +599,24 @@ fn generate_vtable_and_thunks(
         // Create the list of arguments decorated with the collision-avoiding
         // names. Using mixed-site hygeine could be a better solution.
         let mut argument_counter = 1_u32;
-        let thunk_call_args = entry.inputs.clone().into_iter().skip(1).map(|x| {
-            let arg = to_nth_thunk_arg(x, argument_counter);
-            argument_counter += 1;
-            arg
-        });
+        // The pointer for the success value is not passed on to the method.
+        let method_arg_count = entry.inputs.len() - 1 - error_code.is_some() as usize;
+        let thunk_call_args = entry
+            .inputs
+            .clone()
+            .into_iter()
+            .skip(1)
+            .take(method_arg_count)
+            .zip(transmuted_args.into_iter().skip(1))
+            .map(|(x, transmuted)| {
+                let arg = to_nth_thunk_arg(x, argument_counter).name.unwrap().0;
+                argument_counter += 1;
+                if transmuted {
+                    quote! { ::core::mem::transmute(#arg) }
+                } else {
+                    arg.to_token_stream()
+                }
+            });
 
         if double_hop {
             // Clone this out before handing them over to into_signature().
@@ -143,21 +631,32 @@ fn generate_vtable_and_thunks(
 
             // Remember that this gets called in a loop, so we add one vtable
             // constructor entry for every vtable entry.
+            cfg_attributes.to_tokens(&mut vtable_contents);
             write_vtable_thunk_entry(&name, &thunk_name, &mut vtable_contents);
 
             // Generate the thunks, again, one for every vtable entry. Those are
             // pretty simple, actually: just unsafely convert the pointer to a
             // reference to the repr struct and call the appropriate method,
             // offsetting into the actual value.
+            cfg_attributes.to_tokens(&mut thunk_methods);
             write_thunk(
                 &name,
-                &repr_name,
+                &method,
+                repr_type,
+                path_to_box,
+                receiver_kind,
+                constant,
+                is_async,
+                unbound_output,
+                error_code,
+                packed,
                 thunk_signature,
                 thunk_call_args,
                 &mut thunk_methods,
             );
         } else {
-            write_vtable_single_hop_entry(&entry.name, &trait_name, &mut vtable_contents);
+            cfg_attributes.to_tokens(&mut vtable_contents);
+            write_vtable_single_hop_entry(&entry.name, trait_path, &mut vtable_contents);
         }
     }
     (vtable_contents, thunk_methods)
@@ -169,32 +668,195 @@ fn write_vtable_thunk_entry(name: &Ident, val: &Ident, out: &mut TokenStream) {
     })
     .to_tokens(out);
 }
-fn write_vtable_single_hop_entry(name: &Ident, trait_name: &Ident, out: &mut TokenStream) {
+fn write_vtable_single_hop_entry(name: &Ident, trait_path: &TokenStream, out: &mut TokenStream) {
     (quote! {
-        #name: <__ThinTraitObjectMacro_ReprGeneric0 as #trait_name> :: #name,
+        #name: <__ThinTraitObjectMacro_ReprGeneric0 as #trait_path> :: #name,
     })
     .to_tokens(out);
 }
+#[allow(clippy::too_many_arguments)]
 fn write_thunk(
     name: &Ident,
-    repr_name: &Ident,
+    method: &TokenStream,
+    repr_type: &TokenStream,
+    path_to_box: &Path,
+    receiver_kind: Option<ReceiverKind>,
+    constant: Option<TokenStream>,
+    is_async: bool,
+    unbound_output: bool,
+    error_code: Option<(Type, Path)>,
+    packed: bool,
     signature: Signature,
-    args: impl IntoIterator<Item = BareFnArg>,
+    args: impl IntoIterator<Item = TokenStream>,
     out: &mut TokenStream,
 ) {
-    let args = args.into_iter().map(|arg| arg.name.unwrap().0);
+    let args = args.into_iter();
+    let repr_ptr = quote! {
+        (__thintraitobjectmacro_arg0 as *mut #repr_type)
+    };
+    // The calls are spanned to the name of the method in the trait, which is the location seen by
+    // `#[track_caller]` implementations, since the one of the caller of the thin trait object
+    // method cannot be passed through the vtable.
+    let (prelude, call) = match receiver_kind {
+        // Getters of associated constants only need the stored type, which the thunk is generic
+        // over, and not the value.
+        _ if constant.is_some() => (
+            quote! {
+                let _ = __thintraitobjectmacro_arg0;
+            },
+            constant.to_token_stream(),
+        ),
+        // Moving the whole representation struct out of the box deallocates it without dropping
+        // the value, which is then consumed by the method. The trait object will not call the drop
+        // thunk after this.
+        Some(ReceiverKind::Value) => (
+            quote! {
+                let __thintraitobjectmacro_repr = Self::__thintraitobjectmacro_repr_take(__thintraitobjectmacro_arg0);
+            },
+            quote_spanned! {name.span()=>
+                __thintraitobjectmacro_repr.__thintraitobjectmacro_repr_value.#method(#(#args),*)
+            },
+        ),
+        Some(ReceiverKind::Boxed) => (
+            quote! {
+                let __thintraitobjectmacro_repr = Self::__thintraitobjectmacro_repr_take(__thintraitobjectmacro_arg0);
+            },
+            quote_spanned! {name.span()=>
+                #path_to_box::new(__thintraitobjectmacro_repr.__thintraitobjectmacro_repr_value)
+                    .#method(#(#args),*)
+            },
+        ),
+        // The thin trait object is `!Unpin` if the trait has such methods, so the value is never
+        // moved out of its allocation once a method has been called through a pinned reference.
+        Some(ReceiverKind::Pinned) => (
+            quote! {},
+            quote_spanned! {name.span()=>
+                ::core::pin::Pin::new_unchecked(&mut (*#repr_ptr).__thintraitobjectmacro_repr_value)
+                    .#method(#(#args),*)
+            },
+        ),
+        // The value may be misaligned in a packed representation struct, so the method is called
+        // on an aligned copy of it instead, which is written back if the method could change it.
+        // The value is `Copy`, so neither copy needs to be dropped.
+        Some(ReceiverKind::Shared) if packed => (
+            quote! {
+                let __thintraitobjectmacro_value = ::core::ptr::read_unaligned(::core::ptr::addr_of!(
+                    (*(__thintraitobjectmacro_arg0 as *const #repr_type)).__thintraitobjectmacro_repr_value
+                ));
+            },
+            quote_spanned! {name.span()=>
+                __thintraitobjectmacro_value.#method(#(#args),*)
+            },
+        ),
+        _ if packed => {
+            let call = quote_spanned! {name.span()=>
+                __thintraitobjectmacro_value.#method(#(#args),*)
+            };
+            (
+                quote! {
+                    let __thintraitobjectmacro_ptr = ::core::ptr::addr_of_mut!(
+                        (*#repr_ptr).__thintraitobjectmacro_repr_value
+                    );
+                    let mut __thintraitobjectmacro_value =
+                        ::core::ptr::read_unaligned(__thintraitobjectmacro_ptr);
+                },
+                quote! {{
+                    let __thintraitobjectmacro_result = #call;
+                    ::core::ptr::write_unaligned(
+                        __thintraitobjectmacro_ptr,
+                        __thintraitobjectmacro_value,
+                    );
+                    __thintraitobjectmacro_result
+                }},
+            )
+        }
+        // Going through a `*const` pointer makes sure that only a shared reference to the value is
+        // created for `&self` methods, which may be called while other thin trait objects borrow
+        // the same value.
+        Some(ReceiverKind::Shared) => (
+            quote! {},
+            quote_spanned! {name.span()=>
+                (*(__thintraitobjectmacro_arg0 as *const #repr_type))
+                    .__thintraitobjectmacro_repr_value
+                    .#method(#(#args),*)
+            },
+        ),
+        _ => (
+            quote! {},
+            quote_spanned! {name.span()=>
+                (*#repr_ptr).__thintraitobjectmacro_repr_value.#method(#(#args),*)
+            },
+        ),
+    };
+    let body = if is_async {
+        // The future borrows from the arguments, which the caller keeps alive until it completes.
+        quote! {
+            #prelude
+            ::core::mem::transmute(
+                #path_to_box::pin(#call)
+                    as ::core::pin::Pin<#path_to_box<dyn ::core::future::Future<Output = _> + '_>>
+            )
+        }
+    } else if let Some((err, error_code)) = error_code {
+        let result = if unbound_output {
+            quote! { ::core::mem::transmute(#call) }
+        } else {
+            call
+        };
+        let out = error_code_out_arg();
+        // Zero stands for success, so an error converted to it would make the caller read the
+        // success value, which was never written.
+        quote! {
+            #prelude
+            let __thintraitobjectmacro_result: ::core::result::Result<_, #err> = #result;
+            match __thintraitobjectmacro_result {
+                ::core::result::Result::Ok(val) => {
+                    #out.write(val);
+                    0
+                }
+                ::core::result::Result::Err(error) => {
+                    let code = <#err as #error_code>::into_error_code(error);
+                    ::core::assert_ne!(code, 0, "error code 0 is reserved for success");
+                    code
+                }
+            }
+        }
+    } else if unbound_output {
+        // Only differs from the return type of the trait method in its lifetimes, which the caller
+        // is responsible for.
+        quote! {
+            #prelude
+            ::core::mem::transmute(#call)
+        }
+    } else {
+        quote! {
+            #prelude
+            #call
+        }
+    };
     (quote! {
         #signature {
-            (
-                *(__thintraitobjectmacro_arg0
-                    as *mut #repr_name<__ThinTraitObjectMacro_ReprGeneric0>
-                )
-            ).__thintraitobjectmacro_repr_value.#name(#(#args)*)
+            #body
         }
     })
     .to_tokens(out);
 }
 
+/// Allows the lints which the internals generated by the macro — the representation structs, their thunks and everything else which users never name — could otherwise trigger in downstream crates with strict lint settings, which have no way of fixing them. The names of those items are chosen to avoid collisions rather than to follow naming conventions, and the thunks work with raw pointers in ways which the macro itself checks.
+///
+/// `unsafe_op_in_unsafe_fn` is only known to Rust 1.52 and later, which the minimum supported Rust version of the crate covers.
+pub fn allow_internal_lints() -> TokenStream {
+    quote! {
+        #[allow(
+            non_camel_case_types,
+            non_snake_case,
+            unsafe_op_in_unsafe_fn,
+            clippy::all,
+            clippy::pedantic,
+        )]
+    }
+}
+
 fn nth_arg(n: u32) -> Ident {
     format_ident!("__thintraitobjectmacro_arg{}", n)
 }
@@ -0,0 +1,146 @@
+//! Generates the static storage struct, enabled by the `static_storage` option, which wraps a representation struct that can be constructed in constant expressions and placed in a `static`, as well as the `from_static` constructor of the thin trait object which points to it.
+//!
+//! The representation struct inside uses a separate vtable whose drop thunk does nothing, since the value is not owned by any of the thin trait objects pointing to it.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Abi;
+
+use crate::{
+    attr::StageStash,
+    extra_entries::ExtraEntry,
+    options::OutputAdditions,
+    repr::allow_internal_lints,
+    trait_object::check_attribute,
+};
+
+pub fn generate_static_storage(
+    stash: &mut StageStash,
+    additions: &OutputAdditions,
+    inline_vtable: bool,
+    drop_abi: Option<&Abi>,
+    has_static_bound: bool,
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        vtable_name,
+        repr_name,
+        trait_object_name,
+        vtable_items,
+        extra_entries,
+        storage,
+        drop_name,
+        trait_generics,
+        ..
+    } = &*stash;
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let value_generic = trait_generics.unused_ident("T");
+    let value_repr_type = stash.repr_type(&value_generic);
+    let where_clause = trait_generics.where_clause();
+    let OutputAdditions {
+        attributes,
+        visibility,
+        name,
+    } = additions;
+    attributes.iter().try_for_each(check_attribute)?;
+    // A static can't be mutated, moved out of or deallocated.
+    for item in vtable_items {
+        if let Some(receiver) = item.receiver() {
+            if !item.has_shared_receiver() {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "\
+methods taking `&mut self` or `self` are not supported with static storage, since the value may be \
+stored in an immutable static",
+                ));
+            }
+        }
+    }
+    if extra_entries.contains(&ExtraEntry::TypeId) {
+        return Err(syn::Error::new_spanned(
+            name,
+            "static storage cannot be combined with `type_id = true`, since downcasting allows mutating and moving out of the value",
+        ));
+    }
+    if extra_entries.contains(&ExtraEntry::AsDyn) {
+        return Err(syn::Error::new_spanned(
+            name,
+            "static storage cannot be combined with `as_dyn = true`, since `as_dyn_mut` allows mutating the value",
+        ));
+    }
+    if extra_entries.contains(&ExtraEntry::IntoBoxDyn) {
+        return Err(syn::Error::new_spanned(
+            name,
+            "static storage cannot be combined with `box_dyn = true`, since `into_box_dyn` moves out of the value",
+        ));
+    }
+    if storage.is_shared() {
+        return Err(syn::Error::new_spanned(
+            name,
+            "static storage is not supported with `storage = \"arc\"` or `storage = \"rc\"`",
+        ));
+    }
+
+    let vtable_field_val = if inline_vtable {
+        quote! { <#value_repr_type>::__THINTRAITOBJECTMACRO_STATIC_STORAGE_VTABLE }
+    } else {
+        quote! { &<#value_repr_type>::__THINTRAITOBJECTMACRO_STATIC_STORAGE_VTABLE }
+    };
+    let extra_bounds = stash.value_bounds();
+    let trait_object_generics = if has_static_bound {
+        trait_generics.args()
+    } else {
+        trait_generics.args_with(quote! { 'static })
+    };
+    let repr_params = trait_generics.params_with(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0: #trait_path #(+ #extra_bounds)*
+    });
+    let repr_type = stash.repr_type(&quote! { __ThinTraitObjectMacro_ReprGeneric0 });
+    let def_params = trait_generics.params_with_defaults(quote! { #value_generic: #trait_path });
+    let params = trait_generics.params_with(quote! {
+        #value_generic: #trait_path #(+ #extra_bounds)*
+    });
+    let args = trait_generics.args_with(value_generic.to_token_stream());
+    let trait_params = trait_generics.params();
+    let (_, phantom_initializer) = trait_generics.repr_phantom();
+
+    let allow_internal_lints = allow_internal_lints();
+
+    let result = quote! {
+        #allow_internal_lints
+        impl #repr_params #repr_type #where_clause {
+            const __THINTRAITOBJECTMACRO_STATIC_STORAGE_VTABLE: #vtable_type = #vtable_name {
+                #drop_name: Self::__thintraitobjectmacro_repr_drop_nothing,
+                ..Self::__THINTRAITOBJECTMACRO_VTABLE
+            };
+            unsafe #drop_abi fn __thintraitobjectmacro_repr_drop_nothing(
+                _: *mut ::core::ffi::c_void,
+            ) {}
+        }
+
+        #(#attributes)*
+        #[repr(transparent)]
+        #visibility struct #name #def_params (#value_repr_type) #where_clause;
+        impl #params #name #args #where_clause {
+            /// Wraps a value for it to be pointed to by thin trait objects created with `from_static`. Usable in constant expressions, including initializers of statics.
+            #[inline]
+            pub const fn new(val: #value_generic) -> Self {
+                Self(#repr_name {
+                    __thintraitobjectmacro_repr_vtable: #vtable_field_val,
+                    __thintraitobjectmacro_repr_value: val,
+                    #phantom_initializer
+                })
+            }
+        }
+        impl #trait_params #trait_object_name #trait_object_generics #where_clause {
+            /// Constructs a thin trait object which points to a value in a static, without allocating. Dropping the thin trait object does nothing.
+            #[inline]
+            pub const fn from_static<#value_generic: #trait_path #(+ #extra_bounds)*>(
+                val: &'static #name #args,
+            ) -> Self {
+                unsafe { Self::from_raw(val as *const #name #args as *mut ()) }
+            }
+        }
+    };
+    Ok(result)
+}
@@ -0,0 +1,139 @@
+//! The smart pointer types which can back the thin trait object, selected by the `storage` option.
+
+use proc_macro2::{Ident, Span};
+use syn::{punctuated::Punctuated, LitStr, Path, PathArguments, PathSegment};
+
+use crate::{
+    marker_traits::MarkerTrait,
+    vtable::{ReceiverKind, VtableItem},
+};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// `Box`, the default: the thin trait object uniquely owns the value.
+    Box,
+    /// `Arc`: cloning the thin trait object atomically increments the reference count.
+    Arc,
+    /// `Rc`: same as `Arc`, but with a non-atomic reference count.
+    Rc,
+    /// A buffer provided by the caller: the thin trait object uniquely owns the value, but only borrows the memory it is stored in, and never allocates.
+    Buffer,
+}
+impl Storage {
+    pub fn from_lit(lit: &LitStr) -> syn::Result<Self> {
+        let storage = match lit.value().as_str() {
+            "box" => Self::Box,
+            "arc" => Self::Arc,
+            "rc" => Self::Rc,
+            "buffer" => Self::Buffer,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    "expected `\"box\"`, `\"arc\"`, `\"rc\"` or `\"buffer\"`",
+                ))
+            }
+        };
+        Ok(storage)
+    }
+    /// Returns whether the stored value can be shared between multiple thin trait objects, meaning that it can never be mutated or moved out of.
+    #[inline]
+    pub fn is_shared(self) -> bool {
+        matches!(self, Self::Arc | Self::Rc)
+    }
+    /// The path to the smart pointer type. Whatever it is, its `into_raw` must never return a null pointer, since the trait object struct stores it in a `NonNull` without checking.
+    ///
+    /// Buffer storage uses the one of `Box`, which only ends up in the output for features it rejects.
+    pub fn path(self) -> Path {
+        let mut segments = Punctuated::new();
+        let mut push_segment = |name| {
+            segments.push(PathSegment {
+                ident: Ident::new(name, Span::call_site()),
+                arguments: PathArguments::None,
+            });
+        };
+
+        #[cfg(feature = "std")]
+        push_segment("std");
+        #[cfg(not(feature = "std"))]
+        push_segment("alloc");
+
+        match self {
+            Self::Box | Self::Buffer => {
+                push_segment("boxed");
+                push_segment("Box");
+            }
+            Self::Arc => {
+                push_segment("sync");
+                push_segment("Arc");
+            }
+            Self::Rc => {
+                push_segment("rc");
+                push_segment("Rc");
+            }
+        }
+
+        Path {
+            leading_colon: Some(Default::default()),
+            segments,
+        }
+    }
+    /// Checks that the method can be soundly called through a thin trait object with this kind of storage.
+    pub fn check_item(self, item: &VtableItem) -> syn::Result<()> {
+        if self == Self::Buffer {
+            return Self::check_buffer_item(item);
+        }
+        if !self.is_shared() {
+            return Ok(());
+        }
+        match item.receiver() {
+            Some(receiver) if !item.has_shared_receiver() => Err(syn::Error::new_spanned(
+                receiver,
+                "\
+methods taking `&mut self` or `self` are not supported with shared storage, since the value may be \
+owned by multiple thin trait objects at once",
+            )),
+            _ => Ok(()),
+        }
+    }
+    fn check_buffer_item(item: &VtableItem) -> syn::Result<()> {
+        if let Some(asyncness) = item.asyncness {
+            return Err(syn::Error::new(
+                asyncness.span,
+                "async methods are not supported with buffer storage, since the returned futures are boxed",
+            ));
+        }
+        let error = match item.receiver_kind() {
+            Some(ReceiverKind::Boxed) => {
+                "methods taking `self: Box<Self>` are not supported with buffer storage, which never allocates"
+            }
+            // Forgetting the thin trait object ends the borrow of the buffer without dropping the
+            // value, which would break the drop guarantee of `Pin`.
+            Some(ReceiverKind::Pinned) => {
+                "\
+methods taking `self: Pin<&mut Self>` are not supported with buffer storage, since forgetting the thin \
+trait object would let the buffer be reused without the pinned value being dropped"
+            }
+            _ => return Ok(()),
+        };
+        Err(syn::Error::new_spanned(item.receiver(), error))
+    }
+    /// Checks that the marker traits of the trait can be soundly implemented with this kind of storage.
+    pub fn check_trait(self, markers: &[MarkerTrait], span: Span) -> syn::Result<()> {
+        if !self.is_shared() {
+            return Ok(());
+        }
+        let has_marker = |name| markers.iter().any(|marker| marker.is(name));
+        let (has_send, has_sync) = (has_marker("Send"), has_marker("Sync"));
+        // Mirrors the conditions under which `Arc<T>` and `Rc<T>` implement those.
+        let error = match self {
+            Self::Rc if has_send || has_sync => {
+                "`Rc`-backed thin trait objects can't be `Send` or `Sync`, use `storage = \"arc\"` instead"
+            }
+            Self::Arc if has_send != has_sync => {
+                "`Arc`-backed thin trait objects can only be `Send` and `Sync` together, since clones can be sent to other threads"
+            }
+            _ => return Ok(()),
+        };
+        Err(syn::Error::new(span, error))
+    }
+}
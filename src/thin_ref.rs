@@ -0,0 +1,253 @@
+//! Generates the borrowed thin reference structs, which are to the owned trait object struct what `&dyn Trait` and `&mut dyn Trait` are to `Box<dyn Trait>`.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+use crate::{
+    attr::StageStash,
+    extra_entries::ExtraEntry,
+    marker_traits::MarkerTrait,
+    options::OutputAdditions,
+    trait_object::{
+        check_attribute,
+        dispatch_inline_attribute,
+        dispatch_method,
+        forwarded_attributes,
+        header_getter,
+        is_inline_vtable_method,
+        optimization_hints,
+        same_vtable_impl,
+        trait_impls,
+        vtable_getter_impl,
+    },
+    vtable::{ReceiverKind, VtableItem},
+};
+
+pub fn generate_thin_ref(
+    stash: &mut StageStash,
+    additions: &OutputAdditions,
+    mutable: bool,
+    inline_vtable: bool,
+    has_static_bound: bool,
+    markers: &[MarkerTrait],
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        trait_object_name,
+        vtable_items,
+        extra_entries,
+        supertrait_entries,
+        inline_always,
+        trait_generics,
+        ..
+    } = &*stash;
+    let vtable_type = stash.vtable_type();
+    // The lifetime of the borrow, which only needs a different name if the trait already has a
+    // lifetime parameter called `'a`.
+    let lifetime = trait_generics.unused_lifetime("a");
+    let trait_params = trait_generics.params();
+    let where_clause = trait_generics.where_clause();
+    let elided_args = trait_generics.args_with(quote! { '_ });
+    let OutputAdditions {
+        attributes,
+        visibility,
+        name,
+    } = additions;
+    attributes.iter().try_for_each(check_attribute)?;
+
+    // A shared reference is only Send if the referent is Sync, and only UnwindSafe if the referent
+    // is RefUnwindSafe, so the corresponding marker impls from the trait object can only be copied
+    // if the other marker is there as well. A mutable reference is never UnwindSafe.
+    let has_marker = |name| markers.iter().any(|marker| marker.is(name));
+    let markers_for_ref = markers
+        .iter()
+        .filter(|marker| {
+            if marker.is("Send") && !mutable {
+                has_marker("Sync")
+            } else if marker.is("UnwindSafe") {
+                !mutable && has_marker("RefUnwindSafe")
+            } else {
+                true
+            }
+        })
+        .collect::<Vec<_>>();
+    let can_call = |item: &&VtableItem| match item.receiver_kind() {
+        Some(ReceiverKind::Shared) => true,
+        Some(ReceiverKind::Mutable) => mutable,
+        // Borrowed thin trait objects can't give up ownership.
+        _ => false,
+    };
+    // The trait can only be implemented if all of the methods can be called through the reference,
+    // it has no associated constants and the reference satisfies all the supertraits.
+    let implements_trait = vtable_items.iter().all(|item| can_call(&item))
+        && vtable_items.iter().all(|item| item.constant.is_none())
+        && markers_for_ref.len() == markers.len()
+        && !has_static_bound
+        // Shared references are Copy, mutable ones can't be cloned at all.
+        && !(mutable && supertrait_entries.contains(&ExtraEntry::Clone));
+    let marker_impls = markers_for_ref
+        .iter()
+        .map(|marker| marker.as_impl_for(&quote! { #name #elided_args }, trait_generics));
+    let extra_impls = extra_entries
+        .iter()
+        .filter(|entry| entry.usable_through_ref())
+        .map(|entry| entry.trait_object_impl(&quote! { #name #elided_args }, mutable, stash));
+    let inline = dispatch_inline_attribute(*inline_always);
+    let method_visibility = if implements_trait {
+        quote! {}
+    } else {
+        quote! { pub }
+    };
+    let method = |item: &VtableItem| {
+        let cfg_attributes = &item.cfg_attributes;
+        // Only inherent methods get the documentation of the trait method, as trait
+        // implementations are documented by the trait.
+        let attributes = forwarded_attributes(item).filter(|_| !implements_trait);
+        let hints = optimization_hints(item, &inline);
+        let method = if implements_trait {
+            dispatch_method(item, &item.name)
+        } else {
+            dispatch_method(item, item.inherent_name())
+        };
+        quote! {
+            #(#cfg_attributes)*
+            #(#attributes)*
+            #hints
+            #method_visibility #method
+        }
+    };
+    let methods = if implements_trait {
+        trait_impls(stash, &quote! { #name #elided_args }, method)
+    } else {
+        let methods = vtable_items.iter().filter(can_call).map(method);
+        quote! {
+            #[allow(clippy::ref_in_deref)] // see https://github.com/rust-lang/rust-clippy/issues/6658
+            impl #trait_params #name #elided_args #where_clause {
+                #(#methods)*
+            }
+        }
+    };
+    let vtable_getter_impl = vtable_getter_impl(inline_vtable, stash.is_packed(), &vtable_type);
+    let is_inline_vtable_method = is_inline_vtable_method(inline_vtable);
+    let header_getter = header_getter(stash, inline_vtable, lifetime.to_token_stream());
+    let same_vtable_impl = same_vtable_impl(inline_vtable);
+    let (trait_object_generics, static_trait_object_generics) = if has_static_bound {
+        (trait_generics.args(), trait_generics.args())
+    } else {
+        (
+            trait_generics.args_with(quote! { '_ }),
+            trait_generics.args_with(quote! { 'static }),
+        )
+    };
+    let static_args = trait_generics.args_with(quote! { 'static });
+
+    // A `const fn` returning a type with a mutable reference in it, which the phantom data amounts
+    // to, needs Rust 1.83, so `from_raw` is only `const` for the shared one.
+    let from_raw_constness = if mutable {
+        quote! {}
+    } else {
+        quote! { const }
+    };
+    let (phantomdata, from_raw_safety, as_raw_receiver, kind_specific) = if mutable {
+        (
+            trait_generics.phantom_with(quote! { &#lifetime mut () }),
+            quote! {
+                /// The pointer must satisfy the same requirements as for the `from_raw` constructor of the owned trait object, with the exception that ownership of the pointer is not transferred — the thin trait object must stay valid and must not be accessed through any other pointer for as long as the borrowed thin trait object exists.
+            },
+            quote! { &self },
+            quote! {
+                impl #trait_params #name #elided_args #where_clause {
+                    /// Reborrows the borrowed thin trait object for a shorter lifetime, which is analogous to `&mut *r` for an `r: &mut dyn Trait`. While the result exists, the original one is considered mutably borrowed.
+                    #[inline]
+                    pub fn reborrow(&mut self) -> #name #elided_args {
+                        unsafe { #name::from_raw(self.as_raw()) }
+                    }
+                }
+                impl #trait_params #trait_object_name #trait_object_generics #where_clause {
+                    /// Mutably borrows the trait object as a borrowed thin trait object, which is analogous to `&mut dyn Trait`.
+                    #[inline]
+                    pub fn as_thin_mut(&mut self) -> #name #elided_args {
+                        unsafe { #name::from_raw(self.as_raw()) }
+                    }
+                }
+                // Leaking a value which borrows from something would let it outlive the borrow.
+                impl #trait_params #trait_object_name #static_trait_object_generics #where_clause {
+                    /// Leaks the trait object, returning a mutably borrowed thin trait object which stays valid for the rest of the program. The stored value is never dropped or deallocated.
+                    #[inline]
+                    pub fn leak(self) -> #name #static_args {
+                        unsafe { #name::from_raw(self.into_raw()) }
+                    }
+                }
+            },
+        )
+    } else {
+        (
+            trait_generics.phantom_with(quote! { &#lifetime () }),
+            quote! {
+                /// The pointer must satisfy the same requirements as for the `from_raw` constructor of the owned trait object, with the exception that ownership of the pointer is not transferred — the thin trait object must stay valid and must not be mutated for as long as the borrowed thin trait object exists.
+            },
+            quote! { self },
+            quote! {
+                #[allow(clippy::expl_impl_clone_on_copy)]
+                impl #trait_params ::core::clone::Clone for #name #elided_args #where_clause {
+                    #[inline]
+                    fn clone(&self) -> Self {
+                        *self
+                    }
+                }
+                impl #trait_params ::core::marker::Copy for #name #elided_args #where_clause {}
+                impl #trait_params #trait_object_name #trait_object_generics #where_clause {
+                    /// Borrows the trait object as a borrowed thin trait object, which is analogous to `&dyn Trait`.
+                    #[inline]
+                    pub fn as_thin_ref(&self) -> #name #elided_args {
+                        unsafe { #name::from_raw(self.as_raw()) }
+                    }
+                }
+            },
+        )
+    };
+
+    let def_params = trait_generics.params_with_defaults(lifetime.to_token_stream());
+    let params = trait_generics.params_with(lifetime.to_token_stream());
+    let args = trait_generics.args_with(lifetime.to_token_stream());
+    let result = quote! {
+        #(#attributes)*
+        #[repr(transparent)]
+        #visibility struct #name #def_params (
+            ::core::ptr::NonNull<#vtable_type>,
+            #phantomdata,
+        ) #where_clause;
+        impl #params #name #args #where_clause {
+            /// Creates a borrowed thin trait object directly from a raw pointer to a thin trait object.
+            ///
+            /// # Safety
+            #from_raw_safety
+            #[inline]
+            pub #from_raw_constness unsafe fn from_raw(ptr: *mut ()) -> Self {
+                Self(::core::ptr::NonNull::new_unchecked(ptr as *mut _), ::core::marker::PhantomData)
+            }
+            /// Extracts the contained pointer to the trait object.
+            #[inline]
+            pub const fn as_raw(#as_raw_receiver) -> *mut () {
+                self.0.as_ptr() as *mut ()
+            }
+            /// Retrieves the raw vtable of the referenced trait object.
+            pub fn vtable(&self) -> &#lifetime #vtable_type {
+                #vtable_getter_impl
+            }
+            #is_inline_vtable_method
+            /// Returns `true` if both borrowed thin trait objects use the same vtable, which is a cheap way of checking whether they store values of the same type. Compares the addresses of the vtables, or all of their entries if those are stored inline.
+            ///
+            /// The result is only a hint: the compiler may merge identical functions or vtables of distinct types, making values of those types compare equal, and may also duplicate the vtable of a single type across codegen units. Use `type_id = true` if a reliable check is needed.
+            #[inline]
+            pub fn same_vtable(&self, other: &Self) -> bool {
+                #same_vtable_impl
+            }
+            #header_getter
+        }
+        #methods
+        #kind_specific
+        #(#extra_impls)*
+        #(#marker_impls)*
+    };
+    Ok(result)
+}
@@ -1,131 +1,348 @@
 //! Generates the owned trait object struct. Not to be confused with the representation struct.
 
-use proc_macro2::TokenStream;
-use quote::{format_ident, quote, ToTokens};
-use syn::{punctuated::Punctuated, token, Attribute, FnArg, Path, Visibility};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use std::{iter, mem};
+use syn::{
+    parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token,
+    Attribute,
+    Binding,
+    FnArg,
+    GenericArgument,
+    Path,
+    PathArguments,
+    ReturnType,
+    Type,
+    Visibility,
+};
 
-use crate::{attr::StageStash, marker_traits::MarkerTrait, vtable::VtableItem};
+use crate::{
+    attr::StageStash,
+    extra_entries::ExtraEntry,
+    marker_traits::MarkerTrait,
+    repr::ReprModifier,
+    storage::Storage,
+    vtable::{mentions_ident, ReceiverKind, VtableItem},
+};
 
 pub fn generate_trait_object<'a>(
     stash: &mut StageStash,
     visibility: Visibility,
     inline_vtable: bool,
+    store_layout: bool,
     has_static_bound: bool,
     attributes: impl IntoIterator<Item = &'a Attribute> + Clone,
     markers: impl IntoIterator<Item = &'a MarkerTrait>,
 ) -> syn::Result<TokenStream> {
     let StageStash {
-        trait_name,
-        repr_name,
-        vtable_name,
         trait_object_name,
         vtable_items,
+        extra_entries,
+        storage,
+        inline_always,
+        drop_name,
+        trait_generics,
+        header,
         ..
-    } = stash;
-    let trait_object_name_as_path = trait_object_name.clone().into();
-    #[derive(Copy, Clone)]
-    struct MarkerToImpl<'a> {
-        marker_trait: &'a MarkerTrait,
-        implementor: &'a Path,
-        elided_lifetime: bool,
-    }
-    impl<'a> ToTokens for MarkerToImpl<'a> {
-        fn to_tokens(&self, token_stream: &mut TokenStream) {
-            token_stream.extend((*self).into_token_stream());
-        }
-        fn into_token_stream(self) -> TokenStream {
-            let implementor = self.implementor;
-            let implementor = if self.elided_lifetime {
-                quote! {#implementor <'_>}
-            } else {
-                quote! {implementor} // Performance is my passion
-            };
-            self.marker_trait.as_impl_for(&implementor)
-        }
-    }
-    struct VtableItemToImplThunk(VtableItem);
-    impl ToTokens for VtableItemToImplThunk {
-        fn to_tokens(&self, token_stream: &mut TokenStream) {
-            let signature = self
-                .0
-                .clone()
-                .into_signature(|x| format_ident!("__arg{}", x));
-            let call_args = signature
-                .inputs
-                .clone()
-                .into_iter()
-                .map(|param| match param {
-                    FnArg::Typed(param) => param.pat.into_token_stream(),
-                    FnArg::Receiver(..) => quote! {
-                        self.0.as_ptr() as *mut _
-                    },
-                })
-                .collect::<Punctuated<_, token::Comma>>();
-            let call_name = signature.ident.clone();
-            (quote! {
-                #signature {
-                    unsafe {
-                        ((self.vtable()).#call_name)(#call_args)
-                    }
-                }
-            })
-            .to_tokens(token_stream);
-        }
-    }
-
+    } = &*stash;
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let value_generic = trait_generics.unused_ident("T");
+    let value_repr_type = stash.repr_type(&value_generic);
+    let where_clause = trait_generics.where_clause();
     attributes
         .clone()
         .into_iter()
         .try_for_each(check_attribute)?;
     let attributes = attributes.into_iter();
-    let marker_impls = markers.into_iter().map(|marker_trait| MarkerToImpl {
-        marker_trait,
-        implementor: &trait_object_name_as_path,
-        elided_lifetime: !has_static_bound,
-    });
 
-    let impl_thunks = vtable_items.iter().cloned().map(VtableItemToImplThunk);
-    let (phantomdata, generics, creation_bound, impl_elided_lifetime) = if has_static_bound {
-        let phantomdata = quote! {
-            ::core::marker::PhantomData<&'static ()>
-        };
+    // Getters of associated constants only exist as inherent methods.
+    if let Some(item) = vtable_items.iter().find(|item| {
+        item.constant.is_some() && RESERVED_METHOD_NAMES.iter().any(|name| item.name == name)
+    }) {
+        return Err(syn::Error::new_spanned(
+            &item.constant,
+            format!(
+                "\
+the getter for associated constant `{}` would be named `{}`, which is taken by one of the methods \
+generated on the thin trait object struct",
+                item.constant.as_ref().unwrap(),
+                item.name,
+            ),
+        ));
+    }
+
+    let inline = dispatch_inline_attribute(*inline_always);
+    // Attributes like `#[must_use]` and `#[deprecated]` have no effect in trait implementations, so
+    // they are put onto inherent methods, which take precedence over the trait ones when called.
+    let inherent_methods = vtable_items
+        .iter()
+        .filter(|item| {
+            !RESERVED_METHOD_NAMES
+                .iter()
+                .any(|name| item.inherent_name() == name)
+        })
+        .map(|item| {
+            let attributes = forwarded_attributes(item);
+            let hints = optimization_hints(item, &inline);
+            let cfg_attributes = &item.cfg_attributes;
+            let method = dispatch_method(item, item.inherent_name());
+            quote! {
+                #(#cfg_attributes)*
+                #(#attributes)*
+                #hints
+                pub #method
+            }
+        });
+    // Methods taking `self: Pin<&mut Self>` rely on the value not being moved out once the thin
+    // trait object is pinned, which `Unpin` would allow.
+    let pinned = if vtable_items
+        .iter()
+        .any(|item| item.receiver_kind() == Some(ReceiverKind::Pinned))
+    {
+        quote! { ::core::marker::PhantomPinned }
+    } else {
+        quote! {}
+    };
+    let (phantomdata, lifetime, creation_bound, elided_lifetime) = if has_static_bound {
+        let phantomdata = trait_generics.phantom_with(quote! { (&'static (), #pinned) });
         // Those three are empty, so use the tuple Default impl to write this concisely
-        let (generics, creation_bound, impl_elided_lifetime) = Default::default();
-        (phantomdata, generics, creation_bound, impl_elided_lifetime)
+        let (lifetime, creation_bound, elided_lifetime) = Default::default();
+        (phantomdata, lifetime, creation_bound, elided_lifetime)
     } else {
-        let phantomdata = quote! {
-            ::core::marker::PhantomData<&'inner ()>
-        };
-        let generics = quote! { <'inner> };
+        let phantomdata = trait_generics.phantom_with(quote! { (&'inner (), #pinned) });
+        let lifetime = quote! { 'inner };
         let creation_bound = quote! { 'inner };
-        let impl_elided_lifetime = quote! { <'_> };
-        (phantomdata, generics, creation_bound, impl_elided_lifetime)
+        let elided_lifetime = quote! { '_ };
+        (phantomdata, lifetime, creation_bound, elided_lifetime)
     };
-    let vtable_getter_impl = {
-        let vtable_pointer_cast = if inline_vtable {
-            quote! { as *mut }
+    let trait_params = trait_generics.params();
+    let def_params = trait_generics.params_with_defaults(lifetime.clone());
+    let params = trait_generics.params_with(lifetime.clone());
+    let args = trait_generics.args_with(lifetime);
+    let impl_elided_lifetime = trait_generics.args_with(elided_lifetime);
+    let marker_impls = markers.into_iter().map(|marker_trait| {
+        marker_trait.as_impl_for(
+            &quote! { #trait_object_name #impl_elided_lifetime },
+            trait_generics,
+        )
+    });
+    let trait_impls = trait_impls(
+        stash,
+        &quote! { #trait_object_name #impl_elided_lifetime },
+        |item| {
+            let cfg_attributes = &item.cfg_attributes;
+            let hints = optimization_hints(item, &inline);
+            let method = dispatch_method(item, &item.name);
+            quote! {
+                #(#cfg_attributes)*
+                #hints
+                #method
+            }
+        },
+    );
+    // Can't be checked for a generic trait without picking the generic arguments, but those don't
+    // make a difference to the size of a pointer to the vtable anyway.
+    let size_check = if trait_generics.is_empty() {
+        let static_generics = if has_static_bound {
+            quote! {}
         } else {
-            quote! { as *mut &'static }
+            quote! { <'static> }
         };
         quote! {
-            unsafe { &*(self.0.as_ptr() #vtable_pointer_cast #vtable_name) }
+            // FFI code relies on this, so it is checked rather than assumed.
+            const _: [(); 1] = [(); (
+                ::core::mem::size_of::<#trait_object_name #static_generics>()
+                    == ::core::mem::size_of::<*const ()>()
+            ) as usize];
         }
+    } else {
+        quote! {}
     };
-    let result = quote! {
-        #(#attributes)*
-        #[repr(transparent)]
-        #visibility struct #trait_object_name #generics (
-            ::core::ptr::NonNull<#vtable_name>,
-            #phantomdata,
-        );
-        impl #generics #trait_object_name #generics {
+    let same_vtable_impl = same_vtable_impl(inline_vtable);
+    // With `manual_drop = true`, the `drop` entry does nothing anyway.
+    let drop_impl = if extra_entries.contains(&ExtraEntry::Dealloc) {
+        quote! {}
+    } else {
+        quote! {
+            impl #trait_params ::core::ops::Drop for #trait_object_name #impl_elided_lifetime #where_clause {
+                fn drop(&mut self) {
+                    unsafe { (self.vtable().#drop_name)(self.0.as_ptr() as *mut ::core::ffi::c_void) }
+                }
+            }
+        }
+    };
+    let box_dyn_support = if extra_entries.contains(&ExtraEntry::IntoBoxDyn) {
+        generate_box_dyn_support(stash, has_static_bound)
+    } else {
+        quote! {}
+    };
+    let extra_bounds = stash.value_bounds();
+    let buffer_lifetime = if has_static_bound {
+        quote! { 'static }
+    } else {
+        quote! { 'inner }
+    };
+    let constructor = match header {
+        _ if *storage == Storage::Buffer => {
+            buffer_constructor(stash, &buffer_lifetime, &extra_bounds, &creation_bound)
+        }
+        Some(header) => quote! {
+            /// Constructs a boxed thin trait object from a type implementing the trait, storing the given header in front of the value.
+            #[inline]
+            pub fn new<
+                #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+                >(header: #header, val: #value_generic) -> Self {
+                    unsafe {
+                        Self::from_raw(<#value_repr_type>::__thintraitobjectmacro_repr_create(val, header) as *mut _)
+                    }
+            }
+        },
+        None => quote! {
             /// Constructs a boxed thin trait object from a type implementing the trait.
             #[inline]
             pub fn new<
-                T: #trait_name + Sized + #creation_bound
-                >(val: T) -> Self {
-                    unsafe { Self::from_raw(#repr_name::__thintraitobjectmacro_repr_create(val) as *mut _) }
+                #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+                >(val: #value_generic) -> Self {
+                    unsafe {
+                        Self::from_raw(<#value_repr_type>::__thintraitobjectmacro_repr_create(val) as *mut _)
+                    }
+            }
+        },
+    };
+    // `Arc` and `Rc` can't allocate without initializing the contents on stable.
+    let path_to_box = storage.path();
+    let from_box = match header {
+        _ if *storage != Storage::Box => quote! {},
+        Some(header) => quote! {
+            /// Constructs a boxed thin trait object from a boxed value of a type implementing the trait, storing the given header in front of the value. The value is copied from its box into a new allocation, which is then freed, without being moved onto the stack in between.
+            #[inline]
+            pub fn from_box<
+                #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+                >(header: #header, val: #path_to_box<#value_generic>) -> Self {
+                    unsafe {
+                        Self::from_raw(<#value_repr_type>::__thintraitobjectmacro_repr_create_from_box(val, header) as *mut _)
+                    }
+            }
+        },
+        None => quote! {
+            /// Constructs a boxed thin trait object from a boxed value of a type implementing the trait. The value is copied from its box into a new allocation, which is then freed, without being moved onto the stack in between.
+            #[inline]
+            pub fn from_box<
+                #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+                >(val: #path_to_box<#value_generic>) -> Self {
+                    unsafe {
+                        Self::from_raw(<#value_repr_type>::__thintraitobjectmacro_repr_create_from_box(val) as *mut _)
+                    }
+            }
+        },
+    };
+    let header_getter = header_getter(stash, inline_vtable, quote! {});
+    let vtable_getter_impl = vtable_getter_impl(inline_vtable, stash.is_packed(), &vtable_type);
+    let is_inline_vtable_method = is_inline_vtable_method(inline_vtable);
+    let layout_getters = if store_layout {
+        quote! {
+            /// Returns the size of the stored value in bytes, as stored in the vtable.
+            #[inline]
+            pub fn value_size(&self) -> usize {
+                self.vtable().size
+            }
+            /// Returns the alignment of the stored value in bytes, as stored in the vtable.
+            #[inline]
+            pub fn value_align(&self) -> usize {
+                self.vtable().align
+            }
+        }
+    } else {
+        quote! {}
+    };
+    // Swapping in place needs the layout of the value, and the allocations to be owned by the thin
+    // trait objects: static storage is immutable, and shared storage would change the values seen
+    // through the other references.
+    let swap_method = if store_layout && !storage.is_shared() && !stash.static_storage {
+        let vtable_field_type = if inline_vtable {
+            vtable_type.clone()
+        } else {
+            quote! { &'static #vtable_type }
+        };
+        let same_type = if extra_entries.contains(&ExtraEntry::TypeId) {
+            let field_name = ExtraEntry::TypeId.field_name();
+            quote! { (self.vtable().#field_name)() == (other.vtable().#field_name)() }
+        } else {
+            same_vtable_impl.clone()
+        };
+        let header_layout = header.as_ref().map(|header| {
+            quote! {
+                let prefix = match prefix.extend(::core::alloc::Layout::new::<#header>()) {
+                    ::core::result::Result::Ok((prefix, _)) => prefix,
+                    ::core::result::Result::Err(_) => ::core::hint::unreachable_unchecked(),
+                };
+            }
+        });
+        let value_align = match &stash.repr_modifier {
+            Some(ReprModifier::Packed(None)) => quote! { 1 },
+            Some(ReprModifier::Packed(Some(pack))) => {
+                quote! { ::core::cmp::min(vtable.align, #pack) }
+            }
+            _ => quote! { vtable.align },
+        };
+        quote! {
+            /// Swaps the values stored in two thin trait objects, along with their headers. If both store values of the same type, the values are swapped inside of the allocations, which then stay with their thin trait objects, and otherwise the pointers to the allocations are exchanged, like [`mem::swap`] does. Either way, nothing is reallocated.
+            ///
+            /// [`mem::swap`]: https://doc.rust-lang.org/std/mem/fn.swap.html " "
+            #[inline]
+            pub fn swap(&mut self, other: &mut Self) {
+                if !(#same_type) {
+                    return ::core::mem::swap(&mut self.0, &mut other.0);
+                }
+                let vtable = self.vtable();
+                // Mirrors the `#[repr(C)]` layout of the representation struct. Everything between
+                // the vtable and the end of the value is swapped, padding included.
+                unsafe {
+                    let start = ::core::mem::size_of::<#vtable_field_type>();
+                    let prefix = ::core::alloc::Layout::new::<#vtable_field_type>();
+                    #header_layout
+                    let offset = match prefix.extend(::core::alloc::Layout::from_size_align_unchecked(
+                        vtable.size,
+                        #value_align,
+                    )) {
+                        ::core::result::Result::Ok((_, offset)) => offset,
+                        ::core::result::Result::Err(_) => ::core::hint::unreachable_unchecked(),
+                    };
+                    ::core::ptr::swap_nonoverlapping(
+                        (self.as_raw() as *mut ::core::mem::MaybeUninit<u8>).add(start),
+                        (other.as_raw() as *mut ::core::mem::MaybeUninit<u8>).add(start),
+                        offset + vtable.size - start,
+                    );
+                }
             }
+        }
+    } else {
+        quote! {}
+    };
+    let extra_impls = extra_entries.iter().map(|entry| {
+        entry.trait_object_impl(
+            &quote! { #trait_object_name #impl_elided_lifetime },
+            !storage.is_shared(),
+            stash,
+        )
+    });
+    let tagged_downcast =
+        tagged_downcast(stash, &quote! { #trait_object_name #impl_elided_lifetime });
+    let result = quote! {
+        #(#attributes)*
+        #[repr(transparent)]
+        #visibility struct #trait_object_name #def_params (
+            ::core::ptr::NonNull<#vtable_type>,
+            #phantomdata,
+        ) #where_clause;
+        #size_check
+        impl #params #trait_object_name #args #where_clause {
+            #constructor
+            #from_box
             /// Creates a thin trait object directly from a raw pointer to its vtable.
             ///
             /// # Safety
@@ -157,8 +374,29 @@ pub fn generate_trait_object<'a>(
             pub const fn as_raw(&self) -> *mut () {
                 self.0.as_ptr() as *mut ()
             }
+            /// Returns the contained pointer to the trait object as an opaque pointer, for passing the thin trait object to C functions which only borrow it, without releasing ownership like [`into_raw`] does.
+            ///
+            /// The pointer is only valid for as long as the thin trait object is alive, and must not be freed by whoever it is passed to, since the thin trait object still drops the value and deallocates it when it is dropped itself. Use [`as_mut_ptr`] for callees which call methods taking `&mut self` through it.
+            ///
+            /// [`into_raw`]: #method.into_raw " "
+            /// [`as_mut_ptr`]: #method.as_mut_ptr " "
+            #[inline]
+            pub const fn as_ptr(&self) -> *const ::core::ffi::c_void {
+                self.as_raw() as *const ::core::ffi::c_void
+            }
+            /// Returns the contained pointer to the trait object as an opaque pointer which may be used to mutate the value, for passing the thin trait object to C functions which only borrow it, without releasing ownership like [`into_raw`] does.
+            ///
+            /// The pointer is only valid for as long as the thin trait object is alive, and must not be freed by whoever it is passed to, since the thin trait object still drops the value and deallocates it when it is dropped itself.
+            ///
+            /// [`into_raw`]: #method.into_raw " "
+            #[inline]
+            pub fn as_mut_ptr(&mut self) -> *mut ::core::ffi::c_void {
+                self.as_raw() as *mut ::core::ffi::c_void
+            }
             /// Releases ownership of the trait object, returning the contained pointer. It is the caller's responsibility to drop the trait object at a later time using [`from_raw`].
             ///
+            /// The stored value is not dropped and stays in its allocation, and since the vtable pointer is stored there too, the pointer can be passed through FFI as an opaque `*mut c_void` and turned back into a thin trait object of the same type with [`from_raw`], which will then deallocate it using the `drop` vtable entry.
+            ///
             /// For a version which does not release ownership, see [`as_raw`].
             ///
             /// [`from_raw`]: #method.from_raw " "
@@ -170,25 +408,734 @@ pub fn generate_trait_object<'a>(
                 pointer
             }
             /// Retrieves the raw vtable of the contained trait object.
-            pub fn vtable(&self) -> &#vtable_name {
+            pub fn vtable(&self) -> &#vtable_type {
                 #vtable_getter_impl
             }
+            #is_inline_vtable_method
+            /// Returns `true` if both thin trait objects use the same vtable, which is a cheap way of checking whether they store values of the same type. Compares the addresses of the vtables, or all of their entries if those are stored inline.
+            ///
+            /// The result is only a hint: the compiler may merge identical functions or vtables of distinct types, making values of those types compare equal, and may also duplicate the vtable of a single type across codegen units. Use `type_id = true` if a reliable check is needed.
+            #[inline]
+            pub fn same_vtable(&self, other: &Self) -> bool {
+                #same_vtable_impl
+            }
+            /// Returns the vtable which thin trait objects storing a value of type `T` constructed with `new` use, for embedding it in other structures. When called at runtime, the reference is the same one those thin trait objects store, unless the compiler duplicates the vtable across codegen units; constant evaluation makes a copy of the vtable instead. Use `vtable_static(...)` for a vtable with a single address.
+            #[inline]
+            pub const fn vtable_for<
+                #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+                >() -> &'static #vtable_type {
+                <#value_repr_type>::__thintraitobjectmacro_repr_vtable_ref()
+            }
+            #layout_getters
+            #swap_method
+            #header_getter
         }
         #[allow(clippy::ref_in_deref)] // see https://github.com/rust-lang/rust-clippy/issues/6658
-        impl #trait_name for #trait_object_name #impl_elided_lifetime {
-            #(#impl_thunks)*
-        }
-        impl ::core::ops::Drop for #trait_object_name #impl_elided_lifetime {
-            fn drop(&mut self) {
-                unsafe { (self.vtable().drop)(self.0.as_ptr() as *mut ::core::ffi::c_void) }
-            }
+        impl #params #trait_object_name #args #where_clause {
+            #(#inherent_methods)*
         }
+        #trait_impls
+        #box_dyn_support
+        #drop_impl
         #(#marker_impls)*
+        #(#extra_impls)*
+        #tagged_downcast
     };
     Ok(result)
 }
 
-fn check_attribute(attribute: &Attribute) -> syn::Result<()> {
+/// Generates the `Default` implementation of the boxed thin trait object for `default = ...`, which stores the default value of the given type.
+pub fn generate_default_impl(stash: &StageStash, ty: &Type, has_static_bound: bool) -> TokenStream {
+    let StageStash {
+        trait_object_name,
+        trait_generics,
+        header,
+        ..
+    } = stash;
+    let trait_params = trait_generics.params();
+    let where_clause = trait_generics.where_clause();
+    let elided_lifetime = if has_static_bound {
+        quote! {}
+    } else {
+        quote! { '_ }
+    };
+    let impl_elided_lifetime = trait_generics.args_with(elided_lifetime);
+    let header_arg = header
+        .as_ref()
+        .map(|_| quote! { ::core::default::Default::default(), });
+    // Spanned at the type, so that the error for it not implementing the trait or `Default` points
+    // at the option rather than at the whole attribute.
+    let constructor = quote_spanned! {ty.span()=>
+        Self::new(#header_arg <#ty as ::core::default::Default>::default())
+    };
+    quote! {
+        impl #trait_params ::core::default::Default for #trait_object_name #impl_elided_lifetime #where_clause {
+            #[inline]
+            fn default() -> Self {
+                #constructor
+            }
+        }
+    }
+}
+
+/// Generates the constructors of the thin trait object for `storage = "buffer"`, which take the place of `new` and `from_box`.
+fn buffer_constructor(
+    stash: &StageStash,
+    buffer_lifetime: &TokenStream,
+    extra_bounds: &[TokenStream],
+    creation_bound: &TokenStream,
+) -> TokenStream {
+    let StageStash {
+        trait_generics,
+        header,
+        ..
+    } = stash;
+    let trait_path = stash.trait_path();
+    let value_generic = trait_generics.unused_ident("T");
+    let value_repr_type = stash.repr_type(&value_generic);
+    let (header_param, header_arg, header_doc) = match header {
+        Some(header) => (
+            quote! { header: #header, },
+            quote! { header },
+            ", storing the given header in front of the value",
+        ),
+        None => (quote! {}, quote! {}, ""),
+    };
+    let new_doc = format!(
+        "Constructs a thin trait object from a type implementing the trait by moving the value into the given buffer{}, without allocating. The value is placed at the first address in the buffer which is suitably aligned for it, and is given back if the rest of the buffer is too small to hold it, which never happens if the buffer has at least `buffer_size_for::<T>()` bytes.\n\nDropping the thin trait object drops the value, but leaves the buffer to the caller, who can reuse it once the thin trait object is gone.",
+        header_doc,
+    );
+    quote! {
+        #[doc = #new_doc]
+        #[inline]
+        pub fn new_in_buffer<
+            #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+            >(
+                #header_param
+                buffer: &#buffer_lifetime mut [::core::mem::MaybeUninit<u8>],
+                val: #value_generic,
+            ) -> ::core::result::Result<Self, #value_generic> {
+                let ptr = <#value_repr_type>::__thintraitobjectmacro_repr_create_in_buffer(buffer, val, #header_arg)?;
+                unsafe { ::core::result::Result::Ok(Self::from_raw(ptr as *mut _)) }
+        }
+        /// Returns the size in bytes of a buffer which can hold a value of type `T` with `new_in_buffer` regardless of where it is aligned, which includes the vtable pointer stored alongside the value and the padding needed to align it.
+        #[inline]
+        pub const fn buffer_size_for<
+            #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+            >() -> usize {
+            ::core::mem::size_of::<#value_repr_type>() + ::core::mem::align_of::<#value_repr_type>() - 1
+        }
+    }
+}
+
+/// Generates the conversions between the thin trait object and `Box<dyn Trait>`, enabled by the `box_dyn` option, along with the implementation of the trait for `Box<dyn Trait>` which lets the latter be stored in the former.
+fn generate_box_dyn_support(stash: &StageStash, has_static_bound: bool) -> TokenStream {
+    let StageStash {
+        trait_object_name,
+        extra_entries,
+        storage,
+        inline_always,
+        trait_generics,
+        ..
+    } = stash;
+    let trait_path = stash.trait_path();
+    let path_to_box = storage.path();
+    let field_name = ExtraEntry::IntoBoxDyn.field_name();
+    let where_clause = trait_generics.where_clause();
+    let (params, args, dyn_bound, stored_dyn_bound) = if has_static_bound {
+        (
+            trait_generics.params(),
+            trait_generics.args(),
+            quote! {},
+            quote! {},
+        )
+    } else {
+        // Downcasting requires the stored value to be `'static`.
+        let stored_dyn_bound = if extra_entries.contains(&ExtraEntry::TypeId) {
+            quote! { + 'static }
+        } else {
+            quote! { + 'inner }
+        };
+        (
+            trait_generics.params_with(quote! { 'inner }),
+            trait_generics.args_with(quote! { 'inner }),
+            quote! { + 'inner },
+            stored_dyn_bound,
+        )
+    };
+    let elided_dyn_bound = if has_static_bound {
+        quote! {}
+    } else {
+        quote! { + '_ }
+    };
+    let inline = dispatch_inline_attribute(*inline_always);
+    // Those call the method of the boxed trait object, which would otherwise be the method of the
+    // trait implementation itself.
+    let forwarding_impls = trait_impls(
+        stash,
+        &quote! { #path_to_box<dyn #trait_path #elided_dyn_bound> },
+        |item| {
+            let receiver_kind = item.receiver_kind();
+            let signature = item.clone().into_signature(|x| format_ident!("__arg{}", x));
+            let receiver = match receiver_kind {
+                Some(ReceiverKind::Shared) => quote! { &**self },
+                Some(ReceiverKind::Mutable) => quote! { &mut **self },
+                _ => quote! { *self },
+            };
+            let args = signature.inputs.iter().skip(1).map(|input| match input {
+                FnArg::Typed(input) => input.pat.to_token_stream(),
+                FnArg::Receiver(..) => unreachable!(),
+            });
+            let name = &signature.ident;
+            let item_path = match &item.supertrait {
+                Some(path) => split_associated_types(path).0.to_token_stream(),
+                None => stash.trait_impl_path(),
+            };
+            let cfg_attributes = &item.cfg_attributes;
+            quote! {
+                #(#cfg_attributes)*
+                #inline
+                #signature {
+                    <dyn #trait_path #elided_dyn_bound as #item_path>::#name(#receiver, #(#args),*)
+                }
+            }
+        },
+    );
+    quote! {
+        impl #params #trait_object_name #args #where_clause {
+            /// Converts the thin trait object into a regular boxed trait object. The stored value is moved into a new allocation, and the one it was in is freed.
+            #[inline]
+            pub fn into_box_dyn(self) -> #path_to_box<dyn #trait_path #dyn_bound> {
+                let into_box_dyn = self.vtable().#field_name;
+                unsafe {
+                    let ptr: *mut (dyn #trait_path #dyn_bound) = into_box_dyn(self.into_raw() as *mut _);
+                    #path_to_box::from_raw(ptr)
+                }
+            }
+            /// Constructs a thin trait object storing a regular boxed trait object, which is moved into a new allocation along with the vtable without being unboxed.
+            #[inline]
+            pub fn from_box_dyn(boxed: #path_to_box<dyn #trait_path #stored_dyn_bound>) -> Self {
+                Self::new(boxed)
+            }
+        }
+        #forwarding_impls
+    }
+}
+
+/// Names of the inherent methods which the macro may generate on the thin trait object struct. Trait methods with those names are only available through the trait implementation.
+pub const RESERVED_METHOD_NAMES: &[&str] = &[
+    "new",
+    "from_box",
+    "new_in_buffer",
+    "buffer_size_for",
+    "from_parts",
+    "from_raw",
+    "as_raw",
+    "as_ptr",
+    "as_mut_ptr",
+    "into_raw",
+    "vtable",
+    "is_inline_vtable",
+    "same_vtable",
+    "swap",
+    "vtable_for",
+    "value_size",
+    "value_align",
+    "as_thin_ref",
+    "as_thin_mut",
+    "leak",
+    "is",
+    "downcast_ref",
+    "downcast_mut",
+    "downcast",
+    "downcast_ref_tagged",
+    "with_ref",
+    "with_mut",
+    "replace",
+    "value_type_name",
+    "as_dyn",
+    "as_dyn_mut",
+    "into_box_dyn",
+    "from_box_dyn",
+    "try_new",
+    "new_in",
+    "new_with_vtable",
+    "try_new_in",
+    "from_static",
+    "dealloc",
+];
+
+/// Generates a method with the given name which calls the corresponding vtable entry, for use in `impl` blocks of structs which have `as_raw()` and `vtable()` methods. Methods which consume `self` additionally require an `into_raw` method.
+pub fn dispatch_method(item: &VtableItem, name: &Ident) -> TokenStream {
+    let receiver_kind = item.receiver_kind();
+    let mut signature = item.clone().into_signature(|x| format_ident!("__arg{}", x));
+    let call_args = signature
+        .inputs
+        .iter_mut()
+        .enumerate()
+        .map(|(index, param)| match param {
+            // `self` always comes first.
+            FnArg::Typed(..) if index == 0 && receiver_kind == Some(ReceiverKind::Boxed) => {
+                quote! { __thintraitobjectmacro_ptr }
+            }
+            FnArg::Typed(..) if index == 0 && receiver_kind == Some(ReceiverKind::Pinned) => {
+                quote! { self.as_raw() as *mut _ }
+            }
+            // The vtable entry takes those with the lifetime parameters of the trait replaced by
+            // `'static`.
+            FnArg::Typed(param) if item.mentions_trait_lifetime(&param.ty) => {
+                let pat = &param.pat;
+                quote! { ::core::mem::transmute(#pat) }
+            }
+            FnArg::Typed(param) => param.pat.to_token_stream(),
+            FnArg::Receiver(receiver) => {
+                if receiver.reference.is_none() {
+                    // `mut self` would be an unused `mut` here.
+                    receiver.mutability = None;
+                    quote! { __thintraitobjectmacro_ptr }
+                } else {
+                    quote! { self.as_raw() as *mut _ }
+                }
+            }
+        })
+        .collect::<Punctuated<_, token::Comma>>();
+    let call_name = mem::replace(&mut signature.ident, name.clone());
+    // Vtable entries returning an error code write the success value through an extra pointer,
+    // and the `Result` is put back together from the two.
+    let call = |function: TokenStream| match item.error_code_result() {
+        Some((_, err)) => {
+            let error_code = &item.error_code;
+            quote! {{
+                let mut __thintraitobjectmacro_out = ::core::mem::MaybeUninit::uninit();
+                let __thintraitobjectmacro_code = (#function)(
+                    #call_args,
+                    __thintraitobjectmacro_out.as_mut_ptr(),
+                );
+                if __thintraitobjectmacro_code == 0 {
+                    ::core::result::Result::Ok(__thintraitobjectmacro_out.assume_init())
+                } else {
+                    ::core::result::Result::Err(
+                        <#err as #error_code>::from_error_code(__thintraitobjectmacro_code),
+                    )
+                }
+            }}
+        }
+        None => quote! { (#function)(#call_args) },
+    };
+    // The vtable entry returns references borrowed from `self` and types mentioning the lifetime
+    // parameters of the trait with the `'static` lifetime, which may be in an invariant position.
+    let call = |function: TokenStream| {
+        let call = call(function);
+        if item.asyncness.is_none() && item.borrows_from_receiver() {
+            quote! { ::core::mem::transmute(#call) }
+        } else {
+            call
+        }
+    };
+    let mut body = match receiver_kind {
+        // Take the function pointer out of the vtable before giving up ownership, which makes sure
+        // that the drop thunk doesn't run after the method consumes the value.
+        Some(ReceiverKind::Value) => {
+            let call = call(quote! { __thintraitobjectmacro_fn });
+            quote! {
+                let __thintraitobjectmacro_fn = self.vtable().#call_name;
+                let __thintraitobjectmacro_ptr = self.into_raw() as *mut _;
+                #call
+            }
+        }
+        Some(ReceiverKind::Boxed) => {
+            let call = call(quote! { __thintraitobjectmacro_fn });
+            quote! {
+                let __thintraitobjectmacro_this = *self;
+                let __thintraitobjectmacro_fn = __thintraitobjectmacro_this.vtable().#call_name;
+                let __thintraitobjectmacro_ptr = __thintraitobjectmacro_this.into_raw() as *mut _;
+                #call
+            }
+        }
+        _ => call(quote! { (self.vtable()).#call_name }),
+    };
+    if signature.asyncness.is_some() {
+        // The boxed future gets back the lifetimes of the arguments it borrows from, which the
+        // future of this method holds on to until it completes.
+        let output = match &signature.output {
+            ReturnType::Default => quote! { () },
+            ReturnType::Type(_, ty) => ty.to_token_stream(),
+        };
+        let path_to_box = Storage::Box.path();
+        body = quote! {
+            let __thintraitobjectmacro_future: ::core::pin::Pin<
+                #path_to_box<dyn ::core::future::Future<Output = #output> + '_>
+            > = ::core::mem::transmute({ #body });
+            __thintraitobjectmacro_future.await
+        };
+    }
+    quote! {
+        #signature {
+            unsafe {
+                #body
+            }
+        }
+    }
+}
+
+/// Splits the associated types specified by the path to a flattened supertrait off of it, as in `Iterator<Item = u8>`, which can only be used as a bound, into the `type` items of an implementation of the supertrait.
+pub fn split_associated_types(path: &Path) -> (Path, Vec<TokenStream>) {
+    let mut path = path.clone();
+    let mut associated_types = Vec::new();
+    let segment = match path.segments.last_mut() {
+        Some(segment) => segment,
+        None => return (path, associated_types),
+    };
+    if let PathArguments::AngleBracketed(arguments) = &mut segment.arguments {
+        let (bindings, others) = mem::take(&mut arguments.args)
+            .into_iter()
+            .partition::<Vec<_>, _>(|argument| matches!(argument, GenericArgument::Binding(..)));
+        for binding in bindings {
+            if let GenericArgument::Binding(Binding { ident, ty, .. }) = binding {
+                associated_types.push(quote! { type #ident = #ty; });
+            }
+        }
+        arguments.args = others.into_iter().collect();
+        if arguments.args.is_empty() {
+            segment.arguments = PathArguments::None;
+        }
+    }
+    (path, associated_types)
+}
+
+/// Generates implementations of the trait and of the supertraits flattened into the vtable for the given implementor, putting the method generated for every vtable item into the implementation of the trait which declares it.
+pub fn trait_impls(
+    stash: &StageStash,
+    implementor: &TokenStream,
+    mut method: impl FnMut(&VtableItem) -> TokenStream,
+) -> TokenStream {
+    let StageStash {
+        vtable_items,
+        flattened_supertraits,
+        trait_generics,
+        trait_unsafety,
+        blanket_impl,
+        ..
+    } = stash;
+    let params = trait_generics.params();
+    let where_clause = trait_generics.where_clause();
+    let trait_paths = flattened_supertraits
+        .iter()
+        .map(Some)
+        .chain(iter::once(None));
+    let mut token_stream = TokenStream::new();
+    for trait_path in trait_paths {
+        let items = vtable_items
+            .iter()
+            .filter(|item| item.supertrait.as_ref() == trait_path)
+            .collect::<Vec<_>>();
+        // The values of associated constants depend on the stored type, so they can't be given in
+        // an implementation of the trait. The getters are inherent methods instead.
+        if items.iter().any(|item| item.constant.is_some()) {
+            continue;
+        }
+        // The blanket implementation covers the trait itself, as long as the supertraits are
+        // implemented.
+        if trait_path.is_none() && *blanket_impl {
+            continue;
+        }
+        let (mono_items, items) = items
+            .into_iter()
+            .partition::<Vec<_>, _>(|item| item.mono.is_some());
+        let mut methods = items.into_iter().map(&mut method).collect::<Vec<_>>();
+        methods.extend(
+            mono_items
+                .iter()
+                .filter(|item| item.mono.as_ref().unwrap().index == 0)
+                .map(|item| mono_dispatch_method(item)),
+        );
+        if !mono_items.is_empty() {
+            let helpers = mono_items.iter().map(|item| {
+                let cfg_attributes = &item.cfg_attributes;
+                let method = dispatch_method(item, &mono_helper_name(&item.name));
+                quote! {
+                    #(#cfg_attributes)*
+                    #[inline]
+                    #method
+                }
+            });
+            (quote! {
+                #[allow(clippy::ref_in_deref)] // see https://github.com/rust-lang/rust-clippy/issues/6658
+                impl #params #implementor #where_clause {
+                    #(#helpers)*
+                }
+            })
+            .to_tokens(&mut token_stream);
+        }
+        // The implementations only forward to the one of the stored value, which upholds the
+        // contract of an unsafe trait.
+        let (trait_path, associated_types, unsafety) = match trait_path {
+            Some(path) => {
+                let (path, associated_types) = split_associated_types(path);
+                (path.to_token_stream(), associated_types, None)
+            }
+            None => (
+                stash.trait_impl_path(),
+                vec![trait_generics.associated_type_items()],
+                trait_unsafety.as_ref(),
+            ),
+        };
+        (quote! {
+            #[allow(clippy::ref_in_deref)] // see https://github.com/rust-lang/rust-clippy/issues/6658
+            #unsafety impl #params #trait_path for #implementor #where_clause {
+                #(#associated_types)*
+                #(#methods)*
+            }
+        })
+        .to_tokens(&mut token_stream);
+    }
+    token_stream
+}
+
+/// The name of the method calling the vtable entry of an instantiation of a generic method listed with `#[thin(mono(...))]`, which the implementation of the generic method calls.
+fn mono_helper_name(name: &Ident) -> Ident {
+    format_ident!("__thintraitobjectmacro_mono_{}", name)
+}
+/// Generates the implementation of a generic method listed with `#[thin(mono(...))]`, which calls the vtable entry of the instantiation whose type argument has the same `TypeId` as the one it is called with, transmuting the arguments and the return value between the two — they are the same type, which the compiler only doesn't know about.
+fn mono_dispatch_method(item: &VtableItem) -> TokenStream {
+    let mono = item.mono.as_ref().unwrap();
+    let param = &mono.param;
+    let mut signature = mono.signature.clone();
+    let mut index = 0_u32;
+    let call_args = signature
+        .inputs
+        .iter_mut()
+        .map(|input| match input {
+            FnArg::Receiver(receiver) => {
+                // The argument is only passed on, so `mut self` would be an unused `mut`.
+                if receiver.reference.is_none() {
+                    receiver.mutability = None;
+                }
+                quote! { self }
+            }
+            FnArg::Typed(input) => {
+                index += 1;
+                let name = format_ident!("__arg{}", index);
+                input.attrs.clear();
+                *input.pat = parse_quote!(#name);
+                let ty = &input.ty;
+                if mentions_ident(&parse_quote!(#ty: Sized), &param.to_string()) {
+                    quote! { ::core::mem::transmute_copy(&::core::mem::ManuallyDrop::new(#name)) }
+                } else {
+                    name.to_token_stream()
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let branches = mono.instances.iter().map(|(ty, name)| {
+        let helper = mono_helper_name(name);
+        // Points at the type parameter if it isn't `'static`, which `TypeId` requires.
+        let condition = quote_spanned! {param.span()=>
+            ::core::any::TypeId::of::<#param>() == ::core::any::TypeId::of::<#ty>()
+        };
+        quote! {
+            if #condition {
+                return unsafe {
+                    ::core::mem::transmute_copy(&::core::mem::ManuallyDrop::new(
+                        Self::#helper(#(#call_args),*),
+                    ))
+                };
+            }
+        }
+    });
+    let cfg_attributes = &item.cfg_attributes;
+    let message = format!(
+        "method `{}` of a thin trait object was called with `{{}}`, which is not one of the types listed in `#[thin(mono(...))]`",
+        signature.ident,
+    );
+    quote! {
+        #(#cfg_attributes)*
+        #[inline]
+        #signature {
+            #(#branches)*
+            ::core::panic!(#message, ::core::any::type_name::<#param>())
+        }
+    }
+}
+
+/// Generates the `downcast_ref_tagged` method of the boxed thin trait object if one of the methods is marked with `#[thin(tag)]`, which calls the vtable entry of that method through a hidden method and leaves it to the caller to know which type a tag stands for.
+fn tagged_downcast(stash: &StageStash, implementor: &TokenStream) -> TokenStream {
+    let StageStash {
+        vtable_items,
+        trait_generics,
+        ..
+    } = stash;
+    let item = match vtable_items.iter().find(|item| item.tag) {
+        Some(item) => item,
+        None => return quote! {},
+    };
+    let tag_type = match &item.output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => unreachable!("tag methods always return something"),
+    };
+    let trait_path = stash.trait_path();
+    let params = trait_generics.params();
+    let where_clause = trait_generics.where_clause();
+    let value_generic = trait_generics.unused_ident("T");
+    let value_repr_type = stash.repr_type(&value_generic);
+    let cfg_attributes = &item.cfg_attributes;
+    let helper_name = format_ident!("__thintraitobjectmacro_tag_{}", item.name);
+    let helper = dispatch_method(item, &helper_name);
+    // Spanned at the return type, so that the error for the tag not implementing `PartialEq` points
+    // at it.
+    let is_tagged = quote_spanned! {tag_type.span()=>
+        self.#helper_name() == tag
+    };
+    let doc = format!(
+        "\
+Returns a reference to the stored value as a value of type `T` if its `{}` method returns a tag \
+equal to the given one, or `None` if it doesn't. Unlike `downcast_ref`, doesn't require the stored \
+type to be `'static`, since the tag stands in for its `TypeId`.",
+        item.name,
+    );
+    quote! {
+        #(#cfg_attributes)*
+        #[allow(clippy::ref_in_deref)] // see https://github.com/rust-lang/rust-clippy/issues/6658
+        impl #params #implementor #where_clause {
+            #[inline]
+            #helper
+            #[doc = #doc]
+            ///
+            /// # Safety
+            /// Every value whose tag is equal to the given one must be of type `T`, lifetimes included. The macro has no way of checking that the implementations of the trait return tags consistent with the types they are called with, so it is up to the caller to only use tags which identify a single type.
+            #[inline]
+            pub unsafe fn downcast_ref_tagged<#value_generic: #trait_path>(
+                &self,
+                tag: #tag_type,
+            ) -> ::core::option::Option<&#value_generic> {
+                if #is_tagged {
+                    ::core::option::Option::Some(
+                        &(*(self.as_raw() as *const #value_repr_type)).__thintraitobjectmacro_repr_value,
+                    )
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+        }
+    }
+}
+
+/// The inline attribute for methods generated by [`dispatch_method`], which only load the function pointer from the vtable and call it.
+pub fn dispatch_inline_attribute(inline_always: bool) -> TokenStream {
+    if inline_always {
+        quote! { #[inline(always)] }
+    } else {
+        quote! { #[inline] }
+    }
+}
+
+/// Checks whether the attribute is one of `#[inline]` and `#[cold]`, which the trait method can use to override the inlining of the methods calling its vtable entry.
+fn is_optimization_hint(attribute: &Attribute) -> bool {
+    attribute.path.is_ident("inline") || attribute.path.is_ident("cold")
+}
+/// Returns the attributes of the trait method which are put onto inherent methods calling its vtable entry as they are, like its documentation and `#[must_use]`.
+pub fn forwarded_attributes(item: &VtableItem) -> impl Iterator<Item = &Attribute> {
+    item.forwarded_attributes
+        .iter()
+        .filter(|attribute| !is_optimization_hint(attribute))
+}
+/// Returns the `#[inline]` and `#[cold]` attributes of the trait method, to be put onto the methods calling its vtable entry, along with the given default `#[inline]` attribute unless the trait method has one of its own.
+pub fn optimization_hints(item: &VtableItem, inline: &TokenStream) -> TokenStream {
+    let hints = item
+        .forwarded_attributes
+        .iter()
+        .filter(|attribute| is_optimization_hint(attribute));
+    let inline = if item
+        .forwarded_attributes
+        .iter()
+        .any(|attribute| attribute.path.is_ident("inline"))
+    {
+        quote! {}
+    } else {
+        inline.clone()
+    };
+    quote! {
+        #(#hints)*
+        #inline
+    }
+}
+
+/// Generates the body of the `vtable()` method for structs which have an `as_raw()` method returning a pointer to a thin trait object.
+pub fn vtable_getter_impl(
+    inline_vtable: bool,
+    packed: bool,
+    vtable_type: &TokenStream,
+) -> TokenStream {
+    // A packed representation struct only guarantees an alignment of one.
+    if packed {
+        return quote! {
+            unsafe { (self.as_raw() as *const &'static #vtable_type).read_unaligned() }
+        };
+    }
+    let vtable_pointer_cast = if inline_vtable {
+        quote! { as *mut }
+    } else {
+        quote! { as *mut &'static }
+    };
+    quote! {
+        unsafe { &*(self.as_raw() #vtable_pointer_cast #vtable_type) }
+    }
+}
+
+/// Generates the `is_inline_vtable()` method for structs which point to or contain a thin trait object.
+pub fn is_inline_vtable_method(inline_vtable: bool) -> TokenStream {
+    quote! {
+        /// Returns `true` if the vtable itself is stored in front of the value, and `false` if a pointer to it is stored there instead, as specified by the `inline_vtable` option when this type was generated.
+        #[inline]
+        pub const fn is_inline_vtable() -> bool {
+            #inline_vtable
+        }
+    }
+}
+
+/// Generates the `header()` method for structs which have an `as_raw()` method returning a pointer to a thin trait object, returning a reference with the given lifetime. Empty if there is no header.
+pub fn header_getter(
+    stash: &StageStash,
+    inline_vtable: bool,
+    lifetime: TokenStream,
+) -> TokenStream {
+    let header = match &stash.header {
+        Some(header) => header,
+        None => return TokenStream::new(),
+    };
+    let vtable_type = stash.vtable_type();
+    let vtable_field_type = if inline_vtable {
+        vtable_type
+    } else {
+        quote! { &'static #vtable_type }
+    };
+    quote! {
+        /// Returns the header stored in front of the value, which can be read without knowing the type of the latter.
+        #[inline]
+        pub fn header(&self) -> &#lifetime #header {
+            // Mirrors the `#[repr(C)]` layout of the representation struct, in which the offset of
+            // the header doesn't depend on the type of the value coming after it.
+            let offset = match ::core::alloc::Layout::new::<#vtable_field_type>()
+                .extend(::core::alloc::Layout::new::<#header>())
+            {
+                ::core::result::Result::Ok((_, offset)) => offset,
+                ::core::result::Result::Err(_) => unsafe { ::core::hint::unreachable_unchecked() },
+            };
+            unsafe { &*((self.as_raw() as *const u8).add(offset) as *const #header) }
+        }
+    }
+}
+
+/// Generates the body of the `same_vtable()` method for structs which have a `vtable()` method, which takes the other object as `other`.
+pub fn same_vtable_impl(inline_vtable: bool) -> TokenStream {
+    if inline_vtable {
+        quote! { self.vtable().__thintraitobjectmacro_same_entries(other.vtable()) }
+    } else {
+        quote! { ::core::ptr::eq(self.vtable(), other.vtable()) }
+    }
+}
+
+pub fn check_attribute(attribute: &Attribute) -> syn::Result<()> {
     let name = &attribute.path;
     let ident = &name.segments[0].ident;
     let span = ident.span();
@@ -1,40 +1,63 @@
 //! Generates the vtable struct itself.
 
-use crate::attr::StageStash;
+use crate::{
+    attr::StageStash,
+    c_header::generate_c_header,
+    extra_entries::ExtraEntry,
+    options::{MethodOption, MethodOptions, MonoType},
+    storage::Storage,
+};
 use proc_macro2::{Ident, Span, TokenStream};
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 use replace_with::replace_with_or_abort;
-use std::convert::TryFrom;
+use std::{convert::TryFrom, iter, mem};
 use syn::{
+    ext::IdentExt,
+    parse_quote,
+    parse_quote_spanned,
     punctuated::Punctuated,
     spanned::Spanned,
     token::{Colon, Paren, Unsafe},
+    visit_mut::{self, VisitMut},
     Abi,
     AttrStyle,
     Attribute,
     BareFnArg,
     BoundLifetimes,
     FnArg,
+    GenericArgument,
     GenericParam,
     Generics,
+    Lifetime,
+    LitBool,
     LitStr,
+    ParenthesizedGenericArguments,
     Pat,
     PatIdent,
     PatType,
     Path,
     PathArguments,
     PathSegment,
+    PredicateType,
     Receiver,
     ReturnType,
     Signature,
     Token,
     TraitItem,
+    TraitItemConst,
     TraitItemMethod,
     Type,
+    TypeBareFn,
+    TypeImplTrait,
+    TypeParamBound,
     TypePath,
     TypePtr,
+    TypeReference,
+    TypeTraitObject,
     Variadic,
     Visibility,
+    WhereClause,
+    WherePredicate,
 };
 
 pub fn generate_vtable(
@@ -43,15 +66,26 @@ pub fn generate_vtable(
     attributes: impl IntoIterator<Item = Attribute>,
     drop_abi: Option<&Abi>,
     store_layout: bool,
-) -> TokenStream {
+    inline_vtable: bool,
+    c_header: Option<&LitBool>,
+) -> syn::Result<TokenStream> {
     let StageStash {
         vtable_items: items,
         vtable_name: name,
+        vtable_abi,
+        extra_entries,
+        drop_name,
+        trait_generics,
         ..
-    } = stash;
+    } = &*stash;
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let vtable_generics = trait_generics.without_lifetimes();
+    let params = vtable_generics.params();
+    let where_clause = vtable_generics.where_clause();
+    let mut had_repr = false;
     let all_attributes = {
         let mut token_stream = TokenStream::new();
-        let mut had_repr = false;
         for attr in attributes {
             if attr.path.get_ident().map(|x| x == "repr") == Some(true) {
                 had_repr = true;
@@ -63,77 +97,256 @@ pub fn generate_vtable(
         }
         token_stream
     };
-    struct VtableItemToFnPtr(VtableItem);
-    impl<'a> ToTokens for VtableItemToFnPtr {
-        fn to_tokens(&self, out: &mut TokenStream) {
-            out.extend(self.to_token_stream());
-        }
-        fn to_token_stream(&self) -> TokenStream {
-            let name = self.0.name.clone();
-            let ty = {
-                let mut owned = self.0.clone();
-                owned.make_unsafe();
-                owned.make_raw();
-                owned.to_function_pointer()
-            };
-            quote! { #name : #ty }
-        }
-    }
     struct VtableItemToDebugImplLine(VtableItem);
-    impl<'a> ToTokens for VtableItemToDebugImplLine {
+    impl ToTokens for VtableItemToDebugImplLine {
         fn to_tokens(&self, out: &mut TokenStream) {
             out.extend(self.to_token_stream());
         }
         fn to_token_stream(&self) -> TokenStream {
             let name = self.0.name.clone();
-            let namelit = LitStr::new(&name.to_string(), Span::call_site());
-            quote! { .field(#namelit, &(self.#name as *mut ())) }
+            let cfg_attributes = &self.0.cfg_attributes;
+            let namelit = LitStr::new(&name.unraw().to_string(), Span::call_site());
+            quote! {
+                #(#cfg_attributes)*
+                debug_struct.field(#namelit, &(self.#name as *mut ()));
+            }
         }
     }
     struct VtableItemToHashImplLine(VtableItem);
-    impl<'a> ToTokens for VtableItemToHashImplLine {
+    impl ToTokens for VtableItemToHashImplLine {
         fn to_tokens(&self, out: &mut TokenStream) {
             out.extend(self.to_token_stream());
         }
         fn to_token_stream(&self) -> TokenStream {
             let name = self.0.name.clone();
-            quote! { (self.#name as *mut ()).hash(state) }
+            let cfg_attributes = &self.0.cfg_attributes;
+            quote! {
+                #(#cfg_attributes)*
+                (self.#name as *mut ()).hash(state);
+            }
         }
     }
-    let vtable_entries = items.iter().cloned().map(VtableItemToFnPtr);
-    let debug_impl_lines = items.iter().cloned().map(VtableItemToDebugImplLine);
-    let hash_impl_lines = items.iter().cloned().map(VtableItemToHashImplLine);
-    let name_strlit = LitStr::new(&name.to_string(), Span::call_site());
-    let size_and_align = if store_layout {
+    // The one and only list of fields in the order in which they appear in the struct. Everything
+    // which depends on the layout of the vtable is generated from it to keep them in sync.
+    let mut fields = Vec::with_capacity(items.len() + 3);
+    if store_layout {
+        fields.push(VtableField::new("size", quote! { usize }));
+        fields.push(VtableField::new("align", quote! { usize }));
+    }
+    fields.extend(items.iter().map(|item| {
+        let mut owned = item.clone();
+        owned.apply_default_abi(vtable_abi.as_ref());
+        owned.make_unsafe();
+        owned.make_raw();
+        VtableField {
+            name: owned.name.clone(),
+            ty: owned.to_function_pointer(),
+            cfg_attributes: owned.cfg_attributes.clone(),
+            kind: VtableFieldKind::Method(owned),
+        }
+    }));
+    fields.push(VtableField {
+        name: drop_name.clone(),
+        ty: quote! { unsafe #drop_abi fn(*mut ::core::ffi::c_void) },
+        cfg_attributes: Vec::new(),
+        kind: VtableFieldKind::Drop,
+    });
+    fields.extend(extra_entries.iter().map(|entry| VtableField {
+        name: entry.field_name(),
+        ty: entry.field_type(drop_abi, &trait_path),
+        cfg_attributes: Vec::new(),
+        kind: VtableFieldKind::Extra(*entry),
+    }));
+    let field_definitions = fields.iter().map(|field| {
+        let VtableField {
+            name,
+            ty,
+            cfg_attributes,
+            ..
+        } = field;
+        // The entries are documented by the trait, and the macro can't describe them any better.
+        quote! {
+            #(#cfg_attributes)*
+            #[allow(missing_docs)]
+            pub #name: #ty,
+        }
+    });
+    // A custom representation may reorder or pad the fields, making the offsets meaningless.
+    let offsets = if had_repr {
+        quote! {}
+    } else {
+        let offsets = generate_offsets(&fields);
         quote! {
-            pub size: usize,
-            pub align: usize,
+            impl #params #vtable_type #where_clause {
+                #offsets
+            }
+        }
+    };
+    // The allocation starts with the vtable itself in inline mode and with a reference to it
+    // otherwise, which is checked the same way as the size of the thin trait object, unless the
+    // number of fields isn't known or the representation may add padding.
+    let size_check = if trait_generics.is_empty()
+        && !had_repr
+        && fields.iter().all(|field| field.cfg_attributes.is_empty())
+    {
+        let (vtable_field_type, words) = if inline_vtable {
+            (vtable_type.clone(), fields.len())
+        } else {
+            (quote! { &'static #vtable_type }, 1)
+        };
+        quote! {
+            const _: [(); 1] = [(); (
+                ::core::mem::size_of::<#vtable_field_type>()
+                    == #words * ::core::mem::size_of::<usize>()
+            ) as usize];
         }
     } else {
         quote! {}
     };
-    quote! {
-        #[derive(Copy, Clone)]
+    let c_header = match c_header {
+        Some(c_header) if !trait_generics.is_empty() => {
+            return Err(syn::Error::new_spanned(
+                c_header,
+                "the C header cannot be generated for generic traits and traits with associated types",
+            ));
+        }
+        Some(c_header) if had_repr => {
+            return Err(syn::Error::new_spanned(
+                c_header,
+                "the C header can only be generated for vtables with the default `#[repr(C)]`",
+            ));
+        }
+        Some(..) => generate_c_header(stash, &fields, drop_abi, inline_vtable)?,
+        None => quote! {},
+    };
+    // Vtables copied into the representation structs can't be told apart by their addresses.
+    let same_entries = if inline_vtable {
+        let comparisons = fields.iter().map(|field| {
+            let VtableField {
+                name,
+                cfg_attributes,
+                ..
+            } = field;
+            quote! {
+                #(#cfg_attributes)*
+                if self.#name as usize != other.#name as usize {
+                    return false;
+                }
+            }
+        });
+        quote! {
+            impl #params #vtable_type #where_clause {
+                fn __thintraitobjectmacro_same_entries(&self, other: &Self) -> bool {
+                    #(#comparisons)*
+                    true
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let debug_impl_lines = items.iter().cloned().map(VtableItemToDebugImplLine);
+    let hash_impl_lines = items.iter().cloned().map(VtableItemToHashImplLine);
+    let name_strlit = LitStr::new(&name.to_string(), Span::call_site());
+    let def_params = vtable_generics.params_with_defaults(TokenStream::new());
+    let phantom_field = vtable_generics.phantom_type().map(|phantom_type| {
+        quote! {
+            #[allow(missing_docs)]
+            pub __thintraitobjectmacro_phantom: #phantom_type,
+        }
+    });
+    Ok(quote! {
         #all_attributes
-        #visibility struct #name {
-            #size_and_align
-            #(pub #vtable_entries,)*
-            pub drop: unsafe #drop_abi fn(*mut ::core::ffi::c_void),
+        #visibility struct #name #def_params #where_clause {
+            #(#field_definitions)*
+            #phantom_field
         }
-        impl ::core::fmt::Debug for #name {
+        // Deriving those would require the generic parameters of the trait to implement them too.
+        #[allow(clippy::expl_impl_clone_on_copy)]
+        impl #params ::core::clone::Clone for #vtable_type #where_clause {
+            #[inline]
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+        impl #params ::core::marker::Copy for #vtable_type #where_clause {}
+        #offsets
+        #size_check
+        #c_header
+        #same_entries
+        impl #params ::core::fmt::Debug for #vtable_type #where_clause {
             fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
-                f.debug_struct(#name_strlit)
-                    #(#debug_impl_lines)*
-                    .finish()
+                let mut debug_struct = f.debug_struct(#name_strlit);
+                #(#debug_impl_lines)*
+                debug_struct.finish()
             }
         }
-        impl ::core::hash::Hash for #name {
+        impl #params ::core::hash::Hash for #vtable_type #where_clause {
             fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
-                #(#hash_impl_lines;)*
+                #(#hash_impl_lines)*
             }
         }
+    })
+}
+
+/// A field of the vtable struct.
+pub struct VtableField {
+    pub name: Ident,
+    pub ty: TokenStream,
+    /// The `#[cfg(...)]` attributes of the trait method for method fields, empty for all others.
+    pub cfg_attributes: Vec<Attribute>,
+    pub kind: VtableFieldKind,
+}
+impl VtableField {
+    #[inline]
+    fn new(name: &str, ty: TokenStream) -> Self {
+        Self {
+            name: Ident::new(name, Span::call_site()),
+            ty,
+            cfg_attributes: Vec::new(),
+            kind: VtableFieldKind::Layout,
+        }
     }
 }
+/// What a field of the vtable struct is there for.
+#[allow(clippy::large_enum_variant)]
+pub enum VtableFieldKind {
+    /// The size or alignment of the stored type.
+    Layout,
+    /// A trait method, already converted to take a raw pointer.
+    Method(VtableItem),
+    /// The destructor.
+    Drop,
+    /// One of the additional entries.
+    Extra(ExtraEntry),
+}
+
+/// Generates `OFFSET_...` associated constants for every field of the vtable, in declaration order, measured from the actual layout with `offset_of!`.
+fn generate_offsets(fields: &[VtableField]) -> TokenStream {
+    let mut token_stream = TokenStream::new();
+    for field in fields {
+        let const_name = format_ident!("OFFSET_{}", field.name.unraw().to_string().to_uppercase());
+        let doc = LitStr::new(
+            &format!(
+                "The offset of the `{}` field from the beginning of the vtable, in bytes.",
+                field.name.unraw(),
+            ),
+            Span::call_site(),
+        );
+        let VtableField {
+            name,
+            cfg_attributes,
+            ..
+        } = field;
+        (quote! {
+            #(#cfg_attributes)*
+            #[doc = #doc]
+            pub const #const_name: usize = ::core::mem::offset_of!(Self, #name);
+        })
+        .to_tokens(&mut token_stream);
+    }
+    token_stream
+}
 
 fn repr_attribute() -> Attribute {
     let path = {
@@ -163,37 +376,84 @@ fn repr_attribute() -> Attribute {
     }
 }
 
+/// The ways in which a method can take `self`.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ReceiverKind {
+    /// `&self`.
+    Shared,
+    /// `&mut self`.
+    Mutable,
+    /// `self` or `mut self`.
+    Value,
+    /// `self: Box<Self>`.
+    Boxed,
+    /// `self: Pin<&mut Self>`.
+    Pinned,
+}
+impl ReceiverKind {
+    /// Returns whether the method consumes the thin trait object.
+    #[inline]
+    pub fn is_by_value(self) -> bool {
+        matches!(self, Self::Value | Self::Boxed)
+    }
+}
+
 #[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum VtableFnArg {
     Normal(BareFnArg),
     Receiver(Receiver),
+    /// `self: Box<Self>`, which syn doesn't consider to be a `Receiver`.
+    BoxedReceiver(PatType),
+    /// `self: Pin<&mut Self>`, for the same reason.
+    PinnedReceiver(PatType),
 }
 impl VtableFnArg {
     pub fn into_bare_arg_with_ptr_receiver(self) -> BareFnArg {
-        match self {
-            VtableFnArg::Normal(arg) => arg,
-            VtableFnArg::Receiver(arg) => BareFnArg {
-                attrs: arg.attrs,
-                name: None, // Fill this out later.
-                ty: Type::Ptr(TypePtr {
-                    star_token: Default::default(),
-                    const_token: None,
-                    mutability: Some(Default::default()),
-                    elem: Type::Path(TypePath {
-                        qself: None,
-                        path: define_path![::, "core", "ffi", "c_void"],
-                    })
-                    .into(),
-                }),
-            },
+        let attrs = match self {
+            VtableFnArg::Normal(arg) => return arg,
+            VtableFnArg::Receiver(arg) => arg.attrs,
+            VtableFnArg::BoxedReceiver(arg) | VtableFnArg::PinnedReceiver(arg) => arg.attrs,
+        };
+        BareFnArg {
+            attrs,
+            name: None, // Fill this out later.
+            ty: Type::Ptr(TypePtr {
+                star_token: Default::default(),
+                const_token: None,
+                mutability: Some(Default::default()),
+                elem: Type::Path(TypePath {
+                    qself: None,
+                    path: define_path![::, "core", "ffi", "c_void"],
+                })
+                .into(),
+            }),
         }
     }
+    /// Returns how the argument takes `self`, or `None` if it's a normal argument.
+    pub fn receiver_kind(&self) -> Option<ReceiverKind> {
+        let kind = match self {
+            VtableFnArg::Normal(..) => return None,
+            VtableFnArg::Receiver(Receiver {
+                reference: None, ..
+            }) => ReceiverKind::Value,
+            VtableFnArg::Receiver(Receiver {
+                mutability: Some(..),
+                ..
+            }) => ReceiverKind::Mutable,
+            VtableFnArg::Receiver(..) => ReceiverKind::Shared,
+            VtableFnArg::BoxedReceiver(..) => ReceiverKind::Boxed,
+            VtableFnArg::PinnedReceiver(..) => ReceiverKind::Pinned,
+        };
+        Some(kind)
+    }
 }
 impl ToTokens for VtableFnArg {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match self {
             VtableFnArg::Normal(x) => x.to_tokens(tokens),
             VtableFnArg::Receiver(x) => x.to_tokens(tokens),
+            VtableFnArg::BoxedReceiver(x) | VtableFnArg::PinnedReceiver(x) => x.to_tokens(tokens),
         }
     }
 }
@@ -201,6 +461,20 @@ impl TryFrom<FnArg> for VtableFnArg {
     type Error = syn::Error;
     fn try_from(value: FnArg) -> Result<Self, Self::Error> {
         let success = match value {
+            FnArg::Typed(ty) if is_self_pattern(&ty.pat) => {
+                if is_box_of_self(&ty.ty) {
+                    Self::BoxedReceiver(ty)
+                } else if is_pin_of_mut_self(&ty.ty) {
+                    Self::PinnedReceiver(ty)
+                } else {
+                    return Err(syn::Error::new_spanned(
+                        ty.ty,
+                        "\
+`#[thin_trait_object]` only supports `&self`, `&mut self`, `self`, `self: Box<Self>` and \
+`self: Pin<&mut Self>` receivers",
+                    ));
+                }
+            }
             FnArg::Typed(ty) => Self::Normal(BareFnArg {
                 attrs: ty.attrs,
                 name: match *ty.pat {
@@ -209,20 +483,82 @@ impl TryFrom<FnArg> for VtableFnArg {
                 },
                 ty: *ty.ty,
             }),
-            FnArg::Receiver(receiver) => {
-                if receiver.reference.is_none() {
-                    // Pass-by-value, cannot have that just yet
-                    return Err(syn::Error::new_spanned(
-                        receiver.self_token,
-                        "`#[thin_trait_object]` does not support pass-by-value just yet",
-                    ));
-                }
-                Self::Receiver(receiver)
-            }
+            FnArg::Receiver(receiver) => Self::Receiver(receiver),
         };
         Ok(success)
     }
 }
+fn is_self_pattern(pat: &Pat) -> bool {
+    match pat {
+        Pat::Ident(pat) => pat.ident == "self",
+        _ => false,
+    }
+}
+/// The name of the argument of vtable entries returning an error code through which the success value is written.
+pub fn error_code_out_arg() -> Ident {
+    format_ident!("__thintraitobjectmacro_out")
+}
+/// Returns the success and error types if the type is `Result<T, E>`, with or without a path to `Result`.
+fn result_types(ty: &Type) -> Option<(Type, Type)> {
+    let last_segment = match ty {
+        Type::Path(TypePath { qself: None, path }) => path.segments.last()?,
+        _ => return None,
+    };
+    if last_segment.ident != "Result" {
+        return None;
+    }
+    match &last_segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 2 => {
+            match (&arguments.args[0], &arguments.args[1]) {
+                (GenericArgument::Type(ok), GenericArgument::Type(err)) => {
+                    Some((ok.clone(), err.clone()))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+/// Checks whether the type is `Box<Self>`, with or without a path to `Box`.
+fn is_box_of_self(ty: &Type) -> bool {
+    matches!(
+        single_type_argument(ty, "Box"),
+        Some(Type::Path(TypePath { qself: None, path })) if path.is_ident("Self")
+    )
+}
+/// Checks whether the type is `Pin<&mut Self>`, with or without a path to `Pin`.
+fn is_pin_of_mut_self(ty: &Type) -> bool {
+    match single_type_argument(ty, "Pin") {
+        Some(Type::Reference(TypeReference {
+            mutability: Some(..),
+            elem,
+            ..
+        })) => {
+            matches!(&**elem, Type::Path(TypePath { qself: None, path }) if path.is_ident("Self"))
+        }
+        _ => false,
+    }
+}
+/// Returns the generic argument of a type like `Box<T>`, given the name of the last segment of its path.
+fn single_type_argument<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let last_segment = match ty {
+        Type::Path(TypePath { qself: None, path }) => path.segments.last(),
+        _ => None,
+    };
+    let arguments = match last_segment {
+        Some(segment) if segment.ident == name => &segment.arguments,
+        _ => return None,
+    };
+    match arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match &arguments.args[0] {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
 impl From<BareFnArg> for VtableFnArg {
     #[inline]
     fn from(arg: BareFnArg) -> Self {
@@ -241,10 +577,48 @@ pub struct VtableItem {
     pub lifetimes: BoundLifetimes,
     pub unsafety: Option<Unsafe>,
     pub abi: Option<Abi>,
+    /// Set for `async fn` methods, whose vtable entries return the future boxed instead.
+    pub asyncness: Option<Token![async]>,
     pub name: Ident,
+    /// The name of the inherent methods calling the vtable entry, if it was changed using `#[thin(rename = "...")]`.
+    pub rename: Option<Ident>,
+    /// Whether the method is left out of the vtable using `#[thin(skip)]`, making the thin trait objects use its default implementation.
+    pub skip: bool,
     pub inputs: Punctuated<VtableFnArg, Token![,]>,
     pub variadic: Option<Variadic>,
     pub output: ReturnType,
+    /// The `where` clause of the method, which is repeated on the methods of the thin trait object structs but not on the vtable entry, since it may only contain bounds which every stored value satisfies.
+    pub where_clause: Option<WhereClause>,
+    /// Whether the trait provides a default implementation of the method.
+    pub has_default: bool,
+    /// Attributes of the trait method which also make sense on the methods of the thin trait object struct, such as documentation.
+    pub forwarded_attributes: Vec<Attribute>,
+    /// The `#[cfg(...)]` attributes of the trait method, which are put onto everything generated for it so that it is left out together with the method.
+    pub cfg_attributes: Vec<Attribute>,
+    /// The supertrait which declares the method if it was flattened into the vtable from the `supertrait(...)` option, `None` if the trait itself declares it.
+    pub supertrait: Option<Path>,
+    /// The name of the associated constant if this is the getter generated for one rather than a method, in which case the thunk returns the value of the constant for the stored type instead of calling anything.
+    pub constant: Option<Ident>,
+    /// The trait from the `error_code` option if the entry uses a foreign ABI, in which case a method returning a `Result` has its vtable entry write the success value through an extra pointer argument and return an error code converted using that trait.
+    pub error_code: Option<Path>,
+    /// The lifetime parameters of the trait, which the vtable entry replaces with `'static` since the vtable isn't generic over them.
+    pub trait_lifetimes: Vec<Lifetime>,
+    /// The generic method which this is one of the instantiations of, if it is listed with `#[thin(mono(...))]`.
+    pub mono: Option<Mono>,
+    /// Whether the method is marked with `#[thin(tag)]` as returning a tag which identifies the type of the stored value, for `downcast_ref_tagged` to compare to the one it is given.
+    pub tag: bool,
+}
+/// A generic method with a vtable entry for each of the type arguments listed in `#[thin(mono(...))]`, which the implementations of the trait on the thin trait objects pick from by comparing the `TypeId` of the type argument they are called with to the ones of the listed types.
+#[derive(Clone)]
+pub struct Mono {
+    /// The method as declared in the trait, which the implementations of the trait repeat.
+    pub signature: Signature,
+    /// The type parameter of the method.
+    pub param: Ident,
+    /// The listed type arguments, together with the names of their vtable entries.
+    pub instances: Vec<(Type, Ident)>,
+    /// The position of the type argument of this instantiation in the list.
+    pub index: usize,
 }
 impl VtableItem {
     #[inline]
@@ -253,6 +627,63 @@ impl VtableItem {
             self.unsafety = Some(Default::default())
         }
     }
+    /// Returns the name of the inherent methods calling the vtable entry, which the implementations of the trait don't use.
+    #[inline]
+    pub fn inherent_name(&self) -> &Ident {
+        self.rename.as_ref().unwrap_or(&self.name)
+    }
+    /// Turns a generic method listed with `#[thin(mono(...))]` into one vtable item for each of the type arguments, with those substituted for the type parameter, and returns other items as they are.
+    pub fn monomorphize(self) -> Vec<Self> {
+        let mono = match &self.mono {
+            Some(mono) => mono.clone(),
+            None => return vec![self],
+        };
+        (0..mono.instances.len())
+            .map(|index| {
+                let (ty, name) = &mono.instances[index];
+                let mut item = self.clone();
+                let mut substitute = SubstituteType {
+                    param: &mono.param,
+                    ty,
+                };
+                for input in &mut item.inputs {
+                    if let VtableFnArg::Normal(arg) = input {
+                        substitute.visit_bare_fn_arg_mut(arg);
+                    }
+                }
+                substitute.visit_return_type_mut(&mut item.output);
+                item.name = name.clone();
+                item.mono = Some(Mono {
+                    index,
+                    ..mono.clone()
+                });
+                item
+            })
+            .collect()
+    }
+    /// Returns the `self` argument of the method, if it wasn't already replaced with a raw pointer.
+    pub fn receiver(&self) -> Option<&VtableFnArg> {
+        self.inputs
+            .iter()
+            .find(|input| input.receiver_kind().is_some())
+    }
+    /// Returns how the method takes `self`, if it wasn't already replaced with a raw pointer.
+    #[inline]
+    pub fn receiver_kind(&self) -> Option<ReceiverKind> {
+        self.receiver().and_then(VtableFnArg::receiver_kind)
+    }
+    /// Returns whether the method takes `&self`, meaning that it can be called through a shared reference.
+    #[inline]
+    pub fn has_shared_receiver(&self) -> bool {
+        self.receiver_kind() == Some(ReceiverKind::Shared)
+    }
+    /// Sets the ABI of the entry to the given one if the trait definition didn't specify any.
+    #[inline]
+    pub fn apply_default_abi(&mut self, abi: Option<&Abi>) {
+        if self.abi.is_none() {
+            self.abi = abi.cloned();
+        }
+    }
     pub fn to_function_pointer(&self) -> TokenStream {
         let inputs = self.inputs.iter();
         let lifetimes = &self.lifetimes;
@@ -273,12 +704,12 @@ impl VtableItem {
         };
         Signature {
             constness: None,
-            asyncness: None,
+            asyncness: self.asyncness,
             unsafety: self.unsafety,
             abi: self.abi,
             fn_token: Default::default(),
             ident: self.name,
-            generics: lifetimes_to_generics(self.lifetimes),
+            generics: lifetimes_to_generics(self.lifetimes, self.where_clause),
             paren_token: Default::default(),
             inputs: self
                 .inputs
@@ -288,28 +719,347 @@ impl VtableItem {
                         bare_fn_arg_to_fn_arg(arg, &mut inner_default_argname)
                     }
                     VtableFnArg::Receiver(rec) => FnArg::Receiver(rec),
+                    VtableFnArg::BoxedReceiver(rec) | VtableFnArg::PinnedReceiver(rec) => {
+                        FnArg::Typed(rec)
+                    }
                 })
                 .collect(),
             variadic: self.variadic,
             output: self.output,
         }
     }
+    /// Makes sure that the method neither takes nor returns trait objects if its vtable entry uses a foreign ABI, since pointers to trait objects are two pointers wide with no layout guaranteed to foreign code. The Rust ABI passes them as they are.
+    pub fn check_ffi_safe_trait_objects(&self, vtable_abi: Option<&Abi>) -> syn::Result<()> {
+        let abi = match self.abi.as_ref().or(vtable_abi) {
+            Some(abi) if abi_name(abi) != "Rust" => abi_name(abi),
+            _ => return Ok(()),
+        };
+        let contains_trait_object = |ty: &Type| {
+            let mut finder = FindTraitObject(None);
+            finder.visit_type_mut(&mut ty.clone());
+            finder.0.is_some()
+        };
+        for input in &self.inputs {
+            if let VtableFnArg::Normal(arg) = input {
+                if contains_trait_object(&arg.ty) {
+                    let name = match &arg.name {
+                        Some((name, _)) => format!("the type of argument `{}`", name),
+                        None => "the type of an argument".to_string(),
+                    };
+                    return Err(syn::Error::new_spanned(
+                        &arg.ty,
+                        format!(
+                            "\
+{} of method `{}` contains a trait object, which is not FFI-safe in the \"{}\" ABI; take a borrowed thin \
+trait object generated with `thin_ref(...)` instead, or use the Rust ABI",
+                            name, self.name, abi,
+                        ),
+                    ));
+                }
+            }
+        }
+        if let ReturnType::Type(_, ty) = &self.output {
+            if contains_trait_object(ty) {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!(
+                        "\
+the return type of method `{}` contains a trait object, which is not FFI-safe in the \"{}\" ABI; return a thin trait \
+object instead, or use the Rust ABI",
+                        self.name, abi,
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+    /// Returns `true` if the type mentions one of the lifetime parameters of the trait, in which case the argument of that type is passed to the vtable entry with the lifetime replaced by `'static` and needs to be transmuted on both ends.
+    pub fn mentions_trait_lifetime(&self, ty: &Type) -> bool {
+        self.trait_lifetimes.iter().any(|lifetime| {
+            let mut finder = MentionsLifetime {
+                lifetime,
+                found: false,
+            };
+            finder.visit_type_mut(&mut ty.clone());
+            finder.found
+        })
+    }
+    /// Returns `true` if the return type borrows from `self` or mentions one of the lifetime parameters of the trait, in which case the vtable entry returns it with the `'static` lifetime instead and the methods calling the entry need to transmute it back.
+    pub fn borrows_from_receiver(&self) -> bool {
+        let mut raw = self.clone();
+        raw.unbind_receiver();
+        raw.output != self.output
+    }
+    /// Returns the success and error types of the `Result` returned by the method if its vtable entry returns an error code instead, with the lifetimes borrowed from `self` replaced the same way as in the vtable entry.
+    pub fn error_code_result(&self) -> Option<(Type, Type)> {
+        self.error_code.as_ref()?;
+        if self.constant.is_some() {
+            return None;
+        }
+        let mut raw = self.clone();
+        raw.unbind_receiver();
+        match &raw.output {
+            ReturnType::Type(_, ty) => result_types(ty),
+            ReturnType::Default => None,
+        }
+    }
+    /// Turns the method into the signature of its vtable entry, returning whether it had a receiver which was replaced with a raw pointer.
     pub fn make_raw(&mut self) -> bool {
+        let error_code_result = self.error_code_result();
+        let replaced = self.unbind_receiver();
+        if let Some((ok, _)) = error_code_result {
+            self.inputs.push(VtableFnArg::Normal(BareFnArg {
+                attrs: Vec::new(),
+                name: Some((error_code_out_arg(), Default::default())),
+                ty: parse_quote!(*mut #ok),
+            }));
+            self.output = parse_quote!(-> i32);
+        }
+        replaced
+    }
+    fn unbind_receiver(&mut self) -> bool {
+        // Function pointers can't be `async`, so the future is boxed and its type erased instead.
+        // It may borrow from any of the arguments, which the caller has to keep alive until it
+        // completes.
+        if self.asyncness.take().is_some() {
+            let path_to_box = Storage::Box.path();
+            let output = match &self.output {
+                ReturnType::Default => quote! { () },
+                ReturnType::Type(_, ty) => ty.to_token_stream(),
+            };
+            let future = quote! {
+                ::core::pin::Pin<
+                    #path_to_box<dyn ::core::future::Future<Output = #output> + 'static>
+                >
+            };
+            self.output = ReturnType::Type(Default::default(), Box::new(parse_quote!(#future)));
+        }
+        let receiver_lifetime = match self.receiver() {
+            Some(VtableFnArg::Receiver(Receiver {
+                reference: Some((_, lifetime)),
+                ..
+            })) => Some(lifetime.clone()),
+            // The pinned reference is a borrow of `self` as well, which elided lifetimes in the
+            // return type refer to rather than to the references among the other arguments.
+            Some(VtableFnArg::PinnedReceiver(receiver)) => {
+                match single_type_argument(&receiver.ty, "Pin") {
+                    Some(Type::Reference(reference)) => Some(reference.lifetime.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
         let mut replaced = false;
         for input in &mut self.inputs {
             replace_with_or_abort(input, |x| {
-                if matches!(x, VtableFnArg::Receiver(..)) {
+                if x.receiver_kind().is_some() {
                     replaced = true;
                 }
                 x.into_bare_arg_with_ptr_receiver().into()
             });
         }
+        // The raw pointer which replaced the reference carries no lifetime, so references borrowed
+        // from `self` are given the `'static` lifetime instead, which is then shortened back to the
+        // borrow of the thin trait object struct by the methods calling the entry.
+        if let Some(receiver_lifetime) = receiver_lifetime {
+            if let ReturnType::Type(_, output) = &mut self.output {
+                UnbindReceiverLifetime {
+                    named: receiver_lifetime.as_ref(),
+                }
+                .visit_type_mut(output);
+            }
+            if let Some(named) = receiver_lifetime {
+                let mut finder = MentionsLifetime {
+                    lifetime: &named,
+                    found: false,
+                };
+                for input in &mut self.inputs {
+                    if let VtableFnArg::Normal(arg) = input {
+                        finder.visit_bare_fn_arg_mut(arg);
+                    }
+                }
+                if !finder.found {
+                    self.lifetimes.lifetimes = self
+                        .lifetimes
+                        .lifetimes
+                        .clone()
+                        .into_iter()
+                        .filter(|definition| definition.lifetime != named)
+                        .collect();
+                }
+            }
+        }
+        // The same goes for the lifetime parameters of the trait, which the vtable can't name.
+        let mut eraser = EraseLifetimes {
+            lifetimes: &self.trait_lifetimes,
+        };
+        for input in &mut self.inputs {
+            if let VtableFnArg::Normal(arg) = input {
+                eraser.visit_bare_fn_arg_mut(arg);
+            }
+        }
+        if let ReturnType::Type(_, output) = &mut self.output {
+            eraser.visit_type_mut(output);
+        }
         replaced
     }
 }
+/// Checks whether a lifetime is mentioned anywhere in the visited syntax tree.
+struct MentionsLifetime<'a> {
+    lifetime: &'a Lifetime,
+    found: bool,
+}
+impl VisitMut for MentionsLifetime<'_> {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if lifetime == self.lifetime {
+            self.found = true;
+        }
+    }
+}
+/// Replaces the given lifetimes with `'static` everywhere in the visited syntax tree.
+struct EraseLifetimes<'a> {
+    lifetimes: &'a [Lifetime],
+}
+impl VisitMut for EraseLifetimes<'_> {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if self.lifetimes.contains(lifetime) {
+            *lifetime = Lifetime::new("'static", lifetime.span());
+        }
+    }
+}
+/// Checks whether an identifier is mentioned anywhere in the visited syntax tree, other than as the name of a lifetime.
+struct MentionsIdent<'a> {
+    ident: &'a str,
+    found: bool,
+}
+impl VisitMut for MentionsIdent<'_> {
+    fn visit_ident_mut(&mut self, ident: &mut Ident) {
+        if ident == self.ident {
+            self.found = true;
+        }
+    }
+    fn visit_lifetime_mut(&mut self, _: &mut Lifetime) {}
+}
+/// Replaces the type parameter of a generic method listed with `#[thin(mono(...))]` with one of the type arguments everywhere in the visited syntax tree.
+struct SubstituteType<'a> {
+    param: &'a Ident,
+    ty: &'a Type,
+}
+impl VisitMut for SubstituteType<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        match ty {
+            Type::Path(TypePath { qself: None, path }) if path.is_ident(self.param) => {
+                *ty = self.ty.clone();
+            }
+            _ => visit_mut::visit_type_mut(self, ty),
+        }
+    }
+}
+/// Finds the first `impl Trait` type in the visited syntax tree.
+struct FindImplTrait(Option<TypeImplTrait>);
+impl VisitMut for FindImplTrait {
+    fn visit_type_impl_trait_mut(&mut self, ty: &mut TypeImplTrait) {
+        self.0.get_or_insert_with(|| ty.clone());
+    }
+}
+/// Finds trait object types, except for the ones in the signatures of function pointer types, which don't affect the function pointer itself.
+struct FindTraitObject(Option<TypeTraitObject>);
+impl VisitMut for FindTraitObject {
+    fn visit_type_trait_object_mut(&mut self, ty: &mut TypeTraitObject) {
+        self.0.get_or_insert_with(|| ty.clone());
+    }
+    fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+}
+/// Replaces the lifetimes which refer to the borrow of `self` with `'static` — the elided ones, as well as the one named by the receiver.
+struct UnbindReceiverLifetime<'a> {
+    named: Option<&'a Lifetime>,
+}
+impl VisitMut for UnbindReceiverLifetime<'_> {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if lifetime.ident == "_" || Some(&*lifetime) == self.named {
+            *lifetime = Lifetime::new("'static", lifetime.span());
+        }
+    }
+    fn visit_type_reference_mut(&mut self, reference: &mut TypeReference) {
+        if reference.lifetime.is_none() {
+            reference.lifetime = Some(Lifetime::new("'static", reference.and_token.span));
+        }
+        visit_mut::visit_type_reference_mut(self, reference);
+    }
+    // Elided lifetimes inside of function pointer types and `Fn` bounds belong to those.
+    fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+    fn visit_parenthesized_generic_arguments_mut(&mut self, _: &mut ParenthesizedGenericArguments) {
+    }
+}
 impl TryFrom<TraitItemMethod> for VtableItem {
     type Error = syn::Error;
     fn try_from(method: TraitItemMethod) -> Result<Self, Self::Error> {
+        let has_default = method.default.is_some();
+        let (cfg_attributes, attributes) = method
+            .attrs
+            .into_iter()
+            .partition::<Vec<_>, _>(|attr| attr.path.is_ident("cfg"));
+        let (mut rename, mut skip, mut mono_types, mut tag) = (None, false, None, None);
+        for attr in attributes.iter().filter(|attr| attr.path.is_ident("thin")) {
+            for option in attr.parse_args_with(MethodOptions::parse_terminated)? {
+                match option {
+                    MethodOption::Rename { val, .. } => {
+                        if rename.is_some() {
+                            return Err(syn::Error::new_spanned(
+                                val,
+                                "`rename` was specified more than once",
+                            ));
+                        }
+                        let name = val.parse::<Ident>().map_err(|_| {
+                            syn::Error::new_spanned(&val, "expected the name of a method")
+                        })?;
+                        rename = Some(name);
+                    }
+                    MethodOption::Skip { name } => {
+                        if skip {
+                            return Err(syn::Error::new(
+                                name.0,
+                                "`skip` was specified more than once",
+                            ));
+                        }
+                        skip = true;
+                    }
+                    MethodOption::Mono { name, types, .. } => {
+                        if mono_types.is_some() {
+                            return Err(syn::Error::new(
+                                name.0,
+                                "`mono` was specified more than once",
+                            ));
+                        }
+                        mono_types = Some((name, types));
+                    }
+                    MethodOption::Tag { name } => {
+                        if tag.is_some() {
+                            return Err(syn::Error::new(
+                                name.0,
+                                "`tag` was specified more than once",
+                            ));
+                        }
+                        tag = Some(name);
+                    }
+                }
+            }
+        }
+        let forwarded_attributes = attributes
+            .into_iter()
+            .filter(|attr| {
+                [
+                    "doc",
+                    "must_use",
+                    "deprecated",
+                    "inline",
+                    "cold",
+                    "track_caller",
+                ]
+                .iter()
+                .any(|name| attr.path.is_ident(name))
+            })
+            .collect();
         let signature = method.sig;
         if signature.receiver().is_none() {
             return Err(syn::Error::new(
@@ -317,19 +1067,129 @@ impl TryFrom<TraitItemMethod> for VtableItem {
                 "traits with associated functions cannot be made into trait objects",
             ));
         }
-        if let Some(asyncness) = signature.asyncness {
-            return Err(syn::Error::new(
-                asyncness.span,
-                "traits with async methods cannot be made into trait objects",
-            ));
+        // The type of the stored value is erased, but the thin trait object type can be returned by
+        // its generated name.
+        if let ReturnType::Type(_, output) = &signature.output {
+            if let Type::Path(TypePath { qself: None, path }) = &**output {
+                if path.is_ident("Self") {
+                    return Err(syn::Error::new_spanned(
+                        output,
+                        format!(
+                            "\
+method `{}` returns `Self`, which is not object-safe; return the generated thin trait object type by \
+its name instead",
+                            signature.ident,
+                        ),
+                    ));
+                }
+            }
+        }
+        // `impl Trait` in argument position is a generic type parameter in disguise, even if it's
+        // nested inside of another type, like in `&impl Trait` or `Option<impl Trait>`.
+        for input in &signature.inputs {
+            if let FnArg::Typed(PatType { ty, .. }) = input {
+                let mut finder = FindImplTrait(None);
+                finder.visit_type_mut(&mut (**ty).clone());
+                if let Some(impl_trait) = finder.0 {
+                    return Err(syn::Error::new_spanned(
+                        impl_trait,
+                        format!(
+                            "\
+method `{}` takes an `impl Trait` argument, which makes it generic; generic methods are not \
+object-safe, consider taking `&dyn Trait` or `Box<dyn Trait>` instead",
+                            signature.ident,
+                        ),
+                    ));
+                }
+            }
+        }
+        let mut generics = signature.generics.clone();
+        let mut where_clause = generics.where_clause.take();
+        let mono = match mono_types {
+            Some((name, types)) => {
+                let mono = Mono::new(&signature, &mut generics, types.into_iter().collect())?;
+                let conflict = if rename.is_some() {
+                    Some("`rename`")
+                } else if skip {
+                    Some("`skip`")
+                } else if signature.asyncness.is_some() {
+                    Some("async methods")
+                } else {
+                    None
+                };
+                if let Some(conflict) = conflict {
+                    return Err(syn::Error::new(
+                        name.0,
+                        format!("`mono` cannot be combined with {}", conflict),
+                    ));
+                }
+                // Those are checked by the thunk calling the instantiation, and would be trivially
+                // true or false anywhere else.
+                if let Some(where_clause) = &mut where_clause {
+                    where_clause.predicates = mem::take(&mut where_clause.predicates)
+                        .into_iter()
+                        .filter(|predicate| !mentions_ident(predicate, &mono.param.to_string()))
+                        .collect();
+                }
+                Some(mono)
+            }
+            None => None,
+        };
+        if let Some(name) = &tag {
+            let conflict = if skip {
+                Some("`skip`")
+            } else if mono.is_some() {
+                Some("`mono`")
+            } else {
+                None
+            };
+            if let Some(conflict) = conflict {
+                return Err(syn::Error::new(
+                    name.0,
+                    format!("`tag` cannot be combined with {}", conflict),
+                ));
+            }
+            // `downcast_ref_tagged` takes the tag by the return type of the method, which can't name
+            // the lifetimes of the method.
+            let only_takes_shared_self = signature.inputs.len() == 1
+                && matches!(
+                    signature.receiver(),
+                    Some(FnArg::Receiver(Receiver {
+                        reference: Some(..),
+                        mutability: None,
+                        ..
+                    }))
+                );
+            if !only_takes_shared_self
+                || signature.asyncness.is_some()
+                || !signature.generics.params.is_empty()
+                || signature.output == ReturnType::Default
+            {
+                return Err(syn::Error::new(
+                    signature.span(),
+                    format!(
+                        "\
+method `{}` is marked with `#[thin(tag)]`, which requires it to take only `&self` with an elided \
+lifetime, not to be async or generic and to return the tag",
+                        signature.ident,
+                    ),
+                ));
+            }
+        }
+        let lifetimes = generics_to_lifetimes(generics, &signature.ident)?;
+        if let Some(where_clause) = &where_clause {
+            check_method_where_clause(where_clause, &lifetimes, &signature.ident)?;
         }
         Ok(Self {
-            lifetimes: generics_to_lifetimes(signature.generics)?,
+            lifetimes,
             // The function pointer will be made unsafe later,
             // don't touch its unsafety just yet.
             unsafety: signature.unsafety,
             abi: signature.abi,
+            asyncness: signature.asyncness,
             name: signature.ident,
+            rename,
+            skip,
             inputs: signature
                 .inputs
                 .into_iter()
@@ -337,9 +1197,121 @@ impl TryFrom<TraitItemMethod> for VtableItem {
                 .collect::<Result<_, _>>()?,
             variadic: signature.variadic,
             output: signature.output,
+            where_clause,
+            has_default,
+            forwarded_attributes,
+            cfg_attributes,
+            supertrait: None,
+            constant: None,
+            error_code: None,
+            trait_lifetimes: Vec::new(),
+            mono,
+            tag: tag.is_some(),
+        })
+    }
+}
+impl TryFrom<TraitItemConst> for VtableItem {
+    type Error = syn::Error;
+    fn try_from(constant: TraitItemConst) -> Result<Self, Self::Error> {
+        let (cfg_attributes, attributes) = constant
+            .attrs
+            .into_iter()
+            .partition::<Vec<_>, _>(|attr| attr.path.is_ident("cfg"));
+        let forwarded_attributes = attributes
+            .into_iter()
+            .filter(|attr| {
+                ["doc", "deprecated"]
+                    .iter()
+                    .any(|name| attr.path.is_ident(name))
+            })
+            .collect();
+        // The getter is named like a method, so `MAX_LEN` becomes `max_len`, which may turn out to
+        // be a keyword.
+        let getter_name = constant.ident.unraw().to_string().to_lowercase();
+        let name = match syn::parse_str::<Ident>(&getter_name) {
+            Ok(..) => Ident::new(&getter_name, constant.ident.span()),
+            Err(..) if !["self", "super", "crate"].contains(&&*getter_name) => {
+                Ident::new_raw(&getter_name, constant.ident.span())
+            }
+            Err(..) => {
+                return Err(syn::Error::new_spanned(
+                    &constant.ident,
+                    format!(
+                        "the getter for associated constant `{}` would be named `{}`, which cannot be the name of a method",
+                        constant.ident, getter_name,
+                    ),
+                ))
+            }
+        };
+        // References in the type of an associated constant are `'static` when elided, which a
+        // method returning them would tie to the borrow of `self` instead.
+        let mut ty = constant.ty;
+        UnbindReceiverLifetime { named: None }.visit_type_mut(&mut ty);
+        let span = ty.span();
+        // The value is returned by copying the constant out of the implementation, which would
+        // create a new instance of it for every call otherwise.
+        let copy_bound: WherePredicate = parse_quote_spanned!(span=> #ty: ::core::marker::Copy);
+        if mentions_ident(&copy_bound, "Self") {
+            return Err(syn::Error::new_spanned(
+                &ty,
+                format!(
+                    "the type of associated constant `{}` mentions `Self`, which differs between the stored types",
+                    constant.ident,
+                ),
+            ));
+        }
+        Ok(Self {
+            lifetimes: Default::default(),
+            unsafety: None,
+            abi: None,
+            asyncness: None,
+            name,
+            rename: None,
+            skip: false,
+            inputs: iter::once(VtableFnArg::Receiver(parse_quote!(&self))).collect(),
+            variadic: None,
+            output: ReturnType::Type(Default::default(), Box::new(ty)),
+            where_clause: Some(parse_quote!(where #copy_bound)),
+            // The implementations may override the default value, so it is always dispatched.
+            has_default: false,
+            forwarded_attributes,
+            cfg_attributes,
+            supertrait: None,
+            constant: Some(constant.ident),
+            error_code: None,
+            trait_lifetimes: Vec::new(),
+            mono: None,
+            tag: false,
         })
     }
 }
+/// Makes sure that none of the methods in the trait explicitly specify an ABI which is different from the one which was set for the entire vtable.
+pub fn check_abi_consistency(items: &[VtableItem], vtable_abi: &Abi) -> syn::Result<()> {
+    let expected = abi_name(vtable_abi);
+    for item in items {
+        if let Some(abi) = &item.abi {
+            let found = abi_name(abi);
+            if found != expected {
+                return Err(syn::Error::new_spanned(
+                    abi,
+                    format!(
+                        "\
+method `{}` uses the \"{}\" ABI, but all vtable entries were set to use the \"{}\" ABI; \
+remove the ABI from the method or make it match the `abi` option",
+                        item.name, found, expected,
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+/// Returns the name of an ABI, taking into account that `extern` without an ABI string means `extern "C"`.
+pub fn abi_name(abi: &Abi) -> String {
+    abi.name
+        .as_ref()
+        .map_or_else(|| "C".to_string(), LitStr::value)
+}
 fn bare_fn_arg_to_fn_arg(argument: BareFnArg, default_argname: impl FnOnce() -> Ident) -> FnArg {
     let pat = {
         let pat = PatIdent {
@@ -363,43 +1335,285 @@ fn bare_fn_arg_to_fn_arg(argument: BareFnArg, default_argname: impl FnOnce() ->
         ty: Box::new(argument.ty),
     })
 }
+impl Mono {
+    /// Checks that the method has a single type parameter used as a type on its own, removes it from the generics and picks names for the vtable entries of the listed type arguments.
+    fn new(
+        signature: &Signature,
+        generics: &mut Generics,
+        types: Vec<MonoType>,
+    ) -> syn::Result<Self> {
+        let mut type_params = generics.type_params();
+        let param = match (type_params.next(), type_params.next(), generics.const_params().next()) {
+            (Some(param), None, None) => param,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &*generics,
+                    format!(
+                        "`mono` requires method `{}` to have exactly one type parameter and no constant parameters",
+                        signature.ident,
+                    ),
+                ))
+            }
+        };
+        // The implementations of the trait pick the vtable entry by the `TypeId` of the type argument.
+        let is_static = |bounds: &Punctuated<TypeParamBound, Token![+]>| {
+            bounds.iter().any(|bound| {
+                matches!(bound, TypeParamBound::Lifetime(lifetime) if lifetime.ident == "static")
+            })
+        };
+        let param_is_static = is_static(&param.bounds)
+            || signature.generics.where_clause.iter().any(|where_clause| {
+                where_clause.predicates.iter().any(|predicate| {
+                    matches!(
+                        predicate,
+                        WherePredicate::Type(PredicateType {
+                            bounded_ty: Type::Path(TypePath { qself: None, path }),
+                            bounds,
+                            ..
+                        }) if path.is_ident(&param.ident) && is_static(bounds)
+                    )
+                })
+            });
+        let param = param.ident.clone();
+        if !param_is_static {
+            return Err(syn::Error::new_spanned(
+                &param,
+                format!(
+                    "\
+`mono` requires the type parameter `{}` of method `{}` to be bounded by `'static`, since calls are \
+dispatched to the vtable entries by comparing its `TypeId` with the ones of the listed types",
+                    param, signature.ident,
+                ),
+            ));
+        }
+        generics.params = mem::take(&mut generics.params)
+            .into_iter()
+            .filter(|generic| !matches!(generic, GenericParam::Type(..)))
+            .collect();
+        if generics.params.is_empty() {
+            generics.lt_token = None;
+            generics.gt_token = None;
+        }
+        // Paths starting with the parameter, like `T::Output`, have no meaning once it is replaced.
+        let placeholder: Type = parse_quote!(__ThinTraitObjectMacro_Placeholder);
+        let mut substituted = signature.clone();
+        let mut substitute = SubstituteType {
+            param: &param,
+            ty: &placeholder,
+        };
+        substitute.visit_return_type_mut(&mut substituted.output);
+        for input in &mut substituted.inputs {
+            substitute.visit_fn_arg_mut(input);
+        }
+        let mut finder = MentionsIdent {
+            ident: &param.to_string(),
+            found: false,
+        };
+        finder.visit_return_type_mut(&mut substituted.output);
+        for input in &mut substituted.inputs {
+            finder.visit_fn_arg_mut(input);
+        }
+        if finder.found {
+            return Err(syn::Error::new_spanned(
+                &param,
+                format!(
+                    "\
+the type parameter `{}` can only be used as a type on its own in the arguments and the return type \
+of a method with `mono`, since it is replaced with each of the listed types",
+                    param,
+                ),
+            ));
+        }
+        if types.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &signature.ident,
+                "`mono` requires at least one type argument",
+            ));
+        }
+        let mut instances: Vec<(Type, Ident)> = Vec::with_capacity(types.len());
+        for MonoType { ty, name } in types {
+            let name = match name {
+                Some((_, name)) => name,
+                None => match &ty {
+                    Type::Path(TypePath { qself: None, path })
+                        if path
+                            .segments
+                            .iter()
+                            .all(|segment| segment.arguments.is_empty()) =>
+                    {
+                        let last = &path.segments.last().unwrap().ident;
+                        format_ident!(
+                            "{}_{}",
+                            signature.ident,
+                            snake_case(&last.unraw().to_string()),
+                            span = ty.span(),
+                        )
+                    }
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            &ty,
+                            "\
+the name of the vtable entry for this type cannot be derived from it; specify one with `as name`",
+                        ))
+                    }
+                },
+            };
+            if instances.iter().any(|(_, existing)| *existing == name) {
+                return Err(syn::Error::new_spanned(
+                    &name,
+                    format!(
+                        "two of the types listed in `mono` have vtable entries named `{}`; specify a different name for one of them with `as name`",
+                        name,
+                    ),
+                ));
+            }
+            instances.push((ty, name));
+        }
+        Ok(Self {
+            signature: signature.clone(),
+            param,
+            instances,
+            index: 0,
+        })
+    }
+}
+/// Converts a type name in `CamelCase` into `snake_case`, for the names of the vtable entries of a method with `mono`.
+fn snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut previous_lowercase = false;
+    for c in name.chars() {
+        if c.is_uppercase() && previous_lowercase {
+            result.push('_');
+        }
+        previous_lowercase = c.is_lowercase() || c.is_ascii_digit();
+        result.extend(c.to_lowercase());
+    }
+    result
+}
 /// Checks through the generics of the function to see if it has any non-lifetime generics — if it doesn't, convert the generics to an HRTB for a function pointer, if it does, return an error stating that generics are not allowed in function pointers.
-fn generics_to_lifetimes(generics: Generics) -> Result<BoundLifetimes, syn::Error> {
-    if let Some(where_clause) = generics.where_clause {
+fn generics_to_lifetimes(
+    generics: Generics,
+    method_name: &Ident,
+) -> Result<BoundLifetimes, syn::Error> {
+    // Point at the whole generic parameter list rather than the first offending parameter, since
+    // all of those need to be removed anyway.
+    let has_non_lifetime_params = generics
+        .params
+        .iter()
+        .any(|param| !matches!(param, GenericParam::Lifetime(..)));
+    if has_non_lifetime_params {
+        // Defaults only matter when the arguments are inferred, so the method is still generic.
+        let has_defaults = generics.params.iter().any(|param| match param {
+            GenericParam::Type(param) => param.default.is_some(),
+            GenericParam::Const(param) => param.default.is_some(),
+            GenericParam::Lifetime(..) => false,
+        });
         return Err(syn::Error::new_spanned(
-            where_clause,
-            "trait methods with `where` clauses are not object-safe",
+            &generics,
+            format!(
+                "\
+method `{}` has generic type or constant parameters{}, which cannot be dispatched through a single \
+thin trait object vtable entry; generic methods are not object-safe, so move the parameters to the \
+trait itself, replace them with concrete types, or with `&dyn Trait` for ones bounded by a trait, or \
+list the type arguments to generate vtable entries for with `#[thin(mono(...))]`",
+                method_name,
+                if has_defaults {
+                    " (even though they have defaults)"
+                } else {
+                    ""
+                },
+            ),
         ));
     }
     let lifetimes = {
         let mut lifetimes = Punctuated::new();
         for param in generics.params {
-            match param {
-                GenericParam::Lifetime(lifetime) => lifetimes.push(lifetime),
-                GenericParam::Type(ty) => {
-                    return Err(syn::Error::new_spanned(
-                        ty,
-                        "generic type parameters are not object-safe",
-                    ))
-                }
-                GenericParam::Const(constant) => {
-                    return Err(syn::Error::new_spanned(
-                        constant,
-                        "generic constant parameters are not object-safe",
-                    ))
-                }
+            if let GenericParam::Lifetime(lifetime) = param {
+                lifetimes.push(lifetime);
             }
         }
         lifetimes
     };
     Ok(BoundLifetimes {
         for_token: Default::default(),
-        lt_token: generics.lt_token.unwrap_or_else(Default::default),
+        lt_token: generics.lt_token.unwrap_or_default(),
         lifetimes,
-        gt_token: generics.gt_token.unwrap_or_else(Default::default),
+        gt_token: generics.gt_token.unwrap_or_default(),
     })
 }
-fn lifetimes_to_generics(lifetimes: BoundLifetimes) -> Generics {
+/// Makes sure that the `where` clause of a method only contains bounds which every stored value satisfies, since the vtable entry cannot be conditional on them — `Self` can only be required to be `Sized`, and the lifetimes of the method cannot be bounded, since the function pointer in the vtable has no way of expressing that.
+fn check_method_where_clause(
+    where_clause: &WhereClause,
+    lifetimes: &BoundLifetimes,
+    method_name: &Ident,
+) -> syn::Result<()> {
+    for predicate in &where_clause.predicates {
+        let mentions_lifetime = lifetimes.lifetimes.iter().any(|definition| {
+            let mut finder = MentionsLifetime {
+                lifetime: &definition.lifetime,
+                found: false,
+            };
+            finder.visit_where_predicate_mut(&mut predicate.clone());
+            finder.found
+        });
+        if mentions_lifetime {
+            return Err(syn::Error::new_spanned(
+                predicate,
+                format!(
+                    "\
+method `{}` has a `where` clause bounding its lifetime parameters, which the function pointer in \
+the vtable cannot express",
+                    method_name,
+                ),
+            ));
+        }
+        let requires_sized_self = match predicate {
+            WherePredicate::Type(predicate) => {
+                let self_type: Type = parse_quote!(Self);
+                predicate.bounded_ty == self_type
+                    && predicate.bounds.iter().all(|bound| match bound {
+                        TypeParamBound::Trait(bound) => matches!(
+                            bound.path.segments.last(),
+                            Some(segment) if segment.ident == "Sized"
+                        ),
+                        TypeParamBound::Lifetime(..) => false,
+                    })
+            }
+            _ => false,
+        };
+        if !requires_sized_self && mentions_ident(predicate, "Self") {
+            return Err(syn::Error::new_spanned(
+                predicate,
+                format!(
+                    "\
+method `{}` has a `where` clause bounding `Self` by something other than `Sized`, which the \
+vtable entry cannot be conditional on; add the bound to the trait instead",
+                    method_name,
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+/// Checks whether an identifier is mentioned by a bound, other than as the name of a lifetime.
+pub fn mentions_ident(predicate: &WherePredicate, ident: &str) -> bool {
+    let mut finder = MentionsIdent {
+        ident,
+        found: false,
+    };
+    finder.visit_where_predicate_mut(&mut predicate.clone());
+    finder.found
+}
+/// Checks whether a lifetime is mentioned by a bound.
+pub fn mentions_lifetime(predicate: &WherePredicate, lifetime: &Lifetime) -> bool {
+    let mut finder = MentionsLifetime {
+        lifetime,
+        found: false,
+    };
+    finder.visit_where_predicate_mut(&mut predicate.clone());
+    finder.found
+}
+fn lifetimes_to_generics(lifetimes: BoundLifetimes, where_clause: Option<WhereClause>) -> Generics {
     let params = lifetimes
         .lifetimes
         .into_iter()
@@ -409,7 +1623,7 @@ fn lifetimes_to_generics(lifetimes: BoundLifetimes) -> Generics {
         lt_token: Some(lifetimes.lt_token),
         params,
         gt_token: Some(lifetimes.gt_token),
-        where_clause: None,
+        where_clause,
     }
 }
 impl TryFrom<TraitItem> for VtableItem {
@@ -418,13 +1632,12 @@ impl TryFrom<TraitItem> for VtableItem {
         let span = item.span();
         match item {
             TraitItem::Method(method) => Self::try_from(method),
-            TraitItem::Const(constant) => Err(syn::Error::new(
-                constant.span(),
-                "traits with associated constants cannot be made into trait objects",
-            )),
+            TraitItem::Const(constant) => Self::try_from(constant),
+            // The associated types of the trait itself are turned into generic parameters before
+            // the items are converted, so this is a supertrait declared with `supertrait(...)`.
             TraitItem::Type(..) => Err(syn::Error::new(
                 span,
-                "traits with associated types cannot be made into trait objects",
+                "the associated types of a flattened supertrait are specified in its path, as in `Iterator<Item = u8>`, rather than declared inside of the braces",
             )),
             TraitItem::Macro(..) => Err(syn::Error::new(
                 span,
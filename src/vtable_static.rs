@@ -0,0 +1,173 @@
+//! Generates the vtable statics, enabled by the `vtable_static` option, which hold the vtable for a specific stored type under a name of the user's choosing, along with the `new_with_vtable` constructor of the boxed thin trait object which points to one of them.
+//!
+//! The vtable which `new` uses is a borrowed constant, which the compiler places wherever it likes and may even duplicate. A static has a single address and can be given attributes like `#[link_section = "..."]`, which is what firmware placing its vtables in a specific region of flash needs, but it cannot be generic, hence the types being listed up front.
+
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+
+use crate::{
+    attr::StageStash,
+    options::{OutputAdditions, VtableStatic},
+    repr::allow_internal_lints,
+    storage::Storage,
+};
+
+pub fn generate_vtable_statics(
+    stash: &StageStash,
+    vtable_statics: &[VtableStatic],
+    inline_vtable: bool,
+    has_static_bound: bool,
+) -> syn::Result<TokenStream> {
+    let StageStash {
+        repr_name,
+        trait_object_name,
+        storage,
+        trait_generics,
+        header,
+        ..
+    } = stash;
+    let first_name = &vtable_statics[0].additions.name;
+    // A static cannot be generic, and neither can its type.
+    if !trait_generics.is_empty() {
+        return Err(syn::Error::new_spanned(
+            first_name,
+            "vtable statics are not supported for generic traits and traits with associated types",
+        ));
+    }
+    if inline_vtable {
+        return Err(syn::Error::new_spanned(
+            first_name,
+            "vtable statics are not supported with `vtable = \"inline\"`, since the vtable is then copied into every allocation",
+        ));
+    }
+    if *storage == Storage::Buffer {
+        return Err(syn::Error::new_spanned(
+            first_name,
+            "vtable statics are not supported with `storage = \"buffer\"`, since `new_with_vtable` allocates the value",
+        ));
+    }
+    let trait_path = stash.trait_path();
+    let vtable_type = stash.vtable_type();
+    let value_generic = trait_generics.unused_ident("T");
+    let extra_bounds = stash.value_bounds();
+    let path_to_box = storage.path();
+
+    let statics = vtable_statics.iter().map(|vtable_static| {
+        let VtableStatic {
+            additions:
+                OutputAdditions {
+                    attributes,
+                    visibility,
+                    name,
+                },
+            ty,
+            ..
+        } = vtable_static;
+        // Spanned at the type, so that the error for it not implementing the trait points at the
+        // option rather than at the whole attribute. The trait isn't generic, so neither is the
+        // representation struct beyond the stored type.
+        let mut repr_name = repr_name.clone();
+        repr_name.set_span(ty.span());
+        let vtable = quote_spanned! {ty.span()=>
+            <#repr_name<#ty>>::__THINTRAITOBJECTMACRO_VTABLE
+        };
+        quote! {
+            #(#attributes)*
+            #visibility static #name: #vtable_type = #vtable;
+        }
+    });
+    let (lifetime, creation_bound) = if has_static_bound {
+        (quote! {}, quote! { 'static })
+    } else {
+        (quote! { 'inner }, quote! { 'inner })
+    };
+    let params = trait_generics.params_with(lifetime.clone());
+    let args = trait_generics.args_with(lifetime);
+    let where_clause = trait_generics.where_clause();
+    let repr_params = trait_generics.params_with(quote! {
+        __ThinTraitObjectMacro_ReprGeneric0: #trait_path #(+ #extra_bounds)*
+    });
+    let repr_type = stash.repr_type(&quote! { __ThinTraitObjectMacro_ReprGeneric0 });
+    let value_repr_type = stash.repr_type(&value_generic);
+    let (_, phantom_initializer) = trait_generics.repr_phantom();
+    let (header_field, repr_header_param, header_param, header_arg) = match header {
+        Some(header) => (
+            quote! { __thintraitobjectmacro_repr_header: __thintraitobjectmacro_arg1, },
+            quote! { __thintraitobjectmacro_arg1: #header, },
+            quote! { header: #header, },
+            quote! { header, },
+        ),
+        None => (quote! {}, quote! {}, quote! {}, quote! {}),
+    };
+
+    // Zero-sized values aren't allocated, the same as in `__thintraitobjectmacro_repr_create`,
+    // but the slot which the thin trait object points to instead has to refer to the given vtable,
+    // so each static gets one of its own. Any other vtable is the one for the type either way, so
+    // the slot for the vtable which `new` uses stands in for it.
+    let stateless_create = if *storage == Storage::Box && header.is_none() {
+        let slots = vtable_statics.iter().map(|vtable_static| {
+            let OutputAdditions {
+                attributes, name, ..
+            } = &vtable_static.additions;
+            let cfg_attributes = attributes.iter().filter(|attr| attr.path.is_ident("cfg"));
+            quote! {
+                #(#cfg_attributes)*
+                {
+                    static SLOT: &'static #vtable_type = &#name;
+                    if ::core::ptr::eq(__thintraitobjectmacro_vtable, &#name) {
+                        return &SLOT as *const &'static #vtable_type as *mut _;
+                    }
+                }
+            }
+        });
+        quote! {
+            if Self::__THINTRAITOBJECTMACRO_STATELESS {
+                ::core::mem::forget(__thintraitobjectmacro_arg0);
+                #(#slots)*
+                return Self::__THINTRAITOBJECTMACRO_STATELESS_SLOT as *const &'static #vtable_type
+                    as *mut _;
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let allow_internal_lints = allow_internal_lints();
+
+    let result = quote! {
+        #(#statics)*
+
+        #allow_internal_lints
+        impl #repr_params #repr_type #where_clause {
+            fn __thintraitobjectmacro_repr_create_with_vtable(
+                __thintraitobjectmacro_vtable: &'static #vtable_type,
+                __thintraitobjectmacro_arg0: __ThinTraitObjectMacro_ReprGeneric0,
+                #repr_header_param
+            ) -> *mut #vtable_type {
+                #stateless_create
+                #path_to_box::into_raw(#path_to_box::new(Self {
+                    __thintraitobjectmacro_repr_vtable: __thintraitobjectmacro_vtable,
+                    #header_field
+                    __thintraitobjectmacro_repr_value: __thintraitobjectmacro_arg0,
+                    #phantom_initializer
+                })) as *mut _
+            }
+        }
+        impl #params #trait_object_name #args #where_clause {
+            /// Constructs a boxed thin trait object from a type implementing the trait, pointing it to the given vtable instead of the one `new` uses, such as one of the statics generated with `vtable_static(...)`. Zero-sized values aren't allocated, like with `new`, in which case a vtable other than those statics is replaced by the one `new` uses.
+            ///
+            /// # Safety
+            /// The vtable must be the one for values of type `T`, lifetimes aside, like the static generated with `vtable_static(...)` for `T` or the vtable returned by `vtable_for::<T>()`.
+            #[inline]
+            pub unsafe fn new_with_vtable<
+                #value_generic: #trait_path + Sized #(+ #extra_bounds)* + #creation_bound
+                >(#header_param vtable: &'static #vtable_type, val: #value_generic) -> Self {
+                Self::from_raw(
+                    <#value_repr_type>::__thintraitobjectmacro_repr_create_with_vtable(vtable, val, #header_arg) as *mut _
+                )
+            }
+        }
+    };
+    Ok(result)
+}